@@ -0,0 +1,566 @@
+use crate::history::History;
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use crate::telegram::TelegramBot;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// How long the "Silence" link mutes a chat
+pub const SILENCE_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// Seconds a `getUpdates` long poll waits for new messages
+const POLL_TIMEOUT_SECS: u64 = 30;
+
+/// Chats whose notifications get action links (`[action_links]` in the config file)
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActionLinkSettings {
+    /// Chat IDs or aliases; `"*"` enables the links for every chat
+    pub chats: Vec<String>,
+}
+
+impl ActionLinkSettings {
+    pub fn enabled_for(&self, chat_id: &str) -> bool {
+        self.chats.iter().any(|chat| chat == "*" || chat == chat_id)
+    }
+}
+
+/// What a deep link asks the bot to do, encoded in its `start` payload
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Ack { delivery_id: String },
+    Silence { delivery_id: String, minutes: u64 },
+    Manage,
+}
+
+impl Action {
+    /// `start` payload; Telegram allows up to 64 characters of `A-Za-z0-9_-`
+    pub fn payload(&self) -> String {
+        match self {
+            Action::Ack { delivery_id } => format!("ack-{delivery_id}"),
+            Action::Silence {
+                delivery_id,
+                minutes,
+            } => format!("silence-{minutes}-{delivery_id}"),
+            Action::Manage => "manage".to_string(),
+        }
+    }
+
+    pub fn parse(payload: &str) -> Option<Self> {
+        let mut parts = payload.split('-');
+        let action = match (parts.next()?, parts.next(), parts.next()) {
+            ("ack", Some(delivery_id), None) => Action::Ack {
+                delivery_id: delivery_id.to_string(),
+            },
+            ("silence", Some(minutes), Some(delivery_id)) => Action::Silence {
+                delivery_id: delivery_id.to_string(),
+                minutes: minutes.parse().ok()?,
+            },
+            ("manage", None, None) => Action::Manage,
+            _ => return None,
+        };
+        parts.next().is_none().then_some(action)
+    }
+
+    /// `https://t.me/<bot>?start=<payload>` link opening a chat with the bot
+    pub fn deep_link(&self, bot_username: &str) -> String {
+        format!("https://t.me/{bot_username}?start={}", self.payload())
+    }
+}
+
+/// End of a chat's silence
+#[derive(Debug, Clone, Copy)]
+struct Silence {
+    end: Instant,
+    /// The same moment as a Unix timestamp, for reporting
+    until: u64,
+}
+
+/// Chats muted through "Silence" links or `PUT /admin/silences/{chat}`,
+/// until the time they were muted for
+#[derive(Clone, Default)]
+pub struct Silences {
+    until: Arc<Mutex<HashMap<String, Silence>>>,
+}
+
+impl Silences {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn silence(&self, chat_id: &str, duration: Duration, now: Instant) {
+        let silence = Silence {
+            end: now + duration,
+            until: unix_now() + duration.as_secs(),
+        };
+        self.until
+            .lock()
+            .unwrap()
+            .insert(chat_id.to_string(), silence);
+    }
+
+    /// Silence a chat until a Unix timestamp
+    pub fn silence_until(&self, chat_id: &str, until: u64) {
+        let duration = Duration::from_secs(until.saturating_sub(unix_now()));
+        let silence = Silence {
+            end: Instant::now() + duration,
+            until,
+        };
+        self.until
+            .lock()
+            .unwrap()
+            .insert(chat_id.to_string(), silence);
+    }
+
+    /// End a chat's silence early, returning whether it was silenced
+    pub fn lift(&self, chat_id: &str) -> bool {
+        let now = Instant::now();
+        let mut until = self.until.lock().unwrap();
+        until.retain(|_, silence| silence.end > now);
+        until.remove(chat_id).is_some()
+    }
+
+    /// Remaining silence for a chat, if any
+    pub fn remaining(&self, chat_id: &str, now: Instant) -> Option<Duration> {
+        let mut until = self.until.lock().unwrap();
+        until.retain(|_, silence| silence.end > now);
+        until.get(chat_id).map(|silence| silence.end - now)
+    }
+
+    /// Unix timestamp a chat's silence ends at, if it is silenced
+    pub fn until(&self, chat_id: &str) -> Option<u64> {
+        let now = Instant::now();
+        let mut until = self.until.lock().unwrap();
+        until.retain(|_, silence| silence.end > now);
+        until.get(chat_id).map(|silence| silence.until)
+    }
+
+    /// Currently silenced chats with their remaining time
+    pub fn active(&self, now: Instant) -> Vec<(String, Duration)> {
+        let mut until = self.until.lock().unwrap();
+        until.retain(|_, silence| silence.end > now);
+        let mut active: Vec<_> = until
+            .iter()
+            .map(|(chat, silence)| (chat.clone(), silence.end - now))
+            .collect();
+        active.sort();
+        active
+    }
+
+    /// Currently silenced chats with the Unix timestamp their silence ends at
+    pub fn list(&self) -> Vec<(String, u64)> {
+        let now = Instant::now();
+        let mut until = self.until.lock().unwrap();
+        until.retain(|_, silence| silence.end > now);
+        let mut list: Vec<_> = until
+            .iter()
+            .map(|(chat, silence)| (chat.clone(), silence.until))
+            .collect();
+        list.sort();
+        list
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Appends "Ack", "Silence 1h" and "Manage subscriptions" deep links to
+/// notifications for the configured chats
+pub struct ActionLinks {
+    bot_username: String,
+    settings: ActionLinkSettings,
+}
+
+impl ActionLinks {
+    pub fn new(bot_username: String, settings: ActionLinkSettings) -> Self {
+        Self {
+            bot_username,
+            settings,
+        }
+    }
+}
+
+impl Stage for ActionLinks {
+    fn name(&self) -> &str {
+        "action-links"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Render
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        if !self.settings.enabled_for(&notification.chat_id) {
+            return Ok(Outcome::Continue);
+        }
+
+        let delivery_id = notification.delivery_id.clone();
+        let links = [
+            (
+                "Ack",
+                Action::Ack {
+                    delivery_id: delivery_id.clone(),
+                },
+            ),
+            (
+                "Silence 1h",
+                Action::Silence {
+                    delivery_id,
+                    minutes: SILENCE_DURATION.as_secs() / 60,
+                },
+            ),
+            ("Manage subscriptions", Action::Manage),
+        ]
+        .map(|(label, action)| (label, action.deep_link(&self.bot_username)));
+
+        let mode = notification
+            .parse_mode
+            .as_deref()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let footer = match mode.as_str() {
+            "markdown" | "markdownv2" => links
+                .iter()
+                .map(|(label, url)| format!("[{label}]({url})"))
+                .collect::<Vec<_>>()
+                .join(" · "),
+            "html" => links
+                .iter()
+                .map(|(label, url)| format!("<a href=\"{url}\">{label}</a>"))
+                .collect::<Vec<_>>()
+                .join(" · "),
+            _ => links
+                .iter()
+                .map(|(label, url)| format!("{label}: {url}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        notification.message.push_str("\n\n");
+        notification.message.push_str(&footer);
+        Ok(Outcome::Continue)
+    }
+}
+
+/// Drops notifications for chats muted through a "Silence" link
+pub struct SilenceFilter {
+    silences: Silences,
+}
+
+impl SilenceFilter {
+    pub fn new(silences: Silences) -> Self {
+        Self { silences }
+    }
+}
+
+impl Stage for SilenceFilter {
+    fn name(&self) -> &str {
+        "silence"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Route
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        match self
+            .silences
+            .remaining(&notification.chat_id, Instant::now())
+        {
+            Some(remaining) => Ok(Outcome::Drop {
+                reason: format!(
+                    "Chat {} is silenced for another {} min",
+                    notification.chat_id,
+                    remaining.as_secs().div_ceil(60)
+                ),
+            }),
+            None => Ok(Outcome::Continue),
+        }
+    }
+}
+
+/// Long-poll the Bot API for `/start <payload>` messages sent by the action
+/// links and carry them out, replying to the user who clicked
+pub async fn handle_updates(bot: TelegramBot, history: History, silences: Silences) {
+    let mut offset = 0;
+    loop {
+        let updates = match bot.get_updates(offset, POLL_TIMEOUT_SECS).await {
+            Ok(response) => response.result.unwrap_or_default(),
+            Err(e) => {
+                warn!("⚠️ Failed to fetch bot updates: {}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        for update in updates.as_array().into_iter().flatten() {
+            if let Some(id) = update["update_id"].as_i64() {
+                offset = offset.max(id + 1);
+            }
+            let Some((reply_to, action, user)) = start_command(update) else {
+                continue;
+            };
+            let reply = perform(&action, &user, &history, &silences, Instant::now());
+            if let Err(e) = bot
+                .send_message_advanced(&reply_to, &reply, None, false)
+                .await
+            {
+                warn!("⚠️ Failed to answer action link: {}", e);
+            }
+        }
+    }
+}
+
+/// Chat to reply to, action and user name of a `/start <payload>` update
+fn start_command(update: &Value) -> Option<(String, Action, String)> {
+    let message = &update["message"];
+    let payload = message["text"].as_str()?.strip_prefix("/start ")?;
+    let action = Action::parse(payload.trim())?;
+    let chat_id = message["chat"]["id"].as_i64()?.to_string();
+    let from = &message["from"];
+    let user = from["username"]
+        .as_str()
+        .map(|name| format!("@{name}"))
+        .or_else(|| from["first_name"].as_str().map(str::to_string))
+        .unwrap_or_else(|| "unknown".to_string());
+    Some((chat_id, action, user))
+}
+
+/// Carry out an action, returning the reply for the user
+fn perform(
+    action: &Action,
+    user: &str,
+    history: &History,
+    silences: &Silences,
+    now: Instant,
+) -> String {
+    match action {
+        Action::Ack { delivery_id } => {
+            if history.acknowledge(delivery_id, user) {
+                info!("👍 Notification {} acknowledged by {}", delivery_id, user);
+                format!("✅ Notification {delivery_id} acknowledged")
+            } else {
+                format!("❓ Notification {delivery_id} is no longer in the history")
+            }
+        }
+        Action::Silence {
+            delivery_id,
+            minutes,
+        } => match history.get(delivery_id) {
+            Some(record) => {
+                let chat_id = record.notification.chat_id;
+                silences.silence(&chat_id, Duration::from_secs(minutes * 60), now);
+                info!(
+                    "🔕 Chat {} silenced for {} min by {}",
+                    chat_id, minutes, user
+                );
+                format!("🔕 Notifications to chat {chat_id} silenced for {minutes} min")
+            }
+            None => format!("❓ Notification {delivery_id} is no longer in the history"),
+        },
+        Action::Manage => {
+            let active = silences.active(now);
+            if active.is_empty() {
+                "🔔 No chats are silenced".to_string()
+            } else {
+                let lines: Vec<String> = active
+                    .iter()
+                    .map(|(chat, remaining)| {
+                        format!("🔕 {chat}: {} min left", remaining.as_secs().div_ceil(60))
+                    })
+                    .collect();
+                format!("Silenced chats:\n{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::DeliveryStatus;
+    use serde_json::json;
+
+    fn notification(chat_id: &str, parse_mode: Option<&str>) -> Notification {
+        Notification {
+            message: "Disk full".to_string(),
+            chat_id: chat_id.to_string(),
+            parse_mode: parse_mode.map(str::to_string),
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn stage(chats: &[&str]) -> ActionLinks {
+        ActionLinks::new(
+            "alerts_bot".to_string(),
+            ActionLinkSettings {
+                chats: chats.iter().map(|c| c.to_string()).collect(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        for action in [
+            Action::Ack {
+                delivery_id: "abc123def456".to_string(),
+            },
+            Action::Silence {
+                delivery_id: "abc123def456".to_string(),
+                minutes: 60,
+            },
+            Action::Manage,
+        ] {
+            assert_eq!(Action::parse(&action.payload()), Some(action));
+        }
+        assert_eq!(Action::parse("ack"), None);
+        assert_eq!(Action::parse("silence-soon-abc"), None);
+        assert_eq!(Action::parse("ack-abc-extra"), None);
+    }
+
+    #[test]
+    fn test_deep_link() {
+        let link = Action::Manage.deep_link("alerts_bot");
+        assert_eq!(link, "https://t.me/alerts_bot?start=manage");
+    }
+
+    #[test]
+    fn test_footer_only_for_configured_chats() {
+        let mut other = notification("-100999", None);
+        stage(&["-100123"]).process(&mut other).unwrap();
+        assert_eq!(other.message, "Disk full");
+
+        let mut configured = notification("-100123", None);
+        stage(&["-100123"]).process(&mut configured).unwrap();
+        assert!(
+            configured
+                .message
+                .contains("Ack: https://t.me/alerts_bot?start=ack-abc123def456")
+        );
+        assert!(
+            configured
+                .message
+                .contains("Silence 1h: https://t.me/alerts_bot?start=silence-60-abc123def456")
+        );
+    }
+
+    #[test]
+    fn test_footer_formats() {
+        let mut markdown = notification("-100123", Some("Markdown"));
+        stage(&["*"]).process(&mut markdown).unwrap();
+        assert!(
+            markdown
+                .message
+                .ends_with("[Manage subscriptions](https://t.me/alerts_bot?start=manage)")
+        );
+
+        let mut html = notification("-100123", Some("HTML"));
+        stage(&["*"]).process(&mut html).unwrap();
+        assert!(
+            html.message
+                .contains("<a href=\"https://t.me/alerts_bot?start=ack-abc123def456\">Ack</a>")
+        );
+    }
+
+    #[test]
+    fn test_silence_filter() {
+        let silences = Silences::new();
+        let filter = SilenceFilter::new(silences.clone());
+        silences.silence("-100123", SILENCE_DURATION, Instant::now());
+
+        let outcome = filter.process(&mut notification("-100123", None)).unwrap();
+        assert!(matches!(outcome, Outcome::Drop { .. }));
+
+        let outcome = filter.process(&mut notification("-100999", None)).unwrap();
+        assert_eq!(outcome, Outcome::Continue);
+    }
+
+    #[test]
+    fn test_silence_expires() {
+        let silences = Silences::new();
+        let now = Instant::now();
+        silences.silence("-100123", Duration::from_secs(60), now);
+
+        assert!(silences.remaining("-100123", now).is_some());
+        assert!(
+            silences
+                .remaining("-100123", now + Duration::from_secs(61))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_silence_until_and_lift() {
+        let silences = Silences::new();
+        let until = unix_now() + 3_600;
+
+        silences.silence_until("-100123", until);
+        silences.silence_until("-100999", unix_now() - 1);
+
+        assert_eq!(silences.until("-100123"), Some(until));
+        assert_eq!(silences.list(), vec![("-100123".to_string(), until)]);
+        assert!(silences.lift("-100123"));
+        assert!(!silences.lift("-100123"));
+        assert_eq!(silences.until("-100123"), None);
+    }
+
+    #[test]
+    fn test_start_command() {
+        let update = json!({
+            "update_id": 1,
+            "message": {
+                "text": "/start ack-abc123def456",
+                "chat": {"id": 42},
+                "from": {"username": "ada"}
+            }
+        });
+
+        let (chat_id, action, user) = start_command(&update).unwrap();
+
+        assert_eq!(chat_id, "42");
+        assert_eq!(
+            action,
+            Action::Ack {
+                delivery_id: "abc123def456".to_string()
+            }
+        );
+        assert_eq!(user, "@ada");
+        assert!(start_command(&json!({"message": {"text": "hello"}})).is_none());
+    }
+
+    #[test]
+    fn test_perform_ack_and_silence() {
+        let history = History::new(10);
+        let silences = Silences::new();
+        let now = Instant::now();
+        history.record(
+            &notification("-100123", None),
+            DeliveryStatus::Sent {
+                telegram_message_id: Some(42),
+            },
+            None,
+        );
+
+        let ack = Action::parse("ack-abc123def456").unwrap();
+        perform(&ack, "@ada", &history, &silences, now);
+        assert_eq!(
+            history.get("abc123def456").unwrap().acknowledged_by,
+            Some("@ada".to_string())
+        );
+
+        let silence = Action::parse("silence-60-abc123def456").unwrap();
+        perform(&silence, "@ada", &history, &silences, now);
+        assert!(silences.remaining("-100123", now).is_some());
+
+        let reply = perform(&Action::Manage, "@ada", &history, &silences, now);
+        assert!(reply.contains("-100123: 60 min left"));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::api::SendNotificationRequest;
+use crate::pages::escape;
+use serde::de::{Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::{Deserialize, de::DeserializeOwned};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Source of notifications from `POST /integrations/alertmanager`
+pub const SOURCE: &str = "alertmanager";
+
+/// Annotations describing an alert, the first one present wins
+const TEXT_ANNOTATIONS: [&str; 3] = ["summary", "description", "message"];
+
+/// Alerts of a group kept for the message; the others are only counted
+pub const MAX_ALERTS: usize = 100;
+
+/// Body of an Alertmanager webhook (`webhook_configs`), version 4
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Webhook {
+    /// `firing` while any alert of the group fires, then `resolved`
+    pub status: String,
+    pub receiver: String,
+    pub group_labels: BTreeMap<String, String>,
+    pub common_labels: BTreeMap<String, String>,
+    pub common_annotations: BTreeMap<String, String>,
+    #[serde(rename = "externalURL")]
+    pub external_url: String,
+    pub alerts: Alerts<Alert>,
+    /// Alerts left out because of the receiver's `max_alerts`
+    pub truncated_alerts: usize,
+}
+
+/// The first [`MAX_ALERTS`] alerts of a webhook. Those after them are
+/// parsed past without being kept, so a huge group doesn't fill memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alerts<A> {
+    kept: Vec<A>,
+    /// Alerts left out for being over [`MAX_ALERTS`]
+    dropped: usize,
+}
+
+impl<A> Default for Alerts<A> {
+    fn default() -> Self {
+        Self {
+            kept: Vec::new(),
+            dropped: 0,
+        }
+    }
+}
+
+impl<A> Alerts<A> {
+    pub fn iter(&self) -> std::slice::Iter<'_, A> {
+        self.kept.iter()
+    }
+
+    /// Number of alerts in the webhook, dropped ones included
+    pub fn len(&self) -> usize {
+        self.kept.len() + self.dropped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// The same alerts in another form
+    pub fn map<B>(&self, f: impl FnMut(&A) -> B) -> Alerts<B> {
+        Alerts {
+            kept: self.kept.iter().map(f).collect(),
+            dropped: self.dropped,
+        }
+    }
+}
+
+impl<'de, A: DeserializeOwned> Deserialize<'de> for Alerts<A> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AlertsVisitor<A>(PhantomData<A>);
+
+        impl<'de, A: DeserializeOwned> Visitor<'de> for AlertsVisitor<A> {
+            type Value = Alerts<A>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a list of alerts")
+            }
+
+            fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+                let mut alerts = Alerts::default();
+                while alerts.kept.len() < MAX_ALERTS {
+                    match seq.next_element()? {
+                        Some(alert) => alerts.kept.push(alert),
+                        None => return Ok(alerts),
+                    }
+                }
+                while seq.next_element::<IgnoredAny>()?.is_some() {
+                    alerts.dropped += 1;
+                }
+                Ok(alerts)
+            }
+        }
+
+        deserializer.deserialize_seq(AlertsVisitor(PhantomData))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Alert {
+    /// `firing` or `resolved`
+    pub status: String,
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Webhook {
+    fn is_resolved(&self) -> bool {
+        self.status == "resolved"
+    }
+}
+
+/// Notification for a group of alerts: a header with the alert name and the
+/// labels shared by the group, then the firing and resolved alerts, each with
+/// the labels telling it apart and its summary. Resolved groups are sent
+/// silently.
+pub fn render(webhook: &Webhook) -> SendNotificationRequest {
+    render_group(webhook, "Open Alertmanager")
+}
+
+/// [`render`], ending with a link to `externalURL` labelled `link_text`
+pub(crate) fn render_group(webhook: &Webhook, link_text: &str) -> SendNotificationRequest {
+    let (firing, resolved): (Vec<&Alert>, Vec<&Alert>) = webhook
+        .alerts
+        .iter()
+        .partition(|alert| alert.status != "resolved");
+    let name = webhook
+        .common_labels
+        .get("alertname")
+        .or_else(|| webhook.group_labels.get("alertname"))
+        .unwrap_or(&webhook.receiver);
+
+    let mut lines = vec![if webhook.is_resolved() {
+        format!("✅ <b>[RESOLVED]</b> {}", escape(name))
+    } else {
+        format!("🔥 <b>[FIRING:{}]</b> {}", firing.len(), escape(name))
+    }];
+    let shared = labels(&webhook.common_labels, |name| name != "alertname");
+    if !shared.is_empty() {
+        lines.push(format!("<i>{}</i>", escape(&shared)));
+    }
+    for (title, alerts) in [("Firing", &firing), ("Resolved", &resolved)] {
+        if alerts.is_empty() {
+            continue;
+        }
+        lines.push(String::new());
+        // A section heading only helps when both kinds are in the message
+        if !firing.is_empty() && !resolved.is_empty() {
+            lines.push(format!("<b>{title}</b>"));
+        }
+        lines.extend(alerts.iter().map(|alert| alert_line(alert, webhook)));
+    }
+    let truncated = webhook.truncated_alerts + webhook.alerts.dropped();
+    if truncated > 0 {
+        lines.push(format!("…and {truncated} more"));
+    }
+    if !webhook.external_url.is_empty() {
+        lines.push(String::new());
+        lines.push(format!(
+            "<a href=\"{}\">{}</a>",
+            escape(&webhook.external_url),
+            escape(link_text)
+        ));
+    }
+
+    SendNotificationRequest {
+        message: lines.join("\n"),
+        parse_mode: Some("HTML".to_string()),
+        disable_notification: Some(webhook.is_resolved()),
+        severity: webhook.common_labels.get("severity").cloned(),
+        labels: Some(webhook.common_labels.clone()),
+        ..Default::default()
+    }
+}
+
+/// `• <labels of this alert>: <summary> (runbook)`
+fn alert_line(alert: &Alert, webhook: &Webhook) -> String {
+    let own = labels(&alert.labels, |name| {
+        webhook.common_labels.get(name) != alert.labels.get(name)
+    });
+    let text = TEXT_ANNOTATIONS
+        .iter()
+        .find_map(|name| alert.annotations.get(*name))
+        // Shared text is better than nothing
+        .or_else(|| {
+            TEXT_ANNOTATIONS
+                .iter()
+                .find_map(|name| webhook.common_annotations.get(*name))
+        });
+
+    let mut line = String::from("•");
+    if !own.is_empty() {
+        line.push_str(&format!(" <b>{}</b>", escape(&own)));
+    }
+    match text {
+        Some(text) if own.is_empty() => line.push_str(&format!(" {}", escape(text))),
+        Some(text) => line.push_str(&format!(": {}", escape(text))),
+        None if own.is_empty() => line.push_str(" (no details)"),
+        None => {}
+    }
+    if let Some(runbook) = alert.annotations.get("runbook_url") {
+        line.push_str(&format!(" (<a href=\"{}\">runbook</a>)", escape(runbook)));
+    }
+    line
+}
+
+/// `name=value` pairs separated by commas, for the labels `keep` accepts
+fn labels(labels: &BTreeMap<String, String>, keep: impl Fn(&str) -> bool) -> String {
+    labels
+        .iter()
+        .filter(|(name, _)| keep(name))
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn webhook(value: serde_json::Value) -> Webhook {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_render_firing_group() {
+        let request = render(&webhook(json!({
+            "status": "firing",
+            "receiver": "telegram",
+            "commonLabels": {"alertname": "HighCpuUsage", "severity": "critical"},
+            "externalURL": "http://alertmanager:9093",
+            "alerts": [
+                {
+                    "status": "firing",
+                    "labels": {"alertname": "HighCpuUsage", "severity": "critical", "instance": "web-1"},
+                    "annotations": {"summary": "CPU above 90%", "runbook_url": "https://wiki/cpu"}
+                },
+                {
+                    "status": "resolved",
+                    "labels": {"alertname": "HighCpuUsage", "severity": "critical", "instance": "web-2"},
+                    "annotations": {}
+                }
+            ]
+        })));
+
+        assert_eq!(
+            request.message,
+            "🔥 <b>[FIRING:1]</b> HighCpuUsage\n\
+             <i>severity=critical</i>\n\
+             \n\
+             <b>Firing</b>\n\
+             • <b>instance=web-1</b>: CPU above 90% (<a href=\"https://wiki/cpu\">runbook</a>)\n\
+             \n\
+             <b>Resolved</b>\n\
+             • <b>instance=web-2</b>\n\
+             \n\
+             <a href=\"http://alertmanager:9093\">Open Alertmanager</a>"
+        );
+        assert_eq!(request.parse_mode.as_deref(), Some("HTML"));
+        assert_eq!(request.disable_notification, Some(false));
+    }
+
+    #[test]
+    fn test_render_resolved_group_silently() {
+        let request = render(&webhook(json!({
+            "status": "resolved",
+            "receiver": "telegram",
+            "commonAnnotations": {"summary": "Disk <5% free"},
+            "alerts": [{"status": "resolved", "labels": {}, "annotations": {}}],
+            "truncatedAlerts": 3
+        })));
+
+        assert_eq!(
+            request.message,
+            "✅ <b>[RESOLVED]</b> telegram\n\n• Disk &lt;5% free\n…and 3 more"
+        );
+        assert_eq!(request.disable_notification, Some(true));
+    }
+}
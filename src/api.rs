@@ -1,6 +1,17 @@
+use crate::breaker::BreakerState;
+use crate::dlq::DeadLetter;
+use crate::export::ExportFormat;
+use crate::history::{DeliveryStatus, Record};
+use crate::keys::{ManagedKey, Quota, Scope};
+use crate::pipeline::Notification;
+use crate::scheduler::format_time;
+use crate::schedules::{Schedule, Source};
+use crate::telegram::Button;
+use axum::{http::StatusCode, response::Json};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct SendNotificationRequest {
     /// Message to send
     pub message: String,
@@ -8,19 +19,544 @@ pub struct SendNotificationRequest {
     /// Optional custom chat ID (overrides default)
     pub chat_id: Option<String>,
 
+    /// Optional chat alias from the config file's `[chats]` table, or the
+    /// destination URL of one, instead of `chat_id`
+    pub chat: Option<String>,
+
+    /// Optional list of chat IDs to send the message to, each with its own result
+    pub chat_ids: Option<Vec<String>>,
+
+    /// Optional list of chat aliases to send the message to, combined with `chat_ids`
+    pub chats: Option<Vec<String>>,
+
     /// Optional parse mode (Markdown, HTML, or None)
     pub parse_mode: Option<String>,
 
     /// Optional disable notification (silent message)
     pub disable_notification: Option<bool>,
+
+    /// Optional pin of the message after sending (needs admin rights in groups and channels)
+    pub pin: Option<bool>,
+
+    /// Optional localized variants of the message keyed by locale (e.g. "de")
+    pub translations: Option<HashMap<String, String>>,
+
+    /// Optional asynchronous delivery: queue the message and return 202 immediately
+    #[serde(rename = "async")]
+    pub async_delivery: Option<bool>,
+
+    /// Optional idempotency key, used when no `Idempotency-Key` header is sent
+    pub dedup_key: Option<String>,
+
+    /// Optional maximum time to wait for Telegram in milliseconds; slower
+    /// sends return 202 and finish in the background
+    pub deadline_ms: Option<u64>,
+
+    /// Optional RFC 3339 time to deliver the message at instead of right away
+    pub send_at: Option<String>,
+
+    /// Optional URL the delivery outcome is POSTed to once the message was
+    /// sent or failed for good
+    pub callback_url: Option<String>,
+
+    /// Optional URL buttons shown below the message
+    pub buttons: Option<Vec<Button>>,
+
+    /// Optional named bot from the config file's `[bots]` to send with
+    pub bot: Option<String>,
+
+    /// Optional severity (e.g. "critical") matched by the config file's `[[rules]]`
+    pub severity: Option<String>,
+
+    /// Optional labels matched by the config file's `[[rules]]`
+    pub labels: Option<BTreeMap<String, String>>,
+
+    /// Named API key the request was made with; set by the server
+    #[serde(skip)]
+    pub key: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+impl SendNotificationRequest {
+    /// Whether the request is addressed to a list of chats
+    pub fn is_fan_out(&self) -> bool {
+        self.chat_ids.is_some() || self.chats.is_some()
+    }
+}
+
+/// Query parameters accepted by the notification endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct NotifyQuery {
+    pub mode: Option<DeliveryMode>,
+    /// Same as the body's `deadline_ms`, which it overrides
+    pub deadline_ms: Option<u64>,
+}
+
+/// Query parameters of `GET /history`
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    /// Chat ID or alias
+    pub chat: Option<String>,
+    /// Only records created at or after this time
+    pub since: Option<String>,
+    /// Only records created before this time
+    pub until: Option<String>,
+    /// Delivery status, e.g. `failed`
+    pub status: Option<String>,
+    /// Matching records to skip, newest first
+    #[serde(default)]
+    pub offset: usize,
+    /// Records per page
+    pub limit: Option<usize>,
+}
+
+/// Query parameters of the `/integrations/*` receivers, which can't put the
+/// destination in the body they are sent
+#[derive(Debug, Default, Deserialize)]
+pub struct IntegrationQuery {
+    /// Chat ID, instead of the default chat
+    pub chat_id: Option<String>,
+    /// Chat alias, instead of the default chat
+    pub chat: Option<String>,
+    /// `sync` waits for Telegram; by default the message is queued
+    pub mode: Option<DeliveryMode>,
+}
+
+/// Query parameters of `GET /history/export`
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Only records created at or after this time
+    pub from: Option<String>,
+    /// Only records created before this time
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryMode {
+    Sync,
+    Async,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct SendNotificationResponse {
     pub success: bool,
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub telegram_message_id: Option<i64>,
+    /// ID tracing this notification through logs (and the message footer, if enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery_id: Option<String>,
+    /// Outcome per chat for requests with `chat_ids` or `chats`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub results: Vec<ChatResult>,
+}
+
+/// Delivery outcome for one chat of a fan-out request
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatResult {
+    pub chat_id: String,
+    pub success: bool,
+    /// Status message on success, error description otherwise
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram_message_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery_id: Option<String>,
+}
+
+impl ChatResult {
+    /// Flatten a single-chat outcome, keeping its HTTP status
+    pub fn from_outcome(
+        chat_id: String,
+        outcome: Result<
+            (StatusCode, Json<SendNotificationResponse>),
+            (StatusCode, Json<ErrorResponse>),
+        >,
+    ) -> (StatusCode, Self) {
+        match outcome {
+            Ok((status, Json(response))) => (
+                status,
+                Self {
+                    chat_id,
+                    success: response.success,
+                    message: response.message,
+                    code: None,
+                    telegram_message_id: response.telegram_message_id,
+                    delivery_id: response.delivery_id,
+                },
+            ),
+            Err((status, Json(error))) => (
+                status,
+                Self {
+                    chat_id,
+                    success: false,
+                    message: error.error,
+                    code: error.code,
+                    telegram_message_id: None,
+                    delivery_id: None,
+                },
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreviewResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery_id: Option<String>,
+    /// Whether the notification would actually be delivered
+    pub would_send: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    pub disable_notification: bool,
+    /// Exact message texts that would be sent, in order
+    pub parts: Vec<String>,
+    /// File names of attachments sent after the text
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppressed_reason: Option<String>,
+    /// Per-chat previews for requests with `chat_ids` or `chats`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<PreviewResponse>,
+}
+
+/// Body of `POST /history/{id}/resend`
+#[derive(Debug, Default, Deserialize)]
+pub struct ResendRequest {
+    /// Send to this chat instead of the original one
+    pub chat_id: Option<String>,
+}
+
+/// Body of `POST /admin/keys`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateKeyRequest {
+    /// What the key is for, e.g. "grafana"
+    pub label: Option<String>,
+    #[serde(default)]
+    pub scope: Scope,
+    pub requests_per_minute: Option<u32>,
+    /// Counted per UTC day
+    pub requests_per_day: Option<u32>,
+}
+
+/// A key managed through `/admin/keys`. The key itself is only returned
+/// when it is created.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub scope: Scope,
+    #[serde(flatten)]
+    pub quota: Quota,
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+impl From<ManagedKey> for ApiKeyResponse {
+    fn from(key: ManagedKey) -> Self {
+        Self {
+            id: key.id,
+            label: key.label,
+            scope: key.scope,
+            quota: key.quota,
+            created_at: key.created_at,
+            key: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyListResponse {
+    pub keys: Vec<ApiKeyResponse>,
+}
+
+/// Body of `PUT /admin/keys/{id}`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PutKeyRequest {
+    pub label: Option<String>,
+    #[serde(default)]
+    pub scope: Scope,
+    pub requests_per_minute: Option<u32>,
+    /// Counted per UTC day
+    pub requests_per_day: Option<u32>,
+    /// Use this key instead of a generated one, e.g. from a secrets manager
+    pub key: Option<String>,
+}
+
+/// Body of `PUT /admin/targets/{name}`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetRequest {
+    pub chat_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TargetResponse {
+    pub name: String,
+    pub chat_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TargetListResponse {
+    pub targets: Vec<TargetResponse>,
+}
+
+/// Body of `PUT /admin/silences/{chat}`, with either an end time or a
+/// duration
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SilenceRequest {
+    /// Unix timestamp the silence ends at
+    pub until: Option<u64>,
+    pub minutes: Option<u64>,
+}
+
+/// Response of `POST /checkin/{slug}`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckInResponse {
+    pub slug: String,
+    /// Unix timestamp the next check-in is due by, grace included
+    pub next_due: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SilenceResponse {
+    pub chat_id: String,
+    /// Unix timestamp the silence ends at
+    pub until: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SilenceListResponse {
+    pub silences: Vec<SilenceResponse>,
+}
+
+/// A notification waiting for its `send_at` time
+#[derive(Debug, Serialize)]
+pub struct ScheduledResponse {
+    pub delivery_id: String,
+    pub chat_id: String,
+    pub message: String,
+    /// RFC 3339 time the notification will be sent at
+    pub send_at: String,
+}
+
+impl From<Notification> for ScheduledResponse {
+    fn from(notification: Notification) -> Self {
+        Self {
+            send_at: format_time(notification.send_at.unwrap_or_default()),
+            delivery_id: notification.delivery_id,
+            chat_id: notification.chat_id,
+            message: notification.message,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduledListResponse {
+    pub scheduled: Vec<ScheduledResponse>,
+}
+
+/// Body of `POST /schedules`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateScheduleRequest {
+    /// ID to create the schedule under; generated if not given
+    pub id: Option<String>,
+    pub cron: String,
+    pub message: String,
+    pub chat_id: Option<String>,
+    pub chat: Option<String>,
+    pub parse_mode: Option<String>,
+    pub disable_notification: Option<bool>,
+}
+
+impl From<CreateScheduleRequest> for Schedule {
+    fn from(request: CreateScheduleRequest) -> Self {
+        Self {
+            cron: request.cron.trim().to_string(),
+            message: request.message,
+            chat_id: request.chat_id,
+            chat: request.chat,
+            parse_mode: request.parse_mode,
+            disable_notification: request.disable_notification.unwrap_or(false),
+        }
+    }
+}
+
+/// A recurring notification
+#[derive(Debug, Serialize)]
+pub struct ScheduleResponse {
+    pub id: String,
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    pub source: Source,
+    /// RFC 3339 time of the next run in the server's time zone
+    pub next_run: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleListResponse {
+    pub schedules: Vec<ScheduleResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetterResponse {
+    pub delivery_id: String,
+    pub chat_id: String,
+    pub message: String,
+    pub emergency: bool,
+    pub attempts: u32,
+    /// Error of the last attempt
+    pub error: String,
+    /// Unix timestamp
+    pub failed_at: u64,
+}
+
+impl From<DeadLetter> for DeadLetterResponse {
+    fn from(letter: DeadLetter) -> Self {
+        let notification = letter.notification;
+        Self {
+            delivery_id: notification.delivery_id,
+            chat_id: notification.chat_id,
+            message: notification.message,
+            emergency: notification.emergency,
+            attempts: letter.attempts,
+            error: letter.error,
+            failed_at: letter.failed_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLetterListResponse {
+    pub dead_letters: Vec<DeadLetterResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryRecordResponse {
+    pub delivery_id: String,
+    pub chat_id: String,
+    pub message: String,
+    /// Complete text when `message` was shortened to fit Telegram
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    pub disable_notification: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    /// Named bot the notification was sent with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bot: Option<String>,
+    #[serde(flatten)]
+    pub status: DeliveryStatus,
+    /// Unix timestamp of the delivery attempt
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resent_from: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resends: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acknowledged_by: Option<String>,
+}
+
+impl From<Record> for HistoryRecordResponse {
+    fn from(record: Record) -> Self {
+        let notification = record.notification;
+        Self {
+            delivery_id: notification.delivery_id,
+            chat_id: notification.chat_id,
+            message: notification.message,
+            full_message: notification.full_message,
+            parse_mode: notification.parse_mode,
+            disable_notification: notification.disable_notification,
+            attachments: notification
+                .attachments
+                .into_iter()
+                .map(|attachment| attachment.file_name)
+                .collect(),
+            bot: notification.bot,
+            status: record.status,
+            created_at: record.created_at,
+            resent_from: record.resent_from,
+            resends: record.resends,
+            acknowledged_by: record.acknowledged_by,
+        }
+    }
+}
+
+/// Page of past notifications, as returned by `GET /history`
+#[derive(Debug, Serialize)]
+pub struct HistoryListResponse {
+    pub records: Vec<HistoryRecordResponse>,
+    /// Records matching the filters across all pages
+    pub total: usize,
+    /// `offset` of the next page, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+/// Delivery state of a notification, as returned by `GET /messages/{id}`
+#[derive(Debug, Serialize)]
+pub struct MessageStatusResponse {
+    pub delivery_id: String,
+    pub chat_id: String,
+    /// scheduled, queued, sending, sent, failed, digested or cancelled
+    pub state: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram_message_id: Option<i64>,
+    /// Delivery ID of the digest a digested notification went out in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest_id: Option<String>,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl From<Record> for MessageStatusResponse {
+    fn from(record: Record) -> Self {
+        let mut digest_id = None;
+        let (state, telegram_message_id, last_error) = match record.status {
+            DeliveryStatus::Scheduled => ("scheduled", None, None),
+            DeliveryStatus::Queued => ("queued", None, None),
+            DeliveryStatus::Sending => ("sending", None, None),
+            DeliveryStatus::Sent {
+                telegram_message_id,
+            } => ("sent", telegram_message_id, None),
+            DeliveryStatus::Failed { error } => ("failed", None, Some(error)),
+            DeliveryStatus::Digested { digest_id: id } => {
+                digest_id = Some(id);
+                ("digested", None, None)
+            }
+            DeliveryStatus::Cancelled => ("cancelled", None, None),
+        };
+        Self {
+            delivery_id: record.notification.delivery_id,
+            chat_id: record.notification.chat_id,
+            state,
+            telegram_message_id,
+            digest_id,
+            attempts: record.attempts,
+            last_error,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -31,6 +567,46 @@ pub struct HealthResponse {
     pub bot_verified: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bot_username: Option<String>,
+    /// Circuit breaker guarding the Telegram API
+    pub circuit_breaker: BreakerState,
+    pub uptime_secs: u64,
+    /// Notifications waiting in the delivery queue
+    pub queued_messages: usize,
+    /// Unix timestamp of the last notification Telegram accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_successful_send: Option<u64>,
+}
+
+/// Outcome of one readiness check
+#[derive(Debug, Serialize)]
+pub struct ReadinessCheck {
+    pub ok: bool,
+    /// Why the check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<Result<(), String>> for ReadinessCheck {
+    fn from(outcome: Result<(), String>) -> Self {
+        Self {
+            ok: outcome.is_ok(),
+            error: outcome.err(),
+        }
+    }
+}
+
+/// Returned by `GET /readyz`, with `200` if every check passed and `503`
+/// otherwise
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    /// `ready` or `not_ready`
+    pub status: &'static str,
+    /// The bot token works (verified at most every `--bot-check-cache-secs`)
+    pub bot: ReadinessCheck,
+    /// The delivery queue has room for notifications
+    pub queue: ReadinessCheck,
+    /// `--queue-db` and `--history-db` can be read
+    pub storage: ReadinessCheck,
 }
 
 #[derive(Debug, Serialize)]
@@ -73,6 +649,17 @@ impl Default for InfoResponse {
 }
 
 impl InfoResponse {
+    /// Advertise the endpoints below `base_path` (see `--base-path`)
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        for endpoint in &mut self.endpoints {
+            endpoint.path = match endpoint.path.as_str() {
+                "/" if !base_path.is_empty() => base_path.to_string(),
+                path => format!("{base_path}{path}"),
+            };
+        }
+        self
+    }
+
     pub fn new() -> Self {
         Self {
             name: "Telegram Notifications API".to_string(),
@@ -99,6 +686,225 @@ impl InfoResponse {
                     path: "/send".to_string(),
                     description: "Send a notification message (alias for /notify)".to_string(),
                 },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/notify/preview".to_string(),
+                    description: "Preview the message that would be sent, without sending"
+                        .to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/integrations/alertmanager".to_string(),
+                    description: "Receive Prometheus Alertmanager webhooks".to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/integrations/grafana".to_string(),
+                    description: "Receive Grafana alerting webhooks".to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/integrations/uptime".to_string(),
+                    description: "Receive Uptime Kuma and other monitor status webhooks"
+                        .to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/integrations/pagerduty".to_string(),
+                    description: "Receive PagerDuty Events API v2 events".to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/checkin/{slug}".to_string(),
+                    description: "Check in a job that is alerted about when it doesn't".to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/webhook/{name}".to_string(),
+                    description: "Receive a webhook mapped to a message in the config file"
+                        .to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/history".to_string(),
+                    description: "Search past notifications by chat, time and status".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/history/{id}".to_string(),
+                    description: "Look up a past notification by delivery ID".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/history/export".to_string(),
+                    description: "Export the notification history as JSONL or CSV".to_string(),
+                },
+                EndpointInfo {
+                    method: "DELETE".to_string(),
+                    path: "/history/{id}".to_string(),
+                    description: "Remove a past notification from the history".to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/history/{id}/resend".to_string(),
+                    description: "Deliver a past notification again".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/messages/{id}".to_string(),
+                    description: "Delivery state of a notification".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/n/{id}".to_string(),
+                    description: "HTML page with a notification's full text".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/scheduled".to_string(),
+                    description: "Notifications waiting for their send_at time".to_string(),
+                },
+                EndpointInfo {
+                    method: "DELETE".to_string(),
+                    path: "/scheduled/{id}".to_string(),
+                    description: "Cancel a scheduled notification".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/schedules".to_string(),
+                    description: "Recurring notifications and their next runs".to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/schedules".to_string(),
+                    description: "Create a recurring notification from a cron expression"
+                        .to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/schedules/{id}".to_string(),
+                    description: "Look up a recurring notification".to_string(),
+                },
+                EndpointInfo {
+                    method: "DELETE".to_string(),
+                    path: "/schedules/{id}".to_string(),
+                    description: "Delete a recurring notification".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/keys/{id}/usage".to_string(),
+                    description: "Request counts and quota of a named API key".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/livez".to_string(),
+                    description: "Liveness probe: the process is up".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/readyz".to_string(),
+                    description: "Readiness probe: bot verified, queue and storage usable"
+                        .to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/stats".to_string(),
+                    description: "Delivery counts since startup, queue depth and last error"
+                        .to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/ratelimits".to_string(),
+                    description: "Rate limiter state and recent Telegram 429s (admin key)"
+                        .to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/admin/config/sync".to_string(),
+                    description:
+                        "Pull the routing script from the config repository now (admin key)"
+                            .to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/keys".to_string(),
+                    description: "List API keys created at runtime (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/admin/keys".to_string(),
+                    description: "Create an API key (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/keys/{id}".to_string(),
+                    description: "Look up an API key (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "PUT".to_string(),
+                    path: "/admin/keys/{id}".to_string(),
+                    description: "Create or update an API key (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "DELETE".to_string(),
+                    path: "/admin/keys/{id}".to_string(),
+                    description: "Revoke an API key (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/targets".to_string(),
+                    description: "List named chats (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/targets/{name}".to_string(),
+                    description: "Look up a named chat (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "PUT".to_string(),
+                    path: "/admin/targets/{name}".to_string(),
+                    description: "Create or update a named chat (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "DELETE".to_string(),
+                    path: "/admin/targets/{name}".to_string(),
+                    description: "Remove a named chat (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/silences".to_string(),
+                    description: "List silenced chats (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/silences/{chat}".to_string(),
+                    description: "Look up a chat's silence (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "PUT".to_string(),
+                    path: "/admin/silences/{chat}".to_string(),
+                    description: "Silence a chat (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "DELETE".to_string(),
+                    path: "/admin/silences/{chat}".to_string(),
+                    description: "Lift a chat's silence (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/config".to_string(),
+                    description: "Show the settings in effect (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "GET".to_string(),
+                    path: "/admin/dlq".to_string(),
+                    description: "List notifications that failed for good (admin key)".to_string(),
+                },
+                EndpointInfo {
+                    method: "POST".to_string(),
+                    path: "/admin/dlq/{id}/retry".to_string(),
+                    description: "Queue a failed notification again (admin key)".to_string(),
+                },
             ],
         }
     }
@@ -117,6 +923,9 @@ mod tests {
         assert_eq!(request.chat_id, None);
         assert_eq!(request.parse_mode, None);
         assert_eq!(request.disable_notification, None);
+        assert_eq!(request.translations, None);
+        assert_eq!(request.async_delivery, None);
+        assert_eq!(request.dedup_key, None);
     }
 
     #[test]
@@ -125,7 +934,10 @@ mod tests {
             "message": "Test message",
             "chat_id": "123456789",
             "parse_mode": "Markdown",
-            "disable_notification": true
+            "disable_notification": true,
+            "translations": {"de": "Testnachricht"},
+            "async": true,
+            "dedup_key": "deploy-1234"
         }"#;
         let request: SendNotificationRequest = serde_json::from_str(json).unwrap();
 
@@ -133,6 +945,12 @@ mod tests {
         assert_eq!(request.chat_id, Some("123456789".to_string()));
         assert_eq!(request.parse_mode, Some("Markdown".to_string()));
         assert_eq!(request.disable_notification, Some(true));
+        assert_eq!(
+            request.translations.unwrap().get("de"),
+            Some(&"Testnachricht".to_string())
+        );
+        assert_eq!(request.async_delivery, Some(true));
+        assert_eq!(request.dedup_key, Some("deploy-1234".to_string()));
     }
 
     #[test]
@@ -142,12 +960,26 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_notify_query_deserialization() {
+        let query: NotifyQuery = serde_json::from_str(r#"{"mode": "async"}"#).unwrap();
+        assert_eq!(query.mode, Some(DeliveryMode::Async));
+
+        let query: NotifyQuery = serde_json::from_str(r#"{}"#).unwrap();
+        assert_eq!(query.mode, None);
+
+        let result: Result<NotifyQuery, _> = serde_json::from_str(r#"{"mode": "later"}"#);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_send_notification_response_serialization() {
         let response = SendNotificationResponse {
             success: true,
             message: "Notification sent successfully".to_string(),
             telegram_message_id: Some(42),
+            delivery_id: Some("abc123def456".to_string()),
+            results: Vec::new(),
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -156,6 +988,7 @@ mod tests {
         assert_eq!(parsed["success"], true);
         assert_eq!(parsed["message"], "Notification sent successfully");
         assert_eq!(parsed["telegram_message_id"], 42);
+        assert_eq!(parsed["delivery_id"], "abc123def456");
     }
 
     #[test]
@@ -164,6 +997,8 @@ mod tests {
             success: true,
             message: "Notification sent successfully".to_string(),
             telegram_message_id: None,
+            delivery_id: None,
+            results: Vec::new(),
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -172,6 +1007,122 @@ mod tests {
         assert_eq!(parsed["success"], true);
         assert_eq!(parsed["message"], "Notification sent successfully");
         assert!(parsed.get("telegram_message_id").is_none());
+        assert!(parsed.get("delivery_id").is_none());
+    }
+
+    #[test]
+    fn test_fan_out_request() {
+        let json = r#"{"message": "Deploy done", "chat_ids": ["-100", "-200"], "chats": ["ops"]}"#;
+        let request: SendNotificationRequest = serde_json::from_str(json).unwrap();
+
+        assert!(request.is_fan_out());
+        assert_eq!(
+            request.chat_ids,
+            Some(vec!["-100".to_string(), "-200".to_string()])
+        );
+        assert_eq!(request.chats, Some(vec!["ops".to_string()]));
+
+        let single: SendNotificationRequest =
+            serde_json::from_str(r#"{"message": "hi", "chat_id": "-100"}"#).unwrap();
+        assert!(!single.is_fan_out());
+    }
+
+    #[test]
+    fn test_chat_result_from_outcome() {
+        let (status, result) = ChatResult::from_outcome(
+            "-100".to_string(),
+            Err((
+                StatusCode::BAD_GATEWAY,
+                Json(ErrorResponse::with_code(
+                    "chat not found".to_string(),
+                    "TELEGRAM_API_ERROR".to_string(),
+                )),
+            )),
+        );
+
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert!(!result.success);
+        assert_eq!(result.message, "chat not found");
+        assert_eq!(result.code, Some("TELEGRAM_API_ERROR".to_string()));
+    }
+
+    #[test]
+    fn test_message_status_from_failed_record() {
+        let history = crate::history::History::new(10);
+        let notification = crate::pipeline::Notification {
+            message: "Disk full".to_string(),
+            chat_id: "-100".to_string(),
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        };
+        history.record(&notification, DeliveryStatus::Queued, None);
+        history.start_attempt("abc123def456");
+        history.set_status(
+            "abc123def456",
+            DeliveryStatus::Failed {
+                error: "chat not found".to_string(),
+            },
+        );
+
+        let response = MessageStatusResponse::from(history.get("abc123def456").unwrap());
+        let parsed = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(parsed["state"], "failed");
+        assert_eq!(parsed["attempts"], 1);
+        assert_eq!(parsed["last_error"], "chat not found");
+        assert!(parsed.get("telegram_message_id").is_none());
+    }
+
+    #[test]
+    fn test_preview_response_serialization() {
+        let response = PreviewResponse {
+            success: true,
+            delivery_id: Some("abc123def456".to_string()),
+            would_send: true,
+            chat_id: Some("123456789".to_string()),
+            parse_mode: Some("HTML".to_string()),
+            disable_notification: false,
+            parts: vec!["<b>Hi</b>".to_string()],
+            attachments: vec!["build.log".to_string()],
+            suppressed_reason: None,
+            targets: Vec::new(),
+        };
+
+        let json = serde_json::to_string(&response).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed["would_send"], true);
+        assert_eq!(parsed["delivery_id"], "abc123def456");
+        assert_eq!(parsed["chat_id"], "123456789");
+        assert_eq!(parsed["parse_mode"], "HTML");
+        assert_eq!(parsed["parts"][0], "<b>Hi</b>");
+        assert_eq!(parsed["attachments"][0], "build.log");
+        assert!(parsed.get("suppressed_reason").is_none());
+    }
+
+    #[test]
+    fn test_preview_response_serialization_suppressed() {
+        let response = PreviewResponse {
+            success: true,
+            delivery_id: Some("abc123def456".to_string()),
+            would_send: false,
+            chat_id: None,
+            parse_mode: None,
+            disable_notification: false,
+            parts: vec![],
+            attachments: vec![],
+            suppressed_reason: Some("debug noise".to_string()),
+            targets: Vec::new(),
+        };
+
+        let json = serde_json::to_string(&response).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed["would_send"], false);
+        assert!(parsed.get("chat_id").is_none());
+        assert_eq!(parsed["parts"].as_array().unwrap().len(), 0);
+        assert!(parsed.get("attachments").is_none());
+        assert_eq!(parsed["suppressed_reason"], "debug noise");
     }
 
     #[test]
@@ -182,6 +1133,14 @@ mod tests {
             version: "0.1.0".to_string(),
             bot_verified: true,
             bot_username: Some("test_bot".to_string()),
+            circuit_breaker: BreakerState {
+                state: "open",
+                consecutive_failures: 5,
+                retry_in_secs: Some(12),
+            },
+            uptime_secs: 3600,
+            queued_messages: 2,
+            last_successful_send: Some(1_760_601_600),
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -192,6 +1151,11 @@ mod tests {
         assert_eq!(parsed["version"], "0.1.0");
         assert_eq!(parsed["bot_verified"], true);
         assert_eq!(parsed["bot_username"], "test_bot");
+        assert_eq!(parsed["circuit_breaker"]["state"], "open");
+        assert_eq!(parsed["circuit_breaker"]["retry_in_secs"], 12);
+        assert_eq!(parsed["uptime_secs"], 3600);
+        assert_eq!(parsed["queued_messages"], 2);
+        assert_eq!(parsed["last_successful_send"], 1_760_601_600);
     }
 
     #[test]
@@ -202,6 +1166,14 @@ mod tests {
             version: "0.1.0".to_string(),
             bot_verified: true,
             bot_username: None,
+            circuit_breaker: BreakerState {
+                state: "closed",
+                consecutive_failures: 0,
+                retry_in_secs: None,
+            },
+            uptime_secs: 5,
+            queued_messages: 0,
+            last_successful_send: None,
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -209,6 +1181,7 @@ mod tests {
 
         assert_eq!(parsed["bot_verified"], true);
         assert!(parsed.get("bot_username").is_none());
+        assert!(parsed.get("last_successful_send").is_none());
     }
 
     #[test]
@@ -255,7 +1228,7 @@ mod tests {
         assert_eq!(info.name, "Telegram Notifications API");
         assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
         assert_eq!(info.description, "Send notifications via Telegram Bot API");
-        assert_eq!(info.endpoints.len(), 4);
+        assert_eq!(info.endpoints.len(), 46);
 
         // Check specific endpoints
         let root_endpoint = &info.endpoints[0];
@@ -273,6 +1246,10 @@ mod tests {
         let send_endpoint = &info.endpoints[3];
         assert_eq!(send_endpoint.method, "POST");
         assert_eq!(send_endpoint.path, "/send");
+
+        let preview_endpoint = &info.endpoints[4];
+        assert_eq!(preview_endpoint.method, "POST");
+        assert_eq!(preview_endpoint.path, "/notify/preview");
     }
 
     #[test]
@@ -285,7 +1262,19 @@ mod tests {
         assert_eq!(parsed["name"], "Telegram Notifications API");
         assert_eq!(parsed["version"], env!("CARGO_PKG_VERSION"));
         assert!(parsed["endpoints"].is_array());
-        assert_eq!(parsed["endpoints"].as_array().unwrap().len(), 4);
+        assert_eq!(parsed["endpoints"].as_array().unwrap().len(), 46);
+    }
+
+    #[test]
+    fn test_info_response_with_base_path() {
+        let info = InfoResponse::new().with_base_path("/telegram");
+
+        assert_eq!(info.endpoints[0].path, "/telegram");
+        assert_eq!(info.endpoints[2].path, "/telegram/notify");
+        assert_eq!(
+            InfoResponse::new().with_base_path("").endpoints[0].path,
+            "/"
+        );
     }
 
     #[test]
@@ -0,0 +1,939 @@
+use crate::api::ErrorResponse;
+use crate::jwt::{JwtSettings, JwtValidator};
+use crate::keys::{KeyStore, Scope};
+use crate::limits::BodyLimit;
+use crate::quota::{KeyQuotas, KeySettings, QuotaExceeded};
+use axum::BoxError;
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{AUTHORIZATION, RETRY_AFTER, WWW_AUTHENTICATE},
+    },
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use futures_util::{StreamExt, stream};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+
+/// Header carrying an API key, as an alternative to `Authorization: Bearer`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// API keys, JWT validation and request signing for the notification
+/// endpoints (`[auth]` in the config file, plus the TELEGRAM_NOTIFICATIONS_*
+/// environment variables). With none configured the endpoints are open.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    pub api_keys: Vec<String>,
+    /// Keys that may also manage keys at `/admin/keys`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub admin_keys: Vec<String>,
+    /// JSON file keys created through `/admin/keys` are saved to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keys_file: Option<PathBuf>,
+    /// Named keys, whose requests are counted and may be limited
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, KeySettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt: Option<JwtSettings>,
+    /// Shared secret clients sign request bodies with (`X-Signature`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hmac_secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub basic: Option<BasicCredentials>,
+}
+
+/// Username and password accepted with HTTP Basic auth (`[auth.basic]`),
+/// for tools that can't send API keys
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BasicCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl BasicCredentials {
+    /// Whether an `Authorization: Basic` value carries these credentials
+    fn accepts(&self, encoded: &str) -> bool {
+        let Some((username, password)) = BASE64
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .and_then(|decoded| {
+                decoded
+                    .split_once(':')
+                    .map(|(user, pass)| (user.to_string(), pass.to_string()))
+            })
+        else {
+            return false;
+        };
+        // Check both, so timing doesn't tell which one was wrong
+        constant_time_eq(&self.username, &username) & constant_time_eq(&self.password, &password)
+    }
+}
+
+/// Keys a request may authenticate with
+#[derive(Clone, Default)]
+pub struct ApiKeys {
+    keys: Vec<String>,
+    /// Key → name, for named keys
+    names: HashMap<String, String>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            keys: keys.into_iter().filter(|key| !key.is_empty()).collect(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Also accept named keys, given as `(name, key)`
+    pub fn with_named(mut self, keys: impl IntoIterator<Item = (String, String)>) -> Self {
+        for (name, key) in keys.into_iter().filter(|(_, key)| !key.is_empty()) {
+            self.keys.push(key.clone());
+            self.names.insert(key, name);
+        }
+        self
+    }
+
+    /// Name of an accepted key, if it has one
+    pub fn name_of(&self, key: &str) -> Option<&str> {
+        self.names.get(key).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn accepts(&self, key: &str) -> bool {
+        // Compare every key in full so response times don't leak a prefix match
+        self.keys.iter().fold(false, |found, allowed| {
+            constant_time_eq(allowed, key) | found
+        })
+    }
+}
+
+/// Key from `X-Api-Key` or an `Authorization: Bearer` header
+pub fn api_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        return key.to_str().ok().map(str::trim);
+    }
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// `X-Signature` value for a body signed with `secret`: `sha256=` and the
+/// hex HMAC-SHA256 of `<timestamp>.<body>`, or of the body alone when no
+/// X-Timestamp is sent
+pub fn sign(secret: &str, timestamp: Option<&str>, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(mac(secret, timestamp, body).finalize().into_bytes())
+    )
+}
+
+fn mac(secret: &str, timestamp: Option<&str>, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    if let Some(timestamp) = timestamp {
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+    }
+    mac.update(body);
+    mac
+}
+
+/// Verifies `X-Signature` headers against the shared secret
+pub struct SignatureVerifier {
+    secret: String,
+    max_body_bytes: usize,
+}
+
+impl SignatureVerifier {
+    /// Verifier reading at most `max_body_bytes` of a body to check it
+    pub fn new(secret: String, max_body_bytes: usize) -> Self {
+        Self {
+            secret,
+            max_body_bytes,
+        }
+    }
+
+    /// Whether `signature` (as produced by [`sign`]) matches, in constant time
+    pub fn verify(&self, signature: &str, timestamp: Option<&str>, body: &[u8]) -> bool {
+        let Some(Ok(expected)) = signature.trim().strip_prefix("sha256=").map(hex::decode) else {
+            return false;
+        };
+        mac(&self.secret, timestamp, body)
+            .verify_slice(&expected)
+            .is_ok()
+    }
+
+    /// Buffer the body and check the request's signature, handing back a
+    /// request with the same body. Bodies of streamed routes aren't buffered:
+    /// they are checked as they pass through, see [`verified_body`].
+    async fn check(&self, signature: &str, request: Request) -> Result<Request, Rejection> {
+        let timestamp = header(request.headers(), TIMESTAMP_HEADER);
+        let (parts, body) = request.into_parts();
+        if parts
+            .extensions
+            .get::<BodyLimit>()
+            .is_some_and(|limit| limit.streamed)
+        {
+            let Some(Ok(expected)) = signature.trim().strip_prefix("sha256=").map(hex::decode)
+            else {
+                return Err(signature_mismatch());
+            };
+            let mac = mac(&self.secret, timestamp.as_deref(), &[]);
+            return Ok(Request::from_parts(
+                parts,
+                verified_body(body, mac, expected),
+            ));
+        }
+        let body = to_bytes(body, self.max_body_bytes).await.map_err(|_| {
+            (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "PAYLOAD_TOO_LARGE",
+                format!(
+                    "Request body exceeds the {} byte limit",
+                    self.max_body_bytes
+                ),
+            )
+        })?;
+        if !self.verify(signature, timestamp.as_deref(), &body) {
+            return Err(signature_mismatch());
+        }
+        Ok(Request::from_parts(parts, Body::from(body)))
+    }
+}
+
+/// A body that didn't match its signature, found once it was read to the end
+#[derive(Debug)]
+pub struct InvalidSignature;
+
+impl fmt::Display for InvalidSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Request signature does not match the body")
+    }
+}
+
+impl std::error::Error for InvalidSignature {}
+
+/// `body`, feeding every chunk to `mac` as it passes and failing with
+/// [`InvalidSignature`] at its end unless the MAC matches `expected`. Whoever
+/// reads the body only sees its end, and so accepts it, once it checked out.
+fn verified_body(body: Body, mac: Hmac<Sha256>, expected: Vec<u8>) -> Body {
+    let stream = stream::unfold(
+        Some((body.into_data_stream(), mac, expected)),
+        |state| async move {
+            let (mut chunks, mut mac, expected) = state?;
+            match chunks.next().await {
+                Some(Ok(chunk)) => {
+                    mac.update(&chunk);
+                    Some((Ok(chunk), Some((chunks, mac, expected))))
+                }
+                Some(Err(e)) => Some((Err(BoxError::from(e)), None)),
+                None => match mac.verify_slice(&expected) {
+                    Ok(()) => None,
+                    Err(_) => Some((Err(BoxError::from(InvalidSignature)), None)),
+                },
+            }
+        },
+    );
+    Body::from_stream(stream)
+}
+
+fn signature_mismatch() -> Rejection {
+    (
+        StatusCode::UNAUTHORIZED,
+        "INVALID_SIGNATURE",
+        InvalidSignature.to_string(),
+    )
+}
+
+/// The response to a body that didn't match its signature
+pub fn invalid_signature() -> Response {
+    let (status, code, message) = signature_mismatch();
+    (
+        status,
+        Json(ErrorResponse::with_code(message, code.to_string())),
+    )
+        .into_response()
+}
+
+type Rejection = (StatusCode, &'static str, String);
+
+/// Who an accepted request comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    /// Name of a named key (`[auth.keys.<name>]`), or ID of a managed one
+    pub key_name: Option<String>,
+    pub scope: Scope,
+}
+
+impl Caller {
+    fn send() -> Self {
+        Self {
+            key_name: None,
+            scope: Scope::Send,
+        }
+    }
+}
+
+/// Credentials requests may authenticate with: static API keys and,
+/// optionally, JWTs from an identity provider and signed bodies
+pub struct Authenticator {
+    keys: ApiKeys,
+    admin_keys: ApiKeys,
+    managed: KeyStore,
+    jwt: Option<JwtValidator>,
+    signatures: Option<SignatureVerifier>,
+    basic: Option<BasicCredentials>,
+    quotas: KeyQuotas,
+}
+
+impl Authenticator {
+    pub fn new(keys: ApiKeys, jwt: Option<JwtValidator>) -> Self {
+        Self {
+            keys,
+            admin_keys: ApiKeys::new(Vec::new()),
+            managed: KeyStore::default(),
+            jwt,
+            signatures: None,
+            basic: None,
+            quotas: KeyQuotas::default(),
+        }
+    }
+
+    /// Also accept these keys, with the admin scope
+    pub fn with_admin_keys(mut self, keys: ApiKeys) -> Self {
+        self.admin_keys = keys;
+        self
+    }
+
+    /// Also accept keys created through the admin API
+    pub fn with_managed_keys(mut self, store: KeyStore) -> Self {
+        self.managed = store;
+        self
+    }
+
+    /// Count requests by named keys, refusing those over their quota
+    pub fn with_quotas(mut self, quotas: KeyQuotas) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
+    /// Also accept requests with a valid `X-Signature`
+    pub fn with_signatures(mut self, signatures: SignatureVerifier) -> Self {
+        self.signatures = Some(signatures);
+        self
+    }
+
+    /// Also accept HTTP Basic auth with these credentials
+    pub fn with_basic(mut self, credentials: BasicCredentials) -> Self {
+        self.basic = Some(credentials);
+        self
+    }
+
+    /// Whether any credentials are configured; otherwise requests go unchecked
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+            || !self.admin_keys.is_empty()
+            || !self.managed.is_empty()
+            || self.jwt.is_some()
+            || self.signatures.is_some()
+            || self.basic.is_some()
+    }
+
+    /// Accept a request carrying a known API key, a valid JWT or the Basic
+    /// auth credentials
+    async fn check(&self, headers: &HeaderMap) -> Result<Caller, (&'static str, String)> {
+        let basic = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Basic "));
+        if let (Some(credentials), Some(encoded)) = (&self.basic, basic) {
+            return if credentials.accepts(encoded) {
+                Ok(Caller::send())
+            } else {
+                Err((
+                    "INVALID_CREDENTIALS",
+                    "Username or password is not valid".to_string(),
+                ))
+            };
+        }
+
+        let Some(credential) = api_key(headers) else {
+            return Err((
+                "MISSING_API_KEY",
+                "An API key or token is required in X-Api-Key or Authorization: Bearer".to_string(),
+            ));
+        };
+        if self.admin_keys.accepts(credential) {
+            return Ok(Caller {
+                key_name: None,
+                scope: Scope::Admin,
+            });
+        }
+        if self.keys.accepts(credential) {
+            return Ok(Caller {
+                key_name: self.keys.name_of(credential).map(str::to_string),
+                scope: Scope::Send,
+            });
+        }
+        if let Some(managed) = self.managed.find(credential) {
+            return Ok(Caller {
+                key_name: Some(managed.id),
+                scope: managed.scope,
+            });
+        }
+        match &self.jwt {
+            Some(jwt) => jwt
+                .validate(credential)
+                .await
+                .map(|_| Caller::send())
+                .map_err(|e| ("INVALID_TOKEN", format!("{e:#}"))),
+            None => Err(("INVALID_API_KEY", "API key is not valid".to_string())),
+        }
+    }
+}
+
+/// Middleware rejecting requests without a valid API key, token or signature.
+/// Accepted requests carry the caller's [`Scope`] in their extensions.
+pub async fn require_auth(
+    State(auth): State<Arc<Authenticator>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let signature = header(request.headers(), SIGNATURE_HEADER);
+    let result = match (&auth.signatures, signature) {
+        // Signed requests are judged by their signature alone
+        (Some(signatures), Some(signature)) => signatures
+            .check(&signature, request)
+            .await
+            .map(|request| (request, Caller::send())),
+        _ => {
+            let checked = auth.check(request.headers()).await;
+            checked
+                .map(|caller| (request, caller))
+                .map_err(|(code, message)| (StatusCode::UNAUTHORIZED, code, message))
+        }
+    };
+    let (status, code, message) = match result {
+        Ok((mut request, caller)) => {
+            if let Some(name) = &caller.key_name
+                && let Err(exceeded) = auth.quotas.record(name, unix_now())
+            {
+                return quota_exceeded(name, exceeded);
+            }
+            request.extensions_mut().insert(caller.scope);
+            return next.run(request).await;
+        }
+        Err(rejection) => rejection,
+    };
+
+    warn!(
+        "🚫 Rejected unauthenticated request to {}: {}",
+        path, message
+    );
+    let mut response = (
+        status,
+        Json(ErrorResponse::with_code(message, code.to_string())),
+    )
+        .into_response();
+    let headers = response.headers_mut();
+    headers.insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    if auth.basic.is_some() {
+        // Lets browsers and legacy tools prompt for the credentials
+        headers.append(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static(r#"Basic realm="telegram-notifications", charset="UTF-8""#),
+        );
+    }
+    response
+}
+
+/// Middleware letting only admin-scoped callers through; runs after
+/// [`require_auth`], so requests without credentials never get here unchecked
+pub async fn require_admin(request: Request, next: Next) -> Response {
+    if request.extensions().get::<Scope>() == Some(&Scope::Admin) {
+        return next.run(request).await;
+    }
+    warn!(
+        "🚫 Rejected request to {} without an admin key",
+        request.uri().path()
+    );
+    (
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse::with_code(
+            "This endpoint needs an admin key".to_string(),
+            "ADMIN_KEY_REQUIRED".to_string(),
+        )),
+    )
+        .into_response()
+}
+
+fn quota_exceeded(name: &str, exceeded: QuotaExceeded) -> Response {
+    warn!(
+        "🚦 Key {} is over its quota of {} requests per {}",
+        name, exceeded.limit, exceeded.window
+    );
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(ErrorResponse::with_code(
+            format!(
+                "API key {name} exceeded its quota of {} requests per {}",
+                exceeded.limit, exceeded.window
+            ),
+            "QUOTA_EXCEEDED".to_string(),
+        )),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(exceeded.retry_after_secs));
+    response
+}
+
+/// Rejects signed requests whose timestamp is outside the allowed skew window,
+/// and signatures that were already used within that window.
+pub struct ReplayGuard {
+    window: Duration,
+    seen: Mutex<HashMap<String, u64>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayError {
+    MissingTimestamp,
+    InvalidTimestamp,
+    OutsideWindow,
+    Replayed,
+}
+
+impl ReplayError {
+    fn code(self) -> &'static str {
+        match self {
+            ReplayError::MissingTimestamp => "MISSING_TIMESTAMP",
+            ReplayError::InvalidTimestamp => "INVALID_TIMESTAMP",
+            ReplayError::OutsideWindow => "TIMESTAMP_OUT_OF_WINDOW",
+            ReplayError::Replayed => "REPLAYED_REQUEST",
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            ReplayError::MissingTimestamp => "Signed requests require an X-Timestamp header",
+            ReplayError::InvalidTimestamp => "X-Timestamp must be a Unix timestamp in seconds",
+            ReplayError::OutsideWindow => "Request timestamp is outside the allowed window",
+            ReplayError::Replayed => "Request signature has already been used",
+        }
+    }
+}
+
+impl ReplayGuard {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check a signature/timestamp pair against the clock and the seen set,
+    /// remembering the signature if it is accepted
+    pub fn check(
+        &self,
+        signature: &str,
+        timestamp: Option<&str>,
+        now: u64,
+    ) -> Result<(), ReplayError> {
+        let timestamp: u64 = timestamp
+            .ok_or(ReplayError::MissingTimestamp)?
+            .trim()
+            .parse()
+            .map_err(|_| ReplayError::InvalidTimestamp)?;
+
+        let window = self.window.as_secs();
+        if timestamp.abs_diff(now) > window {
+            return Err(ReplayError::OutsideWindow);
+        }
+
+        let signature = replay_key(signature);
+        let mut seen = self.seen.lock().unwrap();
+        // Signatures older than the window can't pass the timestamp check anymore
+        seen.retain(|_, seen_at| seen_at.abs_diff(now) <= window);
+        if seen.contains_key(&signature) {
+            return Err(ReplayError::Replayed);
+        }
+        seen.insert(signature, timestamp);
+        Ok(())
+    }
+}
+
+/// The MAC a signature carries, as lowercase hex, so the same signature
+/// spelled in another case or without `sha256=` counts as seen
+fn replay_key(signature: &str) -> String {
+    let signature = signature.trim();
+    let hex = signature.strip_prefix("sha256=").unwrap_or(signature);
+    match hex::decode(hex) {
+        Ok(mac) => hex::encode(mac),
+        Err(_) => signature.to_string(),
+    }
+}
+
+/// Middleware applying the replay guard to requests carrying an `X-Signature` header
+pub async fn replay_protection(
+    State(guard): State<Arc<ReplayGuard>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(signature) = header(request.headers(), SIGNATURE_HEADER) {
+        let timestamp = header(request.headers(), TIMESTAMP_HEADER);
+        if let Err(e) = guard.check(&signature, timestamp.as_deref(), unix_now()) {
+            warn!("🚫 Rejected signed request: {}", e.message());
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::with_code(
+                    e.message().to_string(),
+                    e.code().to_string(),
+                )),
+            )
+                .into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::Quota;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn guard() -> ReplayGuard {
+        ReplayGuard::new(Duration::from_secs(300))
+    }
+
+    #[test]
+    fn test_accepts_fresh_signature() {
+        assert_eq!(guard().check("sig", Some("1700000000"), NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_accepts_skew_within_window() {
+        let guard = guard();
+        assert_eq!(guard.check("a", Some("1699999800"), NOW), Ok(()));
+        assert_eq!(guard.check("b", Some("1700000200"), NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_missing_timestamp() {
+        assert_eq!(
+            guard().check("sig", None, NOW),
+            Err(ReplayError::MissingTimestamp)
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_timestamp() {
+        assert_eq!(
+            guard().check("sig", Some("yesterday"), NOW),
+            Err(ReplayError::InvalidTimestamp)
+        );
+    }
+
+    #[test]
+    fn test_rejects_timestamp_outside_window() {
+        let guard = guard();
+        assert_eq!(
+            guard.check("old", Some("1699999000"), NOW),
+            Err(ReplayError::OutsideWindow)
+        );
+        assert_eq!(
+            guard.check("future", Some("1700001000"), NOW),
+            Err(ReplayError::OutsideWindow)
+        );
+    }
+
+    #[test]
+    fn test_rejects_replayed_signature() {
+        let guard = guard();
+        assert_eq!(guard.check("sig", Some("1700000000"), NOW), Ok(()));
+        assert_eq!(
+            guard.check("sig", Some("1700000000"), NOW + 10),
+            Err(ReplayError::Replayed)
+        );
+    }
+
+    #[test]
+    fn test_rejects_replayed_signature_in_other_case() {
+        let guard = guard();
+        let signature = sign("secret", Some("1700000000"), b"{}");
+        assert_eq!(guard.check(&signature, Some("1700000000"), NOW), Ok(()));
+        let upper = format!("sha256={}", signature["sha256=".len()..].to_uppercase());
+        assert!(SignatureVerifier::new("secret".to_string(), 1024).verify(
+            &upper,
+            Some("1700000000"),
+            b"{}"
+        ));
+        assert_eq!(
+            guard.check(&upper, Some("1700000000"), NOW + 10),
+            Err(ReplayError::Replayed)
+        );
+    }
+
+    #[test]
+    fn test_forgets_signatures_after_window() {
+        let guard = guard();
+        assert_eq!(guard.check("sig", Some("1700000000"), NOW), Ok(()));
+        guard
+            .check("other", Some("1700001000"), NOW + 1000)
+            .unwrap();
+
+        assert!(!guard.seen.lock().unwrap().contains_key("sig"));
+    }
+
+    #[test]
+    fn test_api_keys_accept_configured_keys_only() {
+        let keys = ApiKeys::new(["ci-key".to_string(), String::new()]);
+
+        assert!(keys.accepts("ci-key"));
+        assert!(!keys.accepts("ci-ke"));
+        assert!(!keys.accepts(""));
+        assert!(ApiKeys::new(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_authenticator_names_keys() {
+        let keys = ApiKeys::new(["ci-key".to_string()])
+            .with_named([("staging".to_string(), "staging-key".to_string())]);
+        let auth = Authenticator::new(keys, None);
+
+        assert_eq!(
+            auth.check(&bearer("staging-key")).await.unwrap().key_name,
+            Some("staging".to_string())
+        );
+        assert_eq!(auth.check(&bearer("ci-key")).await.unwrap().key_name, None);
+    }
+
+    #[tokio::test]
+    async fn test_authenticator_scopes() {
+        let managed = KeyStore::default();
+        let created = managed
+            .create(None, Scope::Admin, Quota::default(), 0)
+            .unwrap();
+        let auth = Authenticator::new(ApiKeys::new(["ci-key".to_string()]), None)
+            .with_admin_keys(ApiKeys::new(["root-key".to_string()]))
+            .with_managed_keys(managed.clone());
+
+        assert_eq!(
+            auth.check(&bearer("root-key")).await.unwrap().scope,
+            Scope::Admin
+        );
+        assert_eq!(
+            auth.check(&bearer("ci-key")).await.unwrap().scope,
+            Scope::Send
+        );
+        let caller = auth.check(&bearer(&created.key)).await.unwrap();
+        assert_eq!(caller.scope, Scope::Admin);
+        assert_eq!(caller.key_name, Some(created.id.clone()));
+        // Revoked keys stop working right away
+        managed.revoke(&created.id).unwrap();
+        assert_eq!(
+            auth.check(&bearer(&created.key)).await.unwrap_err().0,
+            "INVALID_API_KEY"
+        );
+        assert!(
+            Authenticator::new(ApiKeys::new(Vec::new()), None)
+                .with_admin_keys(ApiKeys::new(["root-key".to_string()]))
+                .is_enabled()
+        );
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_authenticator_keys_and_jwt() {
+        use jsonwebtoken::{EncodingKey, Header, encode, get_current_timestamp};
+
+        let jwt = JwtValidator::new(&JwtSettings {
+            secret: Some("shared-secret".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let auth = Authenticator::new(ApiKeys::new(["ci-key".to_string()]), Some(jwt));
+        let token = encode(
+            &Header::default(),
+            &serde_json::json!({"sub": "grafana", "exp": get_current_timestamp() + 60}),
+            &EncodingKey::from_secret(b"shared-secret"),
+        )
+        .unwrap();
+
+        assert!(auth.check(&bearer("ci-key")).await.is_ok());
+        assert!(auth.check(&bearer(&token)).await.is_ok());
+        assert_eq!(
+            auth.check(&bearer("not-a-token")).await.unwrap_err().0,
+            "INVALID_TOKEN"
+        );
+        assert_eq!(
+            auth.check(&HeaderMap::new()).await.unwrap_err().0,
+            "MISSING_API_KEY"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_authenticator_without_jwt() {
+        let auth = Authenticator::new(ApiKeys::new(["ci-key".to_string()]), None);
+
+        assert!(auth.is_enabled());
+        assert_eq!(
+            auth.check(&bearer("other")).await.unwrap_err().0,
+            "INVALID_API_KEY"
+        );
+        assert!(!Authenticator::new(ApiKeys::new(Vec::new()), None).is_enabled());
+    }
+
+    #[tokio::test]
+    async fn test_authenticator_basic_auth() {
+        let auth = Authenticator::new(ApiKeys::new(["ci-key".to_string()]), None).with_basic(
+            BasicCredentials {
+                username: "legacy".to_string(),
+                password: "s3cret:pass".to_string(),
+            },
+        );
+        let basic = |credentials: &str| {
+            let mut headers = HeaderMap::new();
+            let value = format!("Basic {}", BASE64.encode(credentials));
+            headers.insert(AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert!(auth.check(&basic("legacy:s3cret:pass")).await.is_ok());
+        assert_eq!(
+            auth.check(&basic("legacy:wrong")).await.unwrap_err().0,
+            "INVALID_CREDENTIALS"
+        );
+        assert_eq!(
+            auth.check(&basic("no-colon")).await.unwrap_err().0,
+            "INVALID_CREDENTIALS"
+        );
+        // API keys keep working alongside
+        assert!(auth.check(&bearer("ci-key")).await.is_ok());
+        assert!(
+            Authenticator::new(ApiKeys::new(Vec::new()), None)
+                .with_basic(BasicCredentials {
+                    username: "legacy".to_string(),
+                    password: "pass".to_string(),
+                })
+                .is_enabled()
+        );
+    }
+
+    #[test]
+    fn test_signature_covers_timestamp_and_body() {
+        let verifier = SignatureVerifier::new("webhook-secret".to_string(), 1024);
+        let body = br#"{"message":"deploy finished"}"#;
+        let signature = sign("webhook-secret", Some("1700000000"), body);
+
+        assert!(signature.starts_with("sha256="));
+        assert!(verifier.verify(&signature, Some("1700000000"), body));
+        assert!(!verifier.verify(&signature, Some("1700000001"), body));
+        assert!(!verifier.verify(&signature, None, body));
+        assert!(!verifier.verify(&signature, Some("1700000000"), b"{}"));
+        assert!(verifier.verify(&sign("webhook-secret", None, body), None, body));
+        assert!(!verifier.verify(&sign("other", None, body), None, body));
+        assert!(!verifier.verify("sha256=zz", None, body));
+        assert!(!verifier.verify("md5=abc", None, body));
+    }
+
+    #[tokio::test]
+    async fn test_signature_check_keeps_body() {
+        let verifier = SignatureVerifier::new("webhook-secret".to_string(), 1024);
+        let request = Request::builder().body(Body::from("disk full")).unwrap();
+
+        let request = verifier
+            .check(&sign("webhook-secret", None, b"disk full"), request)
+            .await
+            .unwrap();
+
+        let body = to_bytes(request.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"disk full");
+    }
+
+    #[tokio::test]
+    async fn test_signature_check_limits_body() {
+        let verifier = SignatureVerifier::new("webhook-secret".to_string(), 4);
+        let request = Request::builder().body(Body::from("disk full")).unwrap();
+
+        let (status, code, _) = verifier.check("sha256=00", request).await.unwrap_err();
+
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(code, "PAYLOAD_TOO_LARGE");
+    }
+
+    #[test]
+    fn test_api_key_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(api_key(&headers), None);
+
+        headers.insert(AUTHORIZATION, "Bearer ci-key".parse().unwrap());
+        assert_eq!(api_key(&headers), Some("ci-key"));
+
+        // X-Api-Key wins over Authorization
+        headers.insert(API_KEY_HEADER, "other".parse().unwrap());
+        assert_eq!(api_key(&headers), Some("other"));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(ReplayError::Replayed.code(), "REPLAYED_REQUEST");
+        assert_eq!(ReplayError::OutsideWindow.code(), "TIMESTAMP_OUT_OF_WINDOW");
+    }
+}
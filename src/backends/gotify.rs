@@ -0,0 +1,198 @@
+use crate::backends::{TIMEOUT, severity};
+use crate::notifier::Notifier;
+use crate::pipeline::Notification;
+use anyhow::{Result, bail};
+use futures_util::FutureExt;
+use futures_util::future::BoxFuture;
+use reqwest::{Client, Url};
+use serde_json::json;
+
+/// Sends notifications to a Gotify server as one of its applications, with a
+/// title and priority by their severity
+pub struct GotifyBackend {
+    url: Url,
+    token: String,
+    client: Client,
+}
+
+impl GotifyBackend {
+    /// `url` is the server's, e.g. `https://gotify.example.com`, `token` the
+    /// application's token
+    pub fn new(url: Url, token: String) -> Result<Self> {
+        if url.cannot_be_a_base() {
+            bail!("The Gotify URL must be the server's, e.g. https://gotify.example.com");
+        }
+        if token.trim().is_empty() {
+            bail!("A Gotify backend needs an application token");
+        }
+        let client = Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Ok(Self { url, token, client })
+    }
+
+    /// `path` under the server's URL, which may itself have a path
+    fn endpoint(&self, path: &str) -> Url {
+        let mut url = self.url.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().push(path);
+        }
+        url
+    }
+
+    async fn post(&self, notification: &Notification) -> Result<Option<i64>> {
+        let mut payload = json!({
+            "message": notification.message,
+            "priority": priority(notification),
+        });
+        if let Some(title) = title(notification) {
+            payload["title"] = json!(title);
+        }
+        self.client
+            .post(self.endpoint("message"))
+            .header("X-Gotify-Key", &self.token)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(None)
+    }
+}
+
+impl Notifier for GotifyBackend {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<Option<i64>>> {
+        self.post(notification).boxed()
+    }
+
+    /// The server's own health endpoint, which needs no token
+    fn health_check(&self) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.client
+                .get(self.endpoint("health"))
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Gotify priority from 0 (no notification) to 10: emergency notifications
+/// are max, critical ones pop up, silent ones arrive without a sound
+fn priority(notification: &Notification) -> u8 {
+    if notification.emergency {
+        return 10;
+    }
+    let priority = match severity(notification).as_str() {
+        "critical" => 8,
+        "error" => 6,
+        "info" => 2,
+        _ => 5,
+    };
+    if notification.disable_notification {
+        priority.min(2)
+    } else {
+        priority
+    }
+}
+
+/// The severity, capitalized, or none to leave Gotify showing the
+/// application's name
+fn title(notification: &Notification) -> Option<String> {
+    let severity = match severity(notification) {
+        severity if severity.is_empty() && notification.emergency => "emergency".to_string(),
+        severity => severity,
+    };
+    let mut chars = severity.chars();
+    let first = chars.next()?;
+    Some(first.to_uppercase().chain(chars).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn notification(severity: Option<&str>) -> Notification {
+        Notification {
+            message: "🔥 Disk full on web-1".to_string(),
+            chat_id: "desktop".to_string(),
+            severity: severity.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_post() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/gotify/message")
+            .match_header("X-Gotify-Key", "app-token")
+            .match_body(Matcher::Json(json!({
+                "title": "Critical",
+                "message": "🔥 Disk full on web-1",
+                "priority": 8,
+            })))
+            .create_async()
+            .await;
+        let health = server
+            .mock("GET", "/gotify/health")
+            .with_body(r#"{"health":"green","database":"green"}"#)
+            .create_async()
+            .await;
+        let backend = GotifyBackend::new(
+            Url::parse(&format!("{}/gotify/", server.url())).unwrap(),
+            "app-token".to_string(),
+        )
+        .unwrap();
+
+        backend.send(&notification(Some("CRITICAL"))).await.unwrap();
+        backend.health_check().await.unwrap();
+        mock.assert_async().await;
+        health.assert_async().await;
+
+        server
+            .mock("POST", "/gotify/message")
+            .with_status(401)
+            .create_async()
+            .await;
+        assert!(backend.send(&notification(None)).await.is_err());
+        assert!(
+            GotifyBackend::new(
+                Url::parse("https://gotify.example.com").unwrap(),
+                String::new()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_priority_and_title() {
+        let priorities: Vec<_> = [
+            Some("critical"),
+            Some("error"),
+            Some("warning"),
+            Some("info"),
+            None,
+        ]
+        .into_iter()
+        .map(|severity| priority(&notification(severity)))
+        .collect();
+        assert_eq!(priorities, vec![8, 6, 5, 2, 5]);
+
+        let mut silent = notification(Some("critical"));
+        silent.disable_notification = true;
+        assert_eq!(priority(&silent), 2);
+        let mut emergency = notification(None);
+        emergency.emergency = true;
+        assert_eq!(priority(&emergency), 10);
+        assert_eq!(title(&emergency).as_deref(), Some("Emergency"));
+        assert_eq!(
+            title(&notification(Some("warning"))).as_deref(),
+            Some("Warning")
+        );
+        assert_eq!(title(&notification(None)), None);
+    }
+}
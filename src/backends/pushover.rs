@@ -0,0 +1,172 @@
+use crate::backends::{TIMEOUT, severity};
+use crate::notifier::Notifier;
+use crate::pipeline::Notification;
+use anyhow::{Result, bail};
+use futures_util::FutureExt;
+use futures_util::future::BoxFuture;
+use reqwest::Client;
+
+const PUSHOVER_API_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Seconds between the repeats of an emergency notification until it's
+/// acknowledged in the Pushover app
+const EMERGENCY_RETRY: u32 = 60;
+
+/// Seconds after which an unacknowledged emergency notification stops
+/// repeating
+const EMERGENCY_EXPIRE: u32 = 3600;
+
+/// Pushes notifications to a Pushover user or group, with a priority by
+/// their severity
+pub struct PushoverBackend {
+    token: String,
+    user: String,
+    api_url: String,
+    client: Client,
+}
+
+impl PushoverBackend {
+    /// `token` is the application's API token, `user` the user or group key
+    pub fn new(token: String, user: String) -> Result<Self> {
+        Self::with_api_url(token, user, PUSHOVER_API_URL)
+    }
+
+    pub fn with_api_url(token: String, user: String, api_url: &str) -> Result<Self> {
+        if token.trim().is_empty() || user.trim().is_empty() {
+            bail!("A Pushover backend needs an application token and a user key");
+        }
+        let client = Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Ok(Self {
+            token,
+            user,
+            api_url: api_url.to_string(),
+            client,
+        })
+    }
+
+    async fn push(&self, notification: &Notification) -> Result<Option<i64>> {
+        let priority = priority(notification);
+        let mut form = vec![
+            ("token", self.token.clone()),
+            ("user", self.user.clone()),
+            ("message", notification.message.clone()),
+            ("priority", priority.to_string()),
+        ];
+        if priority == 2 {
+            form.push(("retry", EMERGENCY_RETRY.to_string()));
+            form.push(("expire", EMERGENCY_EXPIRE.to_string()));
+        }
+        if notification
+            .parse_mode
+            .as_deref()
+            .is_some_and(|mode| mode.eq_ignore_ascii_case("HTML"))
+        {
+            form.push(("html", "1".to_string()));
+        }
+        self.client
+            .post(&self.api_url)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(None)
+    }
+}
+
+impl Notifier for PushoverBackend {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<Option<i64>>> {
+        self.push(notification).boxed()
+    }
+
+    /// Pushover has nothing to check without pushing
+    fn health_check(&self) -> BoxFuture<'_, Result<()>> {
+        async { Ok(()) }.boxed()
+    }
+}
+
+/// Pushover priority from -2 (lowest) to 2 (emergency): emergency
+/// notifications repeat until acknowledged, critical ones are high, silent
+/// ones arrive without a sound
+fn priority(notification: &Notification) -> i8 {
+    if notification.emergency {
+        return 2;
+    }
+    let priority = match severity(notification).as_str() {
+        "critical" => 1,
+        "info" => -1,
+        _ => 0,
+    };
+    if notification.disable_notification {
+        priority.min(-1)
+    } else {
+        priority
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn notification(severity: Option<&str>) -> Notification {
+        Notification {
+            message: "🔥 Disk full on web-1".to_string(),
+            chat_id: "pager".to_string(),
+            severity: severity.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/1/messages.json")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("token".to_string(), "app-token".to_string()),
+                Matcher::UrlEncoded("user".to_string(), "user-key".to_string()),
+                Matcher::UrlEncoded("message".to_string(), "🔥 Disk full on web-1".to_string()),
+                Matcher::UrlEncoded("priority".to_string(), "2".to_string()),
+                Matcher::UrlEncoded("retry".to_string(), "60".to_string()),
+            ]))
+            .with_body(r#"{"status":1,"request":"647d2300"}"#)
+            .create_async()
+            .await;
+        let backend = PushoverBackend::with_api_url(
+            "app-token".to_string(),
+            "user-key".to_string(),
+            &format!("{}/1/messages.json", server.url()),
+        )
+        .unwrap();
+
+        let mut emergency = notification(None);
+        emergency.emergency = true;
+        backend.send(&emergency).await.unwrap();
+        mock.assert_async().await;
+
+        server
+            .mock("POST", "/1/messages.json")
+            .with_status(400)
+            .with_body(r#"{"user":"invalid","status":0}"#)
+            .create_async()
+            .await;
+        assert!(backend.send(&notification(None)).await.is_err());
+        assert!(PushoverBackend::new("app-token".to_string(), " ".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_priority() {
+        let priorities: Vec<_> = [Some("critical"), Some("error"), Some("info"), None]
+            .into_iter()
+            .map(|severity| priority(&notification(severity)))
+            .collect();
+        assert_eq!(priorities, vec![1, 0, -1, 0]);
+
+        let mut silent = notification(Some("critical"));
+        silent.disable_notification = true;
+        assert_eq!(priority(&silent), -1);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::pipeline::Notification;
+use crate::telegram::TelegramBot;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Another bot notifications can be sent with, e.g. a team's own
+/// (`[bots.<name>]` in the config file)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotSettings {
+    pub token: String,
+    /// Chats (IDs or aliases) sent to with this bot; when set, the bot sends
+    /// to no others and the first is its default chat
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chats: Vec<String>,
+    /// API keys whose requests are sent with this bot, and only with it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+}
+
+/// Why a request can't be sent with the bot it names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotError {
+    Unknown(String),
+    /// The API key is bound to another bot
+    KeyBound(String),
+    /// The bot only sends to its own chats
+    ChatNotAllowed {
+        bot: String,
+        chat_id: String,
+    },
+}
+
+impl BotError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            BotError::Unknown(_) => "UNKNOWN_BOT",
+            BotError::KeyBound(_) => "BOT_NOT_ALLOWED",
+            BotError::ChatNotAllowed { .. } => "CHAT_NOT_ALLOWED",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            BotError::Unknown(name) => format!("Unknown bot: {name}"),
+            BotError::KeyBound(name) => format!("This API key can only send with bot {name}"),
+            BotError::ChatNotAllowed { bot, chat_id } => {
+                format!("Bot {bot} doesn't send to chat {chat_id}")
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct NamedBot {
+    bot: TelegramBot,
+    chats: Vec<String>,
+    keys: Vec<String>,
+}
+
+/// The main bot and the named ones. A notification is sent with the bot it
+/// names, else the bot whose chats it goes to, else the main bot. Cloning
+/// shares the same bots.
+#[derive(Clone)]
+pub struct Bots {
+    default: TelegramBot,
+    named: Arc<BTreeMap<String, NamedBot>>,
+}
+
+impl Bots {
+    pub fn new(default: TelegramBot) -> Self {
+        Self {
+            default,
+            named: Arc::default(),
+        }
+    }
+
+    /// Add a named bot; `chats` are chat IDs, not aliases
+    pub fn with_bot(
+        mut self,
+        name: &str,
+        bot: TelegramBot,
+        chats: Vec<String>,
+        keys: Vec<String>,
+    ) -> Self {
+        Arc::make_mut(&mut self.named).insert(name.to_string(), NamedBot { bot, chats, keys });
+        self
+    }
+
+    /// The bot of `--bot-token`
+    pub fn main(&self) -> &TelegramBot {
+        &self.default
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.named.keys().map(String::as_str)
+    }
+
+    /// The bot a request sends with: the one its API key is bound to, which
+    /// it may name but not change, or else the one it names
+    pub fn select(
+        &self,
+        key: Option<&str>,
+        requested: Option<&str>,
+    ) -> Result<Option<String>, BotError> {
+        let bound = key.and_then(|key| {
+            self.named
+                .iter()
+                .find(|(_, named)| named.keys.iter().any(|allowed| allowed == key))
+                .map(|(name, _)| name)
+        });
+        match (bound, requested) {
+            (Some(bound), Some(requested)) if bound != requested => {
+                Err(BotError::KeyBound(bound.clone()))
+            }
+            (Some(bound), _) => Ok(Some(bound.clone())),
+            (None, Some(requested)) if !self.named.contains_key(requested) => {
+                Err(BotError::Unknown(requested.to_string()))
+            }
+            (None, requested) => Ok(requested.map(str::to_string)),
+        }
+    }
+
+    /// Fail if `bot` keeps to chats other than `chat_id`
+    pub fn check_chat(&self, bot: Option<&str>, chat_id: &str) -> Result<(), BotError> {
+        let Some((name, named)) = bot.and_then(|name| self.named.get_key_value(name)) else {
+            return Ok(());
+        };
+        if named.chats.is_empty() || named.chats.iter().any(|chat| chat == chat_id) {
+            Ok(())
+        } else {
+            Err(BotError::ChatNotAllowed {
+                bot: name.clone(),
+                chat_id: chat_id.to_string(),
+            })
+        }
+    }
+
+    /// First chat of a bot that keeps to its own chats
+    pub fn default_chat(&self, bot: Option<&str>) -> Option<&str> {
+        self.named.get(bot?)?.chats.first().map(String::as_str)
+    }
+
+    /// The bot sending to `chat_id` when none is named: the one keeping to
+    /// that chat, else the main bot
+    pub fn for_chat(&self, chat_id: &str) -> &TelegramBot {
+        self.named
+            .values()
+            .find(|named| named.chats.iter().any(|chat| chat == chat_id))
+            .map_or(&self.default, |named| &named.bot)
+    }
+
+    /// Name of the bot `notification` is sent with; None for the main bot
+    pub fn name_for<'a>(&'a self, notification: &'a Notification) -> Option<&'a str> {
+        match notification.bot.as_deref() {
+            Some(name) => Some(name),
+            None => self
+                .named
+                .iter()
+                .find(|(_, named)| named.chats.contains(&notification.chat_id))
+                .map(|(name, _)| name.as_str()),
+        }
+    }
+
+    /// The bot `notification` is sent with
+    pub fn for_notification(&self, notification: &Notification) -> &TelegramBot {
+        let Some(name) = self.name_for(notification) else {
+            return &self.default;
+        };
+        match self.named.get(name) {
+            Some(named) => &named.bot,
+            None => {
+                // Queued before the bot was removed from the config
+                warn!(
+                    "⚠️ Bot {} of notification {} is no longer configured; sending with the main bot",
+                    name, notification.delivery_id
+                );
+                &self.default
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bots() -> Bots {
+        Bots::new(TelegramBot::new("1:main".to_string()))
+            .with_bot(
+                "billing",
+                TelegramBot::new("2:billing".to_string()),
+                vec!["-100200".to_string(), "-100201".to_string()],
+                vec!["billing-key".to_string()],
+            )
+            .with_bot(
+                "ops",
+                TelegramBot::new("3:ops".to_string()),
+                Vec::new(),
+                Vec::new(),
+            )
+    }
+
+    #[test]
+    fn test_select() {
+        let bots = bots();
+        assert_eq!(bots.select(None, None), Ok(None));
+        assert_eq!(
+            bots.select(Some("other-key"), Some("ops")),
+            Ok(Some("ops".to_string()))
+        );
+        assert_eq!(
+            bots.select(None, Some("marketing")),
+            Err(BotError::Unknown("marketing".to_string()))
+        );
+        // Keys bound to a bot always send with it
+        assert_eq!(
+            bots.select(Some("billing-key"), None),
+            Ok(Some("billing".to_string()))
+        );
+        assert_eq!(
+            bots.select(Some("billing-key"), Some("billing")),
+            Ok(Some("billing".to_string()))
+        );
+        assert_eq!(
+            bots.select(Some("billing-key"), Some("ops")),
+            Err(BotError::KeyBound("billing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_chats() {
+        let bots = bots();
+        assert_eq!(bots.check_chat(Some("billing"), "-100201"), Ok(()));
+        assert_eq!(
+            bots.check_chat(Some("billing"), "-100999")
+                .unwrap_err()
+                .code(),
+            "CHAT_NOT_ALLOWED"
+        );
+        assert_eq!(bots.check_chat(Some("ops"), "-100999"), Ok(()));
+        assert_eq!(bots.check_chat(None, "-100999"), Ok(()));
+        assert_eq!(bots.default_chat(Some("billing")), Some("-100200"));
+        assert_eq!(bots.default_chat(Some("ops")), None);
+
+        // Without a bot named, a chat of a bot's own is sent to with it
+        let mut notification = Notification {
+            chat_id: "-100201".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(bots.name_for(&notification), Some("billing"));
+        notification.bot = Some("ops".to_string());
+        assert_eq!(bots.name_for(&notification), Some("ops"));
+        notification.chat_id = "42".to_string();
+        notification.bot = None;
+        assert_eq!(bots.name_for(&notification), None);
+        assert!(!std::ptr::eq(bots.for_chat("-100201"), bots.main()));
+        assert!(std::ptr::eq(bots.for_chat("42"), bots.main()));
+    }
+}
@@ -0,0 +1,238 @@
+use serde::Serialize;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Consecutive failed Telegram requests that open the breaker
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open breaker fails requests before letting one through again
+pub const DEFAULT_COOLDOWN_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Closed,
+    /// Failing fast until `until`
+    Open {
+        until: Instant,
+    },
+    /// A trial request is out; the others still fail fast
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: State,
+    consecutive_failures: u32,
+}
+
+/// Returned instead of sending a request while the breaker is open
+#[derive(Debug)]
+pub struct CircuitOpen {
+    /// Time until the breaker lets a request through again
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Telegram API unavailable after repeated failures, retrying in {}s",
+            self.retry_in.as_secs().max(1)
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// State of the breaker, as shown on `/health`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BreakerState {
+    /// closed, open or half_open
+    pub state: &'static str,
+    pub consecutive_failures: u32,
+    /// Seconds until an open breaker lets a trial request through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+}
+
+/// Circuit breaker for the Telegram API: after `threshold` consecutive
+/// failures, requests fail right away for a cool-down period instead of each
+/// waiting for Telegram to time out. Once it has passed, a single trial
+/// request decides whether the breaker closes again. A threshold of 0
+/// disables it. Cloning shares the same state.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_FAILURE_THRESHOLD,
+            Duration::from_secs(DEFAULT_COOLDOWN_SECS),
+        )
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            inner: Arc::new(Mutex::new(Inner {
+                state: State::Closed,
+                consecutive_failures: 0,
+            })),
+        }
+    }
+
+    /// Consecutive failures that open the breaker; 0 if disabled
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Whether a request may be sent now
+    pub fn allow(&self, now: Instant) -> Result<(), CircuitOpen> {
+        if self.threshold == 0 {
+            return Ok(());
+        }
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            State::Closed => Ok(()),
+            State::Open { until } if now < until => Err(CircuitOpen {
+                retry_in: until - now,
+            }),
+            // A trial that never reported back doesn't hold the breaker forever
+            State::HalfOpen { since } if now < since + self.cooldown => Err(CircuitOpen {
+                retry_in: since + self.cooldown - now,
+            }),
+            State::Open { .. } | State::HalfOpen { .. } => {
+                inner.state = State::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    /// Record the outcome of a request that was let through. `failed` means
+    /// Telegram couldn't be reached or had an error of its own, not that it
+    /// refused the request.
+    pub fn record(&self, failed: bool, now: Instant) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if !failed {
+            if inner.state != State::Closed {
+                info!("✅ Telegram API reachable again, circuit breaker closed");
+            }
+            inner.state = State::Closed;
+            inner.consecutive_failures = 0;
+            return;
+        }
+
+        inner.consecutive_failures += 1;
+        let open = match inner.state {
+            State::HalfOpen { .. } => true,
+            State::Closed => inner.consecutive_failures >= self.threshold,
+            State::Open { .. } => false,
+        };
+        if open {
+            warn!(
+                "⚠️ Circuit breaker opened after {} consecutive Telegram API failures, failing fast for {}s",
+                inner.consecutive_failures,
+                self.cooldown.as_secs()
+            );
+            inner.state = State::Open {
+                until: now + self.cooldown,
+            };
+        }
+    }
+
+    pub fn state(&self, now: Instant) -> BreakerState {
+        let inner = self.inner.lock().unwrap();
+        let (state, retry_in) = match inner.state {
+            State::Closed => ("closed", None),
+            State::Open { until } => ("open", Some(until.saturating_duration_since(now))),
+            State::HalfOpen { .. } => ("half_open", None),
+        };
+        BreakerState {
+            state,
+            consecutive_failures: inner.consecutive_failures,
+            retry_in_secs: retry_in.map(|wait| wait.as_secs()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+        let now = Instant::now();
+
+        breaker.record(true, now);
+        breaker.record(true, now);
+        // A success in between starts the count over
+        breaker.record(false, now);
+        breaker.record(true, now);
+        breaker.record(true, now);
+        assert!(breaker.allow(now).is_ok());
+
+        breaker.record(true, now);
+        let open = breaker.allow(now + Duration::from_secs(10)).unwrap_err();
+        assert_eq!(open.retry_in, Duration::from_secs(20));
+        assert_eq!(
+            breaker.state(now),
+            BreakerState {
+                state: "open",
+                consecutive_failures: 3,
+                retry_in_secs: Some(30),
+            }
+        );
+    }
+
+    #[test]
+    fn test_trial_request_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let now = Instant::now();
+        breaker.record(true, now);
+
+        // One request gets through, the rest wait for its outcome
+        let later = now + COOLDOWN;
+        assert!(breaker.allow(later).is_ok());
+        assert!(breaker.allow(later).is_err());
+        breaker.record(true, later);
+        assert!(breaker.allow(later + Duration::from_secs(1)).is_err());
+
+        let recovered = later + COOLDOWN;
+        assert!(breaker.allow(recovered).is_ok());
+        breaker.record(false, recovered);
+        assert_eq!(breaker.state(recovered).state, "closed");
+        assert!(breaker.allow(recovered).is_ok());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let breaker = CircuitBreaker::new(0, COOLDOWN);
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            breaker.record(true, now);
+        }
+        assert!(breaker.allow(now).is_ok());
+    }
+}
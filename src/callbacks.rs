@@ -0,0 +1,219 @@
+use crate::auth::{self, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::pipeline::Notification;
+use anyhow::{Context, Result, bail};
+use reqwest::{Client, Url};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+/// Longest a callback URL may take to answer
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Times a callback is POSTed before giving up on it
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Where delivery outcomes are reported and how they are signed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallbackSettings {
+    /// Used for notifications without their own `callback_url`
+    /// (`--callback-url`)
+    pub default_url: Option<Url>,
+    /// Key of the `X-Signature` HMAC (`--callback-secret`)
+    pub secret: Option<String>,
+}
+
+/// Body POSTed to a callback URL once a notification is delivered or has
+/// failed for good
+#[derive(Debug, Serialize)]
+pub struct CallbackPayload {
+    pub delivery_id: String,
+    pub chat_id: String,
+    /// `sent` or `failed`
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram_message_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix timestamp of the outcome
+    pub timestamp: u64,
+}
+
+/// Tells the caller's `callback_url` (or `--callback-url`) how the delivery
+/// of a notification ended, so orchestration systems don't have to poll
+/// `/messages/{id}`. Does nothing for notifications without a URL.
+#[derive(Clone, Default)]
+pub struct Callbacks {
+    settings: Arc<CallbackSettings>,
+    client: Client,
+}
+
+impl Callbacks {
+    pub fn new(settings: CallbackSettings) -> Self {
+        let client = Client::builder()
+            .timeout(CALLBACK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            settings: Arc::new(settings),
+            client,
+        }
+    }
+
+    /// Report a notification Telegram accepted
+    pub fn sent(&self, notification: &Notification, telegram_message_id: Option<i64>) {
+        self.report(notification, "sent", telegram_message_id, None);
+    }
+
+    /// Report a notification that won't be delivered
+    pub fn failed(&self, notification: &Notification, error: &str) {
+        self.report(notification, "failed", None, Some(error.to_string()));
+    }
+
+    /// POST the outcome in the background, retrying a few times
+    fn report(
+        &self,
+        notification: &Notification,
+        status: &'static str,
+        telegram_message_id: Option<i64>,
+        error: Option<String>,
+    ) {
+        let url = match &notification.callback_url {
+            Some(url) => url.clone(),
+            None => match &self.settings.default_url {
+                Some(url) => url.to_string(),
+                None => return,
+            },
+        };
+        let payload = CallbackPayload {
+            delivery_id: notification.delivery_id.clone(),
+            chat_id: notification.chat_id.clone(),
+            status,
+            telegram_message_id,
+            error,
+            timestamp: unix_now(),
+        };
+        let callbacks = self.clone();
+        tokio::spawn(async move {
+            let delivery_id = payload.delivery_id.clone();
+            let body = serde_json::to_vec(&payload).expect("callback payloads serialize");
+            for attempt in 1..=MAX_ATTEMPTS {
+                match callbacks.post(&url, &body).await {
+                    Ok(()) => {
+                        info!("📣 Delivery callback for notification {} sent", delivery_id);
+                        return;
+                    }
+                    Err(e) if attempt < MAX_ATTEMPTS => {
+                        warn!(
+                            "⚠️ Delivery callback for notification {} failed (attempt {}/{}): {:#}",
+                            delivery_id, attempt, MAX_ATTEMPTS, e
+                        );
+                        tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                    }
+                    Err(e) => error!(
+                        "❌ Giving up on the delivery callback for notification {}: {:#}",
+                        delivery_id, e
+                    ),
+                }
+            }
+        });
+    }
+
+    async fn post(&self, url: &str, body: &[u8]) -> Result<()> {
+        let mut request = self
+            .client
+            .post(url)
+            .header("content-type", "application/json");
+        if let Some(secret) = &self.settings.secret {
+            let timestamp = unix_now().to_string();
+            request = request
+                .header(SIGNATURE_HEADER, auth::sign(secret, Some(&timestamp), body))
+                .header(TIMESTAMP_HEADER, timestamp);
+        }
+        request
+            .body(body.to_vec())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Check a callback URL, which has to be http:// or https://
+pub fn parse_url(url: &str) -> Result<Url> {
+    let parsed = Url::parse(url.trim()).with_context(|| format!("Invalid callback URL {url:?}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        bail!("Callback URL {url:?} must start with http:// or https://");
+    }
+    Ok(parsed)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn notification(callback_url: Option<String>) -> Notification {
+        Notification {
+            message: "Deploy finished".to_string(),
+            chat_id: "-100123".to_string(),
+            delivery_id: "abc123".to_string(),
+            callback_url,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_callback() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/deliveries")
+            .match_header(
+                SIGNATURE_HEADER,
+                Matcher::Regex("^sha256=[0-9a-f]{64}$".into()),
+            )
+            .match_header(TIMESTAMP_HEADER, Matcher::Regex("^[0-9]+$".into()))
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "delivery_id": "abc123",
+                "chat_id": "-100123",
+                "status": "sent",
+                "telegram_message_id": 42,
+            })))
+            .with_status(204)
+            .create_async()
+            .await;
+        let callbacks = Callbacks::new(CallbackSettings {
+            default_url: None,
+            secret: Some("s3cret".to_string()),
+        });
+
+        callbacks.sent(
+            &notification(Some(format!("{}/deliveries", server.url()))),
+            Some(42),
+        );
+        // Without a URL of its own or a default, nothing is sent
+        callbacks.sent(&notification(None), Some(43));
+
+        for _ in 0..50 {
+            if mock.matched_async().await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert!(parse_url("https://ci.example.com/hooks/telegram").is_ok());
+        assert!(parse_url("ftp://ci.example.com/hooks").is_err());
+        assert!(parse_url("not a url").is_err());
+    }
+}
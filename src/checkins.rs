@@ -0,0 +1,237 @@
+use crate::api::SendNotificationRequest;
+use crate::pages::escape;
+use crate::scheduler::format_time;
+use crate::uptime::format_duration;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of notifications about missed check-ins
+pub const SOURCE: &str = "checkin";
+
+/// A job expected to check in at `POST /checkin/{slug}` regularly
+/// (`[checkins.<slug>]` in the config file)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckIn {
+    /// How often the job runs
+    pub period_minutes: u64,
+    /// How late a check-in may be before the alert goes out
+    #[serde(default)]
+    pub grace_minutes: u64,
+    /// Chat to alert; the default chat if neither this nor `chat` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<String>,
+    /// Chat alias, instead of `chat_id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat: Option<String>,
+}
+
+impl CheckIn {
+    /// Seconds after a check-in until the next one is missed
+    fn deadline_secs(&self) -> u64 {
+        (self.period_minutes + self.grace_minutes) * 60
+    }
+}
+
+struct Timer {
+    /// Unix time of the last check-in, None before the first one
+    last: Option<u64>,
+    /// Unix time the alert is due
+    due: u64,
+    /// Whether the missed check-in has been alerted
+    alerted: bool,
+}
+
+/// What a check-in did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ping {
+    /// Unix time the next check-in is due by
+    pub due: u64,
+    /// Seconds since the previous check-in, if the job had been alerted as
+    /// missing
+    pub recovered_after: Option<u64>,
+}
+
+/// Timers of the configured check-ins, started when the server starts so a
+/// job that never checks in is noticed too. Kept in memory; cloning shares
+/// them.
+#[derive(Clone, Default)]
+pub struct CheckIns {
+    configured: Arc<BTreeMap<String, CheckIn>>,
+    timers: Arc<Mutex<HashMap<String, Timer>>>,
+    started: u64,
+}
+
+impl CheckIns {
+    /// Start the timers now
+    pub fn start(configured: BTreeMap<String, CheckIn>) -> Self {
+        Self::new(configured, unix_now())
+    }
+
+    pub fn new(configured: BTreeMap<String, CheckIn>, now: u64) -> Self {
+        let timers = configured
+            .iter()
+            .map(|(slug, check_in)| {
+                let timer = Timer {
+                    last: None,
+                    due: now + check_in.deadline_secs(),
+                    alerted: false,
+                };
+                (slug.clone(), timer)
+            })
+            .collect();
+        Self {
+            configured: Arc::new(configured),
+            timers: Arc::new(Mutex::new(timers)),
+            started: now,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.configured.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.configured.is_empty()
+    }
+
+    /// Reset the timer of `slug`; None if there is no such check-in
+    pub fn ping(&self, slug: &str, now: u64) -> Option<Ping> {
+        let check_in = self.configured.get(slug)?;
+        let mut timers = self.timers.lock().unwrap();
+        let timer = timers.get_mut(slug)?;
+        let recovered_after = timer
+            .alerted
+            .then(|| now.saturating_sub(timer.last.unwrap_or(self.started)));
+        *timer = Timer {
+            last: Some(now),
+            due: now + check_in.deadline_secs(),
+            alerted: false,
+        };
+        Some(Ping {
+            due: timer.due,
+            recovered_after,
+        })
+    }
+
+    /// Alerts for the check-ins missed by `now`, each once until the job
+    /// checks in again
+    pub fn missed(&self, now: u64) -> Vec<(String, SendNotificationRequest)> {
+        let mut timers = self.timers.lock().unwrap();
+        let mut missed = Vec::new();
+        for (slug, check_in) in self.configured.iter() {
+            let Some(timer) = timers.get_mut(slug) else {
+                continue;
+            };
+            if timer.alerted || now < timer.due {
+                continue;
+            }
+            timer.alerted = true;
+            let mut request = render_missed(slug, timer.last, self.started, now);
+            request.chat_id = check_in.chat_id.clone();
+            request.chat = check_in.chat.clone();
+            missed.push((slug.clone(), request));
+        }
+        missed
+    }
+
+    /// The message for a job checking in again after it was alerted
+    pub fn recovered(&self, slug: &str, after: u64) -> Option<SendNotificationRequest> {
+        let check_in = self.configured.get(slug)?;
+        Some(SendNotificationRequest {
+            message: format!(
+                "🟢 <b>{}</b> checked in again after {}",
+                escape(slug),
+                format_duration(after)
+            ),
+            chat_id: check_in.chat_id.clone(),
+            chat: check_in.chat.clone(),
+            parse_mode: Some("HTML".to_string()),
+            disable_notification: Some(true),
+            ..Default::default()
+        })
+    }
+}
+
+fn render_missed(slug: &str, last: Option<u64>, started: u64, now: u64) -> SendNotificationRequest {
+    let since = match last {
+        Some(last) => format!(
+            "Last check-in {} ago, at {}",
+            format_duration(now.saturating_sub(last)),
+            format_time(last)
+        ),
+        None => format!(
+            "No check-in since the server started {} ago",
+            format_duration(now.saturating_sub(started))
+        ),
+    };
+    SendNotificationRequest {
+        message: format!("🔴 <b>{}</b> missed its check-in\n{since}", escape(slug)),
+        parse_mode: Some("HTML".to_string()),
+        ..Default::default()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_800_000_000;
+
+    fn check_ins() -> CheckIns {
+        let backup = CheckIn {
+            period_minutes: 60,
+            grace_minutes: 10,
+            chat_id: None,
+            chat: Some("ops-team".to_string()),
+        };
+        CheckIns::new(BTreeMap::from([("backup".to_string(), backup)]), START)
+    }
+
+    #[test]
+    fn test_missed_once_until_check_in() {
+        let check_ins = check_ins();
+        assert!(check_ins.missed(START + 70 * 60 - 1).is_empty());
+        let missed = check_ins.missed(START + 70 * 60);
+        assert_eq!(missed.len(), 1);
+        let (slug, request) = &missed[0];
+        assert_eq!(slug, "backup");
+        assert_eq!(
+            request.message,
+            "🔴 <b>backup</b> missed its check-in\nNo check-in since the server started 1h 10m 0s ago"
+        );
+        assert_eq!(request.chat.as_deref(), Some("ops-team"));
+        assert!(check_ins.missed(START + 80 * 60).is_empty());
+
+        let ping = check_ins.ping("backup", START + 90 * 60).unwrap();
+        assert_eq!(ping.recovered_after, Some(90 * 60));
+        assert_eq!(ping.due, START + 160 * 60);
+        assert!(
+            check_ins
+                .recovered("backup", 90 * 60)
+                .unwrap()
+                .message
+                .ends_with("checked in again after 1h 30m 0s")
+        );
+    }
+
+    #[test]
+    fn test_check_in_resets_timer() {
+        let check_ins = check_ins();
+        let ping = check_ins.ping("backup", START + 60 * 60).unwrap();
+        assert_eq!(ping.recovered_after, None);
+        assert!(check_ins.missed(START + 120 * 60).is_empty());
+        let missed = check_ins.missed(START + 130 * 60);
+        assert!(missed[0].1.message.contains("Last check-in 1h 10m 0s ago"));
+        assert_eq!(check_ins.ping("unknown", START), None);
+    }
+}
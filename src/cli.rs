@@ -0,0 +1,470 @@
+use crate::bots::Bots;
+use crate::config::{ConfigResolved, SendArgs};
+use crate::defaults::ChatDefaults;
+use crate::delivery::{extract_message_id, telegram_error};
+use crate::telegram::{
+    Attachment, MAX_MESSAGE_LENGTH, SendMessageRequest, TELEGRAM_API_BASE, TelegramBot,
+    TelegramResponse, check_caption,
+};
+use crate::webhooks::Template;
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Parse mode of messages sent from the command line, unless the profile or
+/// the chat's defaults set one
+const PARSE_MODE: &str = "Markdown";
+
+/// How `send` reports what happened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// Log lines
+    #[default]
+    Text,
+    /// One JSON object on stdout, logs going to stderr
+    Json,
+}
+
+/// The outcome of a send, as `--output json` prints it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SendResult {
+    pub ok: bool,
+    pub chat_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<i64>,
+    /// Telegram's error code, if Telegram refused the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SendResult {
+    pub fn new(chat_id: &str, result: &Result<TelegramResponse>) -> Self {
+        match result {
+            Ok(response) => Self {
+                ok: true,
+                chat_id: chat_id.to_string(),
+                message_id: extract_message_id(&response.result),
+                error_code: None,
+                error: None,
+            },
+            Err(e) => Self::failed(chat_id, e),
+        }
+    }
+
+    pub fn failed(chat_id: &str, error: &anyhow::Error) -> Self {
+        Self {
+            ok: false,
+            chat_id: chat_id.to_string(),
+            message_id: None,
+            error_code: telegram_error(error).and_then(|e| e.error_code),
+            error: Some(format!("{error:#}")),
+        }
+    }
+}
+
+/// The message in a file ("-" for stdin), wrapped in a Markdown code block
+/// if `code_block`. Files too big for a message aren't read.
+pub fn read_message(path: &Path, code_block: bool) -> Result<String> {
+    // A message of the most characters, all of the widest UTF-8
+    let limit = (MAX_MESSAGE_LENGTH * 4) as u64;
+    let mut data = Vec::new();
+    if path == Path::new("-") {
+        std::io::stdin().take(limit + 1).read_to_end(&mut data)?;
+    } else {
+        let file =
+            File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        file.take(limit + 1).read_to_end(&mut data)?;
+    }
+    if data.len() as u64 > limit {
+        bail!(
+            "{} is too big for a message of at most {MAX_MESSAGE_LENGTH} characters",
+            path.display()
+        );
+    }
+    let text =
+        String::from_utf8(data).with_context(|| format!("{} is not UTF-8 text", path.display()))?;
+    let text = text.trim_end();
+    Ok(if code_block {
+        format!("```\n{text}\n```")
+    } else {
+        text.to_string()
+    })
+}
+
+/// The message of a template file, its `{{ $.name }}` placeholders filled
+/// in from `vars`
+pub fn render_template(path: &Path, vars: &[(String, String)]) -> Result<String> {
+    let template = Template::parse(&read_message(path, false)?)
+        .with_context(|| format!("Invalid template {}", path.display()))?;
+    let values: Map<String, Value> = vars
+        .iter()
+        .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+        .collect();
+    Ok(template.render(&Value::Object(values), false))
+}
+
+/// What a CLI send sends, read and checked before anything goes out
+pub enum Outgoing {
+    Message {
+        text: String,
+        /// The profile's; the chat's default if None
+        parse_mode: Option<String>,
+    },
+    File {
+        attachment: Attachment,
+        as_photo: bool,
+        caption: Option<String>,
+    },
+}
+
+impl Outgoing {
+    pub fn new(config: &ConfigResolved, send: &SendArgs) -> Result<Self> {
+        let Some(path) = send.file.as_ref().or(send.photo.as_ref()) else {
+            let message = match (&send.template, &send.message_file) {
+                (Some(path), _) => render_template(path, &send.vars)?,
+                (None, Some(path)) => read_message(path, send.code_block)?,
+                (None, None) => config.message.clone(),
+            };
+            if message.chars().count() > MAX_MESSAGE_LENGTH {
+                bail!("Messages are limited to {MAX_MESSAGE_LENGTH} characters");
+            }
+            return Ok(Self::Message {
+                text: message,
+                parse_mode: config.parse_mode.clone(),
+            });
+        };
+        if let Some(caption) = &send.caption {
+            check_caption(caption)?;
+        }
+        Ok(Self::File {
+            attachment: Attachment::from_path(path)?,
+            as_photo: send.photo.is_some(),
+            caption: send.caption.clone(),
+        })
+    }
+
+    /// Send to `chat_id` with the chat's defaults
+    pub async fn send(
+        &self,
+        bot: &TelegramBot,
+        chat_id: &str,
+        defaults: &ChatDefaults,
+    ) -> Result<TelegramResponse> {
+        match self {
+            Self::Message { text, parse_mode } => {
+                let parse_mode = Self::parse_mode(parse_mode, defaults);
+                bot.send_message_with_options(
+                    chat_id,
+                    text,
+                    Some(parse_mode),
+                    defaults.options(),
+                    &[],
+                )
+                .await
+            }
+            Self::File {
+                attachment,
+                as_photo,
+                caption,
+            } => {
+                bot.send_file_with_options(
+                    chat_id,
+                    attachment,
+                    *as_photo,
+                    caption.as_deref(),
+                    defaults.options(),
+                )
+                .await
+            }
+        }
+    }
+
+    fn parse_mode<'a>(parse_mode: &'a Option<String>, defaults: &'a ChatDefaults) -> &'a str {
+        parse_mode
+            .as_deref()
+            .or(defaults.parse_mode.as_deref())
+            .unwrap_or(PARSE_MODE)
+    }
+
+    /// The Bot API request `send` would make, with the token left out
+    pub fn preview(&self, chat_id: &str, defaults: &ChatDefaults) -> Result<String> {
+        let mut preview = String::new();
+        let options = defaults.options();
+        match self {
+            Self::Message { text, parse_mode } => {
+                let parse_mode = Self::parse_mode(parse_mode, defaults);
+                let request =
+                    SendMessageRequest::with_options(chat_id, text, Some(parse_mode), options, &[]);
+                writeln!(preview, "POST {TELEGRAM_API_BASE}<token>/sendMessage")?;
+                writeln!(preview, "Content-Type: application/json\n")?;
+                writeln!(preview, "{}", serde_json::to_string_pretty(&request)?)?;
+            }
+            Self::File {
+                attachment,
+                as_photo,
+                caption,
+            } => {
+                let (method, field) = if *as_photo {
+                    ("sendPhoto", "photo")
+                } else {
+                    ("sendDocument", "document")
+                };
+                writeln!(preview, "POST {TELEGRAM_API_BASE}<token>/{method}")?;
+                writeln!(preview, "Content-Type: multipart/form-data\n")?;
+                writeln!(preview, "chat_id: {chat_id}")?;
+                writeln!(
+                    preview,
+                    "{field}: {} ({}, {} bytes)",
+                    attachment.file_name,
+                    attachment
+                        .content_type
+                        .as_deref()
+                        .unwrap_or("application/octet-stream"),
+                    attachment.data.len()
+                )?;
+                if let Some(caption) = caption {
+                    writeln!(preview, "caption: {caption}")?;
+                }
+                if options.disable_notification {
+                    writeln!(preview, "disable_notification: true")?;
+                }
+                if options.protect_content {
+                    writeln!(preview, "protect_content: true")?;
+                }
+                if let Some(thread_id) = options.thread_id {
+                    writeln!(preview, "message_thread_id: {thread_id}")?;
+                }
+            }
+        }
+        Ok(preview)
+    }
+}
+
+/// Send the message of `send` to every recipient, as `send` does,
+/// through the bot of its chat
+pub async fn run(config: &ConfigResolved, bots: &Bots, send: &SendArgs) -> Result<()> {
+    let outgoing = match Outgoing::new(config, send) {
+        Ok(outgoing) => outgoing,
+        Err(e) if send.output == Output::Json => {
+            for chat_id in &config.recipients {
+                let result = SendResult::failed(chat_id, &e);
+                println!("{}", serde_json::to_string(&result)?);
+            }
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+    if let Outgoing::Message { text, .. } = &outgoing {
+        info!("📝 Message: {}", text);
+    }
+
+    let mut failed = 0;
+    for chat_id in &config.recipients {
+        if let Outgoing::File { attachment, .. } = &outgoing {
+            info!(
+                "📤 Sending {} to chat ID: {}",
+                attachment.file_name, chat_id
+            );
+        } else {
+            info!("📤 Sending message to chat ID: {}", chat_id);
+        }
+        let result = outgoing
+            .send(
+                bots.for_chat(chat_id),
+                chat_id,
+                &config.defaults.for_chat(chat_id),
+            )
+            .await;
+        if send.output == Output::Json {
+            println!(
+                "{}",
+                serde_json::to_string(&SendResult::new(chat_id, &result))?
+            );
+        }
+        match result {
+            Ok(_) => info!("✅ Message sent successfully to {}! 🎉", chat_id),
+            Err(e) => {
+                failed += 1;
+                tracing::error!("❌ Failed to send message to {}: {}", chat_id, e);
+                if config.recipients.len() == 1 {
+                    warn!("💡 Common issues:");
+                    warn!("   - Make sure the chat ID is correct");
+                    warn!("   - If using a group chat, add the bot to the group first");
+                    warn!("   - If using a private chat, start a conversation with the bot first");
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    let total = config.recipients.len();
+    if total > 1 {
+        info!("📊 Sent to {} of {} chats", total - failed, total);
+    }
+    if failed > 0 {
+        anyhow::bail!("Failed to send to {failed} of {total} chats");
+    }
+    info!("💡 Check your Telegram chat to see the message.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::TelegramError;
+    use serde_json::json;
+
+    #[test]
+    fn test_send_result() {
+        let sent = TelegramResponse {
+            ok: true,
+            result: Some(json!({"message_id": 42})),
+            description: None,
+            error_code: None,
+            parameters: None,
+        };
+        assert_eq!(
+            serde_json::to_value(SendResult::new("123", &Ok(sent))).unwrap(),
+            json!({"ok": true, "chat_id": "123", "message_id": 42})
+        );
+
+        let refused = Err(anyhow::Error::new(TelegramError {
+            description: "Bad Request: chat not found".to_string(),
+            error_code: Some(400),
+            retry_after: None,
+        }));
+        assert_eq!(
+            serde_json::to_value(SendResult::new("123", &refused)).unwrap(),
+            json!({
+                "ok": false,
+                "chat_id": "123",
+                "error_code": 400,
+                "error": "Telegram API error: Bad Request: chat not found (code: Some(400))"
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_message() {
+        let path = std::env::temp_dir().join(format!("message-{}.txt", std::process::id()));
+        std::fs::write(&path, "disk  usage\n/   91%\n\n").unwrap();
+        assert_eq!(read_message(&path, false).unwrap(), "disk  usage\n/   91%");
+        assert_eq!(
+            read_message(&path, true).unwrap(),
+            "```\ndisk  usage\n/   91%\n```"
+        );
+
+        std::fs::write(&path, "x".repeat(MAX_MESSAGE_LENGTH * 4 + 1)).unwrap();
+        assert!(read_message(&path, false).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_render_template() {
+        let path = std::env::temp_dir().join(format!("deploy-{}.tmpl", std::process::id()));
+        std::fs::write(
+            &path,
+            "🚀 Deployed {{ $.version }} to *{{ $.env }}*{{ $.missing }}\n",
+        )
+        .unwrap();
+        let vars = [("env", "prod"), ("version", "1.2.3")]
+            .map(|(name, value)| (name.to_string(), value.to_string()));
+        assert_eq!(
+            render_template(&path, &vars).unwrap(),
+            "🚀 Deployed 1.2.3 to *prod*"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_preview() {
+        let message = Outgoing::Message {
+            text: "*Deployed* v1.2".to_string(),
+            parse_mode: None,
+        };
+        assert_eq!(
+            message.preview("123", &ChatDefaults::default()).unwrap(),
+            "POST https://api.telegram.org/bot<token>/sendMessage\n\
+             Content-Type: application/json\n\n\
+             {\n  \"chat_id\": \"123\",\n  \"text\": \"*Deployed* v1.2\",\n  \"parse_mode\": \"Markdown\"\n}\n"
+        );
+
+        let file = Outgoing::File {
+            attachment: Attachment {
+                file_name: "report.pdf".to_string(),
+                content_type: Some("application/pdf".to_string()),
+                data: vec![0; 2048],
+            },
+            as_photo: false,
+            caption: Some("Nightly report".to_string()),
+        };
+        assert!(file.preview("123", &ChatDefaults::default()).unwrap().ends_with(
+            "/sendDocument\nContent-Type: multipart/form-data\n\n\
+             chat_id: 123\ndocument: report.pdf (application/pdf, 2048 bytes)\ncaption: Nightly report\n"
+        ));
+
+        // A channel's topic, parse mode and silence
+        let channel = ChatDefaults {
+            parse_mode: Some("HTML".to_string()),
+            disable_notification: Some(true),
+            thread_id: Some(7),
+            ..Default::default()
+        };
+        let preview = message.preview("-100123", &channel).unwrap();
+        assert!(preview.contains("\"parse_mode\": \"HTML\""), "{preview}");
+        assert!(preview.contains("\"message_thread_id\": 7"), "{preview}");
+        assert!(
+            preview.contains("\"disable_notification\": true"),
+            "{preview}"
+        );
+        assert!(
+            file.preview("-100123", &channel)
+                .unwrap()
+                .ends_with("disable_notification: true\nmessage_thread_id: 7\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_sends_through_each_chats_bot() {
+        let mut server = mockito::Server::new_async().await;
+        let mut sent = |token: &str, chat_id: &str| {
+            server
+                .mock("POST", format!("/bot{token}/sendMessage").as_str())
+                .match_body(mockito::Matcher::PartialJson(
+                    serde_json::json!({"chat_id": chat_id, "text": "Deployed"}),
+                ))
+                .with_body(r#"{"ok":true,"result":{"message_id":1}}"#)
+                .create()
+        };
+        let main = sent("1:main", "42");
+        let billing = sent("2:billing", "-100200");
+        let bots = Bots::new(TelegramBot::with_base_url(
+            "1:main".to_string(),
+            &server.url(),
+        ))
+        .with_bot(
+            "billing",
+            TelegramBot::with_base_url("2:billing".to_string(), &server.url()),
+            vec!["-100200".to_string()],
+            Vec::new(),
+        );
+        let config = ConfigResolved {
+            chat_id: "42".to_string(),
+            recipients: vec!["42".to_string(), "-100200".to_string()],
+            message: "Deployed".to_string(),
+            ..Default::default()
+        };
+
+        run(&config, &bots, &SendArgs::default()).await.unwrap();
+        main.assert_async().await;
+        billing.assert_async().await;
+    }
+}
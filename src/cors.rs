@@ -0,0 +1,166 @@
+use anyhow::{Context, Result, bail};
+use axum::http::{HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer, ExposeHeaders};
+
+const ANY: &str = "*";
+
+/// Which browser origins may call the API (`[cors]` in the config file).
+/// The default allows every origin, method and header.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsSettings {
+    /// Without CORS, browsers only allow calls from pages on the server's own origin
+    pub enabled: bool,
+    /// Origins such as `"https://dash.example.com"`, or `"*"` for any
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// Response headers scripts may read, e.g. `ETag` or `Retry-After`
+    pub expose_headers: Vec<String>,
+    /// Let browsers send cookies and `Authorization` from the page; needs
+    /// explicit origins, methods and headers
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        let any = vec![ANY.to_string()];
+        Self {
+            enabled: true,
+            allowed_origins: any.clone(),
+            allowed_methods: any.clone(),
+            allowed_headers: any.clone(),
+            expose_headers: any,
+            allow_credentials: false,
+            max_age_secs: None,
+        }
+    }
+}
+
+impl CorsSettings {
+    /// The CORS layer for these settings, or None if CORS is disabled
+    pub fn layer(&self) -> Result<Option<CorsLayer>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        if self.allow_credentials {
+            for (field, values) in [
+                ("allowed_origins", &self.allowed_origins),
+                ("allowed_methods", &self.allowed_methods),
+                ("allowed_headers", &self.allowed_headers),
+                ("expose_headers", &self.expose_headers),
+            ] {
+                if values.iter().any(|value| value == ANY) {
+                    bail!("[cors] allow_credentials can't be combined with \"*\" in {field}");
+                }
+            }
+        }
+
+        let origins = if is_any(&self.allowed_origins) {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(parse(&self.allowed_origins, "origin", |origin| {
+                HeaderValue::from_str(origin.trim_end_matches('/')).ok()
+            })?)
+        };
+        let methods = if is_any(&self.allowed_methods) {
+            AllowMethods::any()
+        } else {
+            AllowMethods::list(parse(&self.allowed_methods, "method", |method| {
+                Method::from_bytes(method.to_ascii_uppercase().as_bytes()).ok()
+            })?)
+        };
+        let headers = if is_any(&self.allowed_headers) {
+            AllowHeaders::any()
+        } else {
+            AllowHeaders::list(parse(&self.allowed_headers, "header", header_name)?)
+        };
+        let exposed = if is_any(&self.expose_headers) {
+            ExposeHeaders::any()
+        } else {
+            ExposeHeaders::list(parse(&self.expose_headers, "header", header_name)?)
+        };
+
+        let mut layer = CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .expose_headers(exposed)
+            .allow_credentials(self.allow_credentials);
+        if let Some(secs) = self.max_age_secs {
+            layer = layer.max_age(Duration::from_secs(secs));
+        }
+        Ok(Some(layer))
+    }
+}
+
+fn is_any(values: &[String]) -> bool {
+    values.iter().any(|value| value == ANY)
+}
+
+fn header_name(name: &str) -> Option<HeaderName> {
+    HeaderName::from_bytes(name.as_bytes()).ok()
+}
+
+/// Parse every value of a `[cors]` list, naming the first invalid one
+fn parse<T>(values: &[String], kind: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Vec<T>> {
+    values
+        .iter()
+        .map(|value| parse(value.trim()).with_context(|| format!("Invalid CORS {kind} {value:?}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(origins: &[&str]) -> CorsSettings {
+        CorsSettings {
+            allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_layer() {
+        assert!(CorsSettings::default().layer().unwrap().is_some());
+        assert!(
+            settings(&["https://dash.example.com", "http://localhost:5173/"])
+                .layer()
+                .unwrap()
+                .is_some()
+        );
+        let disabled = CorsSettings {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(disabled.layer().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let bad_method = CorsSettings {
+            allowed_methods: vec!["GET".to_string(), "NOT A METHOD".to_string()],
+            ..Default::default()
+        };
+        assert!(bad_method.layer().is_err());
+        assert!(
+            settings(&["https://dash\u{1}.example.com"])
+                .layer()
+                .is_err()
+        );
+
+        // Browsers refuse credentials with wildcards, so don't start with them
+        let credentials = CorsSettings {
+            allow_credentials: true,
+            ..settings(&["https://dash.example.com"])
+        };
+        let error = credentials.layer().unwrap_err().to_string();
+        assert!(error.contains("allowed_methods"), "{error}");
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use keyring::Entry;
+
+/// Service name the tokens are stored under in the OS keyring
+const SERVICE: &str = "telegram-notifications";
+
+/// Keyring entry of the bot token for a profile, or for no profile
+fn entry(profile: Option<&str>) -> Result<Entry> {
+    Entry::new(SERVICE, profile.unwrap_or("default")).context("Failed to open the OS keyring")
+}
+
+/// Store the bot token in the OS keyring (`auth login`)
+pub fn store(profile: Option<&str>, token: &str) -> Result<()> {
+    entry(profile)?
+        .set_password(token)
+        .context("Failed to store the bot token in the OS keyring")
+}
+
+/// The stored bot token, if there is one
+pub fn load(profile: Option<&str>) -> Result<Option<String>> {
+    match entry(profile)?.get_password() {
+        Ok(token) => Ok(Some(token)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e).context("Failed to read the OS keyring"),
+    }
+}
+
+/// Remove the stored bot token (`auth logout`); true if there was one
+pub fn delete(profile: Option<&str>) -> Result<bool> {
+    match entry(profile)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(e).context("Failed to remove the bot token from the OS keyring"),
+    }
+}
+
+/// The token to log in with: typed at a hidden prompt, or the first line
+/// of standard input when it isn't a terminal (`pass show bot | ... login`)
+pub fn read_token() -> Result<String> {
+    use std::io::IsTerminal;
+    let token = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password("Bot token: ")?
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        line
+    };
+    let token = token.trim().to_string();
+    if token.is_empty() {
+        anyhow::bail!("No bot token given");
+    }
+    Ok(token)
+}
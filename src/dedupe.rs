@@ -0,0 +1,349 @@
+use crate::bots::Bots;
+use crate::history::{DeliveryStatus, History};
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use crate::telegram::MAX_MESSAGE_LENGTH;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Collapses identical messages to the same chat within a window into the
+/// first one, so a flapping check doesn't flood the chat. Emergencies always
+/// get through, and a message whose first copy failed isn't a duplicate.
+#[derive(Clone)]
+pub struct Deduplicator {
+    window: Duration,
+    history: History,
+    /// Edits the first message to show how often it was repeated
+    counter: Option<Bots>,
+    seen: Arc<Mutex<HashMap<(String, String), Seen>>>,
+    /// Keeps counter edits in order
+    edits: Arc<tokio::sync::Mutex<()>>,
+}
+
+struct Seen {
+    delivery_id: String,
+    since: Instant,
+    /// Copies received, the first included
+    count: u32,
+    /// Count the first message was last edited to show
+    shown: u32,
+}
+
+impl Deduplicator {
+    pub fn new(window: Duration, history: History) -> Self {
+        Self {
+            window,
+            history,
+            counter: None,
+            seen: Arc::default(),
+            edits: Arc::default(),
+        }
+    }
+
+    /// Append "×N" to the first message whenever a duplicate is dropped
+    pub fn with_counter(mut self, bots: Bots) -> Self {
+        self.counter = Some(bots);
+        self
+    }
+
+    /// Whether `notification` repeats one sent within the window, counting it
+    /// if it does. Returns the first copy's delivery ID and the count. A
+    /// preview leaves the count alone and isn't remembered as a first copy.
+    fn duplicate(
+        &self,
+        notification: &Notification,
+        now: Instant,
+        preview: bool,
+    ) -> Option<(String, u32)> {
+        let key = (notification.chat_id.clone(), notification.message.clone());
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, seen| now.duration_since(seen.since) < self.window);
+
+        if let Some(first) = seen.get_mut(&key) {
+            let failed = matches!(
+                self.history
+                    .get(&first.delivery_id)
+                    .map(|record| record.status),
+                Some(DeliveryStatus::Failed { .. })
+            );
+            if !failed {
+                if preview {
+                    return Some((first.delivery_id.clone(), first.count + 1));
+                }
+                first.count += 1;
+                return Some((first.delivery_id.clone(), first.count));
+            }
+        }
+        if preview {
+            return None;
+        }
+        seen.insert(
+            key,
+            Seen {
+                delivery_id: notification.delivery_id.clone(),
+                since: now,
+                count: 1,
+                shown: 1,
+            },
+        );
+        None
+    }
+
+    fn dropped(&self, first: &str, count: u32) -> Outcome {
+        Outcome::Drop {
+            reason: format!(
+                "Duplicate of {first} (×{count} within {}s)",
+                self.window.as_secs()
+            ),
+        }
+    }
+
+    /// Edit the first message in the background to show the latest count
+    fn update_counter(&self, notification: &Notification, first: String) {
+        let Some(bots) = self.counter.clone() else {
+            return;
+        };
+        let dedupe = self.clone();
+        let key = (notification.chat_id.clone(), notification.message.clone());
+        tokio::spawn(async move {
+            let _edits = dedupe.edits.lock().await;
+            // A later edit may already have shown this duplicate
+            let count = match dedupe.seen.lock().unwrap().get(&key) {
+                Some(seen) if seen.delivery_id == first && seen.count > seen.shown => seen.count,
+                _ => return,
+            };
+            let Some(record) = dedupe.history.get(&first) else {
+                return;
+            };
+            let DeliveryStatus::Sent {
+                telegram_message_id: Some(message_id),
+            } = record.status
+            else {
+                return;
+            };
+            let Some(text) = counted(&record.notification.message, count) else {
+                return;
+            };
+            let sent = &record.notification;
+            match bots
+                .for_notification(sent)
+                .edit_message_text(&sent.chat_id, message_id, &text, sent.parse_mode.as_deref())
+                .await
+            {
+                Ok(_) => {
+                    if let Some(seen) = dedupe.seen.lock().unwrap().get_mut(&key) {
+                        seen.shown = seen.shown.max(count);
+                    }
+                }
+                Err(e) => warn!(
+                    "⚠️ Failed to update the repeat count of notification {}: {}",
+                    first, e
+                ),
+            }
+        });
+    }
+}
+
+/// `message` with its repeat count appended, or None if the result wouldn't
+/// fit into the one message it has to replace
+fn counted(message: &str, count: u32) -> Option<String> {
+    let text = format!("{message}\n\n×{count}");
+    (text.chars().count() <= MAX_MESSAGE_LENGTH).then_some(text)
+}
+
+impl Stage for Deduplicator {
+    fn name(&self) -> &str {
+        "dedupe"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Dedupe
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        if notification.emergency {
+            return Ok(Outcome::Continue);
+        }
+        let Some((first, count)) = self.duplicate(notification, Instant::now(), false) else {
+            return Ok(Outcome::Continue);
+        };
+        info!(
+            "🔁 Notification {} repeats {} (×{})",
+            notification.delivery_id, first, count
+        );
+        self.update_counter(notification, first.clone());
+        Ok(self.dropped(&first, count))
+    }
+
+    fn preview(&self, notification: &mut Notification) -> Result<Outcome> {
+        if notification.emergency {
+            return Ok(Outcome::Continue);
+        }
+        Ok(match self.duplicate(notification, Instant::now(), true) {
+            Some((first, count)) => self.dropped(&first, count),
+            None => Outcome::Continue,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::TelegramBot;
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    const WINDOW: Duration = Duration::from_secs(300);
+
+    fn notification(delivery_id: &str, message: &str) -> Notification {
+        Notification {
+            message: message.to_string(),
+            chat_id: "-100123".to_string(),
+            delivery_id: delivery_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_duplicates_within_window() {
+        let dedupe = Deduplicator::new(WINDOW, History::new(10));
+        let start = Instant::now();
+
+        let first = notification("first", "🔴 web-1 down");
+        assert_eq!(dedupe.duplicate(&first, start, false), None);
+        assert_eq!(
+            dedupe.duplicate(&notification("second", "🔴 web-1 down"), start, false),
+            Some(("first".to_string(), 2))
+        );
+        assert_eq!(
+            dedupe.duplicate(
+                &notification("third", "🔴 web-1 down"),
+                start + WINDOW / 2,
+                false
+            ),
+            Some(("first".to_string(), 3))
+        );
+
+        // Other messages and other chats aren't duplicates
+        assert_eq!(
+            dedupe.duplicate(&notification("other", "🟢 web-1 up"), start, false),
+            None
+        );
+        let mut elsewhere = notification("elsewhere", "🔴 web-1 down");
+        elsewhere.chat_id = "-100999".to_string();
+        assert_eq!(dedupe.duplicate(&elsewhere, start, false), None);
+
+        // The window counts from the first copy
+        assert_eq!(
+            dedupe.duplicate(
+                &notification("later", "🔴 web-1 down"),
+                start + WINDOW,
+                false
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_preview_is_not_remembered() {
+        let dedupe = Deduplicator::new(WINDOW, History::new(10));
+
+        let mut preview = notification("preview", "🔴 web-1 down");
+        assert_eq!(dedupe.preview(&mut preview).unwrap(), Outcome::Continue);
+        let mut first = notification("first", "🔴 web-1 down");
+        assert_eq!(dedupe.process(&mut first).unwrap(), Outcome::Continue);
+
+        // A preview of a repeat shows it dropped without counting it
+        let mut preview = notification("preview", "🔴 web-1 down");
+        assert!(matches!(
+            dedupe.preview(&mut preview).unwrap(),
+            Outcome::Drop { reason } if reason == "Duplicate of first (×2 within 300s)"
+        ));
+        let mut repeat = notification("repeat", "🔴 web-1 down");
+        assert!(matches!(
+            dedupe.process(&mut repeat).unwrap(),
+            Outcome::Drop { reason } if reason == "Duplicate of first (×2 within 300s)"
+        ));
+    }
+
+    #[test]
+    fn test_failed_and_emergency_notifications_get_through() {
+        let history = History::new(10);
+        let dedupe = Deduplicator::new(WINDOW, history.clone());
+
+        let first = notification("first", "Backup failed");
+        history.record(
+            &first,
+            DeliveryStatus::Failed {
+                error: "Bad Gateway".to_string(),
+            },
+            None,
+        );
+        assert_eq!(
+            dedupe.process(&mut first.clone()).unwrap(),
+            Outcome::Continue
+        );
+        let mut retry = notification("retry", "Backup failed");
+        assert_eq!(dedupe.process(&mut retry).unwrap(), Outcome::Continue);
+
+        let mut repeat = notification("repeat", "Backup failed");
+        assert!(matches!(
+            dedupe.process(&mut repeat).unwrap(),
+            Outcome::Drop { reason } if reason == "Duplicate of retry (×2 within 300s)"
+        ));
+        let mut emergency = notification("emergency", "Backup failed");
+        emergency.emergency = true;
+        assert_eq!(dedupe.process(&mut emergency).unwrap(), Outcome::Continue);
+    }
+
+    #[test]
+    fn test_counted() {
+        assert_eq!(counted("Disk full", 3).unwrap(), "Disk full\n\n×3");
+        assert_eq!(counted(&"x".repeat(MAX_MESSAGE_LENGTH - 3), 2), None);
+    }
+
+    #[tokio::test]
+    async fn test_counter_edits_first_message() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/bottoken/editMessageText")
+            .match_body(Matcher::Json(json!({
+                "chat_id": "-100123",
+                "message_id": 7,
+                "text": "🔴 web-1 down\n\n×2",
+            })))
+            .with_body(r#"{"ok": true, "result": {"message_id": 7}}"#)
+            .create_async()
+            .await;
+        let history = History::new(10);
+        let bots = Bots::new(TelegramBot::with_base_url(
+            "token".to_string(),
+            &server.url(),
+        ));
+        let dedupe = Deduplicator::new(WINDOW, history.clone()).with_counter(bots);
+
+        let mut first = notification("first", "🔴 web-1 down");
+        dedupe.process(&mut first).unwrap();
+        history.record(
+            &first,
+            DeliveryStatus::Sent {
+                telegram_message_id: Some(7),
+            },
+            None,
+        );
+        let outcome = dedupe
+            .process(&mut notification("second", "🔴 web-1 down"))
+            .unwrap();
+        assert!(matches!(outcome, Outcome::Drop { .. }));
+
+        for _ in 0..50 {
+            if mock.matched_async().await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,168 @@
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use crate::telegram::MessageOptions;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How messages are sent unless the notification says otherwise
+/// (`[defaults]` in the config file), with overrides per chat
+/// (`[defaults.chats."<chat ID or alias>"]`)
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SendDefaults {
+    /// For notifications without a parse mode, e.g. `HTML`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    /// Send every message silently
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_notification: Option<bool>,
+    /// Don't show previews of the links in messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_link_preview: Option<bool>,
+    /// Keep messages from being forwarded and saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protect_content: Option<bool>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub chats: BTreeMap<String, ChatDefaults>,
+}
+
+/// Defaults of one chat, each falling back to the global one
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_notification: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_link_preview: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protect_content: Option<bool>,
+    /// Forum topic messages are posted in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<i64>,
+}
+
+impl ChatDefaults {
+    /// These defaults, each unset one taken from `fallback`
+    pub fn or(self, fallback: &ChatDefaults) -> ChatDefaults {
+        ChatDefaults {
+            parse_mode: self.parse_mode.or_else(|| fallback.parse_mode.clone()),
+            disable_notification: self.disable_notification.or(fallback.disable_notification),
+            disable_link_preview: self.disable_link_preview.or(fallback.disable_link_preview),
+            protect_content: self.protect_content.or(fallback.protect_content),
+            thread_id: self.thread_id.or(fallback.thread_id),
+        }
+    }
+
+    /// How messages are sent with these defaults
+    pub fn options(&self) -> MessageOptions {
+        MessageOptions {
+            disable_notification: self.disable_notification.unwrap_or(false),
+            disable_link_preview: self.disable_link_preview.unwrap_or(false),
+            protect_content: self.protect_content.unwrap_or(false),
+            thread_id: self.thread_id,
+        }
+    }
+}
+
+impl SendDefaults {
+    /// The defaults of `chat_id`: its overrides on top of the global ones
+    pub fn for_chat(&self, chat_id: &str) -> ChatDefaults {
+        let chat = self.chats.get(chat_id).cloned().unwrap_or_default();
+        chat.or(&ChatDefaults {
+            parse_mode: self.parse_mode.clone(),
+            disable_notification: self.disable_notification,
+            disable_link_preview: self.disable_link_preview,
+            protect_content: self.protect_content,
+            thread_id: None,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Applies the defaults of the chat a notification ends up in. A parse mode
+/// or topic set on the notification wins; a chat sent to silently stays
+/// silent.
+pub struct ApplyDefaults {
+    defaults: SendDefaults,
+}
+
+impl ApplyDefaults {
+    pub fn new(defaults: SendDefaults) -> Self {
+        Self { defaults }
+    }
+}
+
+impl Stage for ApplyDefaults {
+    fn name(&self) -> &str {
+        "defaults"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Render
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        let defaults = self.defaults.for_chat(&notification.chat_id);
+        if notification.parse_mode.is_none() {
+            notification.parse_mode = defaults.parse_mode;
+        }
+        notification.disable_notification |= defaults.disable_notification.unwrap_or(false);
+        notification.disable_link_preview |= defaults.disable_link_preview.unwrap_or(false);
+        notification.protect_content |= defaults.protect_content.unwrap_or(false);
+        if notification.thread_id.is_none() {
+            notification.thread_id = defaults.thread_id;
+        }
+        Ok(Outcome::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_overrides() {
+        let defaults: SendDefaults = toml::from_str(
+            r#"
+parse_mode = "HTML"
+disable_link_preview = true
+
+[chats."-100123"]
+parse_mode = "MarkdownV2"
+disable_notification = true
+protect_content = true
+thread_id = 7
+"#,
+        )
+        .unwrap();
+        let stage = ApplyDefaults::new(defaults);
+
+        let mut plain = Notification {
+            chat_id: "42".to_string(),
+            ..Default::default()
+        };
+        stage.process(&mut plain).unwrap();
+        assert_eq!(plain.parse_mode.as_deref(), Some("HTML"));
+        assert!(plain.disable_link_preview);
+        assert!(!plain.disable_notification);
+        assert!(!plain.protect_content);
+        assert_eq!(plain.thread_id, None);
+
+        let mut explicit = Notification {
+            chat_id: "-100123".to_string(),
+            parse_mode: Some("Markdown".to_string()),
+            ..Default::default()
+        };
+        stage.process(&mut explicit).unwrap();
+        assert_eq!(explicit.parse_mode.as_deref(), Some("Markdown"));
+        assert!(explicit.disable_notification);
+        assert!(explicit.disable_link_preview);
+        assert!(explicit.protect_content);
+        assert_eq!(explicit.thread_id, Some(7));
+    }
+}
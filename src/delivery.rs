@@ -0,0 +1,995 @@
+use crate::breaker::CircuitOpen;
+use crate::callbacks::Callbacks;
+use crate::digest::SOURCE as DIGEST_SOURCE;
+use crate::dlq::{DeadLetter, DeadLetters};
+use crate::fallback::Fallback;
+use crate::history::{DeliveryStatus, History};
+use crate::notifier::Notifier;
+use crate::outbox::Outbox;
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use crate::stats::Stats;
+use crate::telegram::{MessageOptions, TelegramBot, TelegramError, split_message};
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Length of the delivery IDs handed out for every notification
+pub const DELIVERY_ID_LENGTH: usize = 12;
+
+/// Default number of notifications the async queue holds before rejecting more
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1000;
+
+/// Default number of delivery attempts for a queued notification
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// Whether real sends are skipped (TELEGRAM_NOTIFICATIONS_SKIP_VALIDATION=true)
+pub fn test_mode() -> bool {
+    std::env::var("TELEGRAM_NOTIFICATIONS_SKIP_VALIDATION")
+        .unwrap_or_default()
+        .to_lowercase()
+        == "true"
+}
+
+/// Send a prepared notification, split into as many messages as Telegram
+/// requires with its buttons below the last one, followed by its attachments, pinning the first message if asked.
+/// Returns the ID of the first message sent.
+pub async fn deliver(bot: &TelegramBot, notification: &Notification) -> Result<Option<i64>> {
+    let mut message_id = None;
+    let options = MessageOptions {
+        disable_notification: notification.disable_notification,
+        disable_link_preview: notification.disable_link_preview,
+        protect_content: notification.protect_content,
+        thread_id: notification.thread_id,
+    };
+    let parts = split_message(&notification.message);
+    let last = parts.len().saturating_sub(1);
+    for (index, part) in parts.iter().enumerate() {
+        let buttons = if index == last {
+            notification.buttons.as_slice()
+        } else {
+            &[]
+        };
+        let response = bot
+            .send_message_with_options(
+                &notification.chat_id,
+                part,
+                notification.parse_mode.as_deref(),
+                options,
+                buttons,
+            )
+            .await?;
+        message_id = message_id.or(extract_message_id(&response.result));
+    }
+    for attachment in &notification.attachments {
+        bot.send_file_with_options(
+            &notification.chat_id,
+            attachment,
+            attachment.is_photo(),
+            None,
+            options,
+        )
+        .await?;
+    }
+    if let (true, Some(id)) = (notification.pin, message_id) {
+        // The message is out; a missing admin right shouldn't fail the delivery
+        if let Err(e) = bot
+            .pin_chat_message(&notification.chat_id, id, notification.disable_notification)
+            .await
+        {
+            warn!(
+                "⚠️ Failed to pin notification {} in chat {}: {}",
+                notification.delivery_id, notification.chat_id, e
+            );
+        }
+    }
+    Ok(message_id)
+}
+
+pub fn extract_message_id(result: &Option<Value>) -> Option<i64> {
+    result.as_ref()?.get("message_id")?.as_i64()
+}
+
+/// How often the worker tries to deliver a queued notification before
+/// giving up, waiting longer after every failed attempt
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first
+    pub max_attempts: u32,
+    /// Wait after the first failed attempt, doubled after every further one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the attempt following failed attempt number `attempt`,
+    /// or what Telegram asked for after a 429
+    pub fn delay(&self, attempt: u32, retry_after: Option<u64>) -> Duration {
+        if let Some(secs) = retry_after {
+            return Duration::from_secs(secs);
+        }
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// Whether a failed delivery may succeed later: network errors, flood
+/// control, server errors and an open circuit breaker are retried, anything
+/// Telegram refused outright (unknown chat, blocked bot, bad markup) is not
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    match telegram_error(error) {
+        Some(e) => matches!(e.error_code, None | Some(429) | Some(500..)),
+        None => true,
+    }
+}
+
+pub(crate) fn telegram_error(error: &anyhow::Error) -> Option<&TelegramError> {
+    error
+        .chain()
+        .find_map(|e| e.downcast_ref::<TelegramError>())
+}
+
+/// Where the final outcome of a queued notification is reported besides
+/// the history
+#[derive(Clone, Default)]
+pub struct Outcomes {
+    /// Channels notifications that failed for good go to
+    pub fallback: Fallback,
+    /// The notification's `callback_url`, told about either outcome
+    pub callbacks: Callbacks,
+    /// Counts of `GET /stats`
+    pub stats: Stats,
+}
+
+/// Queue of notifications accepted for asynchronous delivery, in lanes by
+/// priority. With an outbox, queued notifications are also stored on disk
+/// until they have been delivered. Those that fail for good end up in the
+/// dead-letter queue and are passed to the fallback channels.
+#[derive(Clone)]
+pub struct DeliveryQueue {
+    lanes: Lanes,
+    outbox: Option<Outbox>,
+    dead_letters: DeadLetters,
+    stats: Stats,
+}
+
+impl DeliveryQueue {
+    /// Create an in-memory queue and spawn the background worker draining
+    /// it. With `simulate` set, the worker only logs what it would send.
+    /// Outcomes are written back to `history`.
+    pub fn start(bot: TelegramBot, capacity: usize, simulate: bool, history: History) -> Self {
+        Self::start_with(
+            Arc::new(bot),
+            capacity,
+            simulate,
+            history,
+            RetryPolicy::default(),
+            None,
+            Outcomes::default(),
+        )
+        .expect("queues without an outbox always start")
+    }
+
+    /// Like `start`, but with any notifier (e.g. the named bots), a retry
+    /// policy, optionally an outbox, and where outcomes are reported. The
+    /// notifications left in the outbox by a previous run are queued again.
+    pub fn start_with(
+        notifier: Arc<dyn Notifier>,
+        capacity: usize,
+        simulate: bool,
+        history: History,
+        retry: RetryPolicy,
+        outbox: Option<Outbox>,
+        outcomes: Outcomes,
+    ) -> Result<Self> {
+        let pending = match &outbox {
+            Some(outbox) => outbox.pending()?,
+            None => Vec::new(),
+        };
+        // Everything restored has to fit, even if the capacity was lowered
+        let capacity = capacity.max(pending.len()).max(1);
+        let (lanes, receivers) = Lanes::new(capacity);
+        let dead_letters = DeadLetters::new(outbox.clone());
+        let stats = outcomes.stats;
+        let mut worker = Worker {
+            notifier,
+            simulate,
+            history,
+            retry,
+            outbox: outbox.clone(),
+            lanes: lanes.clone(),
+            dead_letters: dead_letters.clone(),
+            fallback: outcomes.fallback,
+            callbacks: outcomes.callbacks,
+            stats: stats.clone(),
+            attempts: HashMap::new(),
+        };
+
+        let now = unix_now();
+        for pending in pending {
+            let notification = pending.notification;
+            worker
+                .history
+                .record(&notification, DeliveryStatus::Queued, None);
+            worker
+                .attempts
+                .insert(notification.delivery_id.clone(), pending.attempts);
+            let wait = Duration::from_secs(pending.next_attempt_at.saturating_sub(now));
+            worker.schedule(notification, wait);
+        }
+
+        tokio::spawn(worker.run(receivers));
+        Ok(Self {
+            lanes,
+            outbox,
+            dead_letters,
+            stats,
+        })
+    }
+
+    /// Notifications that failed for good
+    pub fn dead_letters(&self) -> &DeadLetters {
+        &self.dead_letters
+    }
+
+    /// Delivery outcomes since startup, queued and synchronous sends alike
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Notifications waiting for delivery, not counting those waiting to be
+    /// retried
+    pub fn len(&self) -> usize {
+        self.lanes
+            .all()
+            .iter()
+            .map(|sender| sender.max_capacity() - sender.capacity())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether regular notifications are being turned away
+    pub fn is_full(&self) -> bool {
+        self.lanes.normal.capacity() == 0
+    }
+
+    /// Make sure the outbox, if any, can still be read
+    pub fn check_storage(&self) -> Result<()> {
+        if let Some(outbox) = &self.outbox {
+            outbox.len()?;
+        }
+        Ok(())
+    }
+
+    /// Queue a notification, handing it back if the queue is full
+    pub fn enqueue(&self, notification: Notification) -> Result<(), Box<Notification>> {
+        if let Some(outbox) = &self.outbox
+            && let Err(e) = outbox.push(&notification, unix_now())
+        {
+            // It can still be delivered, it just won't survive a restart
+            error!(
+                "❌ Failed to store queued notification {}: {:#}",
+                notification.delivery_id, e
+            );
+        }
+        self.lanes
+            .for_notification(&notification)
+            .try_send(notification)
+            .map_err(|e| {
+                let notification = e.into_inner();
+                if let Some(outbox) = &self.outbox {
+                    forget(outbox, &notification.delivery_id);
+                }
+                Box::new(notification)
+            })
+    }
+}
+
+/// The lanes of the queue, the worker taking the next notification from
+/// the first lane that has one: emergency and critical notifications, then
+/// the others, then low-priority ones and digests. Each lane holds up to the
+/// queue's capacity.
+#[derive(Clone)]
+struct Lanes {
+    urgent: mpsc::Sender<Notification>,
+    normal: mpsc::Sender<Notification>,
+    bulk: mpsc::Sender<Notification>,
+}
+
+/// Receiving ends of [`Lanes`]
+struct Receivers {
+    urgent: mpsc::Receiver<Notification>,
+    normal: mpsc::Receiver<Notification>,
+    bulk: mpsc::Receiver<Notification>,
+}
+
+impl Lanes {
+    fn new(capacity: usize) -> (Self, Receivers) {
+        let (urgent, urgent_receiver) = mpsc::channel(capacity);
+        let (normal, normal_receiver) = mpsc::channel(capacity);
+        let (bulk, bulk_receiver) = mpsc::channel(capacity);
+        (
+            Self {
+                urgent,
+                normal,
+                bulk,
+            },
+            Receivers {
+                urgent: urgent_receiver,
+                normal: normal_receiver,
+                bulk: bulk_receiver,
+            },
+        )
+    }
+
+    fn for_notification(&self, notification: &Notification) -> &mpsc::Sender<Notification> {
+        if notification.emergency || notification.is_critical() {
+            &self.urgent
+        } else if notification.low_priority || notification.source == DIGEST_SOURCE {
+            &self.bulk
+        } else {
+            &self.normal
+        }
+    }
+
+    fn all(&self) -> [&mpsc::Sender<Notification>; 3] {
+        [&self.urgent, &self.normal, &self.bulk]
+    }
+}
+
+impl Receivers {
+    /// Next notification to deliver, from the most urgent lane with one
+    async fn next(&mut self) -> Option<Notification> {
+        tokio::select! {
+            biased;
+            Some(notification) = self.urgent.recv() => Some(notification),
+            Some(notification) = self.normal.recv() => Some(notification),
+            Some(notification) = self.bulk.recv() => Some(notification),
+            else => None,
+        }
+    }
+}
+
+/// Background task delivering queued notifications one at a time
+struct Worker {
+    notifier: Arc<dyn Notifier>,
+    simulate: bool,
+    history: History,
+    retry: RetryPolicy,
+    outbox: Option<Outbox>,
+    /// Held to queue retries again
+    lanes: Lanes,
+    dead_letters: DeadLetters,
+    fallback: Fallback,
+    callbacks: Callbacks,
+    stats: Stats,
+    /// Attempts made so far, by delivery ID
+    attempts: HashMap<String, u32>,
+}
+
+impl Worker {
+    async fn run(mut self, mut receivers: Receivers) {
+        while let Some(notification) = receivers.next().await {
+            self.attempt(notification).await;
+        }
+    }
+
+    async fn attempt(&mut self, notification: Notification) {
+        let delivery_id = notification.delivery_id.clone();
+        self.history.start_attempt(&delivery_id);
+        let attempt = self.attempts.entry(delivery_id.clone()).or_default();
+        *attempt += 1;
+        let attempt = *attempt;
+
+        if self.simulate {
+            info!(
+                delivery_id = %delivery_id,
+                chat_id = %notification.chat_id,
+                message_id = 42,
+                "⚠️  Test mode: Simulating queued message send"
+            );
+            self.stats.sent(&notification);
+            self.callbacks.sent(&notification, Some(42));
+            self.delivered(&delivery_id, Some(42));
+            return;
+        }
+
+        match self.notifier.send(&notification).await {
+            Ok(message_id) => {
+                info!(
+                    delivery_id = %delivery_id,
+                    chat_id = %notification.chat_id,
+                    message_id = message_id,
+                    "✅ Queued notification sent successfully"
+                );
+                self.stats.sent(&notification);
+                self.callbacks.sent(&notification, message_id);
+                self.delivered(&delivery_id, message_id);
+            }
+            Err(e) if attempt < self.retry.max_attempts && is_retryable(&e) => {
+                let retry_after = telegram_error(&e).and_then(|e| e.retry_after).or_else(|| {
+                    // No point in trying before the circuit breaker lets requests through
+                    e.downcast_ref::<CircuitOpen>()
+                        .map(|open| open.retry_in.as_secs().max(1))
+                });
+                let wait = self.retry.delay(attempt, retry_after);
+                warn!(
+                    delivery_id = %delivery_id,
+                    chat_id = %notification.chat_id,
+                    "⚠️ Failed to send queued notification (attempt {}/{}), retrying in {}s: {}",
+                    attempt,
+                    self.retry.max_attempts,
+                    wait.as_secs(),
+                    e
+                );
+                if let Some(outbox) = &self.outbox
+                    && let Err(db) = outbox.reschedule(
+                        &delivery_id,
+                        attempt,
+                        &e.to_string(),
+                        unix_now() + wait.as_secs(),
+                    )
+                {
+                    error!(
+                        "❌ Failed to update queued notification {}: {:#}",
+                        delivery_id, db
+                    );
+                }
+                self.history
+                    .set_status(&delivery_id, DeliveryStatus::Queued);
+                self.schedule(notification, wait);
+            }
+            Err(e) => {
+                error!(
+                    delivery_id = %delivery_id,
+                    chat_id = %notification.chat_id,
+                    "❌ Failed to send queued notification after {} attempt(s), moving it to the dead-letter queue: {}",
+                    attempt,
+                    e
+                );
+                self.attempts.remove(&delivery_id);
+                self.stats.failed(&notification, &e.to_string());
+                self.fallback.notify(&notification, &e.to_string());
+                self.callbacks.failed(&notification, &e.to_string());
+                let letter = DeadLetter {
+                    notification,
+                    attempts: attempt,
+                    error: e.to_string(),
+                    failed_at: unix_now(),
+                };
+                if let Err(db) = self.dead_letters.add(letter) {
+                    // Still in the outbox, so it's tried again after a restart
+                    error!(
+                        "❌ Failed to add notification {} to the dead-letter queue: {:#}",
+                        delivery_id, db
+                    );
+                }
+                self.history.set_status(
+                    &delivery_id,
+                    DeliveryStatus::Failed {
+                        error: e.to_string(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Record the delivery of a notification and forget about it
+    fn delivered(&mut self, delivery_id: &str, telegram_message_id: Option<i64>) {
+        self.attempts.remove(delivery_id);
+        if let Some(outbox) = &self.outbox {
+            forget(outbox, delivery_id);
+        }
+        self.history.set_status(
+            delivery_id,
+            DeliveryStatus::Sent {
+                telegram_message_id,
+            },
+        );
+    }
+
+    /// Queue a notification again once `wait` has passed
+    fn schedule(&self, notification: Notification, wait: Duration) {
+        let sender = self.lanes.for_notification(&notification).clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(wait).await;
+            // Waits for room rather than dropping an accepted notification
+            let _ = sender.send(notification).await;
+        });
+    }
+}
+
+fn forget(outbox: &Outbox, delivery_id: &str) {
+    if let Err(e) = outbox.remove(delivery_id) {
+        error!(
+            "❌ Failed to remove notification {} from the queue database: {:#}",
+            delivery_id, e
+        );
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Generate a short, random delivery ID used to trace a notification end to end
+pub fn new_delivery_id() -> String {
+    Uuid::new_v4().simple().to_string()[..DELIVERY_ID_LENGTH].to_string()
+}
+
+/// Appends the delivery ID as a footer so screenshots of an alert can be
+/// traced back to the request that produced it
+pub struct EmbedDeliveryId;
+
+impl Stage for EmbedDeliveryId {
+    fn name(&self) -> &str {
+        "embed-delivery-id"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Render
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        notification
+            .message
+            .push_str(&format!("\n\n🔖 {}", notification.delivery_id));
+        Ok(Outcome::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fallback::FallbackSettings;
+    use crate::notifier::mock::MockNotifier;
+    use crate::telegram::Attachment;
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    const SEND_PATH: &str = "/bottest_token:ABC/sendMessage";
+
+    fn notification(message: &str) -> Notification {
+        Notification {
+            message: message.to_string(),
+            chat_id: "123456789".to_string(),
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn ok_body(message_id: i64) -> String {
+        json!({"ok": true, "result": {"message_id": message_id}}).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_deliver_returns_first_message_id() {
+        let mut server = Server::new_async().await;
+        let first = server
+            .mock("POST", SEND_PATH)
+            .match_body(Matcher::PartialJson(json!({"text": "x".repeat(4096)})))
+            .with_body(ok_body(7))
+            .create_async()
+            .await;
+        let second = server
+            .mock("POST", SEND_PATH)
+            .match_body(Matcher::PartialJson(json!({"text": "x"})))
+            .with_body(ok_body(8))
+            .create_async()
+            .await;
+
+        let bot = TelegramBot::with_base_url("test_token:ABC".to_string(), &server.url());
+        let message_id = deliver(&bot, &notification(&"x".repeat(4097)))
+            .await
+            .unwrap();
+
+        assert_eq!(message_id, Some(7));
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_deliver_sends_attachments_after_text() {
+        let mut server = Server::new_async().await;
+        let text = server
+            .mock("POST", SEND_PATH)
+            .with_body(ok_body(10))
+            .create_async()
+            .await;
+        let document = server
+            .mock("POST", "/bottest_token:ABC/sendDocument")
+            .match_body(Matcher::Regex(r#"filename="build.log""#.to_string()))
+            .with_body(ok_body(11))
+            .create_async()
+            .await;
+
+        let bot = TelegramBot::with_base_url("test_token:ABC".to_string(), &server.url());
+        let mut notification = notification("Build failed");
+        notification.attachments.push(Attachment {
+            file_name: "build.log".to_string(),
+            content_type: Some("text/plain".to_string()),
+            data: b"error".to_vec(),
+        });
+
+        let message_id = deliver(&bot, &notification).await.unwrap();
+
+        assert_eq!(message_id, Some(10));
+        text.assert_async().await;
+        document.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_deliver_pins_first_message() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", SEND_PATH)
+            .with_body(ok_body(12))
+            .create_async()
+            .await;
+        let pin = server
+            .mock("POST", "/bottest_token:ABC/pinChatMessage")
+            .match_body(Matcher::PartialJson(json!({"message_id": 12})))
+            .with_body(
+                json!({"ok": false, "error_code": 400, "description": "not enough rights"})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let bot = TelegramBot::with_base_url("test_token:ABC".to_string(), &server.url());
+        let mut notification = notification("Release v2.0 is out");
+        notification.pin = true;
+
+        // Pin failures are logged, not reported as a failed delivery
+        let message_id = deliver(&bot, &notification).await.unwrap();
+
+        assert_eq!(message_id, Some(12));
+        pin.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_deliver_propagates_api_error() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", SEND_PATH)
+            .with_body(
+                json!({"ok": false, "error_code": 400, "description": "Bad Request"}).to_string(),
+            )
+            .create_async()
+            .await;
+
+        let bot = TelegramBot::with_base_url("test_token:ABC".to_string(), &server.url());
+        let result = deliver(&bot, &notification("hello")).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queue_worker_delivers() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", SEND_PATH)
+            .match_body(Matcher::PartialJson(json!({"text": "queued"})))
+            .with_body(ok_body(9))
+            .create_async()
+            .await;
+
+        let bot = TelegramBot::with_base_url("test_token:ABC".to_string(), &server.url());
+        let history = History::new(10);
+        let queue = DeliveryQueue::start(bot, 10, false, history.clone());
+        let queued = notification("queued");
+        history.record(&queued, DeliveryStatus::Queued, None);
+        assert!(queue.enqueue(queued.clone()).is_ok());
+
+        for _ in 0..50 {
+            if history.get(&queued.delivery_id).unwrap().status != DeliveryStatus::Queued {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        mock.assert_async().await;
+        assert_eq!(
+            history.get(&queued.delivery_id).unwrap().status,
+            DeliveryStatus::Sent {
+                telegram_message_id: Some(9)
+            }
+        );
+    }
+
+    async fn wait_for_outcome(history: &History, delivery_id: &str) -> DeliveryStatus {
+        for _ in 0..100 {
+            let status = history.get(delivery_id).unwrap().status;
+            if !matches!(status, DeliveryStatus::Queued | DeliveryStatus::Sending) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("notification {delivery_id} was not delivered in time");
+    }
+
+    fn fast_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queue_moves_exhausted_notifications_to_dlq() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", SEND_PATH)
+            .with_status(502)
+            .with_body(
+                json!({"ok": false, "error_code": 502, "description": "Bad Gateway"}).to_string(),
+            )
+            .expect(3)
+            .create_async()
+            .await;
+        let fallback = server
+            .mock("POST", "/fallback")
+            .match_body(Matcher::PartialJson(json!({"message": "queued"})))
+            .create_async()
+            .await;
+
+        let bot = TelegramBot::with_base_url("test_token:ABC".to_string(), &server.url());
+        let history = History::new(10);
+        let outbox = Outbox::in_memory().unwrap();
+        let queue = DeliveryQueue::start_with(
+            Arc::new(bot),
+            10,
+            false,
+            history.clone(),
+            fast_retries(3),
+            Some(outbox.clone()),
+            Outcomes {
+                fallback: Fallback::new(FallbackSettings {
+                    webhook: Some(format!("{}/fallback", server.url()).parse().unwrap()),
+                    email: None,
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let queued = notification("queued");
+        history.record(&queued, DeliveryStatus::Queued, None);
+        queue.enqueue(queued.clone()).unwrap();
+
+        let status = wait_for_outcome(&history, &queued.delivery_id).await;
+
+        mock.assert_async().await;
+        assert!(matches!(status, DeliveryStatus::Failed { .. }));
+        assert_eq!(history.get(&queued.delivery_id).unwrap().attempts, 3);
+        assert_eq!(outbox.len().unwrap(), 0);
+        let letters = queue.dead_letters().list().unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].notification, queued);
+        assert_eq!(letters[0].attempts, 3);
+        // Passed on in the background
+        for _ in 0..50 {
+            if fallback.matched_async().await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        fallback.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_queue_restores_stored_notifications() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", SEND_PATH)
+            .match_body(Matcher::PartialJson(json!({"text": "left behind"})))
+            .with_body(ok_body(5))
+            .create_async()
+            .await;
+
+        let outbox = Outbox::in_memory().unwrap();
+        let stored = notification("left behind");
+        outbox.push(&stored, 0).unwrap();
+        outbox
+            .reschedule(&stored.delivery_id, 1, "timeout", 0)
+            .unwrap();
+
+        let bot = TelegramBot::with_base_url("test_token:ABC".to_string(), &server.url());
+        let history = History::new(10);
+        DeliveryQueue::start_with(
+            Arc::new(bot),
+            10,
+            false,
+            history.clone(),
+            fast_retries(3),
+            Some(outbox.clone()),
+            Outcomes::default(),
+        )
+        .unwrap();
+
+        let status = wait_for_outcome(&history, &stored.delivery_id).await;
+
+        mock.assert_async().await;
+        assert_eq!(
+            status,
+            DeliveryStatus::Sent {
+                telegram_message_id: Some(5)
+            }
+        );
+        assert_eq!(outbox.len().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(policy.delay(1, None), Duration::from_secs(5));
+        assert_eq!(policy.delay(3, None), Duration::from_secs(20));
+        assert_eq!(policy.delay(30, None), Duration::from_secs(300));
+        // Flood control says exactly how long to wait
+        assert_eq!(policy.delay(1, Some(17)), Duration::from_secs(17));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let api_error = |code| {
+            anyhow::Error::new(TelegramError {
+                description: "error".to_string(),
+                error_code: Some(code),
+                retry_after: None,
+            })
+        };
+
+        assert!(is_retryable(&api_error(429)));
+        assert!(is_retryable(&api_error(502)));
+        assert!(is_retryable(&anyhow::anyhow!("connection reset")));
+        assert!(!is_retryable(&api_error(400)));
+        assert!(!is_retryable(&api_error(403).context("Failed to send")));
+    }
+
+    #[tokio::test]
+    async fn test_queue_rejects_when_full() {
+        let (lanes, _receivers) = Lanes::new(1);
+        let queue = DeliveryQueue {
+            lanes,
+            outbox: None,
+            dead_letters: DeadLetters::default(),
+            stats: Stats::new(),
+        };
+
+        assert!(queue.enqueue(notification("first")).is_ok());
+        let rejected = queue.enqueue(notification("second")).unwrap_err();
+        assert_eq!(rejected.message, "second");
+        assert_eq!(queue.len(), 1);
+        assert!(queue.is_full());
+    }
+
+    #[tokio::test]
+    async fn test_queue_retries_with_any_notifier() {
+        let notifier = Arc::new(MockNotifier::default());
+        *notifier.failing.lock().unwrap() = Some("connection reset".to_string());
+        let history = History::new(10);
+        let queue = DeliveryQueue::start_with(
+            notifier.clone(),
+            10,
+            false,
+            history.clone(),
+            fast_retries(5),
+            None,
+            Outcomes::default(),
+        )
+        .unwrap();
+        let queued = notification("flaky backend");
+        history.record(&queued, DeliveryStatus::Queued, None);
+        queue.enqueue(queued.clone()).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        *notifier.failing.lock().unwrap() = None;
+        let status = wait_for_outcome(&history, &queued.delivery_id).await;
+
+        assert_eq!(
+            status,
+            DeliveryStatus::Sent {
+                telegram_message_id: Some(1)
+            }
+        );
+        assert_eq!(notifier.messages(), vec!["flaky backend"]);
+        assert!(history.get(&queued.delivery_id).unwrap().attempts > 1);
+    }
+
+    #[tokio::test]
+    async fn test_urgent_notifications_are_delivered_first() {
+        let (lanes, mut receivers) = Lanes::new(10);
+        let queue = DeliveryQueue {
+            lanes,
+            outbox: None,
+            dead_letters: DeadLetters::default(),
+            stats: Stats::new(),
+        };
+        let mut debug = notification("debug output");
+        debug.low_priority = true;
+        let mut critical = notification("replica lag");
+        critical.severity = Some("critical".to_string());
+        let mut emergency = notification("database down");
+        emergency.emergency = true;
+
+        queue.enqueue(debug).unwrap();
+        queue.enqueue(notification("backup finished")).unwrap();
+        queue.enqueue(critical).unwrap();
+        queue.enqueue(emergency).unwrap();
+        assert_eq!(queue.len(), 4);
+
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            order.push(receivers.next().await.unwrap().message);
+        }
+        assert_eq!(
+            order,
+            vec![
+                "replica lag",
+                "database down",
+                "backup finished",
+                "debug output"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_message_id() {
+        assert_eq!(
+            extract_message_id(&Some(json!({"message_id": 42}))),
+            Some(42)
+        );
+        assert_eq!(extract_message_id(&Some(json!({}))), None);
+        assert_eq!(extract_message_id(&None), None);
+    }
+
+    #[test]
+    fn test_new_delivery_id_format() {
+        let id = new_delivery_id();
+
+        assert_eq!(id.len(), DELIVERY_ID_LENGTH);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_new_delivery_id_unique() {
+        assert_ne!(new_delivery_id(), new_delivery_id());
+    }
+
+    #[test]
+    fn test_embed_delivery_id_appends_footer() {
+        let mut notification = Notification {
+            message: "Disk full".to_string(),
+            chat_id: "123456789".to_string(),
+            parse_mode: None,
+            disable_notification: false,
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        };
+
+        let outcome = EmbedDeliveryId.process(&mut notification).unwrap();
+
+        assert_eq!(outcome, Outcome::Continue);
+        assert_eq!(notification.message, "Disk full\n\n🔖 abc123def456");
+    }
+}
@@ -0,0 +1,316 @@
+use crate::delivery::{DeliveryQueue, new_delivery_id};
+use crate::history::{DeliveryStatus, History};
+use crate::pipeline::Notification;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Source of the digests sent
+pub const SOURCE: &str = "digest";
+
+/// How often held notifications are checked for a digest that is due
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Digest of a chat (`[digests]` in the config file)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DigestSettings {
+    /// Minutes low-priority notifications are collected before they are sent
+    /// together
+    pub every_minutes: u64,
+}
+
+/// Holds low-priority notifications to chats with a digest and combines them
+/// into one message per chat once the chat's interval has passed.
+/// Cloning shares the held notifications.
+#[derive(Clone, Default)]
+pub struct Digests {
+    intervals: Arc<HashMap<String, Duration>>,
+    held: Arc<Mutex<HashMap<DigestKey, Held>>>,
+}
+
+/// Chat ID, parse mode, bot and topic: messages with different parse modes
+/// can't be mixed, and each bot and topic of a chat gets a digest of its own
+type DigestKey = (String, Option<String>, Option<String>, Option<i64>);
+
+fn key(notification: &Notification) -> DigestKey {
+    (
+        notification.chat_id.clone(),
+        notification.parse_mode.clone(),
+        notification.bot.clone(),
+        notification.thread_id,
+    )
+}
+
+struct Held {
+    due: Instant,
+    notifications: Vec<Notification>,
+}
+
+impl Digests {
+    /// `intervals` maps chat IDs to how long their digest collects
+    pub fn new(intervals: HashMap<String, Duration>) -> Self {
+        Self {
+            intervals: Arc::new(intervals),
+            held: Arc::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.intervals.is_empty()
+    }
+
+    /// Hold a low-priority notification for its chat's digest, returning when
+    /// the digest goes out. None if the notification isn't held.
+    pub fn hold(&self, notification: &Notification, now: Instant) -> Option<Instant> {
+        if !notification.low_priority {
+            return None;
+        }
+        let interval = self.intervals.get(&notification.chat_id)?;
+        let mut held = self.held.lock().unwrap();
+        let digest = held.entry(key(notification)).or_insert_with(|| Held {
+            due: now + *interval,
+            notifications: Vec::new(),
+        });
+        digest.notifications.push(notification.clone());
+        Some(digest.due)
+    }
+
+    /// Take the digests that are due, each with the delivery IDs it combines
+    pub fn due(&self, now: Instant) -> Vec<(Notification, Vec<String>)> {
+        let mut held = self.held.lock().unwrap();
+        let due: Vec<_> = held
+            .iter()
+            .filter(|(_, digest)| digest.due <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.iter()
+            .filter_map(|key| held.remove(key))
+            .map(|digest| combine(digest.notifications))
+            .collect()
+    }
+
+    /// Notifications waiting for their digest
+    pub fn held(&self) -> usize {
+        self.held
+            .lock()
+            .unwrap()
+            .values()
+            .map(|digest| digest.notifications.len())
+            .sum()
+    }
+}
+
+/// One notification with the texts and attachments of all of them
+fn combine(notifications: Vec<Notification>) -> (Notification, Vec<String>) {
+    let count = notifications.len();
+    let mut message = format!(
+        "🗞 Digest: {count} notification{}",
+        if count == 1 { "" } else { "s" }
+    );
+    for notification in &notifications {
+        message.push_str("\n\n");
+        message.push_str(&notification.message);
+    }
+    let first = &notifications[0];
+    let digest = Notification {
+        message,
+        chat_id: first.chat_id.clone(),
+        parse_mode: first.parse_mode.clone(),
+        // Only silent if every part was
+        disable_notification: notifications.iter().all(|n| n.disable_notification),
+        disable_link_preview: notifications.iter().all(|n| n.disable_link_preview),
+        // Protected if any part was
+        protect_content: notifications.iter().any(|n| n.protect_content),
+        thread_id: first.thread_id,
+        bot: first.bot.clone(),
+        delivery_id: new_delivery_id(),
+        attachments: notifications
+            .iter()
+            .flat_map(|notification| notification.attachments.clone())
+            .collect(),
+        source: SOURCE.to_string(),
+        ..Default::default()
+    };
+    let combined = notifications
+        .into_iter()
+        .map(|notification| notification.delivery_id)
+        .collect();
+    (digest, combined)
+}
+
+/// Background task queueing digests for delivery once they are due
+pub async fn deliver_due(digests: Digests, queue: DeliveryQueue, history: History) {
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        for (digest, combined) in digests.due(Instant::now()) {
+            send(&queue, &history, digest, &combined);
+        }
+    }
+}
+
+/// Queue a digest, pointing the history of the notifications it combines to it
+fn send(queue: &DeliveryQueue, history: &History, digest: Notification, combined: &[String]) {
+    let digest_id = digest.delivery_id.clone();
+    let chat_id = digest.chat_id.clone();
+    history.record(&digest, DeliveryStatus::Queued, None);
+    for delivery_id in combined {
+        history.set_status(
+            delivery_id,
+            DeliveryStatus::Digested {
+                digest_id: digest_id.clone(),
+            },
+        );
+    }
+    match queue.enqueue(digest) {
+        Ok(()) => info!(
+            "🗞 Digest {} of {} notification(s) queued for chat {}",
+            digest_id,
+            combined.len(),
+            chat_id
+        ),
+        Err(_) => {
+            warn!(
+                "⚠️ Delivery queue full, dropping digest {} for chat {}",
+                digest_id, chat_id
+            );
+            history.set_status(
+                &digest_id,
+                DeliveryStatus::Failed {
+                    error: "Delivery queue full".to_string(),
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::TelegramBot;
+
+    const INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+    fn digests() -> Digests {
+        Digests::new(HashMap::from([("-100123".to_string(), INTERVAL)]))
+    }
+
+    fn notification(delivery_id: &str, message: &str) -> Notification {
+        Notification {
+            message: message.to_string(),
+            chat_id: "-100123".to_string(),
+            delivery_id: delivery_id.to_string(),
+            low_priority: true,
+            disable_notification: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hold_until_due() {
+        let digests = digests();
+        let start = Instant::now();
+
+        let due = digests.hold(&notification("a", "backup ok"), start);
+        assert_eq!(due, Some(start + INTERVAL));
+        // Later notifications join the digest that is already waiting
+        let later = digests.hold(&notification("b", "rotate ok"), start + INTERVAL / 2);
+        assert_eq!(later, Some(start + INTERVAL));
+        assert_eq!(digests.held(), 2);
+
+        // Normal priority and chats without a digest are sent right away
+        let mut normal = notification("c", "deploy done");
+        normal.low_priority = false;
+        assert_eq!(digests.hold(&normal, start), None);
+        let mut elsewhere = notification("d", "backup ok");
+        elsewhere.chat_id = "-100999".to_string();
+        assert_eq!(digests.hold(&elsewhere, start), None);
+
+        assert!(digests.due(start + INTERVAL / 2).is_empty());
+        let due = digests.due(start + INTERVAL);
+        assert_eq!(due.len(), 1);
+        let (digest, combined) = &due[0];
+        assert_eq!(
+            digest.message,
+            "🗞 Digest: 2 notifications\n\nbackup ok\n\nrotate ok"
+        );
+        assert_eq!(digest.chat_id, "-100123");
+        assert!(digest.disable_notification);
+        assert!(!digest.low_priority);
+        assert_eq!(combined, &["a", "b"]);
+        assert_eq!(digests.held(), 0);
+    }
+
+    #[test]
+    fn test_parse_modes_are_not_mixed() {
+        let digests = digests();
+        let start = Instant::now();
+        let mut markdown = notification("a", "*backup* ok");
+        markdown.parse_mode = Some("Markdown".to_string());
+        digests.hold(&markdown, start);
+        digests.hold(&notification("b", "rotate ok"), start);
+
+        let mut due = digests.due(start + INTERVAL);
+        due.sort_by(|a, b| a.0.parse_mode.cmp(&b.0.parse_mode));
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[0].0.message, "🗞 Digest: 1 notification\n\nrotate ok");
+        assert_eq!(due[1].0.parse_mode.as_deref(), Some("Markdown"));
+    }
+
+    #[test]
+    fn test_bots_and_topics_are_not_mixed() {
+        let digests = digests();
+        let start = Instant::now();
+        let mut other_bot = notification("a", "backup ok");
+        other_bot.bot = Some("tenant-b".to_string());
+        let mut topic = notification("b", "rotate ok");
+        topic.thread_id = Some(42);
+        digests.hold(&other_bot, start);
+        digests.hold(&topic, start);
+        digests.hold(&notification("c", "deploy done"), start);
+
+        let due = digests.due(start + INTERVAL);
+        assert_eq!(due.len(), 3);
+        for (digest, combined) in &due {
+            assert_eq!(combined.len(), 1);
+            match combined[0].as_str() {
+                "a" => assert_eq!(digest.bot.as_deref(), Some("tenant-b")),
+                "b" => assert_eq!(digest.thread_id, Some(42)),
+                _ => assert_eq!((&digest.bot, digest.thread_id), (&None, None)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_links_history() {
+        let history = History::new(10);
+        let queue = DeliveryQueue::start(
+            TelegramBot::new("token".to_string()),
+            10,
+            true,
+            history.clone(),
+        );
+        let digests = digests();
+        let start = Instant::now();
+        for id in ["a", "b"] {
+            let held = notification(id, "backup ok");
+            history.record(&held, DeliveryStatus::Queued, None);
+            digests.hold(&held, start);
+        }
+
+        let (digest, combined) = digests.due(start + INTERVAL).remove(0);
+        let digest_id = digest.delivery_id.clone();
+        send(&queue, &history, digest, &combined);
+
+        assert_eq!(
+            history.get("a").unwrap().status,
+            DeliveryStatus::Digested {
+                digest_id: digest_id.clone()
+            }
+        );
+        assert!(history.get(&digest_id).is_some());
+    }
+}
@@ -0,0 +1,109 @@
+use crate::outbox::Outbox;
+use crate::pipeline::Notification;
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+
+/// A queued notification whose delivery failed for good
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetter {
+    pub notification: Notification,
+    pub attempts: u32,
+    /// Error of the last attempt
+    pub error: String,
+    /// Unix timestamp
+    pub failed_at: u64,
+}
+
+/// Dead-letter queue: queued notifications that ran out of attempts or were
+/// refused by Telegram, kept until they are retried through
+/// `/admin/dlq/{id}/retry`. Stored in the queue database with `--queue-db`,
+/// in memory otherwise. Cloning shares the same entries.
+#[derive(Clone, Default)]
+pub struct DeadLetters {
+    outbox: Option<Outbox>,
+    memory: Arc<Mutex<Vec<DeadLetter>>>,
+}
+
+impl DeadLetters {
+    pub fn new(outbox: Option<Outbox>) -> Self {
+        Self {
+            outbox,
+            memory: Arc::default(),
+        }
+    }
+
+    /// Add a notification, taking it out of the outbox
+    pub fn add(&self, letter: DeadLetter) -> Result<()> {
+        match &self.outbox {
+            Some(outbox) => outbox.bury(&letter),
+            None => {
+                let mut memory = self.memory.lock().unwrap();
+                memory.retain(|other| {
+                    other.notification.delivery_id != letter.notification.delivery_id
+                });
+                memory.push(letter);
+                Ok(())
+            }
+        }
+    }
+
+    /// Every entry, oldest first
+    pub fn list(&self) -> Result<Vec<DeadLetter>> {
+        match &self.outbox {
+            Some(outbox) => outbox.dead_letters(),
+            None => Ok(self.memory.lock().unwrap().clone()),
+        }
+    }
+
+    /// Remove an entry and return it, e.g. to queue it again
+    pub fn take(&self, delivery_id: &str) -> Result<Option<DeadLetter>> {
+        match &self.outbox {
+            Some(outbox) => outbox.take_dead_letter(delivery_id),
+            None => {
+                let mut memory = self.memory.lock().unwrap();
+                let index = memory
+                    .iter()
+                    .position(|letter| letter.notification.delivery_id == delivery_id);
+                Ok(index.map(|index| memory.remove(index)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letter(delivery_id: &str, failed_at: u64) -> DeadLetter {
+        DeadLetter {
+            notification: Notification {
+                message: "Disk full".to_string(),
+                chat_id: "-100123".to_string(),
+                delivery_id: delivery_id.to_string(),
+                ..Default::default()
+            },
+            attempts: 5,
+            error: "Bad Gateway".to_string(),
+            failed_at,
+        }
+    }
+
+    #[test]
+    fn test_add_and_take() {
+        for dead_letters in [
+            DeadLetters::default(),
+            DeadLetters::new(Some(Outbox::in_memory().unwrap())),
+        ] {
+            dead_letters.add(letter("first", 1)).unwrap();
+            dead_letters.add(letter("second", 2)).unwrap();
+
+            assert_eq!(dead_letters.list().unwrap().len(), 2);
+            assert_eq!(
+                dead_letters.take("first").unwrap(),
+                Some(letter("first", 1))
+            );
+            assert_eq!(dead_letters.take("first").unwrap(), None);
+            assert_eq!(dead_letters.list().unwrap(), vec![letter("second", 2)]);
+        }
+    }
+}
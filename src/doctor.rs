@@ -0,0 +1,256 @@
+use crate::bots::Bots;
+use crate::telegram::TelegramBot;
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Result of a single diagnostic check
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub status: Status,
+    pub message: String,
+}
+
+impl Check {
+    fn ok(message: impl Into<String>) -> Self {
+        Self {
+            status: Status::Ok,
+            message: message.into(),
+        }
+    }
+
+    fn warn(message: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warn,
+            message: message.into(),
+        }
+    }
+
+    fn fail(message: impl Into<String>) -> Self {
+        Self {
+            status: Status::Fail,
+            message: message.into(),
+        }
+    }
+
+    fn print(&self) {
+        let icon = match self.status {
+            Status::Ok => "✅",
+            Status::Warn => "⚠️ ",
+            Status::Fail => "❌",
+        };
+        println!("{icon} {}", self.message);
+    }
+}
+
+/// Check that the bot token works and the bot may post (and pin) in `chat_id`
+pub async fn run(bot: &TelegramBot, chat_id: &str) -> Result<()> {
+    let me = match bot.get_me().await {
+        Ok(response) => response.result.unwrap_or_default(),
+        Err(e) => {
+            Check::fail(format!("Bot token rejected: {e}")).print();
+            bail!("Bot token is invalid");
+        }
+    };
+    Check::ok(format!(
+        "Bot token valid (@{})",
+        me["username"].as_str().unwrap_or("unknown")
+    ))
+    .print();
+
+    let chat = match bot.get_chat(chat_id).await {
+        Ok(response) => response.result.unwrap_or_default(),
+        Err(e) => {
+            Check::fail(format!("Chat {chat_id} not reachable: {e}")).print();
+            println!(
+                "💡 Channel and supergroup IDs start with -100; the bot must be a member (an administrator for channels)"
+            );
+            bail!("Chat {chat_id} is not reachable");
+        }
+    };
+
+    // Bots can't look up their own membership in private chats
+    let member = match (chat["type"].as_str(), me["id"].as_i64()) {
+        (Some("private"), _) | (_, None) => None,
+        (_, Some(bot_id)) => match bot.get_chat_member(chat_id, bot_id).await {
+            Ok(response) => response.result,
+            Err(e) => {
+                Check::fail(format!("Could not read the bot's permissions: {e}")).print();
+                bail!("Could not read the bot's permissions in {chat_id}");
+            }
+        },
+    };
+
+    let checks = chat_checks(&chat, member.as_ref());
+    for check in &checks {
+        check.print();
+    }
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    if failed > 0 {
+        bail!("{failed} check(s) failed");
+    }
+    Ok(())
+}
+
+/// Check every chat in `chat_ids` with the bot sending to it, going on to
+/// the next chat after one fails
+pub async fn run_all(bots: &Bots, chat_ids: &[String]) -> Result<()> {
+    let mut failed = 0;
+    for (index, chat_id) in chat_ids.iter().enumerate() {
+        if chat_ids.len() > 1 {
+            if index > 0 {
+                println!();
+            }
+            println!("🔎 Chat {chat_id}");
+        }
+        if let Err(e) = run(bots.for_chat(chat_id), chat_id).await {
+            if chat_ids.len() == 1 {
+                return Err(e);
+            }
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("Checks failed for {failed} of {} chats", chat_ids.len());
+    }
+    Ok(())
+}
+
+/// Judge from `getChat` and `getChatMember` results whether the bot can post
+/// and pin messages in a chat
+pub fn chat_checks(chat: &Value, member: Option<&Value>) -> Vec<Check> {
+    let kind = chat["type"].as_str().unwrap_or("unknown");
+    let title = chat["title"]
+        .as_str()
+        .or_else(|| chat["username"].as_str())
+        .or_else(|| chat["first_name"].as_str())
+        .unwrap_or("untitled");
+    let mut checks = vec![Check::ok(format!("Chat \"{title}\" found ({kind})"))];
+
+    let status = member
+        .and_then(|m| m["status"].as_str())
+        .unwrap_or_default();
+    let right = |name: &str| member.and_then(|m| m[name].as_bool()).unwrap_or(false);
+
+    match kind {
+        "channel" => {
+            let admin = status == "administrator";
+            if status == "creator" || (admin && right("can_post_messages")) {
+                checks.push(Check::ok("Bot can post in the channel"));
+            } else if admin {
+                checks.push(Check::fail(
+                    "Bot is an administrator but lacks the \"Post messages\" right",
+                ));
+            } else {
+                checks.push(Check::fail(
+                    "Bot must be an administrator of the channel to post",
+                ));
+            }
+            if status == "creator" || (admin && right("can_edit_messages")) {
+                checks.push(Check::ok("Bot can pin messages"));
+            } else {
+                checks.push(Check::warn(
+                    "Pinning in channels needs the \"Edit messages\" admin right",
+                ));
+            }
+        }
+        "group" | "supergroup" => {
+            let restricted = status == "restricted" && !right("can_send_messages");
+            if matches!(status, "left" | "kicked") {
+                checks.push(Check::fail("Bot is not a member of the group"));
+            } else if restricted {
+                checks.push(Check::fail("Bot is not allowed to send messages"));
+            } else {
+                checks.push(Check::ok("Bot can post in the group"));
+            }
+            if status == "creator" || (status == "administrator" && right("can_pin_messages")) {
+                checks.push(Check::ok("Bot can pin messages"));
+            } else {
+                checks.push(Check::warn(
+                    "Pinning needs an administrator with the \"Pin messages\" right",
+                ));
+            }
+        }
+        _ => checks.push(Check::ok("Bot can post and pin in the chat")),
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn statuses(checks: &[Check]) -> Vec<Status> {
+        checks.iter().map(|c| c.status).collect()
+    }
+
+    #[test]
+    fn test_channel_admin_with_rights() {
+        let chat = json!({"id": -1001234567890i64, "type": "channel", "title": "Alerts"});
+        let member = json!({
+            "status": "administrator",
+            "can_post_messages": true,
+            "can_edit_messages": true,
+        });
+
+        let checks = chat_checks(&chat, Some(&member));
+
+        assert_eq!(checks[0].message, "Chat \"Alerts\" found (channel)");
+        assert_eq!(statuses(&checks), vec![Status::Ok; 3]);
+    }
+
+    #[test]
+    fn test_channel_member_cannot_post() {
+        let chat = json!({"type": "channel", "title": "Alerts"});
+        let member = json!({"status": "member"});
+
+        let checks = chat_checks(&chat, Some(&member));
+
+        assert_eq!(
+            statuses(&checks),
+            vec![Status::Ok, Status::Fail, Status::Warn]
+        );
+    }
+
+    #[test]
+    fn test_channel_admin_without_post_right() {
+        let chat = json!({"type": "channel", "title": "Alerts"});
+        let member = json!({"status": "administrator", "can_post_messages": false});
+
+        let checks = chat_checks(&chat, Some(&member));
+
+        assert_eq!(checks[1].status, Status::Fail);
+        assert!(checks[1].message.contains("Post messages"));
+    }
+
+    #[test]
+    fn test_group_member_can_post_but_not_pin() {
+        let chat = json!({"type": "supergroup", "title": "Ops"});
+        let member = json!({"status": "member"});
+
+        let checks = chat_checks(&chat, Some(&member));
+
+        assert_eq!(
+            statuses(&checks),
+            vec![Status::Ok, Status::Ok, Status::Warn]
+        );
+    }
+
+    #[test]
+    fn test_private_chat() {
+        let chat = json!({"type": "private", "first_name": "Ada"});
+
+        let checks = chat_checks(&chat, None);
+
+        assert_eq!(checks[0].message, "Chat \"Ada\" found (private)");
+        assert_eq!(statuses(&checks), vec![Status::Ok; 2]);
+    }
+}
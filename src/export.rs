@@ -0,0 +1,275 @@
+use crate::api::HistoryRecordResponse;
+use crate::history::{DeliveryStatus, Record};
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::io::Write;
+
+/// Formats the history can be exported in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One JSON object per line, as returned by `GET /history/{id}`
+    #[default]
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    /// Value of the `format` query parameter, also used as file extension
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+const CSV_COLUMNS: &[&str] = &[
+    "delivery_id",
+    "chat_id",
+    "status",
+    "telegram_message_id",
+    "error",
+    "attempts",
+    "created_at",
+    "updated_at",
+    "parse_mode",
+    "resent_from",
+    "acknowledged_by",
+    "message",
+];
+
+/// First line of an export, if the format has one
+pub fn header(format: ExportFormat) -> Option<String> {
+    match format {
+        ExportFormat::Jsonl => None,
+        ExportFormat::Csv => Some(format!("{}\n", CSV_COLUMNS.join(","))),
+    }
+}
+
+/// One exported record, including the line break
+pub fn line(format: ExportFormat, record: Record) -> String {
+    match format {
+        ExportFormat::Jsonl => {
+            let response = HistoryRecordResponse::from(record);
+            let json = serde_json::to_string(&response).expect("history records serialize");
+            format!("{json}\n")
+        }
+        ExportFormat::Csv => {
+            let (status, telegram_message_id, error) = match &record.status {
+                DeliveryStatus::Scheduled => ("scheduled", None, None),
+                DeliveryStatus::Queued => ("queued", None, None),
+                DeliveryStatus::Sending => ("sending", None, None),
+                DeliveryStatus::Sent {
+                    telegram_message_id,
+                } => ("sent", *telegram_message_id, None),
+                DeliveryStatus::Failed { error } => ("failed", None, Some(error.as_str())),
+                DeliveryStatus::Digested { .. } => ("digested", None, None),
+                DeliveryStatus::Cancelled => ("cancelled", None, None),
+            };
+            let notification = &record.notification;
+            // The complete text, not the shortened one sent to Telegram
+            let message = notification
+                .full_message
+                .as_deref()
+                .unwrap_or(&notification.message);
+            let fields = [
+                notification.delivery_id.clone(),
+                notification.chat_id.clone(),
+                status.to_string(),
+                telegram_message_id
+                    .map(|id| id.to_string())
+                    .unwrap_or_default(),
+                error.unwrap_or_default().to_string(),
+                record.attempts.to_string(),
+                record.created_at.to_string(),
+                record.updated_at.to_string(),
+                notification.parse_mode.clone().unwrap_or_default(),
+                record.resent_from.clone().unwrap_or_default(),
+                record.acknowledged_by.clone().unwrap_or_default(),
+                message.to_string(),
+            ];
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            format!("{}\n", fields.join(","))
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Parse an export bound: Unix seconds, a date (`2025-10-16`) or a UTC time
+/// (`2025-10-16T08:00:00Z`)
+pub fn parse_time(value: &str) -> Result<u64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Ok(seconds);
+    }
+
+    let invalid = || format!("Invalid time {value:?}; use Unix seconds or 2025-10-16T08:00:00Z");
+    let (date, time) = match value.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    let numbers = |text: &str, separator: char| -> Result<Vec<u64>> {
+        text.split(separator)
+            .map(|part| part.parse::<u64>().with_context(invalid))
+            .collect()
+    };
+
+    let [year, month, day] = numbers(date, '-')?[..] else {
+        bail!(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        bail!(invalid());
+    }
+    let seconds = match time {
+        Some(time) => {
+            let time = time.strip_suffix(['Z', 'z']).with_context(invalid)?;
+            let [hours, minutes, seconds] = numbers(time, ':')?[..] else {
+                bail!(invalid());
+            };
+            if hours > 23 || minutes > 59 || seconds > 60 {
+                bail!(invalid());
+            }
+            hours * 3_600 + minutes * 60 + seconds
+        }
+        None => 0,
+    };
+    Ok(days_from_civil(year, month, day) * 86_400 + seconds)
+}
+
+/// Days since 1970-01-01 (Howard Hinnant's algorithm)
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Download an export from a running server (`history export`), writing it
+/// to `out` as it arrives
+pub async fn download(
+    server_url: &str,
+    format: ExportFormat,
+    from: Option<&str>,
+    to: Option<&str>,
+    api_key: Option<&str>,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut query = vec![("format", format.name())];
+    query.extend(from.map(|from| ("from", from)));
+    query.extend(to.map(|to| ("to", to)));
+
+    let url = format!("{}/history/export", server_url.trim_end_matches('/'));
+    let mut request = reqwest::Client::new().get(&url).query(&query);
+    if let Some(key) = api_key {
+        request = request.bearer_auth(key);
+    }
+    let mut response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {url}"))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        bail!("Export failed with {status}: {body}");
+    }
+
+    while let Some(chunk) = response.chunk().await? {
+        out.write_all(&chunk)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::History;
+    use crate::pipeline::Notification;
+
+    fn record(message: &str, status: DeliveryStatus) -> Record {
+        let history = History::new(1);
+        let notification = Notification {
+            message: message.to_string(),
+            chat_id: "-100".to_string(),
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        };
+        history.record(&notification, status, None);
+        history.get("abc123def456").unwrap()
+    }
+
+    #[test]
+    fn test_jsonl_line() {
+        let line = line(
+            ExportFormat::Jsonl,
+            record(
+                "Disk full",
+                DeliveryStatus::Sent {
+                    telegram_message_id: Some(42),
+                },
+            ),
+        );
+
+        assert!(line.ends_with('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["delivery_id"], "abc123def456");
+        assert_eq!(json["status"], "sent");
+        assert_eq!(json["telegram_message_id"], 42);
+    }
+
+    #[test]
+    fn test_csv_line_quotes_fields() {
+        let line = line(
+            ExportFormat::Csv,
+            record(
+                "Disk full, \"/var\"\nat 95%",
+                DeliveryStatus::Failed {
+                    error: "chat not found".to_string(),
+                },
+            ),
+        );
+
+        assert!(line.starts_with("abc123def456,-100,failed,,chat not found,0,"));
+        assert!(line.ends_with(",,,,\"Disk full, \"\"/var\"\"\nat 95%\"\n"));
+        assert_eq!(
+            header(ExportFormat::Csv)
+                .unwrap()
+                .trim_end()
+                .split(',')
+                .count(),
+            CSV_COLUMNS.len()
+        );
+        assert_eq!(header(ExportFormat::Jsonl), None);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1760601600").unwrap(), 1_760_601_600);
+        assert_eq!(parse_time("2025-10-16T08:00:00Z").unwrap(), 1_760_601_600);
+        assert_eq!(parse_time("2025-10-16").unwrap(), 1_760_572_800);
+        assert_eq!(parse_time("2000-03-01").unwrap(), 951_868_800);
+        assert!(parse_time("2025-13-01").is_err());
+        assert!(parse_time("2025-10-16T08:00:00+02:00").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+}
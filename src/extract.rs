@@ -0,0 +1,592 @@
+use crate::api::{ErrorResponse, SendNotificationRequest};
+use crate::auth::{self, InvalidSignature};
+use crate::limits::{self, BodyLimit, DEFAULT_MAX_BODY_BYTES};
+use crate::telegram::Attachment;
+use axum::{
+    Form,
+    body::{BodyDataStream, Bytes},
+    extract::{FromRequest, Multipart, Request},
+    http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Json, Response},
+};
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use std::io::{self, BufReader, ErrorKind, Read};
+use tokio::runtime::Handle;
+
+/// Source of notifications sent to the notification endpoints
+pub const API_SOURCE: &str = "api";
+
+/// A notification request in any of the accepted body formats:
+/// `application/json`, `application/x-www-form-urlencoded`,
+/// `multipart/form-data` with file attachments, or `text/plain` where the
+/// whole body is the message
+#[derive(Debug)]
+pub struct NotifyBody {
+    pub request: SendNotificationRequest,
+    pub attachments: Vec<Attachment>,
+    /// Where the request came from, e.g. [`API_SOURCE`]; counted in
+    /// `GET /stats`
+    pub source: String,
+}
+
+impl<S: Send + Sync> FromRequest<S> for NotifyBody {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        // Raw text from minimal shell scripts, sent to the default chat
+        if content_type.is_empty() || content_type.starts_with("text/plain") {
+            let text = String::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(Self {
+                request: SendNotificationRequest {
+                    // `echo ... | curl --data-binary @-` leaves a trailing newline
+                    message: text.trim_end_matches(['\r', '\n']).to_string(),
+                    ..Default::default()
+                },
+                attachments: Vec::new(),
+                source: API_SOURCE.to_string(),
+            });
+        }
+
+        if content_type.starts_with("multipart/form-data") {
+            let multipart = Multipart::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return from_multipart(multipart).await.map_err(field_error);
+        }
+
+        // Legacy monitoring tools that can only POST HTML-style forms
+        if content_type.starts_with("application/x-www-form-urlencoded") {
+            let Form(fields) = Form::<Vec<(String, String)>>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return from_form(fields).map_err(field_error);
+        }
+
+        let Json(request) = Json::<SendNotificationRequest>::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(Self {
+            request,
+            attachments: Vec::new(),
+            source: API_SOURCE.to_string(),
+        })
+    }
+}
+
+/// A JSON body deserialized while it streams in, for webhooks that can run
+/// to megabytes. Memory holds one chunk of the body plus what `T` keeps of
+/// it: fields `T` doesn't name are parsed past without being stored. Bodies
+/// are cut off at the route's [`BodyLimit`].
+#[derive(Debug)]
+pub struct StreamedJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for StreamedJson<T>
+where
+    T: DeserializeOwned + Send + 'static,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json(request.headers()) {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Json(ErrorResponse::with_code(
+                    "Expected request with `Content-Type: application/json`".to_string(),
+                    "UNSUPPORTED_MEDIA_TYPE".to_string(),
+                )),
+            )
+                .into_response());
+        }
+        let max_bytes = request
+            .extensions()
+            .get::<BodyLimit>()
+            .map_or(DEFAULT_MAX_BODY_BYTES, |limit| limit.max_bytes);
+        let reader = BodyReader {
+            stream: request.into_body().into_data_stream(),
+            chunk: Bytes::new(),
+            read: 0,
+            max_bytes,
+            runtime: Handle::current(),
+        };
+        // serde_json reads synchronously, so the chunks are waited for on a
+        // blocking thread
+        let parsed = tokio::task::spawn_blocking(move || {
+            serde_json::from_reader::<_, T>(BufReader::new(reader))
+        })
+        .await
+        .map_err(|e| json_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        match parsed {
+            Ok(value) => Ok(Self(value)),
+            Err(e) if e.io_error_kind() == Some(ErrorKind::FileTooLarge) => {
+                Err(limits::too_large(max_bytes))
+            }
+            Err(e) if e.io_error_kind() == Some(ErrorKind::PermissionDenied) => {
+                Err(auth::invalid_signature())
+            }
+            Err(e) if e.is_data() => Err(json_error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Failed to deserialize the JSON body: {e}"),
+            )),
+            Err(e) => Err(json_error(
+                StatusCode::BAD_REQUEST,
+                format!("Failed to parse the request body as JSON: {e}"),
+            )),
+        }
+    }
+}
+
+/// `application/json` or a `+json` type
+fn is_json(headers: &HeaderMap) -> bool {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    content_type == "application/json"
+        || (content_type.starts_with("application/") && content_type.ends_with("+json"))
+}
+
+fn json_error(status: StatusCode, error: String) -> Response {
+    (
+        status,
+        Json(ErrorResponse::with_code(error, "INVALID_JSON".to_string())),
+    )
+        .into_response()
+}
+
+/// Blocking reader over a request body, failing with `FileTooLarge` once
+/// more than `max_bytes` came in, and with `PermissionDenied` when the body
+/// doesn't match its signature
+struct BodyReader {
+    stream: BodyDataStream,
+    /// What is left of the chunk being read
+    chunk: Bytes,
+    read: usize,
+    max_bytes: usize,
+    runtime: Handle,
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.runtime.block_on(self.stream.next()) {
+                Some(Ok(chunk)) => {
+                    self.read += chunk.len();
+                    if self.read > self.max_bytes {
+                        return Err(io::Error::new(
+                            ErrorKind::FileTooLarge,
+                            "request body over the limit",
+                        ));
+                    }
+                    self.chunk = chunk;
+                }
+                Some(Err(e)) => {
+                    // Wrapped once more for each body it passed through
+                    let mismatch = std::iter::successors(
+                        Some(&e as &(dyn std::error::Error + 'static)),
+                        |e| e.source(),
+                    )
+                    .any(|e| e.is::<InvalidSignature>());
+                    let kind = if mismatch {
+                        ErrorKind::PermissionDenied
+                    } else {
+                        ErrorKind::Other
+                    };
+                    return Err(io::Error::new(kind, e));
+                }
+                None => return Ok(0),
+            }
+        }
+        let length = buf.len().min(self.chunk.len());
+        buf[..length].copy_from_slice(&self.chunk.split_to(length));
+        Ok(length)
+    }
+}
+
+/// Read the form fields of a multipart request. Text fields mirror the JSON
+/// body; every `file` part becomes an attachment.
+async fn from_multipart(mut multipart: Multipart) -> Result<NotifyBody, (&'static str, String)> {
+    let mut message = None;
+    let mut request = SendNotificationRequest::default();
+    let mut attachments = Vec::new();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ("INVALID_MULTIPART", format!("Invalid multipart body: {e}")))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "file" {
+            let file_name = field.file_name().unwrap_or("attachment").to_string();
+            let content_type = field.content_type().map(str::to_string);
+            let data = field.bytes().await.map_err(|e| {
+                (
+                    "INVALID_MULTIPART",
+                    format!("Failed to read {file_name}: {e}"),
+                )
+            })?;
+            attachments.push(Attachment {
+                file_name,
+                content_type,
+                data: data.to_vec(),
+            });
+            continue;
+        }
+
+        let value = field.text().await.map_err(|e| {
+            (
+                "INVALID_MULTIPART",
+                format!("Failed to read field {name}: {e}"),
+            )
+        })?;
+        set_field(&mut request, &mut message, &name, value)?;
+    }
+
+    request.message = message.ok_or((
+        "MISSING_MESSAGE",
+        "Multipart requests need a `message` field".to_string(),
+    ))?;
+    Ok(NotifyBody {
+        request,
+        attachments,
+        source: API_SOURCE.to_string(),
+    })
+}
+
+/// Read the fields of a URL-encoded form, named as in the JSON body
+fn from_form(fields: Vec<(String, String)>) -> Result<NotifyBody, (&'static str, String)> {
+    let mut message = None;
+    let mut request = SendNotificationRequest::default();
+
+    for (name, value) in fields {
+        set_field(&mut request, &mut message, &name, value)?;
+    }
+
+    request.message = message.ok_or((
+        "MISSING_MESSAGE",
+        "Form requests need a `message` field".to_string(),
+    ))?;
+    Ok(NotifyBody {
+        request,
+        attachments: Vec::new(),
+        source: API_SOURCE.to_string(),
+    })
+}
+
+/// Apply a form field to the request; unknown fields are ignored, as in the
+/// JSON body
+fn set_field(
+    request: &mut SendNotificationRequest,
+    message: &mut Option<String>,
+    name: &str,
+    value: String,
+) -> Result<(), (&'static str, String)> {
+    match name {
+        "message" => *message = Some(value),
+        "chat_id" => request.chat_id = Some(value),
+        "chat" => request.chat = Some(value),
+        // Repeated fields add chats for fan-out
+        "chat_ids" => request.chat_ids.get_or_insert_default().push(value),
+        "chats" => request.chats.get_or_insert_default().push(value),
+        "parse_mode" => request.parse_mode = Some(value),
+        "disable_notification" => request.disable_notification = Some(flag(name, &value)?),
+        "pin" => request.pin = Some(flag(name, &value)?),
+        "async" => request.async_delivery = Some(flag(name, &value)?),
+        "dedup_key" => request.dedup_key = Some(value),
+        "send_at" => request.send_at = Some(value),
+        "callback_url" => request.callback_url = Some(value),
+        "bot" => request.bot = Some(value),
+        "severity" => request.severity = Some(value),
+        "deadline_ms" => {
+            request.deadline_ms = Some(value.trim().parse().map_err(|_| {
+                (
+                    "INVALID_FIELD",
+                    format!("Field `{name}` must be a number of milliseconds"),
+                )
+            })?)
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn field_error((code, error): (&'static str, String)) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::with_code(error, code.to_string())),
+    )
+        .into_response()
+}
+
+fn flag(name: &str, value: &str) -> Result<bool, (&'static str, String)> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" | "" => Ok(false),
+        _ => Err((
+            "INVALID_FIELD",
+            format!("Field `{name}` must be true or false"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    const BOUNDARY: &str = "X-BOUNDARY";
+
+    fn multipart_request(parts: &[(&str, Option<&str>, &str)]) -> Request {
+        let mut body = String::new();
+        for (name, file_name, value) in parts {
+            body.push_str(&format!("--{BOUNDARY}\r\n"));
+            match file_name {
+                Some(file_name) => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{name}\"; filename=\"{file_name}\"\r\nContent-Type: text/plain\r\n\r\n"
+                )),
+                None => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{name}\"\r\n\r\n"
+                )),
+            }
+            body.push_str(value);
+            body.push_str("\r\n");
+        }
+        body.push_str(&format!("--{BOUNDARY}--\r\n"));
+
+        Request::builder()
+            .method("POST")
+            .uri("/notify")
+            .header(
+                CONTENT_TYPE,
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_body() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/notify")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"message": "hello", "chat_id": "-100"}"#))
+            .unwrap();
+
+        let body = NotifyBody::from_request(request, &()).await.unwrap();
+
+        assert_eq!(body.request.message, "hello");
+        assert_eq!(body.request.chat_id, Some("-100".to_string()));
+        assert!(body.attachments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_plain_text_body() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/notify")
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(Body::from("disk full on web-1\n"))
+            .unwrap();
+
+        let body = NotifyBody::from_request(request, &()).await.unwrap();
+
+        assert_eq!(body.request.message, "disk full on web-1");
+        assert_eq!(body.request.chat_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_body_without_content_type_is_plain_text() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/notify")
+            .body(Body::from("disk full"))
+            .unwrap();
+
+        let body = NotifyBody::from_request(request, &()).await.unwrap();
+
+        assert_eq!(body.request.message, "disk full");
+    }
+
+    #[tokio::test]
+    async fn test_multipart_with_files() {
+        let request = multipart_request(&[
+            ("message", None, "Build #42 failed"),
+            ("chat_id", None, "-100"),
+            ("disable_notification", None, "true"),
+            ("file", Some("build.log"), "error: linking failed"),
+            ("file", Some("test.log"), "1 failed"),
+        ]);
+
+        let body = NotifyBody::from_request(request, &()).await.unwrap();
+
+        assert_eq!(body.request.message, "Build #42 failed");
+        assert_eq!(body.request.chat_id, Some("-100".to_string()));
+        assert_eq!(body.request.disable_notification, Some(true));
+        assert_eq!(body.attachments.len(), 2);
+        assert_eq!(body.attachments[0].file_name, "build.log");
+        assert_eq!(
+            body.attachments[0].content_type,
+            Some("text/plain".to_string())
+        );
+        assert_eq!(body.attachments[0].data, b"error: linking failed");
+    }
+
+    #[tokio::test]
+    async fn test_multipart_without_message() {
+        let request = multipart_request(&[("file", Some("build.log"), "log")]);
+
+        let response = NotifyBody::from_request(request, &()).await.unwrap_err();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_invalid_flag() {
+        let request = multipart_request(&[
+            ("message", None, "hello"),
+            ("disable_notification", None, "maybe"),
+        ]);
+
+        let response = NotifyBody::from_request(request, &()).await.unwrap_err();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    fn form_request(body: &str) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri("/notify")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_form_body() {
+        let request = form_request(
+            "message=CPU+high+on+web-1%21&chat_id=-100&parse_mode=HTML&disable_notification=1",
+        );
+
+        let body = NotifyBody::from_request(request, &()).await.unwrap();
+
+        assert_eq!(body.request.message, "CPU high on web-1!");
+        assert_eq!(body.request.chat_id, Some("-100".to_string()));
+        assert_eq!(body.request.parse_mode, Some("HTML".to_string()));
+        assert_eq!(body.request.disable_notification, Some(true));
+        assert!(body.attachments.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_form_without_message() {
+        let request = form_request("chat_id=-100");
+
+        let response = NotifyBody::from_request(request, &()).await.unwrap_err();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_form_deadline() {
+        let body = NotifyBody::from_request(form_request("message=hi&deadline_ms=1500"), &())
+            .await
+            .unwrap();
+        assert_eq!(body.request.deadline_ms, Some(1500));
+
+        let response = NotifyBody::from_request(form_request("message=hi&deadline_ms=soon"), &())
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_flag_values() {
+        assert_eq!(flag("async", "TRUE"), Ok(true));
+        assert_eq!(flag("async", "0"), Ok(false));
+        assert!(flag("async", "maybe").is_err());
+    }
+
+    /// A JSON request whose body arrives in `chunks`, without a length
+    fn streamed_request(chunks: Vec<&'static str>, max_bytes: usize) -> Request {
+        let stream = futures_util::stream::iter(
+            chunks
+                .into_iter()
+                .map(|chunk| Ok::<_, io::Error>(Bytes::from_static(chunk.as_bytes()))),
+        );
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/integrations/alertmanager")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from_stream(stream))
+            .unwrap();
+        request.extensions_mut().insert(BodyLimit {
+            max_bytes,
+            streamed: true,
+        });
+        request
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Status {
+        status: String,
+    }
+
+    #[tokio::test]
+    async fn test_streamed_json() {
+        let request = streamed_request(
+            vec![
+                r#"{"values": ["lots", "#,
+                r#""of data"], "#,
+                r#""status": "firing"}"#,
+            ],
+            1024,
+        );
+        let StreamedJson(parsed) = StreamedJson::<Status>::from_request(request, &())
+            .await
+            .unwrap();
+        assert_eq!(parsed.status, "firing");
+
+        let request = streamed_request(vec![r#"{"status": "#, r#""fir"#], 1024);
+        let response = StreamedJson::<Status>::from_request(request, &())
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let request = streamed_request(vec![r#"{"status": 1}"#], 1024);
+        let response = StreamedJson::<Status>::from_request(request, &())
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_json_cut_off_at_limit() {
+        // No Content-Length, so only counting the chunks catches it
+        let request = streamed_request(vec![r#"{"values": ""#, "0123456789", r#""}"#], 16);
+        let response = StreamedJson::<Status>::from_request(request, &())
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut request = streamed_request(vec![r#"{"status": "firing"}"#], 1024);
+        request.headers_mut().remove(CONTENT_TYPE);
+        let response = StreamedJson::<Status>::from_request(request, &())
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
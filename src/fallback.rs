@@ -0,0 +1,206 @@
+use crate::pipeline::Notification;
+use crate::smtp::{self, Mail, SmtpServer};
+use anyhow::{Result, bail};
+use reqwest::{Client, Url};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Longest the fallback webhook may take to answer
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Mail sent when Telegram delivery fails (`--fallback-smtp`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailSettings {
+    pub server: SmtpServer,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// Channels notifications go to when Telegram can't deliver them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FallbackSettings {
+    /// URL the notification is POSTed to as JSON (`--fallback-webhook`)
+    pub webhook: Option<Url>,
+    pub email: Option<EmailSettings>,
+}
+
+/// Passes notifications whose Telegram delivery failed for good to the
+/// fallback channels, so critical alerts still reach someone during an
+/// outage. Does nothing if no channel is configured.
+#[derive(Clone, Default)]
+pub struct Fallback {
+    settings: Arc<FallbackSettings>,
+    client: Client,
+}
+
+impl Fallback {
+    pub fn new(settings: FallbackSettings) -> Self {
+        let client = Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            settings: Arc::new(settings),
+            client,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.settings.webhook.is_some() || self.settings.email.is_some()
+    }
+
+    /// Pass a notification on in the background, logging the outcome
+    pub fn notify(&self, notification: &Notification, error: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        let fallback = self.clone();
+        let notification = notification.clone();
+        let error = error.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = fallback.send(&notification, &error).await {
+                error!(
+                    "❌ Fallback delivery of notification {} failed: {:#}",
+                    notification.delivery_id, e
+                );
+            }
+        });
+    }
+
+    /// Pass a notification on to every fallback channel, failing if any of
+    /// them didn't take it
+    pub async fn send(&self, notification: &Notification, error: &str) -> Result<()> {
+        let mut failures = Vec::new();
+        if let Some(url) = &self.settings.webhook {
+            match self.post(url, notification, error).await {
+                Ok(()) => info!(
+                    "🛟 Notification {} passed to the fallback webhook",
+                    notification.delivery_id
+                ),
+                Err(e) => failures.push(format!("webhook: {e:#}")),
+            }
+        }
+        if let Some(email) = &self.settings.email {
+            match email.server.send(&mail(email, notification, error)).await {
+                Ok(()) => info!(
+                    "🛟 Notification {} mailed to {}",
+                    notification.delivery_id,
+                    email.to.join(", ")
+                ),
+                Err(e) => failures.push(format!("email: {e:#}")),
+            }
+        }
+        if !failures.is_empty() {
+            bail!(failures.join("; "));
+        }
+        Ok(())
+    }
+
+    async fn post(&self, url: &Url, notification: &Notification, error: &str) -> Result<()> {
+        let payload = json!({
+            "delivery_id": notification.delivery_id,
+            "chat_id": notification.chat_id,
+            "message": notification.message,
+            "parse_mode": notification.parse_mode,
+            "emergency": notification.emergency,
+            "error": error,
+        });
+        self.client
+            .post(url.clone())
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+fn mail(email: &EmailSettings, notification: &Notification, error: &str) -> Mail {
+    Mail {
+        from: email.from.clone(),
+        to: email.to.clone(),
+        subject: smtp::subject(&notification.message),
+        body: format!(
+            "{}\n\n--\nCould not be delivered to Telegram chat {}: {}\nDelivery ID: {}",
+            notification.message, notification.chat_id, error, notification.delivery_id
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn notification() -> Notification {
+        Notification {
+            message: "🔥 Disk full on web-1\nUsage: 100%".to_string(),
+            chat_id: "-100123".to_string(),
+            delivery_id: "abc123".to_string(),
+            emergency: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/hooks/alerts")
+            .match_body(Matcher::Json(json!({
+                "delivery_id": "abc123",
+                "chat_id": "-100123",
+                "message": "🔥 Disk full on web-1\nUsage: 100%",
+                "parse_mode": null,
+                "emergency": true,
+                "error": "Bad Gateway",
+            })))
+            .create_async()
+            .await;
+        let fallback = Fallback::new(FallbackSettings {
+            webhook: Some(Url::parse(&format!("{}/hooks/alerts", server.url())).unwrap()),
+            email: None,
+        });
+
+        fallback.send(&notification(), "Bad Gateway").await.unwrap();
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_webhook_errors_are_reported() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_status(500)
+            .create_async()
+            .await;
+        let fallback = Fallback::new(FallbackSettings {
+            webhook: Some(Url::parse(&server.url()).unwrap()),
+            email: None,
+        });
+
+        assert!(fallback.send(&notification(), "Bad Gateway").await.is_err());
+        assert!(!Fallback::default().is_enabled());
+    }
+
+    #[test]
+    fn test_mail() {
+        let email = EmailSettings {
+            server: SmtpServer::parse("smtp://localhost").unwrap(),
+            from: "alerts@example.com".to_string(),
+            to: vec!["oncall@example.com".to_string()],
+        };
+
+        let mail = mail(&email, &notification(), "Bad Gateway");
+
+        assert_eq!(mail.subject, "🔥 Disk full on web-1");
+        assert!(
+            mail.body
+                .starts_with("🔥 Disk full on web-1\nUsage: 100%\n\n--\n")
+        );
+        assert!(mail.body.contains("chat -100123: Bad Gateway"));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use crate::stats::Stats;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Drops or downgrades notifications whose message matches a regex, e.g. a
+/// known-noisy warning (`[[filters]]` in the config file)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterSettings {
+    /// Counted under this name in `/stats`; the pattern if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Regex searched for in the message, e.g. `(?i)disk usage at 8\d%`
+    pub pattern: String,
+    #[serde(default)]
+    pub action: FilterAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Don't send the notification
+    #[default]
+    Drop,
+    /// Send it silently and as low priority, i.e. in the chat's digest if
+    /// it has one
+    Downgrade,
+}
+
+/// A filter with its pattern compiled
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub settings: FilterSettings,
+    regex: Regex,
+}
+
+impl Filter {
+    pub fn new(settings: FilterSettings) -> Result<Self> {
+        let regex = Regex::new(&settings.pattern)
+            .with_context(|| format!("Invalid filter pattern {:?}", settings.pattern))?;
+        Ok(Self { settings, regex })
+    }
+
+    pub fn name(&self) -> &str {
+        self.settings
+            .name
+            .as_deref()
+            .unwrap_or(&self.settings.pattern)
+    }
+}
+
+/// Applies the first filter matching a notification's message, counting it
+/// in the stats. Emergency notifications are never filtered.
+pub struct Filters {
+    filters: Vec<Filter>,
+    stats: Stats,
+}
+
+impl Filters {
+    pub fn new(filters: Vec<Filter>, stats: Stats) -> Self {
+        Self { filters, stats }
+    }
+}
+
+impl Stage for Filters {
+    fn name(&self) -> &str {
+        "filters"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Transform
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        if notification.emergency {
+            return Ok(Outcome::Continue);
+        }
+        let Some(filter) = self
+            .filters
+            .iter()
+            .find(|filter| filter.regex.is_match(&notification.message))
+        else {
+            return Ok(Outcome::Continue);
+        };
+        self.stats.suppressed(filter.name());
+        match filter.settings.action {
+            FilterAction::Drop => Ok(Outcome::Drop {
+                reason: format!("Matches filter {}", filter.name()),
+            }),
+            FilterAction::Downgrade => {
+                info!(
+                    "🔉 Notification {} downgraded by filter {}",
+                    notification.delivery_id,
+                    filter.name()
+                );
+                notification.disable_notification = true;
+                notification.low_priority = true;
+                Ok(Outcome::Continue)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(name: Option<&str>, pattern: &str, action: FilterAction) -> Filter {
+        Filter::new(FilterSettings {
+            name: name.map(str::to_string),
+            pattern: pattern.to_string(),
+            action,
+        })
+        .unwrap()
+    }
+
+    fn notification(message: &str) -> Notification {
+        Notification {
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let stats = Stats::new();
+        let stage = Filters::new(
+            vec![
+                filter(Some("disk"), r"(?i)disk usage at 8\d%", FilterAction::Drop),
+                filter(None, "^WARN", FilterAction::Downgrade),
+            ],
+            stats.clone(),
+        );
+
+        let outcome = stage
+            .process(&mut notification("Disk usage at 85%"))
+            .unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::Drop {
+                reason: "Matches filter disk".to_string()
+            }
+        );
+
+        let mut warning = notification("WARN slow query");
+        assert_eq!(stage.process(&mut warning).unwrap(), Outcome::Continue);
+        assert!(warning.disable_notification && warning.low_priority);
+
+        let mut other = notification("Disk usage at 95%");
+        assert_eq!(stage.process(&mut other).unwrap(), Outcome::Continue);
+        assert!(!other.disable_notification);
+
+        let mut emergency = notification("Disk usage at 81%");
+        emergency.emergency = true;
+        assert_eq!(stage.process(&mut emergency).unwrap(), Outcome::Continue);
+
+        let suppressed = stats.snapshot(0).suppressed;
+        assert_eq!(suppressed["disk"], 1);
+        assert_eq!(suppressed["^WARN"], 1);
+        assert!(
+            Filter::new(FilterSettings {
+                name: None,
+                pattern: "(unclosed".to_string(),
+                action: FilterAction::Drop,
+            })
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use crate::alertmanager::{self, Alerts};
+use crate::api::SendNotificationRequest;
+use crate::telegram::{Attachment, Button, MAX_BUTTONS};
+use anyhow::{Context, Result, bail};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Source of notifications from `POST /integrations/grafana`
+pub const SOURCE: &str = "grafana";
+
+/// Screenshots attached to one notification at most
+pub const MAX_SCREENSHOTS: usize = 3;
+
+/// Largest screenshot downloaded, Telegram's limit for photos
+const MAX_SCREENSHOT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Longest a screenshot may take to download
+const SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static CLIENT: LazyLock<Client> = LazyLock::new(|| {
+    Client::builder()
+        .timeout(SCREENSHOT_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+});
+
+/// Body of a Grafana unified alerting webhook contact point: Alertmanager's
+/// format plus links to the alert's panel, dashboard and screenshot
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Webhook {
+    pub status: String,
+    pub receiver: String,
+    pub group_labels: BTreeMap<String, String>,
+    pub common_labels: BTreeMap<String, String>,
+    pub common_annotations: BTreeMap<String, String>,
+    #[serde(rename = "externalURL")]
+    pub external_url: String,
+    pub alerts: Alerts<Alert>,
+    pub truncated_alerts: usize,
+}
+
+/// An Alertmanager alert with Grafana's links. The Alertmanager fields are
+/// repeated rather than flattened in, which would buffer every other field.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Alert {
+    pub status: String,
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+    #[serde(rename = "panelURL")]
+    pub panel_url: String,
+    #[serde(rename = "dashboardURL")]
+    pub dashboard_url: String,
+    /// Screenshot of the panel, if Grafana is set up to take them
+    #[serde(rename = "imageURL")]
+    pub image_url: String,
+}
+
+impl Webhook {
+    fn to_alertmanager(&self) -> alertmanager::Webhook {
+        alertmanager::Webhook {
+            status: self.status.clone(),
+            receiver: self.receiver.clone(),
+            group_labels: self.group_labels.clone(),
+            common_labels: self.common_labels.clone(),
+            common_annotations: self.common_annotations.clone(),
+            external_url: self.external_url.clone(),
+            alerts: self.alerts.map(|alert| alertmanager::Alert {
+                status: alert.status.clone(),
+                labels: alert.labels.clone(),
+                annotations: alert.annotations.clone(),
+            }),
+            truncated_alerts: self.truncated_alerts,
+        }
+    }
+}
+
+/// The same message as for Alertmanager, with buttons opening the panels and
+/// dashboards of the alerts
+pub fn render(webhook: &Webhook) -> SendNotificationRequest {
+    let mut request = alertmanager::render_group(&webhook.to_alertmanager(), "Open Grafana");
+    let panels = distinct(webhook.alerts.iter().map(|alert| &alert.panel_url));
+    let dashboards = distinct(webhook.alerts.iter().map(|alert| &alert.dashboard_url));
+    let buttons: Vec<Button> = [("📈 Panel", panels), ("📊 Dashboard", dashboards)]
+        .into_iter()
+        .flat_map(|(text, urls)| {
+            let numbered = urls.len() > 1;
+            urls.into_iter()
+                .enumerate()
+                .map(move |(index, url)| Button {
+                    text: if numbered {
+                        format!("{text} {}", index + 1)
+                    } else {
+                        text.to_string()
+                    },
+                    url: url.to_string(),
+                })
+        })
+        .take(MAX_BUTTONS)
+        .collect();
+    if !buttons.is_empty() {
+        request.buttons = Some(buttons);
+    }
+    request
+}
+
+/// Screenshot URLs of the alerts, without repeats
+pub fn screenshots(webhook: &Webhook) -> Vec<String> {
+    distinct(webhook.alerts.iter().map(|alert| &alert.image_url))
+        .into_iter()
+        .take(MAX_SCREENSHOTS)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Download a screenshot to attach it as a photo
+pub async fn download(url: &str) -> Result<Attachment> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bail!("Screenshot URL {url:?} must start with http:// or https://");
+    }
+    let response = CLIENT
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to download screenshot {url}"))?
+        .error_for_status()?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_SCREENSHOT_BYTES as u64)
+    {
+        bail!("Screenshot {url} is larger than {MAX_SCREENSHOT_BYTES} bytes");
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        });
+    let data = response.bytes().await?;
+    if data.len() > MAX_SCREENSHOT_BYTES {
+        bail!("Screenshot {url} is larger than {MAX_SCREENSHOT_BYTES} bytes");
+    }
+    let file_name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("screenshot.png")
+        .to_string();
+    Ok(Attachment {
+        file_name,
+        content_type,
+        data: data.to_vec(),
+    })
+}
+
+/// Non-empty URLs in order of first appearance
+fn distinct<'a>(urls: impl Iterator<Item = &'a String>) -> Vec<&'a str> {
+    let mut seen = Vec::new();
+    for url in urls {
+        if !url.is_empty() && !seen.contains(&url.as_str()) {
+            seen.push(url.as_str());
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+    use serde_json::json;
+
+    fn webhook() -> Webhook {
+        serde_json::from_value(json!({
+            "receiver": "telegram",
+            "status": "firing",
+            "orgId": 1,
+            "commonLabels": {"alertname": "HighMemory", "grafana_folder": "Infra"},
+            "externalURL": "https://grafana.example.com/",
+            "alerts": [
+                {
+                    "status": "firing",
+                    "labels": {"alertname": "HighMemory", "grafana_folder": "Infra", "instance": "web-1"},
+                    "annotations": {"summary": "Memory above 90%"},
+                    "dashboardURL": "https://grafana.example.com/d/node",
+                    "panelURL": "https://grafana.example.com/d/node?viewPanel=4",
+                    "imageURL": "https://grafana.example.com/public/img/attachments/a1.png"
+                },
+                {
+                    "status": "firing",
+                    "labels": {"alertname": "HighMemory", "grafana_folder": "Infra", "instance": "web-2"},
+                    "annotations": {"summary": "Memory above 90%"},
+                    "dashboardURL": "https://grafana.example.com/d/node",
+                    "panelURL": "https://grafana.example.com/d/node?viewPanel=4",
+                    "imageURL": ""
+                }
+            ],
+            "title": "[FIRING:2] HighMemory (Infra)",
+            "state": "alerting"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_with_buttons() {
+        let request = render(&webhook());
+
+        assert!(
+            request
+                .message
+                .starts_with("🔥 <b>[FIRING:2]</b> HighMemory\n<i>grafana_folder=Infra</i>")
+        );
+        assert!(
+            request
+                .message
+                .contains("• <b>instance=web-2</b>: Memory above 90%")
+        );
+        assert!(
+            request
+                .message
+                .ends_with("<a href=\"https://grafana.example.com/\">Open Grafana</a>")
+        );
+        let buttons = request.buttons.unwrap();
+        assert_eq!(buttons.len(), 2);
+        assert_eq!(buttons[0].text, "📈 Panel");
+        assert_eq!(
+            buttons[0].url,
+            "https://grafana.example.com/d/node?viewPanel=4"
+        );
+        assert_eq!(buttons[1].text, "📊 Dashboard");
+        assert_eq!(
+            screenshots(&webhook()),
+            ["https://grafana.example.com/public/img/attachments/a1.png"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_screenshot() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/public/img/attachments/a1.png")
+            .with_header("content-type", "image/png")
+            .with_body([0x89, b'P', b'N', b'G'])
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/missing.png")
+            .with_status(404)
+            .create_async()
+            .await;
+        let attachment = download(&format!("{}/public/img/attachments/a1.png", server.url()))
+            .await
+            .unwrap();
+        assert_eq!(attachment.file_name, "a1.png");
+        assert!(attachment.is_photo());
+        assert_eq!(attachment.data.len(), 4);
+        assert!(
+            download(&format!("{}/missing.png", server.url()))
+                .await
+                .is_err()
+        );
+        assert!(download("file:///etc/passwd").await.is_err());
+    }
+}
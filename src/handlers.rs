@@ -1,20 +1,175 @@
+use crate::actions::Silences;
+use crate::alertmanager::{self, Webhook};
 use crate::api::{
-    ErrorResponse, HealthResponse, InfoResponse, SendNotificationRequest, SendNotificationResponse,
+    ApiKeyListResponse, ApiKeyResponse, ChatResult, CheckInResponse, CreateKeyRequest,
+    CreateScheduleRequest, DeadLetterListResponse, DeadLetterResponse, DeliveryMode, ErrorResponse,
+    ExportQuery, HealthResponse, HistoryListResponse, HistoryQuery, HistoryRecordResponse,
+    InfoResponse, IntegrationQuery, MessageStatusResponse, NotifyQuery, PreviewResponse,
+    PutKeyRequest, ReadinessResponse, ResendRequest, ScheduleListResponse, ScheduleResponse,
+    ScheduledListResponse, SendNotificationRequest, SendNotificationResponse, SilenceListResponse,
+    SilenceRequest, SilenceResponse, TargetListResponse, TargetRequest, TargetResponse,
 };
-use crate::telegram::TelegramBot;
-use axum::{Json as JsonExtractor, extract::State, http::StatusCode, response::Json};
-use serde_json::Value;
+use crate::auth::{self, ApiKeys};
+use crate::bots::{BotError, Bots};
+use crate::breaker::CircuitOpen;
+use crate::callbacks::{self, Callbacks};
+use crate::checkins::{self, CheckIns};
+use crate::delivery::{DeliveryQueue, new_delivery_id, test_mode};
+use crate::digest::Digests;
+use crate::export;
+use crate::extract::{NotifyBody, StreamedJson};
+use crate::fallback::Fallback;
+use crate::gitsync::{ConfigSync, SyncResult};
+use crate::grafana;
+use crate::history::{self, DeliveryStatus, Filter, History};
+use crate::idempotency::{Begin, IDEMPOTENCY_HEADER, IdempotencyStore, MAX_KEY_LENGTH};
+use crate::keys::{KeyStore, Quota};
+use crate::notifier::Notifier;
+use crate::pagerduty::{self, EventResponse, Incidents};
+use crate::pages;
+use crate::pipeline::{Notification, Pipeline, Processed};
+#[cfg(feature = "plugins")]
+use crate::plugins::{self, Plugin};
+use crate::priority::{Priority, PrioritySettings};
+use crate::provision::{self, check_preconditions, etag_header, validate_name};
+use crate::quota::{KeyQuotas, KeyUsage};
+use crate::ratelimit::RateLimitSnapshot;
+use crate::readiness::BotCheck;
+use crate::scheduler::{self, Scheduler, format_time};
+use crate::schedules::{self, Schedule, ScheduleStore, Source};
+use crate::stats::StatsSnapshot;
+use crate::storage::{self, SizeWatch, Stores};
+use crate::targets::Targets;
+use crate::telegram::{self, split_message};
+use crate::uptime::{self, Outages};
+use crate::webhooks::{self, MappedWebhook};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    },
+    response::{Html, IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Local, SecondsFormat};
+use futures_util::stream;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tracing::{error, info, warn};
 
 pub struct AppState {
-    pub bot: TelegramBot,
+    /// The main bot and those of `[bots]`
+    pub bots: Bots,
+    /// Delivers notifications, usually with `bots`
+    pub notifier: Arc<dyn Notifier>,
+    /// Username of the main bot, as verified at startup
+    pub bot_username: Option<String>,
     pub default_chat_id: String,
+    /// Prefix of every route (`--base-path`), used in advertised paths and links
+    pub base_path: String,
+    pub pipeline: Pipeline,
+    pub queue: DeliveryQueue,
+    /// Where notifications go when Telegram delivery fails
+    pub fallback: Fallback,
+    /// Tells callers how the delivery of their notification ended
+    pub callbacks: Callbacks,
+    /// Low-priority notifications held for their chat's digest
+    pub digests: Digests,
+    /// Notifications waiting for their `send_at` time
+    pub scheduler: Scheduler,
+    /// Recurring notifications from the config file and `/schedules`
+    pub schedules: ScheduleStore,
+    pub idempotency: IdempotencyStore,
+    pub history: History,
+    /// Named chats accepted in the request's `chat` field
+    pub targets: Targets,
+    /// Chats whose notifications are dropped for now
+    pub silences: Silences,
+    /// Keys allowed to send `X-Priority: emergency` notifications
+    pub priority: PrioritySettings,
+    /// Request counts and quotas of named API keys
+    pub quotas: KeyQuotas,
+    /// Keys accepted for sending, telling which named key a request used
+    pub api_keys: ApiKeys,
+    /// Keys managed through `/admin/keys`
+    pub keys: KeyStore,
+    /// Pulls the routing script from `--config-repo`, if set
+    pub config_sync: Option<Arc<ConfigSync>>,
+    /// Cached bot verification for `/readyz`
+    pub bot_check: BotCheck,
+    /// Monitors reported down through `/integrations/uptime`
+    pub outages: Outages,
+    /// Webhooks accepted at `/webhook/{name}`
+    pub webhooks: BTreeMap<String, MappedWebhook>,
+    /// Plugins parsing the webhooks accepted at `/plugin/{name}`
+    #[cfg(feature = "plugins")]
+    pub plugins: BTreeMap<String, Plugin>,
+    /// Incidents triggered through `/integrations/pagerduty`
+    pub incidents: Incidents,
+    /// Jobs expected at `/checkin/{slug}`
+    pub checkins: CheckIns,
+    /// Settings the server runs with, secrets masked, for `/admin/config`
+    pub effective_config: serde_json::Value,
 }
 
 /// GET / - API information
-pub async fn root() -> Json<InfoResponse> {
-    Json(InfoResponse::new())
+pub async fn root(State(state): State<Arc<AppState>>) -> Json<InfoResponse> {
+    Json(InfoResponse::new().with_base_path(&state.base_path))
+}
+
+/// GET /livez - The process is up and serving requests
+pub async fn livez() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "alive" }))
+}
+
+/// GET /readyz - Whether this instance should receive notifications: the bot
+/// token works, the queue isn't full and its storage can be read
+pub async fn readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<ReadinessResponse>) {
+    let bot = if test_mode() {
+        Ok(())
+    } else {
+        state.bot_check.check(state.notifier.as_ref()).await
+    };
+    let queue = if state.queue.is_full() {
+        Err("Delivery queue is full".to_string())
+    } else {
+        Ok(())
+    };
+    let storage = state
+        .queue
+        .check_storage()
+        .and_then(|()| state.history.check_storage())
+        .map_err(|e| format!("{e:#}"));
+
+    let ready = bot.is_ok() && queue.is_ok() && storage.is_ok();
+    if !ready {
+        warn!(
+            "⚠️ Not ready: {}",
+            [&bot, &queue, &storage]
+                .into_iter()
+                .filter_map(|outcome| outcome.as_ref().err().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+    }
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            status: if ready { "ready" } else { "not_ready" },
+            bot: bot.into(),
+            queue: queue.into(),
+            storage: storage.into(),
+        }),
+    )
 }
 
 /// GET /health - Health check and bot verification
@@ -24,38 +179,33 @@ pub async fn health(
     info("🔍 Health check requested");
 
     // Check if we're in test mode (validation was skipped)
-    let skip_validation = std::env::var("TELEGRAM_NOTIFICATIONS_SKIP_VALIDATION")
-        .unwrap_or_default()
-        .to_lowercase()
-        == "true";
-
-    if skip_validation {
+    if test_mode() {
         info("⚠️  Health check in test mode (bot validation skipped)");
-        Ok(Json(HealthResponse {
-            status: "healthy".to_string(),
-            service: "telegram-notifications".to_string(),
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            bot_verified: false,
-            bot_username: Some("test-bot".to_string()),
-        }))
+        Ok(Json(health_response(
+            &state,
+            false,
+            Some("test-bot".to_string()),
+        )))
     } else {
-        match state.bot.get_me().await {
-            Ok(response) => {
-                let bot_username = response
-                    .result
-                    .as_ref()
-                    .and_then(|result| result.get("username"))
-                    .and_then(|username| username.as_str())
-                    .map(|s| s.to_string());
-
+        match state.notifier.health_check().await {
+            Ok(()) => {
                 info("✅ Health check passed - bot verified");
-                Ok(Json(HealthResponse {
-                    status: "healthy".to_string(),
-                    service: "telegram-notifications".to_string(),
-                    version: env!("CARGO_PKG_VERSION").to_string(),
-                    bot_verified: true,
-                    bot_username,
-                }))
+                Ok(Json(health_response(
+                    &state,
+                    true,
+                    state.bot_username.clone(),
+                )))
+            }
+            // Telegram isn't asked while the breaker is open
+            Err(e) if e.is::<CircuitOpen>() => {
+                error!("❌ Health check failed - {}", e);
+                Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Json(ErrorResponse::with_code(
+                        e.to_string(),
+                        "TELEGRAM_UNAVAILABLE".to_string(),
+                    )),
+                ))
             }
             Err(e) => {
                 error!("❌ Health check failed - bot verification error: {}", e);
@@ -71,93 +221,2322 @@ pub async fn health(
     }
 }
 
+fn health_response(
+    state: &AppState,
+    bot_verified: bool,
+    bot_username: Option<String>,
+) -> HealthResponse {
+    let stats = state.queue.stats();
+    HealthResponse {
+        status: "healthy".to_string(),
+        service: "telegram-notifications".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        bot_verified,
+        bot_username,
+        circuit_breaker: state.bots.main().circuit_breaker().state(Instant::now()),
+        uptime_secs: stats.uptime().as_secs(),
+        queued_messages: state.queue.len(),
+        last_successful_send: stats.last_sent_at(),
+    }
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+type NotifyResult = Result<(StatusCode, Json<SendNotificationResponse>), ApiError>;
+
 /// POST /notify - Send notification
 pub async fn notify(
     State(state): State<Arc<AppState>>,
-    JsonExtractor(request): JsonExtractor<SendNotificationRequest>,
-) -> Result<Json<SendNotificationResponse>, (StatusCode, Json<ErrorResponse>)> {
+    headers: HeaderMap,
+    Query(query): Query<NotifyQuery>,
+    body: NotifyBody,
+) -> NotifyResult {
     info!(
         "📤 Notification request received: {}",
-        request.message.chars().take(50).collect::<String>()
+        body.request.message.chars().take(50).collect::<String>()
     );
 
-    // Validate message
-    if request.message.is_empty() {
-        warn!("⚠️ Empty message in notification request");
+    let asynchronous =
+        query.mode == Some(DeliveryMode::Async) || body.request.async_delivery.unwrap_or(false);
+    let wait = match (asynchronous, query.deadline_ms.or(body.request.deadline_ms)) {
+        (true, _) => Wait::Queued,
+        (false, Some(ms)) => Wait::Until(tokio::time::Instant::now() + Duration::from_millis(ms)),
+        (false, None) => Wait::Sent,
+    };
+    let priority = priority(&state, &headers)?;
+    let mut body = body;
+    apply_caller(&state, &headers, &mut body.request)?;
+
+    // Emergencies must get through even if they look like a retry
+    let key = match priority {
+        Priority::Emergency => None,
+        _ => idempotency_key(&headers, &body.request)?,
+    };
+    let Some(key) = key else {
+        return dispatch(&state, body, wait, priority).await;
+    };
+    let scoped = format!("{}/{key}", idempotency_owner(&headers, &body.request));
+
+    match state.idempotency.begin(&scoped, Instant::now()) {
+        Begin::New => {}
+        Begin::Completed(status, response) => {
+            info!("♻️ Returning stored result for idempotency key {}", key);
+            return Ok((status, Json(response)));
+        }
+        Begin::InProgress => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(ErrorResponse::with_code(
+                    "A request with this idempotency key is still being processed".to_string(),
+                    "IDEMPOTENCY_KEY_IN_USE".to_string(),
+                )),
+            ));
+        }
+    }
+
+    let result = dispatch(&state, body, wait, priority).await;
+    match &result {
+        Ok((status, Json(response))) if !status.is_server_error() => {
+            state
+                .idempotency
+                .complete(&scoped, *status, response.clone(), Instant::now())
+        }
+        // Failed requests don't consume the key so the client can retry
+        _ => state.idempotency.abandon(&scoped),
+    }
+    result
+}
+
+/// Send the request with the bot its API key is bound to, or the one it
+/// names, noting which named key it came with
+fn apply_caller(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: &mut SendNotificationRequest,
+) -> Result<(), ApiError> {
+    let key = auth::api_key(headers);
+    request.key = key.and_then(|key| {
+        state
+            .api_keys
+            .name_of(key)
+            .map(str::to_string)
+            .or_else(|| state.keys.find(key).map(|managed| managed.id))
+    });
+    request.bot = state
+        .bots
+        .select(key, request.bot.as_deref())
+        .map_err(|e| {
+            warn!("🚫 Rejected notification: {}", e.message());
+            bot_error(e)
+        })?;
+    Ok(())
+}
+
+fn bot_error(e: BotError) -> ApiError {
+    let status = match e {
+        BotError::Unknown(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::FORBIDDEN,
+    };
+    (
+        status,
+        Json(ErrorResponse::with_code(e.message(), e.code().to_string())),
+    )
+}
+
+/// The requested priority; emergencies only for keys allowed to send them
+fn priority(state: &AppState, headers: &HeaderMap) -> Result<Priority, ApiError> {
+    let priority = state.priority.priority(headers).map_err(|e| {
+        warn!("🚫 Rejected emergency notification: {}", e.message());
+        (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::with_code(
+                e.message().to_string(),
+                e.code().to_string(),
+            )),
+        )
+    })?;
+    if priority == Priority::Emergency {
+        warn!("🚨 Emergency notification requested");
+    }
+    Ok(priority)
+}
+
+/// The request's idempotency key, from the header or the `dedup_key` field
+fn idempotency_key(
+    headers: &HeaderMap,
+    request: &SendNotificationRequest,
+) -> Result<Option<String>, (StatusCode, Json<ErrorResponse>)> {
+    let key = match headers.get(IDEMPOTENCY_HEADER) {
+        Some(value) => value.to_str().ok().map(str::to_string).unwrap_or_default(),
+        None => match &request.dedup_key {
+            Some(key) => key.clone(),
+            None => return Ok(None),
+        },
+    };
+
+    let key = key.trim();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
         return Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse::with_code(
-                "Message cannot be empty".to_string(),
-                "EMPTY_MESSAGE".to_string(),
+                format!("Idempotency key must be between 1 and {MAX_KEY_LENGTH} characters"),
+                "INVALID_IDEMPOTENCY_KEY".to_string(),
             )),
         ));
     }
+    Ok(Some(key.to_string()))
+}
 
-    // Use custom chat_id or default
-    let chat_id = request.chat_id.as_ref().unwrap_or(&state.default_chat_id);
+/// Whose idempotency keys a request uses: its named key's, else those of
+/// its credential, so callers never get each other's stored results
+fn idempotency_owner(headers: &HeaderMap, request: &SendNotificationRequest) -> String {
+    match (&request.key, auth::api_key(headers)) {
+        (Some(name), _) => format!("key:{name}"),
+        (None, Some(credential)) => {
+            format!("sha256:{}", hex::encode(Sha256::digest(credential)))
+        }
+        (None, None) => String::new(),
+    }
+}
 
-    // Check if we're in test mode
-    let skip_validation = std::env::var("TELEGRAM_NOTIFICATIONS_SKIP_VALIDATION")
-        .unwrap_or_default()
-        .to_lowercase()
-        == "true";
+/// How long a request waits for its notification to go out
+#[derive(Debug, Clone, Copy)]
+enum Wait {
+    /// Until Telegram has answered
+    Sent,
+    /// Only until the notification is queued
+    Queued,
+    /// Until Telegram has answered or the deadline passes, whichever is first
+    Until(tokio::time::Instant),
+}
 
-    if skip_validation {
-        info!("⚠️  Test mode: Simulating message send to chat {}", chat_id);
-        Ok(Json(SendNotificationResponse {
-            success: true,
-            message: "Notification sent successfully (test mode)".to_string(),
-            telegram_message_id: Some(42), // Mock message ID
-        }))
-    } else {
-        // Send the message
-        match state
-            .bot
-            .send_message_advanced(
-                chat_id,
-                &request.message,
-                request.parse_mode.as_deref(),
-                request.disable_notification.unwrap_or(false),
-            )
-            .await
-        {
-            Ok(response) => {
-                let message_id = extract_message_id(&response.result);
-                info!("✅ Notification sent successfully to chat {}", chat_id);
+/// Validate, route and send (or queue) a notification
+async fn dispatch(
+    state: &Arc<AppState>,
+    body: NotifyBody,
+    wait: Wait,
+    priority: Priority,
+) -> NotifyResult {
+    let fan_out = body.request.is_fan_out();
+    let mut prepared = prepare(state, body, priority, false)?;
+    if !fan_out {
+        let (_, prepared) = prepared.remove(0);
+        return dispatch_one(state, prepared, wait).await;
+    }
 
-                Ok(Json(SendNotificationResponse {
+    // Every target passed the pipeline; deliver to each in turn
+    let mut results = Vec::with_capacity(prepared.len());
+    let mut first_error = None;
+    for (chat_id, prepared) in prepared {
+        let result = dispatch_one(state, prepared, wait).await;
+        let (status, result) = ChatResult::from_outcome(chat_id, result);
+        if !result.success && first_error.is_none() {
+            first_error = Some(status);
+        }
+        results.push((status, result));
+    }
+
+    let sent = results.iter().filter(|(_, result)| result.success).count();
+    let status = match (sent, first_error) {
+        (_, None) => results[0].0,
+        (0, Some(status)) => status,
+        _ => StatusCode::MULTI_STATUS,
+    };
+    Ok((
+        status,
+        Json(SendNotificationResponse {
+            success: first_error.is_none(),
+            message: format!(
+                "Notification delivered to {sent} of {} chats",
+                results.len()
+            ),
+            telegram_message_id: None,
+            delivery_id: None,
+            results: results.into_iter().map(|(_, result)| result).collect(),
+        }),
+    ))
+}
+
+/// Validate, route and send a notification before returning, for inputs
+/// that acknowledge messages only once Telegram has them
+pub(crate) async fn dispatch_now(state: &Arc<AppState>, body: NotifyBody) -> NotifyResult {
+    dispatch(state, body, Wait::Sent, Priority::Normal).await
+}
+
+/// Send (or queue) one prepared notification
+async fn dispatch_one(state: &Arc<AppState>, prepared: Prepared, wait: Wait) -> NotifyResult {
+    let notification = match prepared {
+        Prepared::Ready(notification) => *notification,
+        Prepared::Suppressed {
+            delivery_id,
+            reason,
+        } => {
+            return Ok((
+                StatusCode::OK,
+                Json(SendNotificationResponse {
                     success: true,
-                    message: "Notification sent successfully".to_string(),
-                    telegram_message_id: message_id,
-                }))
+                    message: format!("Notification suppressed: {reason}"),
+                    telegram_message_id: None,
+                    delivery_id: Some(delivery_id),
+                    results: Vec::new(),
+                }),
+            ));
+        }
+    };
+    let delivery_id = notification.delivery_id.clone();
+
+    if let Some(send_at) = notification.send_at.filter(|send_at| *send_at > unix_now()) {
+        state
+            .history
+            .record(&notification, DeliveryStatus::Scheduled, None);
+        state.scheduler.schedule(notification);
+        info!(
+            "⏰ Notification {} scheduled for {}",
+            delivery_id,
+            format_time(send_at)
+        );
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(SendNotificationResponse {
+                success: true,
+                message: format!("Notification scheduled for {}", format_time(send_at)),
+                telegram_message_id: None,
+                delivery_id: Some(delivery_id),
+                results: Vec::new(),
+            }),
+        ));
+    }
+
+    if let Some(due) = state.digests.hold(&notification, Instant::now()) {
+        state
+            .history
+            .record(&notification, DeliveryStatus::Queued, None);
+        let minutes = due
+            .saturating_duration_since(Instant::now())
+            .as_secs()
+            .div_ceil(60);
+        info!(
+            "🗞 Notification {} held for the digest to chat {}",
+            delivery_id, notification.chat_id
+        );
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(SendNotificationResponse {
+                success: true,
+                message: format!("Notification held for the next digest, due in {minutes} min"),
+                telegram_message_id: None,
+                delivery_id: Some(delivery_id),
+                results: Vec::new(),
+            }),
+        ));
+    }
+
+    if let Wait::Until(deadline) = wait {
+        return send_by(state, notification, deadline).await;
+    }
+    if let Wait::Queued = wait {
+        state
+            .history
+            .record(&notification, DeliveryStatus::Queued, None);
+        return match state.queue.enqueue(notification) {
+            Ok(()) => {
+                info!("📥 Notification {} queued for delivery", delivery_id);
+                Ok((
+                    StatusCode::ACCEPTED,
+                    Json(SendNotificationResponse {
+                        success: true,
+                        message: "Notification queued for delivery".to_string(),
+                        telegram_message_id: None,
+                        delivery_id: Some(delivery_id),
+                        results: Vec::new(),
+                    }),
+                ))
             }
-            Err(e) => {
-                error!("❌ Failed to send notification: {}", e);
+            Err(_) => {
+                warn!(
+                    "⚠️ Delivery queue full, rejecting notification {}",
+                    delivery_id
+                );
+                state.history.set_status(
+                    &delivery_id,
+                    DeliveryStatus::Failed {
+                        error: "Delivery queue full".to_string(),
+                    },
+                );
                 Err((
-                    StatusCode::BAD_GATEWAY,
+                    StatusCode::SERVICE_UNAVAILABLE,
                     Json(ErrorResponse::with_code(
-                        format!("Failed to send notification: {e}"),
-                        "TELEGRAM_API_ERROR".to_string(),
+                        "Delivery queue is full, try again later".to_string(),
+                        "QUEUE_FULL".to_string(),
                     )),
                 ))
             }
+        };
+    }
+
+    send_now(state, notification, None).await
+}
+
+/// Send a notification, answering 202 if Telegram hasn't confirmed it by
+/// `deadline`; the send then carries on in the background
+async fn send_by(
+    state: &Arc<AppState>,
+    notification: Notification,
+    deadline: tokio::time::Instant,
+) -> NotifyResult {
+    let delivery_id = notification.delivery_id.clone();
+    state
+        .history
+        .record(&notification, DeliveryStatus::Queued, None);
+
+    let sending = tokio::spawn({
+        let state = Arc::clone(state);
+        async move { attempt(&state, notification).await }
+    });
+    match tokio::time::timeout_at(deadline, sending).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            error!("❌ Sending notification {} failed: {}", delivery_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::with_code(
+                    format!("Failed to send notification: {e}"),
+                    "DELIVERY_ERROR".to_string(),
+                )),
+            ))
+        }
+        Err(_) => {
+            info!(
+                "⏱️ Notification {} not sent by its deadline; continuing in the background",
+                delivery_id
+            );
+            Ok((
+                StatusCode::ACCEPTED,
+                Json(SendNotificationResponse {
+                    success: true,
+                    message: format!(
+                        "Notification still being delivered; track it at {}/messages/{delivery_id}",
+                        state.base_path
+                    ),
+                    telegram_message_id: None,
+                    delivery_id: Some(delivery_id),
+                    results: Vec::new(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Send a notification right away, recording the outcome in the history
+async fn send_now(
+    state: &AppState,
+    notification: Notification,
+    resent_from: Option<&str>,
+) -> NotifyResult {
+    state
+        .history
+        .record(&notification, DeliveryStatus::Queued, resent_from);
+    attempt(state, notification).await
+}
+
+/// Send a notification already in the history, recording the outcome
+async fn attempt(state: &AppState, notification: Notification) -> NotifyResult {
+    let delivery_id = notification.delivery_id.clone();
+    state.history.start_attempt(&delivery_id);
+
+    if test_mode() {
+        let telegram_message_id = Some(42); // Mock message ID
+        info!(
+            delivery_id = %delivery_id,
+            chat_id = %notification.chat_id,
+            message_id = telegram_message_id,
+            "⚠️  Test mode: Simulating message send"
+        );
+        state.queue.stats().sent(&notification);
+        state.callbacks.sent(&notification, telegram_message_id);
+        state.history.set_status(
+            &delivery_id,
+            DeliveryStatus::Sent {
+                telegram_message_id,
+            },
+        );
+        return Ok((
+            StatusCode::OK,
+            Json(SendNotificationResponse {
+                success: true,
+                message: "Notification sent successfully (test mode)".to_string(),
+                telegram_message_id,
+                delivery_id: Some(delivery_id),
+                results: Vec::new(),
+            }),
+        ));
+    }
+
+    // Send the message, split into as many parts as Telegram requires
+    match state.notifier.send(&notification).await {
+        Ok(message_id) => {
+            info!(
+                delivery_id = %delivery_id,
+                chat_id = %notification.chat_id,
+                message_id = message_id,
+                "✅ Notification sent successfully"
+            );
+            state.queue.stats().sent(&notification);
+            state.callbacks.sent(&notification, message_id);
+            state.history.set_status(
+                &delivery_id,
+                DeliveryStatus::Sent {
+                    telegram_message_id: message_id,
+                },
+            );
+            Ok((
+                StatusCode::OK,
+                Json(SendNotificationResponse {
+                    success: true,
+                    message: "Notification sent successfully".to_string(),
+                    telegram_message_id: message_id,
+                    delivery_id: Some(delivery_id),
+                    results: Vec::new(),
+                }),
+            ))
+        }
+        Err(e) => {
+            error!(
+                delivery_id = %delivery_id,
+                chat_id = %notification.chat_id,
+                "❌ Failed to send notification: {}",
+                e
+            );
+            state.queue.stats().failed(&notification, &e.to_string());
+            state.fallback.notify(&notification, &e.to_string());
+            state.callbacks.failed(&notification, &e.to_string());
+            state.history.set_status(
+                &delivery_id,
+                DeliveryStatus::Failed {
+                    error: e.to_string(),
+                },
+            );
+            let (status, code) = match e.downcast_ref::<CircuitOpen>() {
+                Some(_) => (StatusCode::SERVICE_UNAVAILABLE, "TELEGRAM_UNAVAILABLE"),
+                None => (StatusCode::BAD_GATEWAY, "TELEGRAM_API_ERROR"),
+            };
+            Err((
+                status,
+                Json(ErrorResponse::with_code(
+                    format!("Failed to send notification: {e}"),
+                    code.to_string(),
+                )),
+            ))
+        }
+    }
+}
+
+/// GET /history - Past notifications matching the filters, newest first
+pub async fn history_list(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<HistoryListResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let bound = |value: Option<String>| {
+        value
+            .map(|value| export::parse_time(&value))
+            .transpose()
+            .map_err(|e| bad_request(&e.to_string(), "INVALID_REQUEST"))
+    };
+    if let Some(status) = &query.status
+        && !history::STATUS_NAMES.contains(&status.as_str())
+    {
+        return Err(bad_request(
+            &format!(
+                "Unknown status {status:?}; use one of {}",
+                history::STATUS_NAMES.join(", ")
+            ),
+            "INVALID_REQUEST",
+        ));
+    }
+    let limit = query.limit.unwrap_or(history::DEFAULT_PAGE_SIZE);
+    if !(1..=history::MAX_PAGE_SIZE).contains(&limit) {
+        return Err(bad_request(
+            &format!("limit must be between 1 and {}", history::MAX_PAGE_SIZE),
+            "INVALID_REQUEST",
+        ));
+    }
+    let filter = Filter {
+        // Aliases are searched by the chat they point to now
+        chat_id: query
+            .chat
+            .map(|chat| state.targets.resolve(&chat).unwrap_or(chat)),
+        since: bound(query.since)?,
+        until: bound(query.until)?,
+        status: query.status,
+    };
+
+    let (records, total) = state.history.query(&filter, query.offset, limit);
+    let next_offset = query.offset + records.len();
+    Ok(Json(HistoryListResponse {
+        records: records.into_iter().map(Into::into).collect(),
+        total,
+        next_offset: (next_offset < total).then_some(next_offset),
+    }))
+}
+
+/// GET /history/{id} - Look up a past notification
+pub async fn history_get(
+    State(state): State<Arc<AppState>>,
+    Path(delivery_id): Path<String>,
+) -> Result<Json<HistoryRecordResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.history.get(&delivery_id) {
+        Some(record) => Ok(Json(record.into())),
+        None => Err(history_not_found(&delivery_id)),
+    }
+}
+
+/// GET /history/export - Stream the history as JSONL or CSV, oldest first
+pub async fn history_export(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let bound = |value: Option<String>| {
+        value
+            .map(|value| export::parse_time(&value))
+            .transpose()
+            .map_err(|e| bad_request(&e.to_string(), "INVALID_REQUEST"))
+    };
+    let from = bound(query.from)?;
+    let to = bound(query.to)?;
+
+    let format = query.format;
+    let records = state.history.export(from, to);
+    info!(
+        "📦 Exporting {} notification(s) as {}",
+        records.len(),
+        format.name()
+    );
+    // Records are serialized one at a time as the client reads them
+    let lines = export::header(format)
+        .into_iter()
+        .chain(
+            records
+                .into_iter()
+                .map(move |record| export::line(format, record)),
+        )
+        .map(Ok::<_, Infallible>);
+
+    let mut response = Body::from_stream(stream::iter(lines)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    headers.insert(
+        CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!(
+            "attachment; filename=\"history.{}\"",
+            format.name()
+        ))
+        .expect("file name is a valid header value"),
+    );
+    Ok(response)
+}
+
+/// GET /messages/{id} - Delivery state of a notification
+pub async fn message_status(
+    State(state): State<Arc<AppState>>,
+    Path(delivery_id): Path<String>,
+) -> Result<Json<MessageStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.history.get(&delivery_id) {
+        Some(record) => Ok(Json(record.into())),
+        None => Err(history_not_found(&delivery_id)),
+    }
+}
+
+/// GET /n/{id} - HTML page with a notification's full text and details
+pub async fn notification_page(
+    State(state): State<Arc<AppState>>,
+    Path(delivery_id): Path<String>,
+) -> Result<Html<String>, (StatusCode, Json<ErrorResponse>)> {
+    match state.history.get(&delivery_id) {
+        Some(record) => Ok(Html(pages::render(&record))),
+        None => Err(history_not_found(&delivery_id)),
+    }
+}
+
+/// DELETE /history/{id} - Soft-delete a past notification
+pub async fn history_delete(
+    State(state): State<Arc<AppState>>,
+    Path(delivery_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    if state.history.delete(&delivery_id) {
+        info!("🗑️ Notification {} deleted from history", delivery_id);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(history_not_found(&delivery_id))
+    }
+}
+
+/// POST /history/{id}/resend - Deliver a past notification again, optionally
+/// to a different chat
+pub async fn history_resend(
+    State(state): State<Arc<AppState>>,
+    Path(delivery_id): Path<String>,
+    body: Bytes,
+) -> NotifyResult {
+    let request: ResendRequest = if body.is_empty() {
+        ResendRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::with_code(
+                    format!("Invalid resend request: {e}"),
+                    "INVALID_REQUEST".to_string(),
+                )),
+            )
+        })?
+    };
+
+    let record = state
+        .history
+        .get(&delivery_id)
+        .ok_or_else(|| history_not_found(&delivery_id))?;
+
+    let mut notification = record.notification;
+    notification.delivery_id = new_delivery_id();
+    if let Some(chat_id) = request.chat_id {
+        notification.chat_id = chat_id;
+    }
+    info!(
+        "🔁 Re-sending notification {} as {} to chat {}",
+        delivery_id, notification.delivery_id, notification.chat_id
+    );
+
+    send_now(&state, notification, Some(&delivery_id)).await
+}
+
+fn history_not_found(delivery_id: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::with_code(
+            format!("No notification {delivery_id} in history"),
+            "NOT_FOUND".to_string(),
+        )),
+    )
+}
+
+/// GET /keys/{id}/usage - Request counts and quota of a named API key
+pub async fn key_usage(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<String>,
+) -> Result<Json<KeyUsage>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .quotas
+        .usage(&key_id, unix_now())
+        .map(Json)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::with_code(
+                    format!("No API key named {key_id}"),
+                    "NOT_FOUND".to_string(),
+                )),
+            )
+        })
+}
+
+/// GET /admin/keys - API keys created at runtime, without the keys themselves
+pub async fn list_keys(State(state): State<Arc<AppState>>) -> Json<ApiKeyListResponse> {
+    Json(ApiKeyListResponse {
+        keys: state.keys.list().into_iter().map(Into::into).collect(),
+    })
+}
+
+/// POST /admin/keys - Create an API key, returning it this once
+pub async fn create_key(
+    State(state): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<ApiKeyResponse>), ApiError> {
+    let request: CreateKeyRequest = if body.is_empty() {
+        CreateKeyRequest::default()
+    } else {
+        serde_json::from_slice(&body)
+            .map_err(|e| bad_request(&format!("Invalid key request: {e}"), "INVALID_REQUEST"))?
+    };
+
+    let key = state
+        .keys
+        .create(
+            request.label,
+            request.scope,
+            Quota {
+                requests_per_minute: request.requests_per_minute,
+                requests_per_day: request.requests_per_day,
+            },
+            unix_now(),
+        )
+        .map_err(key_storage_error)?;
+    info!("🔑 API key {} created ({:?} scope)", key.id, key.scope);
+
+    let secret = key.key.clone();
+    let mut response = ApiKeyResponse::from(key);
+    response.key = Some(secret);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// GET /admin/keys/{id} - One API key created at runtime, with its ETag
+pub async fn get_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<String>,
+) -> Result<Response, ApiError> {
+    let key = state
+        .keys
+        .get(&key_id)
+        .ok_or_else(|| key_not_found(&key_id))?;
+    let response = ApiKeyResponse::from(key);
+    Ok((etag_header(&response), Json(response)).into_response())
+}
+
+/// PUT /admin/keys/{id} - Create or update an API key by ID. A generated key
+/// is returned once, when the key is created.
+pub async fn put_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    validate_name(&key_id)?;
+    if state.quotas.is_configured(&key_id) {
+        // Its requests would be counted as the configured key's
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::with_code(
+                format!("A key named {key_id} already exists in [auth.keys]"),
+                "KEY_EXISTS".to_string(),
+            )),
+        ));
+    }
+    let request: PutKeyRequest = serde_json::from_slice(&body)
+        .map_err(|e| bad_request(&format!("Invalid key request: {e}"), "INVALID_REQUEST"))?;
+    if request.key.as_ref().is_some_and(|key| key.len() < 16) {
+        return Err(bad_request(
+            "Keys must be at least 16 characters long",
+            "INVALID_REQUEST",
+        ));
+    }
+    let current = state
+        .keys
+        .get(&key_id)
+        .map(|key| provision::etag(&ApiKeyResponse::from(key)));
+    check_preconditions(&headers, current.as_deref())?;
+
+    let generated = request.key.is_none();
+    let (key, created) = state
+        .keys
+        .put(
+            &key_id,
+            request.label,
+            request.scope,
+            Quota {
+                requests_per_minute: request.requests_per_minute,
+                requests_per_day: request.requests_per_day,
+            },
+            request.key,
+            unix_now(),
+        )
+        .map_err(key_storage_error)?;
+    let secret = key.key.clone();
+    let mut response = ApiKeyResponse::from(key);
+    let headers = etag_header(&response);
+    if created {
+        info!("🔑 API key {} created ({:?} scope)", key_id, response.scope);
+        if generated {
+            response.key = Some(secret);
+        }
+        Ok((StatusCode::CREATED, headers, Json(response)).into_response())
+    } else {
+        Ok((headers, Json(response)).into_response())
+    }
+}
+
+/// DELETE /admin/keys/{id} - Revoke an API key
+pub async fn revoke_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    let current = state
+        .keys
+        .get(&key_id)
+        .map(|key| provision::etag(&ApiKeyResponse::from(key)));
+    check_preconditions(&headers, current.as_deref())?;
+    match state.keys.revoke(&key_id).map_err(key_storage_error)? {
+        Some(_) => {
+            info!("🗑️ API key {} revoked", key_id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(key_not_found(&key_id)),
+    }
+}
+
+fn key_not_found(key_id: &str) -> ApiError {
+    not_found(format!("No API key with ID {key_id}"))
+}
+
+fn not_found(error: String) -> ApiError {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::with_code(error, "NOT_FOUND".to_string())),
+    )
+}
+
+/// GET /admin/targets - Named chats, from the config file or declared
+pub async fn list_targets(State(state): State<Arc<AppState>>) -> Json<TargetListResponse> {
+    let targets = state
+        .targets
+        .list()
+        .into_iter()
+        .map(|(name, chat_id)| TargetResponse { name, chat_id })
+        .collect();
+    Json(TargetListResponse { targets })
+}
+
+/// GET /admin/targets/{name} - One named chat, with its ETag
+pub async fn get_target(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let chat_id = state
+        .targets
+        .resolve(&name)
+        .ok_or_else(|| target_not_found(&name))?;
+    let response = TargetResponse { name, chat_id };
+    Ok((etag_header(&response), Json(response)).into_response())
+}
+
+/// PUT /admin/targets/{name} - Create or update a named chat
+pub async fn put_target(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    validate_name(&name)?;
+    let request: TargetRequest = serde_json::from_slice(&body)
+        .map_err(|e| bad_request(&format!("Invalid target: {e}"), "INVALID_REQUEST"))?;
+    if request.chat_id.trim().is_empty() {
+        return Err(bad_request("chat_id cannot be empty", "INVALID_REQUEST"));
+    }
+    let current = state.targets.resolve(&name).map(|chat_id| {
+        provision::etag(&TargetResponse {
+            name: name.clone(),
+            chat_id,
+        })
+    });
+    check_preconditions(&headers, current.as_deref())?;
+
+    let chat_id = request.chat_id.trim().to_string();
+    let previous = state.targets.put(&name, &chat_id);
+    let response = TargetResponse { name, chat_id };
+    let headers = etag_header(&response);
+    if previous.is_none() {
+        info!("🎯 Target {} → {} created", response.name, response.chat_id);
+        Ok((StatusCode::CREATED, headers, Json(response)).into_response())
+    } else {
+        Ok((headers, Json(response)).into_response())
+    }
+}
+
+/// DELETE /admin/targets/{name} - Remove a named chat
+pub async fn delete_target(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    let current = state.targets.resolve(&name).map(|chat_id| {
+        provision::etag(&TargetResponse {
+            name: name.clone(),
+            chat_id,
+        })
+    });
+    check_preconditions(&headers, current.as_deref())?;
+    match state.targets.remove(&name) {
+        Some(_) => {
+            info!("🗑️ Target {} removed", name);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(target_not_found(&name)),
+    }
+}
+
+fn target_not_found(name: &str) -> ApiError {
+    not_found(format!("No target named {name}"))
+}
+
+/// GET /scheduled - Notifications waiting for their send_at time, the next
+/// one due first
+pub async fn list_scheduled(State(state): State<Arc<AppState>>) -> Json<ScheduledListResponse> {
+    let scheduled = state.scheduler.list().into_iter().map(Into::into).collect();
+    Json(ScheduledListResponse { scheduled })
+}
+
+/// DELETE /scheduled/{id} - Cancel a notification before its send_at time
+pub async fn cancel_scheduled(
+    State(state): State<Arc<AppState>>,
+    Path(delivery_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    match state.scheduler.cancel(&delivery_id) {
+        Some(_) => {
+            state
+                .history
+                .set_status(&delivery_id, DeliveryStatus::Cancelled);
+            info!("🗑️ Scheduled notification {} cancelled", delivery_id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(not_found(format!(
+            "No scheduled notification {delivery_id}"
+        ))),
+    }
+}
+
+/// GET /schedules - Recurring notifications and their next runs
+pub async fn list_schedules(State(state): State<Arc<AppState>>) -> Json<ScheduleListResponse> {
+    let now = Local::now();
+    let schedules = state
+        .schedules
+        .list()
+        .into_iter()
+        .map(|(id, schedule, source)| schedule_response(id, schedule, source, &now))
+        .collect();
+    Json(ScheduleListResponse { schedules })
+}
+
+/// POST /schedules - Create a recurring notification
+pub async fn create_schedule(
+    State(state): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<ScheduleResponse>), ApiError> {
+    let request: CreateScheduleRequest = serde_json::from_slice(&body)
+        .map_err(|e| bad_request(&format!("Invalid schedule: {e}"), "INVALID_REQUEST"))?;
+    if let Some(id) = &request.id {
+        validate_name(id)?;
+    }
+    let id = request.id.clone();
+    let schedule = Schedule::from(request);
+    if schedule.message.trim().is_empty() {
+        return Err(bad_request("Message cannot be empty", "EMPTY_MESSAGE"));
+    }
+    schedules::parse_cron(&schedule.cron)
+        .map_err(|e| bad_request(&format!("{e:#}"), "INVALID_CRON"))?;
+    // Catch unknown aliases now rather than at the first run
+    targets(&state, &schedule_request(&schedule))?;
+
+    match state
+        .schedules
+        .create(id.clone(), schedule.clone())
+        .map_err(schedule_storage_error)?
+    {
+        Some(id) => {
+            info!("🔁 Schedule {} created ({})", id, schedule.cron);
+            let response = schedule_response(id, schedule, Source::Api, &Local::now());
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        None => Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::with_code(
+                format!(
+                    "A schedule with ID {} already exists",
+                    id.unwrap_or_default()
+                ),
+                "SCHEDULE_EXISTS".to_string(),
+            )),
+        )),
+    }
+}
+
+/// GET /schedules/{id} - One recurring notification
+pub async fn get_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ScheduleResponse>, ApiError> {
+    let (schedule, source) = state
+        .schedules
+        .get(&id)
+        .ok_or_else(|| schedule_not_found(&id))?;
+    Ok(Json(schedule_response(id, schedule, source, &Local::now())))
+}
+
+/// DELETE /schedules/{id} - Delete a recurring notification created through
+/// the API
+pub async fn delete_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if let Some((_, Source::Config)) = state.schedules.get(&id) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::with_code(
+                format!("Schedule {id} is defined in the config file"),
+                "SCHEDULE_READ_ONLY".to_string(),
+            )),
+        ));
+    }
+    match state
+        .schedules
+        .delete(&id)
+        .map_err(schedule_storage_error)?
+    {
+        Some(_) => {
+            info!("🗑️ Schedule {} deleted", id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(schedule_not_found(&id)),
+    }
+}
+
+fn schedule_not_found(id: &str) -> ApiError {
+    not_found(format!("No schedule with ID {id}"))
+}
+
+fn schedule_response(
+    id: String,
+    schedule: Schedule,
+    source: Source,
+    now: &DateTime<Local>,
+) -> ScheduleResponse {
+    let next_run = schedule
+        .next_run(now)
+        .ok()
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true));
+    ScheduleResponse {
+        id,
+        schedule,
+        source,
+        next_run,
+    }
+}
+
+/// The notification request a schedule sends
+fn schedule_request(schedule: &Schedule) -> SendNotificationRequest {
+    SendNotificationRequest {
+        message: schedule.message.clone(),
+        chat_id: schedule.chat_id.clone(),
+        chat: schedule.chat.clone(),
+        parse_mode: schedule.parse_mode.clone(),
+        disable_notification: Some(schedule.disable_notification),
+        ..Default::default()
+    }
+}
+
+/// Background task queueing recurring notifications whenever their cron
+/// expression comes due. Runs missed while the server was down are skipped.
+pub async fn run_schedules(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut last_check = Local::now();
+    loop {
+        interval.tick().await;
+        let now = Local::now();
+        for (id, schedule) in state.schedules.due(&last_check, &now) {
+            let body = NotifyBody {
+                request: schedule_request(&schedule),
+                attachments: Vec::new(),
+                source: "schedule".to_string(),
+            };
+            match dispatch(&state, body, Wait::Queued, Priority::Normal).await {
+                Ok((_, Json(response))) => info!("🔁 Schedule {}: {}", id, response.message),
+                Err((_, Json(response))) => {
+                    warn!("⚠️ Schedule {} failed: {}", id, response.error)
+                }
+            }
+        }
+        last_check = now;
+    }
+}
+
+/// POST /checkin/{slug} - A job ran; restart the time it has until the
+/// next check-in
+pub async fn checkin(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<CheckInResponse>, ApiError> {
+    let Some(ping) = state.checkins.ping(&slug, unix_now()) else {
+        return Err(not_found(format!("No check-in named {slug}")));
+    };
+    info!("✔️ {} checked in", slug);
+    if let Some(request) = ping
+        .recovered_after
+        .and_then(|after| state.checkins.recovered(&slug, after))
+    {
+        send_check_in_alert(&state, &slug, request).await;
+    }
+    Ok(Json(CheckInResponse {
+        slug,
+        next_due: ping.due,
+    }))
+}
+
+/// Background task alerting about jobs that missed their check-in
+pub async fn run_checkins(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        for (slug, request) in state.checkins.missed(unix_now()) {
+            warn!("⏰ {} missed its check-in", slug);
+            send_check_in_alert(&state, &slug, request).await;
+        }
+    }
+}
+
+async fn send_check_in_alert(state: &Arc<AppState>, slug: &str, request: SendNotificationRequest) {
+    let body = NotifyBody {
+        request,
+        attachments: Vec::new(),
+        source: checkins::SOURCE.to_string(),
+    };
+    if let Err((_, Json(response))) = dispatch(state, body, Wait::Queued, Priority::Normal).await {
+        warn!("⚠️ Check-in alert for {} failed: {}", slug, response.error);
+    }
+}
+
+/// Background task vacuuming the databases every `vacuum_interval` and
+/// alerting the default chat when one grows past `size_alert` bytes
+pub async fn run_storage_maintenance(
+    state: Arc<AppState>,
+    stores: Stores,
+    vacuum_interval: Option<Duration>,
+    size_alert: Option<u64>,
+) {
+    let mut interval = tokio::time::interval(storage::CHECK_INTERVAL);
+    let mut watch = size_alert.map(SizeWatch::new);
+    let mut last_vacuum = Instant::now();
+    loop {
+        interval.tick().await;
+        if vacuum_interval.is_some_and(|every| last_vacuum.elapsed() >= every) {
+            let stores = stores.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || stores.vacuum()).await {
+                warn!("⚠️ Vacuuming the databases failed: {}", e);
+            }
+            last_vacuum = Instant::now();
+        }
+        let Some(watch) = watch.as_mut() else {
+            continue;
+        };
+        for request in watch.check(&stores.sizes()) {
+            let body = NotifyBody {
+                request,
+                attachments: Vec::new(),
+                source: storage::SOURCE.to_string(),
+            };
+            if let Err((_, Json(response))) =
+                dispatch(&state, body, Wait::Queued, Priority::Normal).await
+            {
+                warn!("⚠️ Database size alert failed: {}", response.error);
+            }
+        }
+    }
+}
+
+/// GET /admin/silences - Silenced chats and when their silence ends
+pub async fn list_silences(State(state): State<Arc<AppState>>) -> Json<SilenceListResponse> {
+    let silences = state
+        .silences
+        .list()
+        .into_iter()
+        .map(|(chat_id, until)| SilenceResponse { chat_id, until })
+        .collect();
+    Json(SilenceListResponse { silences })
+}
+
+/// GET /admin/silences/{chat} - A chat's silence, with its ETag. The chat
+/// may be given by ID or target name.
+pub async fn get_silence(
+    State(state): State<Arc<AppState>>,
+    Path(chat): Path<String>,
+) -> Result<Response, ApiError> {
+    let chat_id = state.targets.resolve(&chat).unwrap_or(chat);
+    let until = state
+        .silences
+        .until(&chat_id)
+        .ok_or_else(|| silence_not_found(&chat_id))?;
+    let response = SilenceResponse { chat_id, until };
+    Ok((etag_header(&response), Json(response)).into_response())
+}
+
+/// PUT /admin/silences/{chat} - Silence a chat until a time or for a number
+/// of minutes, replacing any silence it had
+pub async fn put_silence(
+    State(state): State<Arc<AppState>>,
+    Path(chat): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let request: SilenceRequest = serde_json::from_slice(&body)
+        .map_err(|e| bad_request(&format!("Invalid silence: {e}"), "INVALID_REQUEST"))?;
+    let now = unix_now();
+    let until = match (request.until, request.minutes) {
+        (Some(until), None) if until > now => until,
+        (Some(_), None) => {
+            return Err(bad_request(
+                "until must be in the future",
+                "INVALID_REQUEST",
+            ));
+        }
+        (None, Some(minutes)) if minutes > 0 => now + minutes * 60,
+        _ => {
+            return Err(bad_request(
+                "Give either until (a Unix timestamp) or minutes",
+                "INVALID_REQUEST",
+            ));
+        }
+    };
+    let chat_id = state.targets.resolve(&chat).unwrap_or(chat);
+    let current = state.silences.until(&chat_id).map(|until| {
+        provision::etag(&SilenceResponse {
+            chat_id: chat_id.clone(),
+            until,
+        })
+    });
+    check_preconditions(&headers, current.as_deref())?;
+
+    state.silences.silence_until(&chat_id, until);
+    let response = SilenceResponse { chat_id, until };
+    let headers = etag_header(&response);
+    if current.is_none() {
+        info!("🔕 Chat {} silenced until {}", response.chat_id, until);
+        Ok((StatusCode::CREATED, headers, Json(response)).into_response())
+    } else {
+        Ok((headers, Json(response)).into_response())
+    }
+}
+
+/// DELETE /admin/silences/{chat} - End a chat's silence early
+pub async fn delete_silence(
+    State(state): State<Arc<AppState>>,
+    Path(chat): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    let chat_id = state.targets.resolve(&chat).unwrap_or(chat);
+    let current = state.silences.until(&chat_id).map(|until| {
+        provision::etag(&SilenceResponse {
+            chat_id: chat_id.clone(),
+            until,
+        })
+    });
+    check_preconditions(&headers, current.as_deref())?;
+    if state.silences.lift(&chat_id) {
+        info!("🔔 Silence of chat {} lifted", chat_id);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(silence_not_found(&chat_id))
+    }
+}
+
+fn silence_not_found(chat_id: &str) -> ApiError {
+    not_found(format!("Chat {chat_id} is not silenced"))
+}
+
+/// GET /admin/config - The settings in effect, merged from the flags,
+/// environment and config file, with secrets masked
+pub async fn effective_config(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    Json(state.effective_config.clone())
+}
+
+/// GET /admin/dlq - Queued notifications that failed for good, oldest first
+pub async fn list_dead_letters(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<DeadLetterListResponse>, ApiError> {
+    let dead_letters = state
+        .queue
+        .dead_letters()
+        .list()
+        .map_err(queue_storage_error)?
+        .into_iter()
+        .map(DeadLetterResponse::from)
+        .collect();
+    Ok(Json(DeadLetterListResponse { dead_letters }))
+}
+
+/// POST /admin/dlq/{id}/retry - Take a notification out of the dead-letter
+/// queue and queue it for delivery again, with a fresh set of attempts
+pub async fn retry_dead_letter(
+    State(state): State<Arc<AppState>>,
+    Path(delivery_id): Path<String>,
+) -> Result<(StatusCode, Json<SendNotificationResponse>), ApiError> {
+    let dead_letters = state.queue.dead_letters();
+    let letter = dead_letters
+        .take(&delivery_id)
+        .map_err(queue_storage_error)?
+        .ok_or_else(|| {
+            not_found(format!(
+                "No notification {delivery_id} in the dead-letter queue"
+            ))
+        })?;
+
+    let notification = letter.notification.clone();
+    if state.history.get(&delivery_id).is_some() {
+        state
+            .history
+            .set_status(&delivery_id, DeliveryStatus::Queued);
+    } else {
+        state
+            .history
+            .record(&notification, DeliveryStatus::Queued, None);
+    }
+    if state.queue.enqueue(notification).is_err() {
+        warn!(
+            "⚠️ Delivery queue full, keeping notification {} in the dead-letter queue",
+            delivery_id
+        );
+        state.history.set_status(
+            &delivery_id,
+            DeliveryStatus::Failed {
+                error: letter.error.clone(),
+            },
+        );
+        dead_letters.add(letter).map_err(queue_storage_error)?;
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse::with_code(
+                "Delivery queue is full, try again later".to_string(),
+                "QUEUE_FULL".to_string(),
+            )),
+        ));
+    }
+
+    info!("📥 Notification {} queued for delivery again", delivery_id);
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(SendNotificationResponse {
+            success: true,
+            message: "Notification queued for delivery".to_string(),
+            telegram_message_id: None,
+            delivery_id: Some(delivery_id),
+            results: Vec::new(),
+        }),
+    ))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn queue_storage_error(e: anyhow::Error) -> ApiError {
+    error!("❌ Failed to access the queue database: {:#}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::with_code(
+            format!("Failed to access the queue database: {e:#}"),
+            "STORAGE_ERROR".to_string(),
+        )),
+    )
+}
+
+fn key_storage_error(e: anyhow::Error) -> ApiError {
+    error!("❌ Failed to save API keys: {:#}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::with_code(
+            format!("Failed to save API keys: {e:#}"),
+            "STORAGE_ERROR".to_string(),
+        )),
+    )
+}
+
+fn schedule_storage_error(e: anyhow::Error) -> ApiError {
+    error!("❌ Failed to save schedules: {:#}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::with_code(
+            format!("Failed to save schedules: {e:#}"),
+            "STORAGE_ERROR".to_string(),
+        )),
+    )
+}
+
+/// GET /stats - Delivery counts since startup, queue depth and last error
+pub async fn stats(State(state): State<Arc<AppState>>) -> Json<StatsSnapshot> {
+    Json(state.queue.stats().snapshot(state.queue.len()))
+}
+
+/// GET /admin/ratelimits - Per-chat tokens, wait estimates and recent 429s
+pub async fn rate_limits(State(state): State<Arc<AppState>>) -> Json<RateLimitSnapshot> {
+    Json(state.bots.main().rate_limiter().snapshot(Instant::now()))
+}
+
+/// POST /admin/config/sync - Pull the config repository now, e.g. from a
+/// push webhook, instead of waiting for the next interval
+pub async fn sync_config(State(state): State<Arc<AppState>>) -> Result<Json<SyncResult>, ApiError> {
+    let Some(sync) = &state.config_sync else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::with_code(
+                "No config repository configured; start the server with --config-repo".to_string(),
+                "NOT_FOUND".to_string(),
+            )),
+        ));
+    };
+
+    match sync.sync().await {
+        Ok(result) => {
+            if result.reloaded {
+                info!(
+                    "📥 Routing script updated to commit {}",
+                    crate::gitsync::short(&result.commit)
+                );
+            }
+            Ok(Json(result))
+        }
+        Err(e) => {
+            warn!("⚠️  Config repository sync failed: {:#}", e);
+            Err((
+                StatusCode::BAD_GATEWAY,
+                Json(ErrorResponse::with_code(
+                    format!("Config repository sync failed: {e:#}"),
+                    "SYNC_FAILED".to_string(),
+                )),
+            ))
+        }
+    }
+}
+
+/// POST /integrations/alertmanager - Send a group of Prometheus alerts
+pub async fn alertmanager(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<IntegrationQuery>,
+    StreamedJson(webhook): StreamedJson<Webhook>,
+) -> NotifyResult {
+    info!(
+        "🔔 Alertmanager webhook received: {} ({} alerts)",
+        webhook.status,
+        webhook.alerts.len()
+    );
+    let mut request = alertmanager::render(&webhook);
+    request.chat_id = query.chat_id;
+    request.chat = query.chat;
+    apply_caller(&state, &headers, &mut request)?;
+    let body = NotifyBody {
+        request,
+        attachments: Vec::new(),
+        source: alertmanager::SOURCE.to_string(),
+    };
+    let wait = integration_wait(query.mode);
+    dispatch(&state, body, wait, priority(&state, &headers)?).await
+}
+
+/// POST /integrations/grafana - Send a group of Grafana alerts, with their
+/// screenshots if Grafana took any
+pub async fn grafana(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<IntegrationQuery>,
+    StreamedJson(webhook): StreamedJson<grafana::Webhook>,
+) -> NotifyResult {
+    info!(
+        "🔔 Grafana webhook received: {} ({} alerts)",
+        webhook.status,
+        webhook.alerts.len()
+    );
+    let mut request = grafana::render(&webhook);
+    request.chat_id = query.chat_id;
+    request.chat = query.chat;
+    apply_caller(&state, &headers, &mut request)?;
+    let mut attachments = Vec::new();
+    for url in grafana::screenshots(&webhook) {
+        // The alert matters more than its picture
+        match grafana::download(&url).await {
+            Ok(attachment) => attachments.push(attachment),
+            Err(e) => warn!("⚠️ Sending the alert without its screenshot: {:#}", e),
+        }
+    }
+    let body = NotifyBody {
+        request,
+        attachments,
+        source: grafana::SOURCE.to_string(),
+    };
+    let wait = integration_wait(query.mode);
+    dispatch(&state, body, wait, priority(&state, &headers)?).await
+}
+
+/// POST /integrations/uptime - Send an Uptime Kuma (or similar) monitor's
+/// change of status
+pub async fn uptime(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<IntegrationQuery>,
+    Json(webhook): Json<uptime::Webhook>,
+) -> NotifyResult {
+    info!(
+        "🔔 Uptime webhook received: {}",
+        webhook.msg.chars().take(50).collect::<String>()
+    );
+    let downtime = match (webhook.monitor_key(), webhook.status()) {
+        (Some(monitor), Some(status)) => {
+            state.outages.record(&monitor, status, webhook.checked_at())
+        }
+        _ => None,
+    };
+    let mut request = uptime::render(&webhook, downtime);
+    request.chat_id = query.chat_id;
+    request.chat = query.chat;
+    apply_caller(&state, &headers, &mut request)?;
+    let body = NotifyBody {
+        request,
+        attachments: Vec::new(),
+        source: uptime::SOURCE.to_string(),
+    };
+    let wait = integration_wait(query.mode);
+    dispatch(&state, body, wait, priority(&state, &headers)?).await
+}
+
+/// POST /integrations/pagerduty - Take a PagerDuty Events API v2 event:
+/// triggers send a message, which acknowledge and resolve events then edit
+pub async fn pagerduty(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<IntegrationQuery>,
+    Json(event): Json<pagerduty::Event>,
+) -> Result<(StatusCode, Json<EventResponse>), ApiError> {
+    event
+        .validate()
+        .map_err(|e| bad_request(&e.to_string(), "INVALID_EVENT"))?;
+    let accepted = |dedup_key: String, delivery_id: Option<String>| {
+        (
+            StatusCode::ACCEPTED,
+            Json(EventResponse::processed(dedup_key, delivery_id)),
+        )
+    };
+
+    let action = event.event_action;
+    if action != pagerduty::Action::Trigger {
+        let dedup_key = event.dedup_key.unwrap_or_default();
+        info!("🔔 PagerDuty {:?} of {}", action, dedup_key);
+        let delivery_id = match action {
+            pagerduty::Action::Resolve => state.incidents.close(&dedup_key),
+            _ => state.incidents.get(&dedup_key),
+        };
+        let marked = match &delivery_id {
+            Some(delivery_id) if test_mode() => {
+                info!("⚠️  Test mode: Simulating edit of {}", delivery_id);
+                true
+            }
+            Some(delivery_id) => {
+                match pagerduty::mark(&state.bots, &state.history, delivery_id, action).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        warn!("⚠️ Failed to mark notification {}: {:#}", delivery_id, e);
+                        false
+                    }
+                }
+            }
+            None => false,
+        };
+        // A resolution nobody can see on the trigger gets a message of its own
+        if marked || action != pagerduty::Action::Resolve {
+            return Ok(accepted(dedup_key, delivery_id));
+        }
+        let mut request = pagerduty::render_resolved(&dedup_key);
+        request.chat_id = query.chat_id;
+        request.chat = query.chat;
+        apply_caller(&state, &headers, &mut request)?;
+        let body = NotifyBody {
+            request,
+            attachments: Vec::new(),
+            source: pagerduty::SOURCE.to_string(),
+        };
+        let wait = integration_wait(query.mode);
+        let (_, Json(response)) = dispatch(&state, body, wait, priority(&state, &headers)?).await?;
+        return Ok(accepted(dedup_key, response.delivery_id));
+    }
+
+    // Repeated triggers of an open incident are duplicates, as in PagerDuty
+    if let Some(dedup_key) = &event.dedup_key
+        && let Some(delivery_id) = state.incidents.get(dedup_key)
+    {
+        return Ok(accepted(dedup_key.clone(), Some(delivery_id)));
+    }
+    info!(
+        "🔔 PagerDuty trigger received: {}",
+        event
+            .payload
+            .as_ref()
+            .map(|payload| payload.summary.chars().take(50).collect::<String>())
+            .unwrap_or_default()
+    );
+    let mut request = pagerduty::render(&event);
+    request.chat_id = query.chat_id;
+    request.chat = query.chat;
+    apply_caller(&state, &headers, &mut request)?;
+    let body = NotifyBody {
+        request,
+        attachments: Vec::new(),
+        source: pagerduty::SOURCE.to_string(),
+    };
+    let wait = integration_wait(query.mode);
+    let (_, Json(response)) = dispatch(&state, body, wait, priority(&state, &headers)?).await?;
+    let delivery_id = response.delivery_id.unwrap_or_default();
+    let dedup_key = event.dedup_key.unwrap_or_else(|| delivery_id.clone());
+    state.incidents.open(&dedup_key, &delivery_id);
+    Ok(accepted(dedup_key, Some(delivery_id)))
+}
+
+/// POST /webhook/{name} - Send a notification mapped from any JSON body by
+/// the config file's `[webhooks.<name>]`
+pub async fn mapped_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<IntegrationQuery>,
+    Json(payload): Json<serde_json::Value>,
+) -> NotifyResult {
+    let Some(webhook) = state.webhooks.get(&name) else {
+        return Err(not_found(format!("No webhook named {name}")));
+    };
+    info!("🔔 Webhook {} received", name);
+    let mut request = webhook.render(&payload);
+    if query.chat_id.is_some() || query.chat.is_some() {
+        request.chat_id = query.chat_id;
+        request.chat = query.chat;
+    }
+    apply_caller(&state, &headers, &mut request)?;
+    let body = NotifyBody {
+        request,
+        attachments: Vec::new(),
+        source: webhooks::SOURCE.to_string(),
+    };
+    let wait = integration_wait(query.mode);
+    dispatch(&state, body, wait, priority(&state, &headers)?).await
+}
+
+/// POST /plugin/{name} - Receive a webhook parsed by the plugin of the
+/// config file's `[plugins.<name>]`
+#[cfg(feature = "plugins")]
+pub async fn plugin_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<IntegrationQuery>,
+    payload: Bytes,
+) -> NotifyResult {
+    let Some(plugin) = state.plugins.get(&name).cloned() else {
+        return Err(not_found(format!("No plugin named {name}")));
+    };
+    info!("🧩 Webhook for plugin {} received", name);
+    // Plugins may run for a while, so off the async workers
+    let parsed = tokio::task::spawn_blocking(move || plugin.parse(&payload))
+        .await
+        .unwrap_or_else(|e| Err(e.into()));
+    let mut request = match parsed {
+        Ok(Some(request)) => request,
+        Ok(None) => {
+            return Ok((
+                StatusCode::OK,
+                Json(SendNotificationResponse {
+                    success: true,
+                    message: "Nothing to send".to_string(),
+                    telegram_message_id: None,
+                    delivery_id: None,
+                    results: Vec::new(),
+                }),
+            ));
+        }
+        Err(e) => {
+            warn!("⚠️ Plugin {} failed: {:#}", name, e);
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ErrorResponse::with_code(
+                    format!("Plugin {name} failed: {e:#}"),
+                    "PLUGIN_FAILED".to_string(),
+                )),
+            ));
         }
+    };
+    if query.chat_id.is_some() || query.chat.is_some() {
+        request.chat_id = query.chat_id;
+        request.chat = query.chat;
+    }
+    apply_caller(&state, &headers, &mut request)?;
+    let body = NotifyBody {
+        request,
+        attachments: Vec::new(),
+        source: plugins::SOURCE.to_string(),
+    };
+    let wait = integration_wait(query.mode);
+    dispatch(&state, body, wait, priority(&state, &headers)?).await
+}
+
+/// Integrations are answered once the message is queued, so a slow Telegram
+/// doesn't make the sender time out and send the alerts again
+fn integration_wait(mode: Option<DeliveryMode>) -> Wait {
+    match mode {
+        Some(DeliveryMode::Sync) => Wait::Sent,
+        _ => Wait::Queued,
+    }
+}
+
+/// POST /notify/preview - Show what would be sent without calling Telegram
+pub async fn preview(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: NotifyBody,
+) -> Result<Json<PreviewResponse>, (StatusCode, Json<ErrorResponse>)> {
+    info!("🔎 Notification preview requested");
+
+    let priority = priority(&state, &headers)?;
+    let mut body = body;
+    apply_caller(&state, &headers, &mut body.request)?;
+    let fan_out = body.request.is_fan_out();
+    let mut previews: Vec<PreviewResponse> = prepare(&state, body, priority, true)?
+        .into_iter()
+        .map(|(_, prepared)| preview_of(prepared))
+        .collect();
+    if !fan_out {
+        return Ok(Json(previews.remove(0)));
+    }
+
+    Ok(Json(PreviewResponse {
+        success: true,
+        delivery_id: None,
+        would_send: previews.iter().any(|preview| preview.would_send),
+        chat_id: None,
+        parse_mode: None,
+        disable_notification: false,
+        parts: Vec::new(),
+        attachments: Vec::new(),
+        suppressed_reason: None,
+        targets: previews,
+    }))
+}
+
+fn preview_of(prepared: Prepared) -> PreviewResponse {
+    match prepared {
+        Prepared::Ready(notification) => PreviewResponse {
+            success: true,
+            delivery_id: Some(notification.delivery_id),
+            would_send: true,
+            parts: split_message(&notification.message),
+            chat_id: Some(notification.chat_id),
+            parse_mode: notification.parse_mode,
+            disable_notification: notification.disable_notification,
+            attachments: notification
+                .attachments
+                .into_iter()
+                .map(|attachment| attachment.file_name)
+                .collect(),
+            suppressed_reason: None,
+            targets: Vec::new(),
+        },
+        Prepared::Suppressed {
+            delivery_id,
+            reason,
+        } => PreviewResponse {
+            success: true,
+            delivery_id: Some(delivery_id),
+            would_send: false,
+            chat_id: None,
+            parse_mode: None,
+            disable_notification: false,
+            parts: Vec::new(),
+            attachments: Vec::new(),
+            suppressed_reason: Some(reason),
+            targets: Vec::new(),
+        },
     }
 }
 
 /// POST /send - Alias for /notify
 pub async fn send(
     state: State<Arc<AppState>>,
-    request: JsonExtractor<SendNotificationRequest>,
-) -> Result<Json<SendNotificationResponse>, (StatusCode, Json<ErrorResponse>)> {
-    notify(state, request).await
+    headers: HeaderMap,
+    query: Query<NotifyQuery>,
+    body: NotifyBody,
+) -> NotifyResult {
+    notify(state, headers, query, body).await
+}
+
+enum Prepared {
+    Ready(Box<Notification>),
+    Suppressed { delivery_id: String, reason: String },
 }
 
-fn extract_message_id(result: &Option<Value>) -> Option<i64> {
-    result.as_ref()?.get("message_id")?.as_i64()
+/// Run a request through the pipeline once per target chat, mapping
+/// rejections to API errors. Returns the requested chat with each result.
+/// A preview is a dry run, e.g. not remembered by the deduplicator.
+fn prepare(
+    state: &AppState,
+    body: NotifyBody,
+    priority: Priority,
+    preview: bool,
+) -> Result<Vec<(String, Prepared)>, ApiError> {
+    let chats = targets(state, &body.request)?;
+    chats
+        .into_iter()
+        .map(|chat_id| {
+            let prepared = prepare_for(state, &body, chat_id.clone(), priority, preview)?;
+            Ok((chat_id, prepared))
+        })
+        .collect()
+}
+
+/// Chats a request is addressed to: custom chat_id, an alias, a fan-out list
+/// or the default
+fn targets(state: &AppState, request: &SendNotificationRequest) -> Result<Vec<String>, ApiError> {
+    let given = [
+        request.chat_id.is_some(),
+        request.chat.is_some(),
+        request.is_fan_out(),
+    ];
+    if given.iter().filter(|given| **given).count() > 1 {
+        return Err(bad_request(
+            "Specify only one of chat, chat_id or chat_ids/chats",
+            "CONFLICTING_CHAT",
+        ));
+    }
+
+    if let Some(chat_id) = &request.chat_id {
+        return Ok(vec![chat_id.clone()]);
+    }
+    if let Some(alias) = &request.chat {
+        return Ok(vec![resolve_alias(state, alias)?]);
+    }
+    if !request.is_fan_out() {
+        let default_chat = state.bots.default_chat(request.bot.as_deref());
+        return Ok(vec![
+            default_chat.map_or_else(|| state.default_chat_id.clone(), str::to_string),
+        ]);
+    }
+
+    let mut chats: Vec<String> = Vec::new();
+    let aliased = request
+        .chats
+        .iter()
+        .flatten()
+        .map(|alias| resolve_alias(state, alias));
+    for chat_id in request
+        .chat_ids
+        .iter()
+        .flatten()
+        .cloned()
+        .map(Ok)
+        .chain(aliased)
+    {
+        let chat_id = chat_id?;
+        if !chats.contains(&chat_id) {
+            chats.push(chat_id);
+        }
+    }
+    if chats.is_empty() {
+        return Err(bad_request(
+            "chat_ids/chats must name at least one chat",
+            "NO_CHATS",
+        ));
+    }
+    Ok(chats)
+}
+
+fn resolve_alias(state: &AppState, alias: &str) -> Result<String, ApiError> {
+    state
+        .targets
+        .resolve(alias)
+        .ok_or_else(|| bad_request(&format!("Unknown chat alias: {alias}"), "UNKNOWN_CHAT"))
+}
+
+fn bad_request(error: &str, code: &str) -> ApiError {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::with_code(
+            error.to_string(),
+            code.to_string(),
+        )),
+    )
+}
+
+/// Run the request through the pipeline for one chat; stages may reroute it
+fn prepare_for(
+    state: &AppState,
+    body: &NotifyBody,
+    chat_id: String,
+    priority: Priority,
+    preview: bool,
+) -> Result<Prepared, ApiError> {
+    let delivery_id = new_delivery_id();
+    let request = &body.request;
+    let send_at = match &request.send_at {
+        Some(send_at) => Some(
+            scheduler::parse_time(send_at)
+                .map_err(|e| bad_request(&format!("Invalid send_at: {e:#}"), "INVALID_SEND_AT"))?,
+        ),
+        None => None,
+    };
+    let callback_url = match &request.callback_url {
+        Some(url) => Some(
+            callbacks::parse_url(url)
+                .map_err(|e| bad_request(&format!("{e:#}"), "INVALID_REQUEST"))?
+                .to_string(),
+        ),
+        None => None,
+    };
+    let buttons = request.buttons.clone().unwrap_or_default();
+    telegram::check_buttons(&buttons)
+        .map_err(|e| bad_request(&format!("{e:#}"), "INVALID_REQUEST"))?;
+    state
+        .bots
+        .check_chat(request.bot.as_deref(), &chat_id)
+        .map_err(bot_error)?;
+
+    let notification = Notification {
+        message: request.message.clone(),
+        chat_id,
+        parse_mode: request.parse_mode.clone(),
+        disable_notification: request.disable_notification.unwrap_or(false),
+        disable_link_preview: false,
+        protect_content: false,
+        thread_id: None,
+        bot: request.bot.clone(),
+        pin: request.pin.unwrap_or(false),
+        delivery_id: delivery_id.clone(),
+        translations: request.translations.clone().unwrap_or_default(),
+        attachments: body.attachments.clone(),
+        buttons,
+        emergency: priority == Priority::Emergency,
+        low_priority: priority == Priority::Low,
+        send_at,
+        full_message: None,
+        source: body.source.clone(),
+        severity: request.severity.clone(),
+        labels: request.labels.clone().unwrap_or_default(),
+        key: request.key.clone(),
+        callback_url,
+    };
+
+    let processed = if preview {
+        state.pipeline.preview(notification)
+    } else {
+        state.pipeline.run(notification)
+    };
+    match processed {
+        Ok(Processed::Ready(notification)) => Ok(Prepared::Ready(notification)),
+        Ok(Processed::Dropped { stage, reason }) => {
+            info!(
+                "🔇 Notification {} dropped by {}: {}",
+                delivery_id, stage, reason
+            );
+            Ok(Prepared::Suppressed {
+                delivery_id,
+                reason,
+            })
+        }
+        Ok(Processed::Rejected { stage, code, error }) => {
+            warn!(
+                "⚠️ Notification {} rejected by {}: {}",
+                delivery_id, stage, error
+            );
+            Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::with_code(error, code)),
+            ))
+        }
+        Err(e) => {
+            error!("❌ Notification pipeline failed: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::with_code(
+                    format!("Failed to process notification: {e}"),
+                    "PIPELINE_ERROR".to_string(),
+                )),
+            ))
+        }
+    }
 }
 
 // Convenience function for logging
 fn info(msg: &str) {
     tracing::info!("{}", msg);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Authenticator, SignatureVerifier};
+    use crate::delivery::{Outcomes, RetryPolicy};
+    use crate::keys::Scope;
+    use crate::limits::{self, BodyLimits};
+    use crate::notifier::mock::MockNotifier;
+    use crate::telegram::TelegramBot;
+    use axum::Router;
+    use axum::body::to_bytes;
+    use axum::extract::DefaultBodyLimit;
+    use axum::http::Request;
+    use axum::http::header::CONTENT_LENGTH;
+    use axum::middleware;
+    use axum::routing::{get, post};
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    fn app(notifier: Arc<MockNotifier>) -> Router {
+        authenticated_app(notifier, None)
+    }
+
+    /// The app with requests checked by `authenticator`, as the server does
+    /// when credentials are configured
+    fn authenticated_app(
+        notifier: Arc<MockNotifier>,
+        authenticator: Option<Authenticator>,
+    ) -> Router {
+        let history = History::new(10);
+        let queue = DeliveryQueue::start_with(
+            notifier.clone(),
+            10,
+            false,
+            history.clone(),
+            RetryPolicy::default(),
+            None,
+            Outcomes::default(),
+        )
+        .unwrap();
+        let state = AppState {
+            bots: Bots::new(TelegramBot::new("token".to_string())),
+            notifier,
+            bot_username: Some("alerts_bot".to_string()),
+            default_chat_id: "-100123".to_string(),
+            base_path: String::new(),
+            pipeline: Pipeline::with_defaults(),
+            queue,
+            fallback: Fallback::default(),
+            callbacks: Callbacks::default(),
+            digests: Digests::default(),
+            scheduler: Scheduler::new(),
+            schedules: ScheduleStore::default(),
+            idempotency: IdempotencyStore::new(Duration::from_secs(60)),
+            history,
+            targets: Targets::default(),
+            silences: Silences::default(),
+            priority: PrioritySettings::default(),
+            quotas: KeyQuotas::default(),
+            api_keys: ApiKeys::default(),
+            keys: KeyStore::default(),
+            config_sync: None,
+            bot_check: BotCheck::verified(Duration::from_secs(60)),
+            outages: Outages::default(),
+            webhooks: BTreeMap::new(),
+            #[cfg(feature = "plugins")]
+            plugins: BTreeMap::new(),
+            incidents: Incidents::default(),
+            checkins: CheckIns::default(),
+            effective_config: Value::Null,
+        };
+        let limits = BodyLimits {
+            buffered: 1024,
+            streamed: 8 * 1024 * 1024,
+        };
+        let mut router = Router::new()
+            .route("/health", get(health))
+            .route("/notify", post(notify))
+            .route("/notify/preview", post(preview))
+            .route("/integrations/alertmanager", post(alertmanager))
+            .layer(DefaultBodyLimit::max(limits.buffered));
+        if let Some(authenticator) = authenticator {
+            router = router.route_layer(middleware::from_fn_with_state(
+                Arc::new(authenticator),
+                auth::require_auth,
+            ));
+        }
+        router
+            .route_layer(middleware::from_fn_with_state(
+                limits,
+                limits::reject_oversized,
+            ))
+            .with_state(Arc::new(state))
+    }
+
+    async fn call(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn post_json(uri: &str, body: Value) -> Request<Body> {
+        let body = body.to_string();
+        Request::post(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_LENGTH, body.len())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_health_asks_the_notifier() {
+        let notifier = Arc::new(MockNotifier::default());
+        let app = app(notifier.clone());
+        let health = || Request::get("/health").body(Body::empty()).unwrap();
+
+        let (status, body) = call(&app, health()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["bot_verified"], true);
+        assert_eq!(body["bot_username"], "alerts_bot");
+
+        *notifier.failing.lock().unwrap() = Some("Unauthorized".to_string());
+        let (status, body) = call(&app, health()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["code"], "BOT_VERIFICATION_FAILED");
+    }
+
+    #[tokio::test]
+    async fn test_notify_sends_through_the_notifier() {
+        let notifier = Arc::new(MockNotifier::default());
+        let app = app(notifier.clone());
+
+        let (status, body) = call(
+            &app,
+            post_json("/notify/preview", json!({"message": "🔥 Disk full"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["chat_id"], "-100123");
+        assert!(notifier.messages().is_empty());
+
+        let (status, body) = call(
+            &app,
+            post_json("/notify", json!({"message": "🔥 Disk full"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["telegram_message_id"], 1);
+        assert_eq!(notifier.messages(), vec!["🔥 Disk full"]);
+        assert_eq!(notifier.sent.lock().unwrap()[0].chat_id, "-100123");
+
+        let (status, body) = call(&app, post_json("/notify", json!({"message": ""}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "EMPTY_MESSAGE");
+        assert_eq!(notifier.messages().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_large_webhook_streams_past_body_limit() {
+        let notifier = Arc::new(MockNotifier::default());
+        let app = app(notifier.clone());
+        // Fields the adapter doesn't read make up most of the body
+        let alert = |instance: usize| {
+            json!({
+                "status": "firing",
+                "labels": {"alertname": "HighLatency", "instance": format!("web-{instance}")},
+                "annotations": {"summary": "p99 above 2s"},
+                "values": {"p99": "x".repeat(20_000)},
+            })
+        };
+        let webhook = json!({
+            "status": "firing",
+            "commonLabels": {"alertname": "HighLatency"},
+            "alerts": (0..150).map(alert).collect::<Vec<_>>(),
+        });
+        let request = post_json("/integrations/alertmanager?mode=sync", webhook);
+
+        let (status, _) = call(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let messages = notifier.messages().concat();
+        assert!(messages.contains("[FIRING:100]"), "{messages}");
+        assert!(messages.contains("instance=web-99"));
+        assert!(messages.contains("…and 50 more"));
+
+        // The other endpoints keep the buffered limit
+        let large = json!({"message": "x".repeat(2048)});
+        let (status, body) = call(&app, post_json("/notify", large)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+        let huge = json!({"alerts": [{"values": "x".repeat(9 * 1024 * 1024)}]});
+        let (status, _) = call(&app, post_json("/integrations/alertmanager", huge)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_signed_webhook_streams_past_body_limit() {
+        let notifier = Arc::new(MockNotifier::default());
+        let authenticator = Authenticator::new(ApiKeys::default(), None)
+            .with_signatures(SignatureVerifier::new("s3cret".to_string(), 1024));
+        let app = authenticated_app(notifier.clone(), Some(authenticator));
+        let webhook = json!({
+            "status": "firing",
+            "commonLabels": {"alertname": "HighLatency"},
+            "alerts": [{"status": "firing", "values": "x".repeat(64 * 1024)}],
+        })
+        .to_string();
+        let signed = |signature: String| {
+            Request::post("/integrations/alertmanager?mode=sync")
+                .header(CONTENT_TYPE, "application/json")
+                .header(auth::SIGNATURE_HEADER, signature)
+                .body(Body::from(webhook.clone()))
+                .unwrap()
+        };
+
+        let (status, body) = call(&app, signed(auth::sign("s3cret", None, b"{}"))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "INVALID_SIGNATURE");
+        assert!(notifier.messages().is_empty());
+
+        let signature = auth::sign("s3cret", None, webhook.as_bytes());
+        let (status, _) = call(&app, signed(signature)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(notifier.messages()[0].contains("HighLatency"));
+    }
+
+    #[tokio::test]
+    async fn test_managed_key_quota_is_enforced() {
+        let notifier = Arc::new(MockNotifier::default());
+        let store = KeyStore::default();
+        let quota = Quota {
+            requests_per_minute: Some(1),
+            requests_per_day: None,
+        };
+        let managed = store.create(None, Scope::Send, quota, 0).unwrap();
+        let authenticator = Authenticator::new(ApiKeys::default(), None)
+            .with_managed_keys(store.clone())
+            .with_quotas(KeyQuotas::default().with_managed_keys(store));
+        let app = authenticated_app(notifier.clone(), Some(authenticator));
+        let notify = || {
+            let mut request = post_json("/notify", json!({"message": "Disk full"}));
+            request.headers_mut().insert(
+                auth::API_KEY_HEADER,
+                HeaderValue::from_str(&managed.key).unwrap(),
+            );
+            request
+        };
+
+        let (status, _) = call(&app, notify()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = call(&app, notify()).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["code"], "QUOTA_EXCEEDED");
+        assert_eq!(notifier.messages().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_keys_are_per_caller() {
+        let notifier = Arc::new(MockNotifier::default());
+        let authenticator = Authenticator::new(
+            ApiKeys::new(["team-a-key".to_string(), "team-b-key".to_string()]),
+            None,
+        );
+        let app = authenticated_app(notifier.clone(), Some(authenticator));
+        let notify = |api_key: &'static str, message: &str| {
+            let mut request = post_json("/notify", json!({"message": message}));
+            let headers = request.headers_mut();
+            headers.insert(auth::API_KEY_HEADER, HeaderValue::from_static(api_key));
+            headers.insert(IDEMPOTENCY_HEADER, HeaderValue::from_static("deploy-42"));
+            request
+        };
+
+        let (status, first) = call(&app, notify("team-a-key", "Team A deployed")).await;
+        assert_eq!(status, StatusCode::OK);
+        // Another caller's request with the same key is its own
+        let (status, _) = call(&app, notify("team-b-key", "Team B deployed")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(notifier.messages().len(), 2);
+        assert!(notifier.messages()[1].contains("Team B"));
+
+        let (status, retried) = call(&app, notify("team-a-key", "Team A deployed")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(retried, first);
+        assert_eq!(notifier.messages().len(), 2);
+    }
+}
@@ -0,0 +1,704 @@
+use crate::pipeline::Notification;
+use crate::storage;
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Default number of notifications kept in the history
+pub const DEFAULT_HISTORY_SIZE: usize = 500;
+
+/// Records per page of `GET /history` unless `limit` is given
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Largest `limit` accepted by `GET /history`
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// Names of the delivery statuses, as used by `status=` filters
+pub const STATUS_NAMES: [&str; 7] = [
+    "scheduled",
+    "queued",
+    "sending",
+    "sent",
+    "failed",
+    "digested",
+    "cancelled",
+];
+
+/// How often records past the retention period are removed
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Delivery state of a notification in the history
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Held until its `send_at` time
+    Scheduled,
+    Queued,
+    Sending,
+    Sent {
+        telegram_message_id: Option<i64>,
+    },
+    Failed {
+        error: String,
+    },
+    /// Sent as part of the digest with this delivery ID
+    Digested {
+        digest_id: String,
+    },
+    /// Cancelled before its `send_at` time
+    Cancelled,
+}
+
+impl DeliveryStatus {
+    /// One of `STATUS_NAMES`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Scheduled => "scheduled",
+            Self::Queued => "queued",
+            Self::Sending => "sending",
+            Self::Sent { .. } => "sent",
+            Self::Failed { .. } => "failed",
+            Self::Digested { .. } => "digested",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A delivered (or attempted) notification
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Record {
+    pub notification: Notification,
+    pub status: DeliveryStatus,
+    /// Unix timestamp of when the record was created
+    pub created_at: u64,
+    /// Unix timestamp of the last status change
+    pub updated_at: u64,
+    /// Delivery attempts made so far
+    pub attempts: u32,
+    /// Delivery ID of the notification this one re-sends
+    pub resent_from: Option<String>,
+    /// Delivery IDs of re-sends of this notification
+    pub resends: Vec<String>,
+    /// Who acknowledged the notification through its "Ack" link
+    pub acknowledged_by: Option<String>,
+    deleted: bool,
+}
+
+/// Which records `History::query` returns
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub chat_id: Option<String>,
+    /// Only records created at or after this Unix timestamp
+    pub since: Option<u64>,
+    /// Only records created before this Unix timestamp
+    pub until: Option<u64>,
+    /// One of `STATUS_NAMES`
+    pub status: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, record: &Record) -> bool {
+        !record.deleted
+            && self
+                .chat_id
+                .as_ref()
+                .is_none_or(|chat_id| record.notification.chat_id == *chat_id)
+            && self.since.is_none_or(|since| record.created_at >= since)
+            && self.until.is_none_or(|until| record.created_at < until)
+            && self
+                .status
+                .as_ref()
+                .is_none_or(|status| record.status.name() == status)
+    }
+}
+
+/// Recent notifications in memory, oldest evicted first once full, and
+/// written through to `--history-db` when set so they survive restarts.
+/// Cloning shares the same history.
+#[derive(Clone)]
+pub struct History {
+    records: Arc<Mutex<VecDeque<Record>>>,
+    capacity: usize,
+    db: Option<Database>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+            db: None,
+        }
+    }
+
+    /// History kept in the SQLite database at `path`, creating it if needed.
+    /// The newest `capacity` records stored there are loaded.
+    pub fn open(capacity: usize, path: &Path) -> Result<Self> {
+        let db = Database::open(path)
+            .with_context(|| format!("Failed to open history database {}", path.display()))?;
+        let mut records = VecDeque::from(db.load()?);
+        while records.len() > capacity {
+            if let Some(evicted) = records.pop_front() {
+                db.remove(&evicted.notification.delivery_id)?;
+            }
+        }
+        Ok(Self {
+            records: Arc::new(Mutex::new(records)),
+            capacity,
+            db: Some(db),
+        })
+    }
+
+    /// Remember a notification, linking it to the record it re-sends
+    pub fn record(
+        &self,
+        notification: &Notification,
+        status: DeliveryStatus,
+        resent_from: Option<&str>,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut records = self.records.lock().unwrap();
+        if let Some(original) = resent_from.and_then(|id| find_mut(&mut records, id)) {
+            original.resends.push(notification.delivery_id.clone());
+            self.save(original);
+        }
+        while records.len() >= self.capacity {
+            if let Some(evicted) = records.pop_front() {
+                self.forget(&evicted.notification.delivery_id);
+            }
+        }
+        let now = unix_now();
+        let record = Record {
+            notification: notification.clone(),
+            status,
+            created_at: now,
+            updated_at: now,
+            attempts: 0,
+            resent_from: resent_from.map(str::to_string),
+            resends: Vec::new(),
+            acknowledged_by: None,
+            deleted: false,
+        };
+        self.save(&record);
+        records.push_back(record);
+    }
+
+    pub fn set_status(&self, delivery_id: &str, status: DeliveryStatus) {
+        if let Some(record) = find_mut(&mut self.records.lock().unwrap(), delivery_id) {
+            record.status = status;
+            record.updated_at = unix_now();
+            self.save(record);
+        }
+    }
+
+    /// Mark a notification as being sent, counting the attempt
+    pub fn start_attempt(&self, delivery_id: &str) {
+        if let Some(record) = find_mut(&mut self.records.lock().unwrap(), delivery_id) {
+            record.status = DeliveryStatus::Sending;
+            record.attempts += 1;
+            record.updated_at = unix_now();
+            self.save(record);
+        }
+    }
+
+    /// Mark a notification as acknowledged. Returns false if there was none.
+    pub fn acknowledge(&self, delivery_id: &str, by: &str) -> bool {
+        match find_mut(&mut self.records.lock().unwrap(), delivery_id) {
+            Some(record) if !record.deleted => {
+                record.acknowledged_by = Some(by.to_string());
+                self.save(record);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Look up a record that hasn't been deleted
+    pub fn get(&self, delivery_id: &str) -> Option<Record> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|record| record.notification.delivery_id == delivery_id && !record.deleted)
+            .cloned()
+    }
+
+    /// Records created in `[from, to)` that haven't been deleted, oldest first
+    pub fn export(&self, from: Option<u64>, to: Option<u64>) -> Vec<Record> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|record| !record.deleted)
+            .filter(|record| from.is_none_or(|from| record.created_at >= from))
+            .filter(|record| to.is_none_or(|to| record.created_at < to))
+            .cloned()
+            .collect()
+    }
+
+    /// Page of the records matching `filter`, newest first, skipping the
+    /// first `offset`. Also returns how many match in total.
+    pub fn query(&self, filter: &Filter, offset: usize, limit: usize) -> (Vec<Record>, usize) {
+        let records = self.records.lock().unwrap();
+        let matching: Vec<&Record> = records
+            .iter()
+            .rev()
+            .filter(|record| filter.matches(record))
+            .collect();
+        let page = matching
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|record| (*record).clone())
+            .collect();
+        (page, matching.len())
+    }
+
+    /// Hide a record from lookups and re-sends. Returns false if there was none.
+    pub fn delete(&self, delivery_id: &str) -> bool {
+        match find_mut(&mut self.records.lock().unwrap(), delivery_id) {
+            Some(record) if !record.deleted => {
+                record.deleted = true;
+                self.save(record);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Remove records created before `cutoff` (Unix seconds), deleted ones
+    /// included. Returns how many were removed.
+    pub fn prune(&self, cutoff: u64) -> usize {
+        let mut records = self.records.lock().unwrap();
+        let before = records.len();
+        records.retain(|record| record.created_at >= cutoff);
+        if let Some(db) = &self.db
+            && let Err(e) = db.prune(cutoff)
+        {
+            warn!("⚠️ Failed to prune the history database: {:#}", e);
+        }
+        before - records.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Make sure the database, if any, can still be read
+    pub fn check_storage(&self) -> Result<()> {
+        match &self.db {
+            Some(db) => db.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Size of the database in bytes; None without one
+    pub fn storage_size(&self) -> Result<Option<u64>> {
+        self.db.as_ref().map(Database::size).transpose()
+    }
+
+    /// Give the space of removed records back to the disk
+    pub fn vacuum(&self) -> Result<()> {
+        match &self.db {
+            Some(db) => db.vacuum(),
+            None => Ok(()),
+        }
+    }
+
+    /// Write a record through to the database. The in-memory history stays
+    /// authoritative, so a failed write is only logged.
+    fn save(&self, record: &Record) {
+        if let Some(db) = &self.db
+            && let Err(e) = db.save(record)
+        {
+            warn!(
+                "⚠️ Failed to store notification {} in the history database: {:#}",
+                record.notification.delivery_id, e
+            );
+        }
+    }
+
+    fn forget(&self, delivery_id: &str) {
+        if let Some(db) = &self.db
+            && let Err(e) = db.remove(delivery_id)
+        {
+            warn!(
+                "⚠️ Failed to remove notification {} from the history database: {:#}",
+                delivery_id, e
+            );
+        }
+    }
+}
+
+/// SQLite database (`--history-db`) mirroring the in-memory history
+#[derive(Clone)]
+struct Database {
+    connection: Arc<Mutex<Connection>>,
+    path: Arc<PathBuf>,
+}
+
+impl Database {
+    fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.busy_timeout(storage::BUSY_TIMEOUT)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS history (
+                delivery_id TEXT PRIMARY KEY,
+                record TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+        )?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            path: Arc::new(path.to_path_buf()),
+        })
+    }
+
+    /// Every stored record, oldest first
+    fn load(&self) -> Result<Vec<Record>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare("SELECT record FROM history ORDER BY created_at, rowid")?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+        rows.map(|json| {
+            serde_json::from_str(&json?).context("Invalid record in the history database")
+        })
+        .collect()
+    }
+
+    /// Insert or update a record, keeping its place in the order
+    fn save(&self, record: &Record) -> Result<()> {
+        let json = serde_json::to_string(record)?;
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO history (delivery_id, record, created_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (delivery_id) DO UPDATE SET record = excluded.record",
+            params![record.notification.delivery_id, json, record.created_at],
+        )?;
+        Ok(())
+    }
+
+    fn check(&self) -> Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM history", [], |row| {
+                row.get::<_, i64>(0)
+            })?;
+        Ok(())
+    }
+
+    fn remove(&self, delivery_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM history WHERE delivery_id = ?1", [delivery_id])?;
+        Ok(())
+    }
+
+    fn prune(&self, cutoff: u64) -> Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM history WHERE created_at < ?1", [cutoff])?;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<u64> {
+        storage::size(&self.connection.lock().unwrap())
+    }
+
+    fn vacuum(&self) -> Result<()> {
+        storage::vacuum(&self.path)
+    }
+}
+
+/// Background task removing records older than `retention` every minute,
+/// so notification texts aren't kept longer than allowed
+pub async fn enforce_retention(history: History, retention: Duration) {
+    let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+    loop {
+        interval.tick().await;
+        let cutoff = unix_now().saturating_sub(retention.as_secs());
+        let removed = history.prune(cutoff);
+        if removed > 0 {
+            info!(
+                "🧹 Removed {} notification(s) past the retention period; {} kept",
+                removed,
+                history.len()
+            );
+        }
+    }
+}
+
+fn find_mut<'a>(records: &'a mut VecDeque<Record>, delivery_id: &str) -> Option<&'a mut Record> {
+    records
+        .iter_mut()
+        .find(|record| record.notification.delivery_id == delivery_id)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(delivery_id: &str) -> Notification {
+        Notification {
+            message: "Disk full".to_string(),
+            chat_id: "123456789".to_string(),
+            delivery_id: delivery_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn sent() -> DeliveryStatus {
+        DeliveryStatus::Sent {
+            telegram_message_id: Some(42),
+        }
+    }
+
+    #[test]
+    fn test_record_and_get() {
+        let history = History::new(10);
+        history.record(&notification("a"), sent(), None);
+
+        let record = history.get("a").unwrap();
+        assert_eq!(record.notification.message, "Disk full");
+        assert_eq!(record.status, sent());
+        assert!(history.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_evicts_oldest() {
+        let history = History::new(2);
+        history.record(&notification("a"), sent(), None);
+        history.record(&notification("b"), sent(), None);
+        history.record(&notification("c"), sent(), None);
+
+        assert!(history.get("a").is_none());
+        assert!(history.get("b").is_some());
+        assert!(history.get("c").is_some());
+    }
+
+    #[test]
+    fn test_set_status() {
+        let history = History::new(10);
+        history.record(&notification("a"), DeliveryStatus::Queued, None);
+        history.set_status(
+            "a",
+            DeliveryStatus::Failed {
+                error: "chat not found".to_string(),
+            },
+        );
+
+        assert!(matches!(
+            history.get("a").unwrap().status,
+            DeliveryStatus::Failed { .. }
+        ));
+    }
+
+    #[test]
+    fn test_start_attempt() {
+        let history = History::new(10);
+        history.record(&notification("a"), DeliveryStatus::Queued, None);
+        assert_eq!(history.get("a").unwrap().attempts, 0);
+
+        history.start_attempt("a");
+
+        let record = history.get("a").unwrap();
+        assert_eq!(record.status, DeliveryStatus::Sending);
+        assert_eq!(record.attempts, 1);
+    }
+
+    #[test]
+    fn test_soft_delete() {
+        let history = History::new(10);
+        history.record(&notification("a"), sent(), None);
+
+        assert!(history.delete("a"));
+        assert!(history.get("a").is_none());
+        assert!(!history.delete("a"));
+        assert!(!history.delete("missing"));
+    }
+
+    #[test]
+    fn test_export_range() {
+        let history = History::new(10);
+        history.record(&notification("a"), sent(), None);
+        history.record(&notification("b"), sent(), None);
+        history.record(&notification("c"), sent(), None);
+        history.delete("b");
+        let created_at = history.get("a").unwrap().created_at;
+
+        let ids = |records: Vec<Record>| -> Vec<String> {
+            records
+                .into_iter()
+                .map(|record| record.notification.delivery_id)
+                .collect()
+        };
+        assert_eq!(ids(history.export(None, None)), vec!["a", "c"]);
+        assert_eq!(ids(history.export(Some(created_at), None)), vec!["a", "c"]);
+        assert!(history.export(Some(created_at + 60), None).is_empty());
+        assert!(history.export(None, Some(created_at)).is_empty());
+    }
+
+    #[test]
+    fn test_prune_before_cutoff() {
+        let history = History::new(10);
+        history.record(&notification("a"), sent(), None);
+        history.record(&notification("b"), sent(), None);
+        history.delete("b");
+        let created_at = history.get("a").unwrap().created_at;
+
+        assert_eq!(history.prune(created_at), 0);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.prune(created_at + 1), 2);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_resend_links_records() {
+        let history = History::new(10);
+        history.record(&notification("a"), sent(), None);
+        history.record(&notification("b"), sent(), Some("a"));
+
+        assert_eq!(history.get("a").unwrap().resends, vec!["b".to_string()]);
+        assert_eq!(history.get("b").unwrap().resent_from, Some("a".to_string()));
+    }
+
+    #[test]
+    fn test_status_serialization() {
+        let json = serde_json::to_value(sent()).unwrap();
+        assert_eq!(json["status"], "sent");
+        assert_eq!(json["telegram_message_id"], 42);
+    }
+
+    #[test]
+    fn test_query_filters_and_pages() {
+        let history = History::new(10);
+        for id in ["a", "b", "c", "d"] {
+            history.record(&notification(id), sent(), None);
+        }
+        let mut other_chat = notification("e");
+        other_chat.chat_id = "-100123".to_string();
+        history.record(&other_chat, sent(), None);
+        history.set_status(
+            "b",
+            DeliveryStatus::Failed {
+                error: "Bad Gateway".to_string(),
+            },
+        );
+        history.delete("c");
+
+        let ids = |(records, total): (Vec<Record>, usize)| -> (Vec<String>, usize) {
+            let ids = records
+                .into_iter()
+                .map(|record| record.notification.delivery_id)
+                .collect();
+            (ids, total)
+        };
+        let chat = Filter {
+            chat_id: Some("123456789".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(history.query(&chat, 0, 2)),
+            (vec!["d".into(), "b".into()], 3)
+        );
+        assert_eq!(ids(history.query(&chat, 2, 2)), (vec!["a".into()], 3));
+        let failed = Filter {
+            status: Some("failed".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(history.query(&failed, 0, 10)), (vec!["b".into()], 1));
+        let created_at = history.get("a").unwrap().created_at;
+        let later = Filter {
+            since: Some(created_at + 60),
+            ..Default::default()
+        };
+        assert_eq!(history.query(&later, 0, 10).1, 0);
+    }
+
+    #[test]
+    fn test_history_survives_reopening() {
+        let dir = std::env::temp_dir().join(format!("tn-history-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.db");
+
+        let history = History::open(10, &path).unwrap();
+        history.record(&notification("a"), DeliveryStatus::Queued, None);
+        history.record(&notification("b"), sent(), Some("a"));
+        history.record(&notification("c"), sent(), None);
+        history.set_status(
+            "a",
+            DeliveryStatus::Failed {
+                error: "chat not found".to_string(),
+            },
+        );
+        history.delete("c");
+        drop(history);
+
+        // Only the newest two fit after the restart
+        let reopened = History::open(2, &path).unwrap();
+        let record = reopened.get("b").unwrap();
+        assert_eq!(record.status, sent());
+        assert_eq!(record.resent_from.as_deref(), Some("a"));
+        assert!(reopened.get("a").is_none());
+        assert!(reopened.get("c").is_none());
+        assert_eq!(reopened.len(), 2);
+        drop(reopened);
+        assert_eq!(History::open(10, &path).unwrap().len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_vacuum_shrinks_database() {
+        let dir = std::env::temp_dir().join(format!("tn-history-vacuum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let history = History::open(1000, &dir.join("history.db")).unwrap();
+        for i in 0..500 {
+            let mut notification = notification(&i.to_string());
+            notification.message = "x".repeat(1000);
+            history.record(&notification, sent(), None);
+        }
+        let full = history.storage_size().unwrap().unwrap();
+        history.prune(unix_now() + 60);
+        // Deleting alone leaves the file as large as before
+        assert_eq!(history.storage_size().unwrap(), Some(full));
+
+        // The shared connection isn't needed, so it stays free meanwhile
+        let shared = history.db.as_ref().unwrap().connection.lock().unwrap();
+        history.vacuum().unwrap();
+        drop(shared);
+        let vacuumed = history.storage_size().unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(vacuumed < full / 10, "{vacuumed} of {full} bytes left");
+        assert_eq!(History::new(10).storage_size().unwrap(), None);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_history() {
+        let history = History::new(0);
+        history.record(&notification("a"), sent(), None);
+        assert!(history.get("a").is_none());
+    }
+}
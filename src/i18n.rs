@@ -0,0 +1,534 @@
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use anyhow::Result;
+use axum::{
+    body::{Body, to_bytes},
+    extract::Request,
+    http::{
+        HeaderValue,
+        header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE, CONTENT_LENGTH, CONTENT_TYPE, VARY},
+    },
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Texts the service itself sends to Telegram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    /// Message sent in CLI mode when no `--message` is given
+    TestMessage,
+}
+
+impl Text {
+    fn translations(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Text::TestMessage => &[
+                ("en", "Hello from Telegram Bot! 🤖"),
+                ("de", "Hallo vom Telegram-Bot! 🤖"),
+                ("es", "¡Hola desde el bot de Telegram! 🤖"),
+            ],
+        }
+    }
+
+    /// The text in `locale`, falling back to English
+    pub fn localized(self, locale: &str) -> &'static str {
+        let translations = self.translations();
+        [locale, language(locale), DEFAULT_LOCALE]
+            .iter()
+            .find_map(|candidate| {
+                translations
+                    .iter()
+                    .find(|(code, _)| code.eq_ignore_ascii_case(candidate))
+            })
+            .map(|(_, text)| *text)
+            .unwrap_or(translations[0].1)
+    }
+}
+
+/// Locales API error messages are available in
+pub const ERROR_LOCALES: &[&str] = &["en", "de", "es"];
+
+/// Translations of the API's English error messages, keyed by error code
+fn error_translations(code: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    let translations: &[(&str, &str)] = match code {
+        "ADMIN_KEY_REQUIRED" => &[
+            ("de", "Dieser Endpunkt erfordert einen Admin-Schlüssel"),
+            ("es", "Este endpoint requiere una clave de administrador"),
+        ],
+        "BOT_VERIFICATION_FAILED" => &[
+            ("de", "Überprüfung des Bots fehlgeschlagen"),
+            ("es", "No se pudo verificar el bot"),
+        ],
+        "CONFLICTING_CHAT" => &[
+            ("de", "Nur eines von chat und chat_id angeben"),
+            ("es", "Indica solo uno de chat o chat_id"),
+        ],
+        "DELIVERY_ERROR" | "TELEGRAM_API_ERROR" => &[
+            ("de", "Benachrichtigung konnte nicht gesendet werden"),
+            ("es", "No se pudo enviar la notificación"),
+        ],
+        "EMERGENCY_NOT_ALLOWED" => &[
+            (
+                "de",
+                "X-Priority: emergency erfordert einen API-Schlüssel mit Notfallberechtigung",
+            ),
+            (
+                "es",
+                "X-Priority: emergency requiere una clave de API autorizada para emergencias",
+            ),
+        ],
+        "EMPTY_MESSAGE" | "MISSING_MESSAGE" => &[
+            ("de", "Die Nachricht darf nicht leer sein"),
+            ("es", "El mensaje no puede estar vacío"),
+        ],
+        "IDEMPOTENCY_KEY_IN_USE" => &[
+            (
+                "de",
+                "Eine Anfrage mit diesem Idempotenzschlüssel wird noch verarbeitet",
+            ),
+            (
+                "es",
+                "Todavía se está procesando una solicitud con esta clave de idempotencia",
+            ),
+        ],
+        "INVALID_API_KEY" => &[
+            ("de", "Der API-Schlüssel ist ungültig"),
+            ("es", "La clave de API no es válida"),
+        ],
+        "INVALID_CREDENTIALS" => &[
+            ("de", "Benutzername oder Passwort ist ungültig"),
+            ("es", "El usuario o la contraseña no son válidos"),
+        ],
+        "INVALID_CRON" => &[
+            ("de", "Ungültiger Cron-Ausdruck"),
+            ("es", "Expresión cron no válida"),
+        ],
+        "INVALID_FIELD" | "INVALID_MULTIPART" | "INVALID_REQUEST" => {
+            &[("de", "Ungültige Anfrage"), ("es", "Solicitud no válida")]
+        }
+        "INVALID_IDEMPOTENCY_KEY" => &[
+            ("de", "Ungültiger Idempotenzschlüssel"),
+            ("es", "Clave de idempotencia no válida"),
+        ],
+        "INVALID_NAME" => &[("de", "Ungültiger Name"), ("es", "Nombre no válido")],
+        "INVALID_SEND_AT" => &[
+            (
+                "de",
+                "send_at muss ein Zeitpunkt nach RFC 3339 sein, z. B. 2026-10-16T09:00:00Z",
+            ),
+            (
+                "es",
+                "send_at debe ser una fecha RFC 3339, p. ej. 2026-10-16T09:00:00Z",
+            ),
+        ],
+        "INVALID_SIGNATURE" => &[
+            ("de", "Die Signatur passt nicht zum Inhalt der Anfrage"),
+            ("es", "La firma no coincide con el cuerpo de la solicitud"),
+        ],
+        "INVALID_TIMESTAMP" | "MISSING_TIMESTAMP" => &[
+            (
+                "de",
+                "X-Timestamp muss ein Unix-Zeitstempel in Sekunden sein",
+            ),
+            (
+                "es",
+                "X-Timestamp debe ser una marca de tiempo Unix en segundos",
+            ),
+        ],
+        "INVALID_TOKEN" => &[
+            ("de", "Das Token ist ungültig"),
+            ("es", "El token no es válido"),
+        ],
+        "MISSING_API_KEY" => &[
+            ("de", "Ein API-Schlüssel ist erforderlich"),
+            ("es", "Se requiere una clave de API"),
+        ],
+        "NO_CHATS" => &[
+            ("de", "chat_ids/chats muss mindestens einen Chat enthalten"),
+            ("es", "chat_ids/chats debe indicar al menos un chat"),
+        ],
+        "NOT_FOUND" => &[
+            ("de", "Benachrichtigung nicht im Verlauf gefunden"),
+            ("es", "La notificación no está en el historial"),
+        ],
+        "PAYLOAD_TOO_LARGE" => &[
+            ("de", "Der Inhalt der Anfrage ist zu groß"),
+            ("es", "El cuerpo de la solicitud es demasiado grande"),
+        ],
+        "PIPELINE_ERROR" => &[
+            ("de", "Benachrichtigung konnte nicht verarbeitet werden"),
+            ("es", "No se pudo procesar la notificación"),
+        ],
+        "PRECONDITION_FAILED" => &[
+            (
+                "de",
+                "Die Ressource wurde inzwischen geändert (ETag stimmt nicht überein)",
+            ),
+            (
+                "es",
+                "El recurso ha cambiado mientras tanto (el ETag no coincide)",
+            ),
+        ],
+        "QUEUE_FULL" => &[
+            (
+                "de",
+                "Die Zustellwarteschlange ist voll, bitte später erneut versuchen",
+            ),
+            (
+                "es",
+                "La cola de entrega está llena, inténtalo de nuevo más tarde",
+            ),
+        ],
+        "QUOTA_EXCEEDED" => &[
+            (
+                "de",
+                "Das Anfragekontingent dieses API-Schlüssels ist aufgebraucht",
+            ),
+            ("es", "Esta clave de API ha agotado su cuota de solicitudes"),
+        ],
+        "REPLAYED_REQUEST" => &[
+            ("de", "Diese Anfrage wurde bereits verarbeitet"),
+            ("es", "Esta solicitud ya se procesó"),
+        ],
+        "SCHEDULE_EXISTS" => &[
+            ("de", "Ein Zeitplan mit dieser ID existiert bereits"),
+            ("es", "Ya existe una programación con este ID"),
+        ],
+        "SCHEDULE_READ_ONLY" => &[
+            (
+                "de",
+                "Dieser Zeitplan stammt aus der Konfigurationsdatei und kann nicht gelöscht werden",
+            ),
+            (
+                "es",
+                "Esta programación viene del archivo de configuración y no se puede eliminar",
+            ),
+        ],
+        "STORAGE_ERROR" => &[
+            ("de", "Die Änderung konnte nicht gespeichert werden"),
+            ("es", "No se pudo guardar el cambio"),
+        ],
+        "SYNC_FAILED" => &[
+            (
+                "de",
+                "Das Konfigurations-Repository konnte nicht abgeglichen werden",
+            ),
+            (
+                "es",
+                "No se pudo sincronizar el repositorio de configuración",
+            ),
+        ],
+        "TELEGRAM_UNAVAILABLE" => &[
+            (
+                "de",
+                "Die Telegram-API ist derzeit nicht erreichbar, bitte später erneut versuchen",
+            ),
+            (
+                "es",
+                "La API de Telegram no está disponible, inténtalo de nuevo más tarde",
+            ),
+        ],
+        "TIMESTAMP_OUT_OF_WINDOW" => &[
+            (
+                "de",
+                "X-Timestamp liegt außerhalb des erlaubten Zeitfensters",
+            ),
+            ("es", "X-Timestamp está fuera de la ventana permitida"),
+        ],
+        "UNKNOWN_CHAT" => &[
+            ("de", "Unbekannter Chat-Alias"),
+            ("es", "Alias de chat desconocido"),
+        ],
+        _ => return None,
+    };
+    Some(translations)
+}
+
+/// The message for error `code` in `locale`, if there is a translation.
+/// English callers keep the original, more detailed message.
+pub fn error_message(code: &str, locale: &str) -> Option<&'static str> {
+    let translations = error_translations(code)?;
+    [locale, language(locale)].iter().find_map(|candidate| {
+        translations
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(candidate))
+            .map(|(_, text)| *text)
+    })
+}
+
+/// Best supported locale for an `Accept-Language` header, honoring
+/// q-values and falling back to English
+pub fn negotiate(accept_language: &str) -> &'static str {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+            (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable, so equally weighted ranges keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges
+        .iter()
+        .find_map(|(tag, _)| {
+            ERROR_LOCALES
+                .iter()
+                .find(|locale| locale.eq_ignore_ascii_case(language(tag)))
+        })
+        .copied()
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Middleware translating JSON error responses into the language asked for
+/// by `Accept-Language`. The untranslated message moves to `detail`, since
+/// it often carries specifics such as the Telegram API's answer.
+pub async fn localize_errors(request: Request, next: Next) -> Response {
+    let locale = request
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(DEFAULT_LOCALE, negotiate);
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    let is_error = response.status().is_client_error() || response.status().is_server_error();
+    if locale == DEFAULT_LOCALE || !is_json || !is_error {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // Error bodies are small and built by this service
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    let Ok(mut error) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let Some(message) = error
+        .get("code")
+        .and_then(Value::as_str)
+        .and_then(|code| error_message(code, locale))
+    else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    if let Some(original) = error.get_mut("error").map(Value::take) {
+        error["detail"] = original;
+    }
+    error["error"] = Value::from(message);
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_LANGUAGE, HeaderValue::from_static(locale));
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-language"));
+    Response::from_parts(parts, Body::from(error.to_string()))
+}
+
+/// Per-chat locale configuration (`[i18n]` in the config file)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocaleSettings {
+    pub default_locale: String,
+    /// Chat ID → locale, e.g. `"-1001234567890" = "de"`
+    pub chat_locales: HashMap<String, String>,
+}
+
+impl Default for LocaleSettings {
+    fn default() -> Self {
+        Self {
+            default_locale: DEFAULT_LOCALE.to_string(),
+            chat_locales: HashMap::new(),
+        }
+    }
+}
+
+impl LocaleSettings {
+    pub fn locale_for(&self, chat_id: &str) -> &str {
+        self.chat_locales
+            .get(chat_id)
+            .unwrap_or(&self.default_locale)
+    }
+}
+
+/// Pick the translation for `locale`, trying the exact tag (`de-AT`) before
+/// the bare language (`de`)
+pub fn select<'a>(translations: &'a HashMap<String, String>, locale: &str) -> Option<&'a str> {
+    [locale, language(locale)].iter().find_map(|candidate| {
+        translations
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(candidate))
+            .map(|(_, text)| text.as_str())
+    })
+}
+
+fn language(locale: &str) -> &str {
+    locale.split(['-', '_']).next().unwrap_or(locale)
+}
+
+/// Replaces the message with the translation matching the target chat's locale.
+/// Notifications without a matching translation keep their original text.
+pub struct Localize {
+    settings: LocaleSettings,
+}
+
+impl Localize {
+    pub fn new(settings: LocaleSettings) -> Self {
+        Self { settings }
+    }
+}
+
+impl Stage for Localize {
+    fn name(&self) -> &str {
+        "localize"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Render
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        let locale = self.settings.locale_for(&notification.chat_id);
+        if let Some(text) = select(&notification.translations, locale) {
+            notification.message = text.to_string();
+        }
+        Ok(Outcome::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> LocaleSettings {
+        LocaleSettings {
+            default_locale: "en".to_string(),
+            chat_locales: HashMap::from([
+                ("-100".to_string(), "de".to_string()),
+                ("-200".to_string(), "es-MX".to_string()),
+            ]),
+        }
+    }
+
+    fn translations() -> HashMap<String, String> {
+        HashMap::from([
+            ("de".to_string(), "Festplatte voll".to_string()),
+            ("es".to_string(), "Disco lleno".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_locale_for_chat() {
+        let settings = settings();
+
+        assert_eq!(settings.locale_for("-100"), "de");
+        assert_eq!(settings.locale_for("123"), "en");
+    }
+
+    #[test]
+    fn test_select_exact_and_language_fallback() {
+        let translations = translations();
+
+        assert_eq!(select(&translations, "de"), Some("Festplatte voll"));
+        assert_eq!(select(&translations, "es-MX"), Some("Disco lleno"));
+        assert_eq!(select(&translations, "DE"), Some("Festplatte voll"));
+        assert_eq!(select(&translations, "fr"), None);
+    }
+
+    #[test]
+    fn test_builtin_text_localized() {
+        assert_eq!(
+            Text::TestMessage.localized("de-AT"),
+            "Hallo vom Telegram-Bot! 🤖"
+        );
+        assert_eq!(
+            Text::TestMessage.localized("fr"),
+            "Hello from Telegram Bot! 🤖"
+        );
+    }
+
+    #[test]
+    fn test_negotiate_accept_language() {
+        assert_eq!(negotiate("de-DE,de;q=0.9,en;q=0.8"), "de");
+        assert_eq!(negotiate("fr-FR, es;q=0.5, en;q=0.4"), "es");
+        assert_eq!(negotiate("en;q=0.5, es-MX"), "es");
+        assert_eq!(negotiate("de;q=0, fr"), "en");
+        assert_eq!(negotiate("*"), "en");
+        assert_eq!(negotiate(""), "en");
+    }
+
+    #[test]
+    fn test_error_message_catalog() {
+        assert_eq!(
+            error_message("INVALID_API_KEY", "de-AT"),
+            Some("Der API-Schlüssel ist ungültig")
+        );
+        assert_eq!(
+            error_message("QUEUE_FULL", "es"),
+            Some("La cola de entrega está llena, inténtalo de nuevo más tarde")
+        );
+        assert_eq!(error_message("INVALID_API_KEY", "en"), None);
+        assert_eq!(error_message("SCRIPT_REJECTED", "de"), None);
+    }
+
+    #[test]
+    fn test_localize_stage_uses_chat_locale() {
+        let stage = Localize::new(settings());
+        let mut notification = Notification {
+            message: "Disk full".to_string(),
+            chat_id: "-200".to_string(),
+            translations: translations(),
+            ..Default::default()
+        };
+
+        stage.process(&mut notification).unwrap();
+
+        assert_eq!(notification.message, "Disco lleno");
+    }
+
+    #[test]
+    fn test_localize_stage_keeps_original_without_translation() {
+        let stage = Localize::new(settings());
+        let mut notification = Notification {
+            message: "Disk full".to_string(),
+            chat_id: "123".to_string(),
+            translations: translations(),
+            ..Default::default()
+        };
+
+        stage.process(&mut notification).unwrap();
+
+        assert_eq!(notification.message, "Disk full");
+    }
+
+    #[test]
+    fn test_locale_settings_deserialization() {
+        let settings: LocaleSettings = toml::from_str(
+            r#"
+            default_locale = "de"
+
+            [chat_locales]
+            "-100" = "es"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.default_locale, "de");
+        assert_eq!(settings.locale_for("-100"), "es");
+        assert_eq!(settings.locale_for("1"), "de");
+    }
+}
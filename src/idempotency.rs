@@ -0,0 +1,177 @@
+use crate::api::SendNotificationResponse;
+use axum::http::StatusCode;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Header carrying a client-chosen idempotency key
+pub const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+
+/// Default time a key's result is remembered (24 hours)
+pub const DEFAULT_IDEMPOTENCY_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Longest accepted idempotency key
+pub const MAX_KEY_LENGTH: usize = 255;
+
+/// Remembers the result of requests sent with an idempotency key so a retry
+/// within the window gets the original result instead of a duplicate message
+pub struct IdempotencyStore {
+    window: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+enum Entry {
+    Pending {
+        since: Instant,
+    },
+    Completed {
+        at: Instant,
+        status: StatusCode,
+        response: SendNotificationResponse,
+    },
+}
+
+impl Entry {
+    fn started(&self) -> Instant {
+        match self {
+            Entry::Pending { since } => *since,
+            Entry::Completed { at, .. } => *at,
+        }
+    }
+}
+
+/// What to do with a request carrying an idempotency key
+#[derive(Debug, Clone, PartialEq)]
+pub enum Begin {
+    /// First time the key is seen; process the request
+    New,
+    /// A request with the same key is still being processed
+    InProgress,
+    /// The key was already used; return the stored result
+    Completed(StatusCode, SendNotificationResponse),
+}
+
+impl IdempotencyStore {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claim `key` for a new request, or report how it was already handled
+    pub fn begin(&self, key: &str, now: Instant) -> Begin {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| now.duration_since(entry.started()) < self.window);
+
+        match entries.get(key) {
+            Some(Entry::Pending { .. }) => Begin::InProgress,
+            Some(Entry::Completed {
+                status, response, ..
+            }) => Begin::Completed(*status, response.clone()),
+            None => {
+                entries.insert(key.to_string(), Entry::Pending { since: now });
+                Begin::New
+            }
+        }
+    }
+
+    /// Store the result of a request that claimed `key`
+    pub fn complete(
+        &self,
+        key: &str,
+        status: StatusCode,
+        response: SendNotificationResponse,
+        now: Instant,
+    ) {
+        self.entries.lock().unwrap().insert(
+            key.to_string(),
+            Entry::Completed {
+                at: now,
+                status,
+                response,
+            },
+        );
+    }
+
+    /// Release `key` after a failed request so the client can retry it
+    pub fn abandon(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> IdempotencyStore {
+        IdempotencyStore::new(Duration::from_secs(60))
+    }
+
+    fn response() -> SendNotificationResponse {
+        SendNotificationResponse {
+            success: true,
+            message: "Notification sent successfully".to_string(),
+            telegram_message_id: Some(42),
+            delivery_id: Some("abc123def456".to_string()),
+            results: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_new_key_is_claimed() {
+        let store = store();
+        let now = Instant::now();
+
+        assert_eq!(store.begin("key", now), Begin::New);
+        assert_eq!(store.begin("key", now), Begin::InProgress);
+    }
+
+    #[test]
+    fn test_completed_key_returns_original_result() {
+        let store = store();
+        let now = Instant::now();
+
+        store.begin("key", now);
+        store.complete("key", StatusCode::OK, response(), now);
+
+        assert_eq!(
+            store.begin("key", now + Duration::from_secs(30)),
+            Begin::Completed(StatusCode::OK, response())
+        );
+    }
+
+    #[test]
+    fn test_key_expires_after_window() {
+        let store = store();
+        let now = Instant::now();
+
+        store.begin("key", now);
+        store.complete("key", StatusCode::OK, response(), now);
+
+        assert_eq!(
+            store.begin("key", now + Duration::from_secs(61)),
+            Begin::New
+        );
+    }
+
+    #[test]
+    fn test_abandoned_key_can_be_retried() {
+        let store = store();
+        let now = Instant::now();
+
+        store.begin("key", now);
+        store.abandon("key");
+
+        assert_eq!(store.begin("key", now), Begin::New);
+    }
+
+    #[test]
+    fn test_keys_are_independent() {
+        let store = store();
+        let now = Instant::now();
+
+        assert_eq!(store.begin("a", now), Begin::New);
+        assert_eq!(store.begin("b", now), Begin::New);
+    }
+}
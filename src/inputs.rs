@@ -0,0 +1,83 @@
+use crate::api::SendNotificationRequest;
+use crate::extract::NotifyBody;
+use crate::handlers::{AppState, dispatch_now};
+use axum::http::StatusCode;
+use axum::response::Json;
+use std::sync::Arc;
+
+#[cfg(feature = "amqp")]
+pub mod amqp;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+/// How a notification taken from a message queue ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ingested {
+    /// Sent, or accepted to be sent later; the message can be acknowledged
+    Accepted { delivery_id: Option<String> },
+    /// Can never be sent, e.g. invalid JSON; the message should be dropped
+    Rejected(String),
+    /// Sending failed for now; the message should be delivered again
+    Failed(String),
+}
+
+/// Send a notification from a message queue through the same pipeline as
+/// `/notify`. The payload is a JSON request body as for `/notify`, or any
+/// other text as the message for the default chat.
+pub async fn ingest(state: &Arc<AppState>, payload: &[u8], source: &str) -> Ingested {
+    let request = match parse_payload(payload) {
+        Ok(request) => request,
+        Err(e) => return Ingested::Rejected(e),
+    };
+    let body = NotifyBody {
+        request,
+        attachments: Vec::new(),
+        source: source.to_string(),
+    };
+    match dispatch_now(state, body).await {
+        Ok((_, Json(response))) => Ingested::Accepted {
+            delivery_id: response.delivery_id,
+        },
+        Err((status, Json(error)))
+            if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS =>
+        {
+            Ingested::Rejected(error.error)
+        }
+        Err((_, Json(error))) => Ingested::Failed(error.error),
+    }
+}
+
+fn parse_payload(payload: &[u8]) -> Result<SendNotificationRequest, String> {
+    let text = std::str::from_utf8(payload).map_err(|_| "Payload is not UTF-8 text".to_string())?;
+    if text.trim_start().starts_with('{') {
+        return serde_json::from_str(text).map_err(|e| format!("Invalid notification: {e}"));
+    }
+    if text.trim().is_empty() {
+        return Err("Payload is empty".to_string());
+    }
+    Ok(SendNotificationRequest {
+        message: text.trim_end_matches(['\r', '\n']).to_string(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_payload() {
+        let request = parse_payload(br#"{"message": "Backup done", "chat": "ops-team"}"#).unwrap();
+        assert_eq!(request.message, "Backup done");
+        assert_eq!(request.chat.as_deref(), Some("ops-team"));
+        assert_eq!(
+            parse_payload(b"disk full on web-1\n").unwrap().message,
+            "disk full on web-1"
+        );
+        assert!(parse_payload(br#"{"chat": "ops-team"}"#).is_err());
+        assert!(parse_payload(b"\n").is_err());
+        assert!(parse_payload(&[0xff, 0xfe]).is_err());
+    }
+}
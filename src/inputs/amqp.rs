@@ -0,0 +1,108 @@
+use super::{Ingested, ingest};
+use crate::handlers::AppState;
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use lapin::options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicQosOptions};
+use lapin::types::FieldTable;
+use lapin::{Connection, ConnectionProperties, Consumer};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Source of notifications taken from AMQP
+pub const SOURCE: &str = "amqp";
+
+pub const DEFAULT_QUEUE: &str = "notifications";
+
+const CONSUMER_TAG: &str = "telegram-notifications";
+
+/// Unacknowledged messages held at a time; one, as they are sent one by
+/// one, so other instances get the rest
+const PREFETCH: u16 = 1;
+
+/// How long a failed message is held before it is returned to the queue
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// AMQP input (`--amqp-url`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmqpSettings {
+    pub url: String,
+    pub queue: String,
+}
+
+/// Connect and consume the queue in the background for as long as the
+/// server runs. Fails if the broker can't be reached or the queue doesn't
+/// exist.
+pub async fn start(settings: &AmqpSettings, state: Arc<AppState>) -> Result<()> {
+    let connection = Connection::connect(&settings.url, ConnectionProperties::default())
+        .await
+        .context("Failed to connect to the AMQP broker")?;
+    let channel = connection.create_channel().await?;
+    channel
+        .basic_qos(PREFETCH, BasicQosOptions::default())
+        .await?;
+    let consumer = channel
+        .basic_consume(
+            &settings.queue,
+            CONSUMER_TAG,
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+        .with_context(|| format!("Failed to consume queue {}", settings.queue))?;
+    tokio::spawn(consume(connection, consumer, state));
+    Ok(())
+}
+
+/// Send each message, acknowledging it only once it was sent, so a message
+/// whose sending failed or was cut short by the process dying is delivered
+/// again. Holds the connection so it stays open.
+async fn consume(_connection: Connection, mut consumer: Consumer, state: Arc<AppState>) {
+    while let Some(delivery) = consumer.next().await {
+        let delivery = match delivery {
+            Ok(delivery) => delivery,
+            Err(e) => {
+                warn!("⚠️ Failed to receive from AMQP: {}", e);
+                continue;
+            }
+        };
+        let acked = match ingest(&state, &delivery.data, SOURCE).await {
+            Ingested::Accepted { delivery_id } => {
+                info!(
+                    "📨 AMQP message {} accepted as {:?}",
+                    delivery.delivery_tag, delivery_id
+                );
+                delivery.ack(BasicAckOptions::default()).await
+            }
+            Ingested::Rejected(e) => {
+                warn!("⚠️ Dropped AMQP message {}: {}", delivery.delivery_tag, e);
+                // Dead-lettered if the queue has a dead-letter exchange
+                delivery
+                    .nack(BasicNackOptions {
+                        requeue: false,
+                        ..Default::default()
+                    })
+                    .await
+            }
+            Ingested::Failed(e) => {
+                warn!(
+                    "⚠️ AMQP message {} failed, requeueing in {}s: {}",
+                    delivery.delivery_tag,
+                    RETRY_DELAY.as_secs(),
+                    e
+                );
+                tokio::time::sleep(RETRY_DELAY).await;
+                delivery
+                    .nack(BasicNackOptions {
+                        requeue: true,
+                        ..Default::default()
+                    })
+                    .await
+            }
+        };
+        if let Err(e) = acked {
+            warn!("⚠️ Failed to acknowledge AMQP message: {}", e);
+        }
+    }
+    warn!("⚠️ AMQP consumer ended");
+}
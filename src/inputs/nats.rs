@@ -0,0 +1,132 @@
+use super::{Ingested, ingest};
+use crate::handlers::AppState;
+use anyhow::{Context, Result};
+use async_nats::jetstream::{self, AckKind, consumer::AckPolicy, consumer::pull};
+use async_nats::{Client, Subscriber};
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Source of notifications taken from NATS
+pub const SOURCE: &str = "nats";
+
+pub const DEFAULT_SUBJECT: &str = "notifications";
+
+pub const DEFAULT_DURABLE: &str = "telegram-notifications";
+
+/// Queue group of plain subscriptions, so instances share the messages
+/// instead of each sending them
+const QUEUE_GROUP: &str = "telegram-notifications";
+
+/// How long JetStream waits before delivering a failed message again
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// NATS input (`--nats-url`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct NatsSettings {
+    pub url: String,
+    pub subject: String,
+    /// JetStream stream to consume from; without one the subject is
+    /// subscribed to, and messages published while no instance runs are lost
+    pub stream: Option<String>,
+    /// Name of the durable consumer on the stream
+    pub durable: String,
+}
+
+/// Connect and take notifications from NATS in the background for as long
+/// as the server runs. Fails if the server or the stream can't be reached.
+pub async fn start(settings: &NatsSettings, state: Arc<AppState>) -> Result<()> {
+    let client = async_nats::connect(&settings.url)
+        .await
+        .with_context(|| format!("Failed to connect to NATS at {}", settings.url))?;
+    let Some(stream) = &settings.stream else {
+        let subscriber = client
+            .queue_subscribe(settings.subject.clone(), QUEUE_GROUP.to_string())
+            .await
+            .with_context(|| format!("Failed to subscribe to {}", settings.subject))?;
+        tokio::spawn(subscribe(client, subscriber, state));
+        return Ok(());
+    };
+
+    let consumer = jetstream::new(client)
+        .get_stream(stream)
+        .await
+        .with_context(|| format!("Failed to find JetStream stream {stream}"))?
+        .get_or_create_consumer(
+            &settings.durable,
+            pull::Config {
+                durable_name: Some(settings.durable.clone()),
+                filter_subject: settings.subject.clone(),
+                ack_policy: AckPolicy::Explicit,
+                ..Default::default()
+            },
+        )
+        .await
+        .with_context(|| format!("Failed to create consumer {} on {stream}", settings.durable))?;
+    let messages = consumer
+        .messages()
+        .await
+        .with_context(|| format!("Failed to consume from {stream}"))?;
+    tokio::spawn(consume(messages, state));
+    Ok(())
+}
+
+/// Send what is published on the subject; failures are logged and lost.
+/// Holds the client so the connection stays open.
+async fn subscribe(_client: Client, mut subscriber: Subscriber, state: Arc<AppState>) {
+    while let Some(message) = subscriber.next().await {
+        match ingest(&state, &message.payload, SOURCE).await {
+            Ingested::Accepted { delivery_id } => {
+                info!(
+                    "📨 NATS message on {} accepted as {:?}",
+                    message.subject, delivery_id
+                )
+            }
+            Ingested::Rejected(e) | Ingested::Failed(e) => {
+                warn!("⚠️ Dropped NATS message on {}: {}", message.subject, e)
+            }
+        }
+    }
+    warn!("⚠️ NATS subscription ended");
+}
+
+/// Send what the durable consumer gets, acknowledging each message once it
+/// was sent and having failed ones delivered again
+async fn consume(mut messages: pull::Stream, state: Arc<AppState>) {
+    while let Some(message) = messages.next().await {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("⚠️ Failed to receive from JetStream: {}", e);
+                continue;
+            }
+        };
+        let ack = match ingest(&state, &message.payload, SOURCE).await {
+            Ingested::Accepted { delivery_id } => {
+                info!(
+                    "📨 NATS message on {} accepted as {:?}",
+                    message.subject, delivery_id
+                );
+                AckKind::Ack
+            }
+            Ingested::Rejected(e) => {
+                warn!("⚠️ Dropped NATS message on {}: {}", message.subject, e);
+                AckKind::Term
+            }
+            Ingested::Failed(e) => {
+                warn!(
+                    "⚠️ NATS message on {} failed, redelivering in {}s: {}",
+                    message.subject,
+                    RETRY_DELAY.as_secs(),
+                    e
+                );
+                AckKind::Nak(Some(RETRY_DELAY))
+            }
+        };
+        if let Err(e) = message.ack_with(ack).await {
+            warn!("⚠️ Failed to acknowledge NATS message: {}", e);
+        }
+    }
+    warn!("⚠️ JetStream consumer ended");
+}
@@ -0,0 +1,145 @@
+use super::{Ingested, ingest};
+use crate::handlers::AppState;
+use ::redis::aio::{MultiplexedConnection, PubSub};
+use ::redis::{AsyncCommands, Client};
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Source of notifications taken from Redis
+pub const SOURCE: &str = "redis";
+
+pub const DEFAULT_LIST: &str = "notifications";
+
+/// How long a failed message is held before it is pushed back on the list
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// How long to wait before connecting again after losing the connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Redis input (`--redis-url`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedisSettings {
+    pub url: String,
+    /// List popped with BLPOP
+    pub list: String,
+    /// Channel to subscribe to instead of popping the list; messages
+    /// published while no instance runs are lost
+    pub channel: Option<String>,
+}
+
+/// Connect and take notifications from Redis in the background for as long
+/// as the server runs. Fails if the server can't be reached.
+pub async fn start(settings: &RedisSettings, state: Arc<AppState>) -> Result<()> {
+    let client = Client::open(settings.url.as_str()).context("Invalid Redis URL")?;
+    match settings.channel.clone() {
+        Some(channel) => {
+            let pubsub = subscribe_to(&client, &channel).await?;
+            tokio::spawn(subscribe(client, pubsub, channel, state));
+        }
+        None => {
+            let connection = client
+                .get_multiplexed_async_connection()
+                .await
+                .context("Failed to connect to Redis")?;
+            tokio::spawn(pop(client, connection, settings.list.clone(), state));
+        }
+    }
+    Ok(())
+}
+
+async fn subscribe_to(client: &Client, channel: &str) -> Result<PubSub> {
+    let mut pubsub = client
+        .get_async_pubsub()
+        .await
+        .context("Failed to connect to Redis")?;
+    pubsub
+        .subscribe(channel)
+        .await
+        .with_context(|| format!("Failed to subscribe to {channel}"))?;
+    Ok(pubsub)
+}
+
+/// Send what is published on the channel, subscribing again if the
+/// connection drops; failures are logged and lost
+async fn subscribe(client: Client, mut pubsub: PubSub, channel: String, state: Arc<AppState>) {
+    loop {
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            match ingest(&state, message.get_payload_bytes(), SOURCE).await {
+                Ingested::Accepted { delivery_id } => {
+                    info!(
+                        "📨 Redis message on {} accepted as {:?}",
+                        channel, delivery_id
+                    )
+                }
+                Ingested::Rejected(e) | Ingested::Failed(e) => {
+                    warn!("⚠️ Dropped Redis message on {}: {}", channel, e)
+                }
+            }
+        }
+        drop(messages);
+        warn!(
+            "⚠️ Redis subscription to {} ended, subscribing again",
+            channel
+        );
+        pubsub = loop {
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            match subscribe_to(&client, &channel).await {
+                Ok(pubsub) => break pubsub,
+                Err(e) => warn!("⚠️ {:#}", e),
+            }
+        };
+    }
+}
+
+/// Pop and send the list's messages one by one. A message that failed to
+/// send is pushed back on the head of the list after a while, one that can
+/// never be sent is logged and dropped.
+async fn pop(
+    client: Client,
+    mut connection: MultiplexedConnection,
+    list: String,
+    state: Arc<AppState>,
+) {
+    loop {
+        let popped: Option<(String, Vec<u8>)> = match connection.blpop(&list, 0.0).await {
+            Ok(popped) => popped,
+            Err(e) => {
+                warn!("⚠️ Failed to pop from Redis list {}: {}", list, e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                match client.get_multiplexed_async_connection().await {
+                    Ok(reconnected) => connection = reconnected,
+                    Err(e) => warn!("⚠️ Failed to connect to Redis: {}", e),
+                }
+                continue;
+            }
+        };
+        let Some((_, payload)) = popped else {
+            continue;
+        };
+        match ingest(&state, &payload, SOURCE).await {
+            Ingested::Accepted { delivery_id } => {
+                info!(
+                    "📨 Redis message from {} accepted as {:?}",
+                    list, delivery_id
+                )
+            }
+            Ingested::Rejected(e) => warn!("⚠️ Dropped Redis message from {}: {}", list, e),
+            Ingested::Failed(e) => {
+                warn!(
+                    "⚠️ Redis message from {} failed, pushing it back in {}s: {}",
+                    list,
+                    RETRY_DELAY.as_secs(),
+                    e
+                );
+                tokio::time::sleep(RETRY_DELAY).await;
+                if let Err(e) = connection.lpush::<_, _, ()>(&list, payload).await {
+                    warn!("⚠️ Lost Redis message, failed to push it back: {}", e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use anyhow::{Context, Result, bail};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Minimum time between two JWKS downloads, so tokens with unknown key IDs
+/// can't make the server hammer the identity provider
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// JWT validation settings (`[auth.jwt]` in the config file)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtSettings {
+    /// "HS256" or "RS256"
+    pub algorithm: String,
+    /// Shared secret for HS256 (or TELEGRAM_NOTIFICATIONS_JWT_SECRET)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// PEM file with the RS256 public key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<PathBuf>,
+    /// URL of the identity provider's JWKS, instead of `public_key`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_url: Option<String>,
+    /// Required `iss` claim
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// Required `aud` claim
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: "HS256".to_string(),
+            secret: None,
+            public_key: None,
+            jwks_url: None,
+            issuer: None,
+            audience: None,
+        }
+    }
+}
+
+enum Keys {
+    Static(DecodingKey),
+    Jwks {
+        url: String,
+        client: reqwest::Client,
+        cache: tokio::sync::Mutex<Option<(Instant, JwkSet)>>,
+    },
+}
+
+/// Validates bearer tokens issued by an identity provider
+pub struct JwtValidator {
+    validation: Validation,
+    keys: Keys,
+}
+
+impl JwtValidator {
+    pub fn new(settings: &JwtSettings) -> Result<Self> {
+        let algorithm = match settings.algorithm.to_ascii_uppercase().as_str() {
+            "HS256" => Algorithm::HS256,
+            "RS256" => Algorithm::RS256,
+            other => bail!("Unsupported JWT algorithm {other:?}; use HS256 or RS256"),
+        };
+
+        let keys = match (algorithm, settings) {
+            (
+                Algorithm::HS256,
+                JwtSettings {
+                    secret: Some(secret),
+                    ..
+                },
+            ) => Keys::Static(DecodingKey::from_secret(secret.as_bytes())),
+            (Algorithm::HS256, _) => bail!("HS256 tokens need a JWT secret"),
+            (
+                _,
+                JwtSettings {
+                    public_key: Some(path),
+                    ..
+                },
+            ) => {
+                let pem = std::fs::read(path)
+                    .with_context(|| format!("Failed to read JWT public key {}", path.display()))?;
+                Keys::Static(
+                    DecodingKey::from_rsa_pem(&pem)
+                        .with_context(|| format!("Invalid RSA public key {}", path.display()))?,
+                )
+            }
+            (
+                _,
+                JwtSettings {
+                    jwks_url: Some(url),
+                    ..
+                },
+            ) => Keys::Jwks {
+                url: url.clone(),
+                client: reqwest::Client::new(),
+                cache: tokio::sync::Mutex::new(None),
+            },
+            _ => bail!("RS256 tokens need a public_key or a jwks_url"),
+        };
+
+        let mut validation = Validation::new(algorithm);
+        if let Some(issuer) = &settings.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &settings.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        Ok(Self { validation, keys })
+    }
+
+    /// Check a token's signature and claims, returning the claims if valid
+    pub async fn validate(&self, token: &str) -> Result<Value> {
+        let key = match &self.keys {
+            Keys::Static(key) => key.clone(),
+            Keys::Jwks { .. } => {
+                let header = decode_header(token).context("Malformed token")?;
+                let kid = header.kid.context("Token has no key ID (kid)")?;
+                self.jwks_key(&kid).await?
+            }
+        };
+
+        let data = decode::<Value>(token, &key, &self.validation).context("Invalid token")?;
+        Ok(data.claims)
+    }
+
+    /// Key with ID `kid` from the JWKS, downloading it again when the key is
+    /// unknown (keys rotate) and the last download isn't too recent
+    async fn jwks_key(&self, kid: &str) -> Result<DecodingKey> {
+        let Keys::Jwks { url, client, cache } = &self.keys else {
+            unreachable!("only called for JWKS validators");
+        };
+
+        let mut cache = cache.lock().await;
+        let stale = match &*cache {
+            Some((fetched, set)) => {
+                set.find(kid).is_none() && fetched.elapsed() >= JWKS_REFRESH_INTERVAL
+            }
+            None => true,
+        };
+        if stale {
+            match fetch_jwks(client, url).await {
+                Ok(set) => {
+                    info!("🔑 Loaded {} signing key(s) from {}", set.keys.len(), url);
+                    *cache = Some((Instant::now(), set));
+                }
+                Err(e) => {
+                    warn!("⚠️  Failed to download JWKS from {}: {:#}", url, e);
+                    // Keep the keys we have and wait before trying again
+                    let set = cache
+                        .take()
+                        .map_or_else(|| JwkSet { keys: Vec::new() }, |(_, set)| set);
+                    *cache = Some((Instant::now(), set));
+                }
+            }
+        }
+
+        let jwk = cache
+            .as_ref()
+            .and_then(|(_, set)| set.find(kid))
+            .with_context(|| format!("Unknown signing key {kid}"))?;
+        DecodingKey::from_jwk(jwk).context("Unusable signing key")
+    }
+}
+
+async fn fetch_jwks(client: &reqwest::Client, url: &str) -> Result<JwkSet> {
+    Ok(client
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header, encode, get_current_timestamp};
+    use serde_json::json;
+
+    const SECRET: &str = "shared-secret";
+
+    fn hs256(issuer: Option<&str>, audience: Option<&str>) -> JwtValidator {
+        JwtValidator::new(&JwtSettings {
+            algorithm: "HS256".to_string(),
+            secret: Some(SECRET.to_string()),
+            issuer: issuer.map(str::to_string),
+            audience: audience.map(str::to_string),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn token(claims: Value, secret: &str) -> String {
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn exp() -> u64 {
+        get_current_timestamp() + 300
+    }
+
+    #[tokio::test]
+    async fn test_valid_hs256_token() {
+        let validator = hs256(Some("https://id.example.com"), Some("notifications"));
+        let token = token(
+            json!({
+                "sub": "ci",
+                "iss": "https://id.example.com",
+                "aud": "notifications",
+                "exp": exp(),
+            }),
+            SECRET,
+        );
+
+        let claims = validator.validate(&token).await.unwrap();
+
+        assert_eq!(claims["sub"], "ci");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_signature_and_expired_tokens() {
+        let validator = hs256(None, None);
+
+        let forged = token(json!({"sub": "ci", "exp": exp()}), "other-secret");
+        assert!(validator.validate(&forged).await.is_err());
+
+        let expired = token(json!({"sub": "ci", "exp": 1_000_000}), SECRET);
+        assert!(validator.validate(&expired).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_issuer() {
+        let validator = hs256(Some("https://id.example.com"), None);
+        let token = token(
+            json!({"sub": "ci", "iss": "https://evil.example.com", "exp": exp()}),
+            SECRET,
+        );
+
+        assert!(validator.validate(&token).await.is_err());
+    }
+
+    #[test]
+    fn test_settings_errors() {
+        let missing_secret = JwtSettings {
+            algorithm: "HS256".to_string(),
+            ..Default::default()
+        };
+        let missing_key = JwtSettings {
+            algorithm: "RS256".to_string(),
+            ..Default::default()
+        };
+        let unsupported = JwtSettings {
+            algorithm: "none".to_string(),
+            ..Default::default()
+        };
+
+        assert!(JwtValidator::new(&missing_secret).is_err());
+        assert!(JwtValidator::new(&missing_key).is_err());
+        assert!(JwtValidator::new(&unsupported).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jwks_unknown_key() {
+        let mut server = mockito::Server::new_async().await;
+        let jwks = server
+            .mock("GET", "/jwks.json")
+            .with_body(json!({"keys": []}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let validator = JwtValidator::new(&JwtSettings {
+            algorithm: "RS256".to_string(),
+            jwks_url: Some(format!("{}/jwks.json", server.url())),
+            ..Default::default()
+        })
+        .unwrap();
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("rotated".to_string());
+        let token = encode(
+            &header,
+            &json!({"exp": exp()}),
+            &EncodingKey::from_secret(b"x"),
+        )
+        .unwrap();
+
+        let error = validator.validate(&token).await.unwrap_err();
+        // The second lookup uses the cached set instead of downloading again
+        assert!(validator.validate(&token).await.is_err());
+
+        assert!(error.to_string().contains("Unknown signing key rotated"));
+        jwks.assert_async().await;
+    }
+}
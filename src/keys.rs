@@ -0,0 +1,285 @@
+use crate::auth::constant_time_eq;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+/// What a key may do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Send notifications and read their history
+    #[default]
+    Send,
+    /// Everything, including managing keys at `/admin/keys`
+    Admin,
+}
+
+/// Request quotas of a managed key, as `[auth.keys.<name>]` sets them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Quota {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Counted per UTC day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_day: Option<u32>,
+}
+
+/// A key created at runtime through `POST /admin/keys`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ManagedKey {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub scope: Scope,
+    #[serde(flatten)]
+    pub quota: Quota,
+    pub key: String,
+    /// Unix timestamp
+    pub created_at: u64,
+}
+
+/// Keys managed through the admin API, saved to `[auth] keys_file` when set
+/// so they survive restarts. Cloning shares the same keys.
+#[derive(Clone, Default)]
+pub struct KeyStore {
+    path: Option<Arc<PathBuf>>,
+    keys: Arc<RwLock<Vec<ManagedKey>>>,
+}
+
+impl KeyStore {
+    /// Store backed by `path`, loading the keys saved there; without a path
+    /// keys only live until the server stops
+    pub fn open(path: Option<&Path>) -> Result<Self> {
+        let keys = match path {
+            Some(path) if path.exists() => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read keys file {}", path.display()))?;
+                serde_json::from_str(&contents)
+                    .with_context(|| format!("Invalid keys file {}", path.display()))?
+            }
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            path: path.map(|path| Arc::new(path.to_path_buf())),
+            keys: Arc::new(RwLock::new(keys)),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn list(&self) -> Vec<ManagedKey> {
+        self.keys.read().unwrap().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<ManagedKey> {
+        self.keys
+            .read()
+            .unwrap()
+            .iter()
+            .find(|key| key.id == id)
+            .cloned()
+    }
+
+    /// Generate and save a new key
+    pub fn create(
+        &self,
+        label: Option<String>,
+        scope: Scope,
+        quota: Quota,
+        now: u64,
+    ) -> Result<ManagedKey> {
+        let id = Uuid::new_v4().simple().to_string()[..12].to_string();
+        let (key, _) = self.put(&id, label, scope, quota, None, now)?;
+        Ok(key)
+    }
+
+    /// Create the key with ID `id`, or update its label, scope and quota if
+    /// it exists, returning it and whether it was created. The key itself is
+    /// generated unless given, and only replaced on update when given.
+    pub fn put(
+        &self,
+        id: &str,
+        label: Option<String>,
+        scope: Scope,
+        quota: Quota,
+        key: Option<String>,
+        now: u64,
+    ) -> Result<(ManagedKey, bool)> {
+        let mut keys = self.keys.write().unwrap();
+        let mut updated = keys.clone();
+        let (managed, created) = match updated.iter_mut().find(|managed| managed.id == id) {
+            Some(existing) => {
+                existing.label = label;
+                existing.scope = scope;
+                existing.quota = quota;
+                if let Some(key) = key {
+                    existing.key = key;
+                }
+                (existing.clone(), false)
+            }
+            None => {
+                let managed = ManagedKey {
+                    id: id.to_string(),
+                    label,
+                    scope,
+                    quota,
+                    key: key.unwrap_or_else(|| format!("tn_{}", Uuid::new_v4().simple())),
+                    created_at: now,
+                };
+                updated.push(managed.clone());
+                (managed, true)
+            }
+        };
+        self.save(&updated)?;
+        *keys = updated;
+        Ok((managed, created))
+    }
+
+    /// Remove the key with ID `id`, returning it if there was one
+    pub fn revoke(&self, id: &str) -> Result<Option<ManagedKey>> {
+        let mut keys = self.keys.write().unwrap();
+        let Some(index) = keys.iter().position(|key| key.id == id) else {
+            return Ok(None);
+        };
+        let mut updated = keys.clone();
+        let revoked = updated.remove(index);
+        self.save(&updated)?;
+        *keys = updated;
+        Ok(Some(revoked))
+    }
+
+    /// The managed key `key` is, if it is one
+    pub fn find(&self, key: &str) -> Option<ManagedKey> {
+        // Compare every key in full so response times don't leak a prefix match
+        self.keys
+            .read()
+            .unwrap()
+            .iter()
+            .fold(None, |found, managed| {
+                if constant_time_eq(&managed.key, key) {
+                    Some(managed.clone())
+                } else {
+                    found
+                }
+            })
+    }
+
+    /// Scope of a managed key, if `key` is one
+    pub fn scope_of(&self, key: &str) -> Option<Scope> {
+        self.find(key).map(|managed| managed.scope)
+    }
+
+    /// Write the keys to a temporary file next to the keys file and move it
+    /// into place, so a crash can't leave a half-written file
+    fn save(&self, keys: &[ManagedKey]) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(keys)?;
+        let temp = path.with_extension("tmp");
+        write_private(&temp, json.as_bytes())
+            .with_context(|| format!("Failed to write keys file {}", temp.display()))?;
+        std::fs::rename(&temp, path.as_path())
+            .with_context(|| format!("Failed to replace keys file {}", path.display()))
+    }
+}
+
+/// Write a file only its owner can read, since it holds secrets
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_revoke() {
+        let store = KeyStore::open(None).unwrap();
+
+        let key = store
+            .create(
+                Some("grafana".to_string()),
+                Scope::Send,
+                Quota::default(),
+                1_760_601_600,
+            )
+            .unwrap();
+
+        assert!(key.key.starts_with("tn_"));
+        assert_eq!(store.scope_of(&key.key), Some(Scope::Send));
+        assert_eq!(store.scope_of("tn_unknown"), None);
+        assert_eq!(store.revoke(&key.id).unwrap(), Some(key.clone()));
+        assert_eq!(store.revoke(&key.id).unwrap(), None);
+        assert_eq!(store.scope_of(&key.key), None);
+    }
+
+    #[test]
+    fn test_put_creates_then_updates() {
+        let store = KeyStore::open(None).unwrap();
+
+        let (created, is_new) = store
+            .put("grafana", None, Scope::Send, Quota::default(), None, 1)
+            .unwrap();
+        let (updated, is_new_again) = store
+            .put(
+                "grafana",
+                Some("Dashboards".to_string()),
+                Scope::Admin,
+                Quota {
+                    requests_per_minute: Some(10),
+                    requests_per_day: None,
+                },
+                None,
+                2,
+            )
+            .unwrap();
+
+        assert!(is_new);
+        assert!(!is_new_again);
+        // The generated key and creation time stay, the rest is replaced
+        assert_eq!(updated.key, created.key);
+        assert_eq!(updated.created_at, 1);
+        assert_eq!(store.get("grafana"), Some(updated));
+        assert_eq!(store.scope_of(&created.key), Some(Scope::Admin));
+        assert_eq!(
+            store.find(&created.key).unwrap().quota.requests_per_minute,
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn test_keys_survive_reopening() {
+        let dir = std::env::temp_dir().join(format!("tn-keys-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keys.json");
+
+        let store = KeyStore::open(Some(&path)).unwrap();
+        let admin = store
+            .create(None, Scope::Admin, Quota::default(), 1)
+            .unwrap();
+        let revoked = store
+            .create(None, Scope::Send, Quota::default(), 2)
+            .unwrap();
+        store.revoke(&revoked.id).unwrap();
+        let reopened = KeyStore::open(Some(&path)).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(reopened.list(), vec![admin.clone()]);
+        assert_eq!(reopened.scope_of(&admin.key), Some(Scope::Admin));
+    }
+}
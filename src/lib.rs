@@ -1,4 +1,105 @@
+//! Telegram notifications from the command line, an HTTP API or your own
+//! Rust code.
+//!
+//! The `telegram-notifications` binary is a thin wrapper around this crate:
+//! [`server::run`] serves the API, [`server::app`] builds its routes for
+//! nesting into another axum application, and [`cli::run`] sends the way
+//! the command line does. The parts work on their own too, e.g. a
+//! [`pipeline::Pipeline`] with stages of your choice in front of a
+//! [`telegram::TelegramBot`]:
+//!
+//! ```no_run
+//! use telegram_notifications::delivery::deliver;
+//! use telegram_notifications::pipeline::{Notification, Pipeline, Processed};
+//! use telegram_notifications::telegram::TelegramBot;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let bot = TelegramBot::new(std::env::var("TELEGRAM_BOT_TOKEN")?);
+//! let pipeline = Pipeline::with_defaults();
+//! let notification = Notification {
+//!     message: "Deploy finished".to_string(),
+//!     chat_id: "-1001234567890".to_string(),
+//!     ..Default::default()
+//! };
+//! if let Processed::Ready(notification) = pipeline.run(notification)? {
+//!     deliver(&bot, &notification).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod actions;
+pub mod alertmanager;
 pub mod api;
+pub mod auth;
+pub mod backends;
+pub mod bots;
+pub mod breaker;
+pub mod callbacks;
+pub mod checkins;
+pub mod cli;
 pub mod config;
+pub mod cors;
+#[cfg(feature = "keyring")]
+pub mod credentials;
+pub mod dedupe;
+pub mod defaults;
+pub mod delivery;
+pub mod destinations;
+pub mod digest;
+pub mod dlq;
+pub mod doctor;
+pub mod effective;
+pub mod export;
+pub mod extract;
+pub mod fallback;
+pub mod filters;
+pub mod gitsync;
+pub mod grafana;
 pub mod handlers;
+pub mod history;
+pub mod i18n;
+pub mod idempotency;
+pub mod inputs;
+pub mod jwt;
+pub mod keys;
+pub mod limits;
+pub mod listen;
+pub mod logging;
+pub mod migrate;
+pub mod notifier;
+#[cfg(feature = "operator")]
+pub mod operator;
+pub mod outbox;
+pub mod pagerduty;
+pub mod pages;
+pub mod pipeline;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+pub mod priority;
+pub mod profiles;
+pub mod provision;
+pub mod proxy;
+pub mod quiet;
+pub mod quota;
+pub mod ratelimit;
+pub mod readiness;
+pub mod routes;
+pub mod rules;
+pub mod runner;
+pub mod samples;
+pub mod scheduler;
+pub mod schedules;
+pub mod scripting;
+pub mod server;
+pub mod smtp;
+pub mod stats;
+pub mod storage;
+pub mod tail;
+pub mod targets;
 pub mod telegram;
+pub mod throttle;
+pub mod tls;
+pub mod uptime;
+pub mod wait;
+pub mod webhooks;
@@ -0,0 +1,151 @@
+use crate::api::ErrorResponse;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, StatusCode, header::CONTENT_LENGTH},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use tracing::warn;
+
+/// Default maximum request body size for the notification endpoints (1 MiB)
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Default maximum body size of the webhooks parsed as they stream in (32 MiB)
+pub const DEFAULT_MAX_WEBHOOK_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// Routes whose bodies are parsed as they stream in instead of being
+/// buffered, so they take `--max-webhook-body-bytes`
+const STREAMED_ROUTES: [&str; 2] = ["/integrations/alertmanager", "/integrations/grafana"];
+
+/// Request body size limits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BodyLimits {
+    /// `--max-body-bytes`, for bodies read into memory
+    pub buffered: usize,
+    /// `--max-webhook-body-bytes`, for the [`STREAMED_ROUTES`]
+    pub streamed: usize,
+}
+
+impl BodyLimits {
+    /// The limit of the route matched by `path`, also when the API is nested
+    /// under a prefix
+    fn for_route(&self, path: &str) -> BodyLimit {
+        if STREAMED_ROUTES.iter().any(|route| path.ends_with(route)) {
+            BodyLimit {
+                max_bytes: self.streamed,
+                streamed: true,
+            }
+        } else {
+            BodyLimit {
+                max_bytes: self.buffered,
+                streamed: false,
+            }
+        }
+    }
+}
+
+/// Body limit of the matched route, for extractors and middleware reading
+/// the body themselves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BodyLimit {
+    pub max_bytes: usize,
+    /// Whether the route parses its body as it streams in, so it must not
+    /// be buffered on the way
+    pub streamed: bool,
+}
+
+/// Middleware rejecting requests whose declared `Content-Length` exceeds the
+/// route's limit before any of the body is read. Bodies without a declared
+/// length are cut off by `DefaultBodyLimit`, or the streaming extractor,
+/// while they stream in.
+pub async fn reject_oversized(
+    State(limits): State<BodyLimits>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let limit = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(limits.for_route(""), |path| limits.for_route(path.as_str()));
+    let max_bytes = limit.max_bytes;
+    if let Some(length) = oversized(request.headers(), max_bytes) {
+        warn!(
+            "🚫 Rejected request body of {} bytes (limit {})",
+            length, max_bytes
+        );
+        return too_large(max_bytes);
+    }
+
+    request.extensions_mut().insert(limit);
+    next.run(request).await
+}
+
+/// The response to a body over `max_bytes`
+pub fn too_large(max_bytes: usize) -> Response {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(ErrorResponse::with_code(
+            format!("Request body exceeds the {max_bytes} byte limit"),
+            "PAYLOAD_TOO_LARGE".to_string(),
+        )),
+    )
+        .into_response()
+}
+
+/// The declared body length, if it is larger than `max_bytes`
+fn oversized(headers: &HeaderMap, max_bytes: usize) -> Option<u64> {
+    let length: u64 = headers
+        .get(CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    (length > max_bytes as u64).then_some(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(content_length: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static(content_length));
+        headers
+    }
+
+    #[test]
+    fn test_accepts_body_within_limit() {
+        assert_eq!(oversized(&headers("1024"), 1024), None);
+        assert_eq!(oversized(&headers("0"), 1024), None);
+    }
+
+    #[test]
+    fn test_rejects_declared_oversized_body() {
+        assert_eq!(oversized(&headers("1025"), 1024), Some(1025));
+        assert_eq!(oversized(&headers("5000000"), 1024), Some(5_000_000));
+    }
+
+    #[test]
+    fn test_streamed_routes_take_webhook_limit() {
+        let limits = BodyLimits {
+            buffered: 1024,
+            streamed: 4096,
+        };
+        let streamed = BodyLimit {
+            max_bytes: 4096,
+            streamed: true,
+        };
+        assert_eq!(limits.for_route("/integrations/alertmanager"), streamed);
+        assert_eq!(limits.for_route("/api/integrations/grafana"), streamed);
+        assert_eq!(limits.for_route("/integrations/uptime").max_bytes, 1024);
+        assert!(!limits.for_route("/notify").streamed);
+    }
+
+    #[test]
+    fn test_ignores_missing_or_invalid_length() {
+        assert_eq!(oversized(&HeaderMap::new(), 1024), None);
+        assert_eq!(oversized(&headers("lots"), 1024), None);
+    }
+}
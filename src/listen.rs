@@ -0,0 +1,128 @@
+use anyhow::{Context, Result, bail};
+use std::env;
+use tokio::net::{TcpListener, TcpSocket};
+use tracing::info;
+
+/// First file descriptor systemd passes to a socket-activated service
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Connections the kernel holds until they are accepted
+const BACKLOG: u32 = 1024;
+
+/// The socket the server accepts connections on: the one systemd passed in
+/// with socket activation, otherwise `addr` bound by the server itself.
+/// `reuse_port` sets SO_REUSEPORT, so a new instance can bind the port while
+/// the old one is still finishing its requests.
+pub async fn listener(addr: &str, reuse_port: bool) -> Result<TcpListener> {
+    let fd = activation_fd(
+        env::var("LISTEN_PID").ok().as_deref(),
+        env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )?;
+    match fd {
+        Some(fd) => {
+            info!("🔌 Using the socket passed by systemd");
+            inherit(fd)
+        }
+        None => bind(addr, reuse_port)
+            .await
+            .with_context(|| format!("Failed to listen on {addr}")),
+    }
+}
+
+/// Descriptor of the socket passed by systemd, if this process was started
+/// through socket activation
+fn activation_fd(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    pid: u32,
+) -> Result<Option<i32>> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(None);
+    };
+    // Variables inherited from a socket-activated parent aren't meant for us
+    if listen_pid.trim().parse::<u32>().ok() != Some(pid) {
+        return Ok(None);
+    }
+    match listen_fds.trim().parse::<u32>() {
+        Ok(0) => Ok(None),
+        Ok(1) => Ok(Some(SD_LISTEN_FDS_START)),
+        Ok(count) => bail!("systemd passed {count} sockets; configure a single ListenStream"),
+        Err(_) => bail!("Invalid LISTEN_FDS {listen_fds:?}"),
+    }
+}
+
+#[cfg(unix)]
+fn inherit(fd: i32) -> Result<TcpListener> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: systemd hands the descriptor to this process, and it is only
+    // taken over once, at startup
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+    listener
+        .local_addr()
+        .context("systemd didn't pass a TCP socket")?;
+    listener.set_nonblocking(true)?;
+    Ok(TcpListener::from_std(listener)?)
+}
+
+#[cfg(not(unix))]
+fn inherit(_fd: i32) -> Result<TcpListener> {
+    bail!("Socket activation is only supported on Unix")
+}
+
+async fn bind(addr: &str, reuse_port: bool) -> Result<TcpListener> {
+    if !reuse_port {
+        return Ok(TcpListener::bind(addr).await?);
+    }
+    let address = tokio::net::lookup_host(addr)
+        .await?
+        .next()
+        .with_context(|| format!("{addr} didn't resolve to an address"))?;
+    let socket = if address.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(true)?;
+    set_reuseport(&socket)?;
+    socket.bind(address)?;
+    Ok(socket.listen(BACKLOG)?)
+}
+
+#[cfg(unix)]
+fn set_reuseport(socket: &TcpSocket) -> Result<()> {
+    Ok(socket.set_reuseport(true)?)
+}
+
+#[cfg(not(unix))]
+fn set_reuseport(_socket: &TcpSocket) -> Result<()> {
+    bail!("--reuse-port is only supported on Unix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activation_fd() {
+        assert_eq!(activation_fd(None, None, 42).unwrap(), None);
+        assert_eq!(activation_fd(Some("42"), Some("1"), 42).unwrap(), Some(3));
+        // Meant for another process
+        assert_eq!(activation_fd(Some("7"), Some("1"), 42).unwrap(), None);
+        assert_eq!(activation_fd(Some("42"), Some("0"), 42).unwrap(), None);
+        assert!(activation_fd(Some("42"), Some("2"), 42).is_err());
+        assert!(activation_fd(Some("42"), Some("many"), 42).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reuse_port() {
+        let first = listener("127.0.0.1:0", true).await.unwrap();
+        let addr = first.local_addr().unwrap().to_string();
+
+        // A second instance can take over the port while the first still runs
+        let second = listener(&addr, true).await.unwrap();
+        assert_eq!(second.local_addr().unwrap(), first.local_addr().unwrap());
+        assert!(listener(&addr, false).await.is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::proxy::ClientIp;
+use axum::{extract::Request, http::Response};
+use clap::ValueEnum;
+use std::time::Duration;
+use tower_http::request_id::RequestId;
+use tracing::Span;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Filter used when `RUST_LOG` isn't set
+const DEFAULT_FILTER: &str = "telegram_notifications=info,tower_http=info";
+
+/// How log events are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per event, for log pipelines
+    Json,
+}
+
+/// Install the global subscriber, filtered by `RUST_LOG`
+pub fn init(format: LogFormat) {
+    install(format, std::io::stdout);
+}
+
+/// Like [`init`], but logging to stderr, leaving stdout to a command's output
+pub fn init_stderr(format: LogFormat) {
+    install(format, std::io::stderr);
+}
+
+fn install<W>(format: LogFormat, writer: W)
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let filter =
+        EnvFilter::new(std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_FILTER.into()));
+    let registry = tracing_subscriber::registry().with(filter);
+    match format {
+        LogFormat::Text => registry
+            .with(tracing_subscriber::fmt::layer().with_writer(writer))
+            .init(),
+        // Event fields at the top level next to "message"; the fields of the
+        // request being handled (request_id, method, uri, client_ip) under "span"
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(writer)
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(true)
+                    .with_span_list(false),
+            )
+            .init(),
+    }
+}
+
+/// Span every event logged while handling a request belongs to
+pub fn request_span(request: &Request) -> Span {
+    let client_ip = request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| ip.to_string())
+        .unwrap_or_default();
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        uri = %request.uri(),
+        client_ip = %client_ip,
+    )
+}
+
+/// Log the status and latency of every response
+pub fn log_response<B>(response: &Response<B>, latency: Duration, _span: &Span) {
+    tracing::info!(
+        status = response.status().as_u16(),
+        latency_ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX),
+        "Request finished"
+    );
+}
@@ -1,57 +1,87 @@
-mod api;
-mod config;
-mod handlers;
-mod telegram;
-
-use anyhow::Result;
-use axum::{
-    Router,
-    routing::{get, post},
-};
-use config::Config;
+use anyhow::{Context, Result};
 use dotenv::dotenv;
-use handlers::AppState;
-use std::sync::Arc;
-use telegram::TelegramBot;
-use tower::ServiceBuilder;
-use tower_http::cors::CorsLayer;
-use tower_http::trace::TraceLayer;
+use std::time::Duration;
+use telegram_notifications::cli::{self, Outgoing, Output};
+#[cfg(feature = "keyring")]
+use telegram_notifications::config::AuthCommand;
+use telegram_notifications::config::{self, Command, Config, ConfigCommand, HistoryCommand};
+#[cfg(feature = "keyring")]
+use telegram_notifications::credentials;
+use telegram_notifications::delivery;
+use telegram_notifications::doctor;
+use telegram_notifications::effective;
+use telegram_notifications::export;
+use telegram_notifications::logging;
+use telegram_notifications::migrate;
+use telegram_notifications::readiness;
+use telegram_notifications::runner;
+use telegram_notifications::samples;
+use telegram_notifications::server;
+use telegram_notifications::tail;
+#[cfg(feature = "keyring")]
+use telegram_notifications::telegram::TelegramBot;
+use telegram_notifications::wait;
 use tracing::{info, warn};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load .env file if present (for development)
     dotenv().ok();
 
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG")
-                .unwrap_or_else(|_| "telegram_notifications=info,tower_http=info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
     // Parse configuration from command line arguments and environment variables
-    let config = Config::from_args_and_env()?;
+    let mut args = Config::parse_with_env()?;
+    let json_output =
+        matches!(&args.command, Some(Command::Send(send)) if send.output == Output::Json);
+    if json_output {
+        logging::init_stderr(args.log_format);
+    } else {
+        logging::init(args.log_format);
+    }
 
-    // Create the Telegram bot instance
-    let bot = TelegramBot::new(config.bot_token.clone());
+    let send = match args.command.take() {
+        Some(Command::Send(send)) => Some(send),
+        Some(command) => return run_command(command, args).await,
+        None => None,
+    };
+    let print_config = args.print_config;
+    let config = args.resolve()?;
+    if print_config {
+        let effective = effective::effective_config(&config);
+        println!("{}", serde_json::to_string_pretty(&effective)?);
+        return Ok(());
+    }
+    let server = config.server && send.is_none();
+    if let Some(send) = send.as_ref().filter(|send| send.dry_run) {
+        let outgoing = Outgoing::new(&config, send)?;
+        let previews = config
+            .recipients
+            .iter()
+            .map(|chat_id| outgoing.preview(chat_id, &config.defaults.for_chat(chat_id)))
+            .collect::<Result<Vec<_>>>()?;
+        print!("{}", previews.join("\n"));
+        return Ok(());
+    }
+    if server {
+        config.check_server_chat()?;
+    }
+    if server && !config.wait_for.is_empty() {
+        wait::wait_for(&config.wait_for, config.wait_for_timeout).await?;
+    }
 
-    // Verify the bot token is valid (skip in test mode)
-    let skip_validation = std::env::var("TELEGRAM_NOTIFICATIONS_SKIP_VALIDATION")
-        .unwrap_or_default()
-        .to_lowercase()
-        == "true";
+    // Create the Telegram bot instance
+    let bot = server::main_bot(&config);
 
-    if !skip_validation {
+    // Verify the bot token is valid (skip in test mode, and for JSON output,
+    // where a rejected token is reported as the send's error)
+    let mut bot_username = None;
+    if !delivery::test_mode() && !json_output {
         info!("🔍 Verifying bot configuration...");
         match bot.get_me().await {
             Ok(response) => {
                 if let Some(result) = response.result {
                     if let Some(username) = result["username"].as_str() {
                         info!("✅ Bot verified: @{}", username);
+                        bot_username = Some(username.to_string());
                     } else {
                         info!("✅ Bot verified successfully");
                     }
@@ -69,68 +99,148 @@ async fn main() -> Result<()> {
         warn!("⚠️  Bot validation skipped (test mode)");
     }
 
-    if config.server {
+    if server {
         // Run as HTTP server
-        run_server(config, bot).await
+        server::run(config, bot, bot_username).await
     } else {
         // Run in CLI mode (send single message)
-        run_cli_mode(&config, &bot).await
+        let bots = server::bots(&config, bot);
+        cli::run(&config, &bots, &send.unwrap_or_default()).await
     }
 }
 
-async fn run_server(config: config::ConfigResolved, bot: TelegramBot) -> Result<()> {
-    let state = Arc::new(AppState {
-        bot,
-        default_chat_id: config.chat_id.clone(),
-    });
-
-    let app = Router::new()
-        .route("/", get(handlers::root))
-        .route("/health", get(handlers::health))
-        .route("/notify", post(handlers::notify))
-        .route("/send", post(handlers::send))
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
-        )
-        .with_state(state);
-
-    let addr = format!("{}:{}", config.host, config.port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-
-    info!("🚀 Telegram Notifications API server starting...");
-    info!("🌐 Listening on http://{}", addr);
-    info!("📝 Default chat ID: {}", config.chat_id);
-    info!("📄 Available endpoints:");
-    info!("    GET  /       - API information");
-    info!("    GET  /health - Health check and bot status");
-    info!("    POST /notify - Send notification");
-    info!("    POST /send   - Send notification (alias)");
-
-    axum::serve(listener, app).await?;
-    Ok(())
-}
-
-async fn run_cli_mode(config: &config::ConfigResolved, bot: &TelegramBot) -> Result<()> {
-    // Send the test message
-    info!("📤 Sending message to chat ID: {}", config.chat_id);
-    info!("📝 Message: {}", config.message);
-
-    match bot.send_message(&config.chat_id, &config.message).await {
-        Ok(_) => {
-            info!("✅ Message sent successfully! 🎉");
-            info!("💡 Check your Telegram chat to see the message.");
+/// Run a maintenance subcommand
+async fn run_command(command: Command, args: Config) -> Result<()> {
+    match command {
+        Command::Config {
+            action: ConfigCommand::Migrate { path, write },
+        } => {
+            let path = path
+                .or_else(|| args.config_path())
+                .context("No config file given. Pass a path or use --config")?;
+            migrate::migrate_file(&path, write)
+        }
+        Command::Send(_) => unreachable!("sending is handled by main"),
+        Command::Run(run) => {
+            let config = args.resolve()?;
+            let bots = server::bots(&config, server::main_bot(&config));
+            let name = run.name.unwrap_or_else(|| run.command.join(" "));
+            let code = runner::run(
+                &bots,
+                &config.recipients,
+                &name,
+                &run.command,
+                run.tail_lines,
+                run.failures_only,
+            )
+            .await?;
+            std::process::exit(code)
+        }
+        Command::Tail(tail_args) => {
+            let config = args.resolve()?;
+            let bots = server::bots(&config, server::main_bot(&config));
+            tail::run(
+                &bots,
+                &config.recipients,
+                &tail_args.file,
+                tail_args.pattern.as_deref(),
+                Duration::from_secs(tail_args.batch_secs),
+                tail_args.from_start,
+            )
+            .await
         }
-        Err(e) => {
-            tracing::error!("❌ Failed to send message: {}", e);
-            warn!("💡 Common issues:");
-            warn!("   - Make sure the chat ID is correct");
-            warn!("   - If using a group chat, add the bot to the group first");
-            warn!("   - If using a private chat, start a conversation with the bot first");
-            return Err(e);
+        Command::Doctor => {
+            let config = args.resolve()?;
+            let bots = server::bots(&config, server::main_bot(&config));
+            doctor::run_all(&bots, &config.recipients).await
+        }
+        #[cfg(feature = "keyring")]
+        Command::Auth { action } => {
+            let profile = args.profile_name();
+            match action {
+                AuthCommand::Login => {
+                    let token = match args.bot_token {
+                        Some(token) => token,
+                        None => credentials::read_token()?,
+                    };
+                    let response = TelegramBot::new(token.clone())
+                        .get_me()
+                        .await
+                        .context("The bot token was not accepted")?;
+                    credentials::store(profile.as_deref(), &token)?;
+                    let username = response
+                        .result
+                        .as_ref()
+                        .and_then(|result| result["username"].as_str())
+                        .unwrap_or("bot");
+                    info!(
+                        "✅ Logged in as @{}, token stored in the OS keyring",
+                        username
+                    );
+                }
+                AuthCommand::Logout => {
+                    if credentials::delete(profile.as_deref())? {
+                        info!("🗑️ Removed the stored bot token");
+                    } else {
+                        info!("No bot token was stored");
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Healthcheck {
+            url,
+            timeout_secs,
+            insecure,
+        } => readiness::probe(&url, Duration::from_secs(timeout_secs), insecure).await,
+        Command::History {
+            action:
+                HistoryCommand::Export {
+                    url,
+                    format,
+                    from,
+                    to,
+                    api_key,
+                    output,
+                },
+        } => {
+            let api_key = match api_key {
+                Some(key) => Some(key),
+                None => config::secret_env("TELEGRAM_NOTIFICATIONS_API_KEY")?,
+            };
+            let mut out: Box<dyn std::io::Write> = match output {
+                Some(path) => Box::new(
+                    std::fs::File::create(&path)
+                        .with_context(|| format!("Failed to create {}", path.display()))?,
+                ),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            export::download(
+                &url,
+                format,
+                from.as_deref(),
+                to.as_deref(),
+                api_key.as_deref(),
+                &mut out,
+            )
+            .await
+        }
+        Command::Completions { shell } => {
+            config::write_completions(shell, &mut std::io::stdout());
+            Ok(())
+        }
+        Command::Man => config::write_man_page(&mut std::io::stdout()),
+        Command::GenPayload {
+            adapter,
+            state,
+            curl,
+        } => {
+            let payload = samples::sample_payload(adapter, state);
+            match curl {
+                Some(url) => println!("{}", samples::curl_command(adapter, &payload, &url)),
+                None => println!("{}", serde_json::to_string_pretty(&payload)?),
+            }
+            Ok(())
         }
     }
-
-    Ok(())
 }
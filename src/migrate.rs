@@ -0,0 +1,264 @@
+use crate::config::FileConfig;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use toml::{Table, Value};
+
+/// Changes needed to bring a config file up to `version`
+struct Migration {
+    version: u32,
+    /// Keys moved to a new name, as dotted paths `(old, new)`
+    renames: &'static [(&'static str, &'static str)],
+    /// Keys that are no longer read, with a hint on what replaces them
+    removed: &'static [(&'static str, &'static str)],
+}
+
+/// Every schema change, oldest first. Unversioned files are version 0.
+const MIGRATIONS: &[Migration] = &[Migration {
+    // First versioned schema; same keys as the unversioned one
+    version: 1,
+    renames: &[],
+    removed: &[],
+}];
+
+/// Result of migrating a config file
+#[derive(Debug)]
+pub struct Migrated {
+    pub from_version: u32,
+    pub to_version: u32,
+    /// `(old, new)` key paths that were renamed
+    pub renamed: Vec<(String, String)>,
+    /// `(key, hint)` for options that were dropped
+    pub removed: Vec<(String, String)>,
+    /// The upgraded file with every default filled in
+    pub output: String,
+}
+
+impl Migrated {
+    pub fn is_current(&self) -> bool {
+        self.from_version == self.to_version
+    }
+}
+
+/// Upgrade config file contents to the current schema
+pub fn migrate(source: &str) -> Result<Migrated> {
+    apply(source, MIGRATIONS)
+}
+
+/// Migrate the config file at `path`, printing the result or, with `write`,
+/// replacing the file (the original is kept as `<path>.bak`)
+pub fn migrate_file(path: &Path, write: bool) -> Result<()> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let migrated =
+        migrate(&source).with_context(|| format!("Failed to migrate {}", path.display()))?;
+
+    for (old, new) in &migrated.renamed {
+        eprintln!("🔁 Renamed `{old}` to `{new}`");
+    }
+    for (key, hint) in &migrated.removed {
+        eprintln!("⚠️  Removed deprecated option `{key}`: {hint}");
+    }
+
+    if !write {
+        print!("{}", migrated.output);
+        return Ok(());
+    }
+
+    if migrated.is_current() && migrated.renamed.is_empty() && migrated.removed.is_empty() {
+        eprintln!(
+            "✅ {} is already at schema version {}",
+            path.display(),
+            migrated.to_version
+        );
+        return Ok(());
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = Path::new(&backup);
+    std::fs::copy(path, backup)
+        .with_context(|| format!("Failed to back up config to {}", backup.display()))?;
+    std::fs::write(path, &migrated.output)
+        .with_context(|| format!("Failed to write config file {}", path.display()))?;
+    eprintln!(
+        "✅ Migrated {} from version {} to {} (backup: {})",
+        path.display(),
+        migrated.from_version,
+        migrated.to_version,
+        backup.display()
+    );
+    Ok(())
+}
+
+fn apply(source: &str, migrations: &[Migration]) -> Result<Migrated> {
+    let mut table: Table = source.parse().context("Config file is not valid TOML")?;
+
+    let from_version = match table.get("version") {
+        None => 0,
+        Some(value) => value
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .context("`version` must be a non-negative integer")?,
+    };
+    let to_version = migrations.last().map_or(0, |m| m.version);
+    if from_version > to_version {
+        bail!(
+            "Config file is for schema version {from_version}, this release supports up to {to_version}"
+        );
+    }
+
+    let mut renamed = Vec::new();
+    let mut removed = Vec::new();
+    for migration in migrations.iter().filter(|m| m.version > from_version) {
+        for (old, new) in migration.renames {
+            if let Some(value) = take(&mut table, old) {
+                insert(&mut table, new, value);
+                renamed.push((old.to_string(), new.to_string()));
+            }
+        }
+        for (key, hint) in migration.removed {
+            if take(&mut table, key).is_some() {
+                removed.push((key.to_string(), hint.to_string()));
+            }
+        }
+    }
+    table.insert("version".to_string(), Value::Integer(to_version.into()));
+
+    // Round-trip through the typed config to validate it and fill in defaults
+    let file: FileConfig = table
+        .try_into()
+        .context("Config file is invalid after migration")?;
+    let output = toml::to_string(&file)?;
+
+    Ok(Migrated {
+        from_version,
+        to_version,
+        renamed,
+        removed,
+        output,
+    })
+}
+
+/// Remove the value at a dotted path, dropping tables left empty
+fn take(table: &mut Table, path: &str) -> Option<Value> {
+    match path.split_once('.') {
+        None => table.remove(path),
+        Some((head, rest)) => {
+            let child = table.get_mut(head)?.as_table_mut()?;
+            let value = take(child, rest);
+            if child.is_empty() {
+                table.remove(head);
+            }
+            value
+        }
+    }
+}
+
+/// Insert a value at a dotted path, creating intermediate tables
+fn insert(table: &mut Table, path: &str, value: Value) {
+    match path.split_once('.') {
+        None => {
+            table.insert(path.to_string(), value);
+        }
+        Some((head, rest)) => {
+            let child = table
+                .entry(head)
+                .or_insert_with(|| Value::Table(Table::new()));
+            if let Some(child) = child.as_table_mut() {
+                insert(child, rest, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CONFIG_VERSION;
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            renames: &[],
+            removed: &[],
+        },
+        Migration {
+            version: 2,
+            renames: &[("locale", "i18n.default_locale")],
+            removed: &[("legacy.polling", "use --server instead")],
+        },
+    ];
+
+    #[test]
+    fn test_unversioned_file_gets_current_version() {
+        let migrated = migrate("[i18n]\ndefault_locale = \"de\"\n").unwrap();
+
+        assert_eq!(migrated.from_version, 0);
+        assert_eq!(migrated.to_version, CONFIG_VERSION);
+        assert!(migrated.output.contains("version = 1"));
+        assert!(migrated.output.contains("default_locale = \"de\""));
+    }
+
+    #[test]
+    fn test_fills_defaults() {
+        let migrated = migrate("").unwrap();
+
+        let file = FileConfig::parse(&migrated.output).unwrap();
+        assert_eq!(file.version, CONFIG_VERSION);
+        assert_eq!(file.i18n.default_locale, "en");
+    }
+
+    #[test]
+    fn test_current_file_is_unchanged() {
+        let migrated = migrate("version = 1\n").unwrap();
+        assert!(migrated.is_current());
+    }
+
+    #[test]
+    fn test_renames_and_removes_keys() {
+        let source = r#"
+            version = 1
+            locale = "es"
+
+            [legacy]
+            polling = true
+        "#;
+        let migrated = apply(source, TEST_MIGRATIONS).unwrap();
+
+        assert_eq!(migrated.from_version, 1);
+        assert_eq!(migrated.to_version, 2);
+        assert_eq!(
+            migrated.renamed,
+            vec![("locale".to_string(), "i18n.default_locale".to_string())]
+        );
+        assert_eq!(
+            migrated.removed,
+            vec![(
+                "legacy.polling".to_string(),
+                "use --server instead".to_string()
+            )]
+        );
+        assert!(migrated.output.contains("default_locale = \"es\""));
+        assert!(!migrated.output.contains("legacy"));
+    }
+
+    #[test]
+    fn test_skips_migrations_already_applied() {
+        // A file already at version 2 keeps a key that an older migration would rename
+        let result = apply("version = 2\nlocale = \"es\"\n", TEST_MIGRATIONS);
+
+        // `locale` is unknown in the current schema, so validation fails
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_rejects_newer_version() {
+        let result = migrate("version = 99\n");
+        assert!(result.unwrap_err().to_string().contains("version 99"));
+    }
+
+    #[test]
+    fn test_rejects_unknown_keys() {
+        assert!(migrate("unknown_option = true\n").is_err());
+    }
+}
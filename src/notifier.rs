@@ -0,0 +1,88 @@
+use crate::bots::Bots;
+use crate::delivery::deliver;
+use crate::pipeline::Notification;
+use crate::telegram::TelegramBot;
+use anyhow::Result;
+use futures_util::FutureExt;
+use futures_util::future::BoxFuture;
+
+/// Where prepared notifications are delivered. The server only talks to
+/// Telegram through this, so tests can hand it a mock and other backends can
+/// take Telegram's place.
+pub trait Notifier: Send + Sync {
+    /// Deliver `notification`, returning the ID of the first message sent if
+    /// the backend has message IDs
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<Option<i64>>>;
+
+    /// Fail if the backend can't be reached or refuses the credentials
+    fn health_check(&self) -> BoxFuture<'_, Result<()>>;
+}
+
+impl Notifier for TelegramBot {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<Option<i64>>> {
+        deliver(self, notification).boxed()
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, Result<()>> {
+        self.get_me().map(|response| response.map(|_| ())).boxed()
+    }
+}
+
+/// Sends each notification with its bot; healthy when the main bot is
+impl Notifier for Bots {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<Option<i64>>> {
+        self.for_notification(notification).send(notification)
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, Result<()>> {
+        self.main().health_check()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use anyhow::bail;
+    use std::sync::Mutex;
+
+    /// Records what it is asked to send instead of sending it, failing every
+    /// send while `failing` is set
+    #[derive(Default)]
+    pub struct MockNotifier {
+        pub sent: Mutex<Vec<Notification>>,
+        pub failing: Mutex<Option<String>>,
+    }
+
+    impl MockNotifier {
+        pub fn messages(&self) -> Vec<String> {
+            let sent = self.sent.lock().unwrap();
+            sent.iter().map(|n| n.message.clone()).collect()
+        }
+
+        fn outcome(&self) -> Result<()> {
+            match self.failing.lock().unwrap().clone() {
+                Some(error) => bail!(error),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Notifier for MockNotifier {
+        fn send<'a>(
+            &'a self,
+            notification: &'a Notification,
+        ) -> BoxFuture<'a, Result<Option<i64>>> {
+            let outcome = self.outcome().map(|()| {
+                let mut sent = self.sent.lock().unwrap();
+                sent.push(notification.clone());
+                Some(sent.len() as i64)
+            });
+            async move { outcome }.boxed()
+        }
+
+        fn health_check(&self) -> BoxFuture<'_, Result<()>> {
+            let outcome = self.outcome();
+            async move { outcome }.boxed()
+        }
+    }
+}
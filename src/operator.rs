@@ -0,0 +1,305 @@
+use crate::provision;
+use crate::routes::{DEFAULT_ROUTE_PRIORITY, Route, RouteTable};
+use crate::targets::Targets;
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use kube::api::{Api, ApiResource, DynamicObject, GroupVersionKind};
+use kube::runtime::{WatchStreamExt, reflector, watcher};
+use kube::{Client, ResourceExt};
+use serde::Deserialize;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// API group of the custom resources, see packaging/kubernetes/crds.yaml
+pub const GROUP: &str = "telegram-notifications.grimvoodoo.github.io";
+pub const VERSION: &str = "v1alpha1";
+pub const TARGET_KIND: &str = "TelegramNotificationTarget";
+pub const ROUTE_KIND: &str = "TelegramRoute";
+
+/// Operator mode (`--operator`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OperatorSettings {
+    /// Namespace to watch; every namespace if not set
+    pub namespace: Option<String>,
+}
+
+/// Spec of a `TelegramNotificationTarget`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TargetSpec {
+    /// Target name; the resource's name if not set
+    #[serde(default)]
+    name: Option<String>,
+    chat_id: String,
+}
+
+/// Spec of a `TelegramRoute`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RouteSpec {
+    chat: String,
+    #[serde(default)]
+    message_contains: Option<String>,
+    target: String,
+    #[serde(default)]
+    priority: Option<i32>,
+}
+
+/// Watch the custom resources in the cluster and keep `targets` and
+/// `routes` in line with them for as long as the server runs. Fails if
+/// there is no cluster to connect to.
+pub async fn start(
+    settings: &OperatorSettings,
+    targets: Targets,
+    routes: RouteTable,
+    aliases: HashMap<String, String>,
+) -> Result<()> {
+    let client = Client::try_default()
+        .await
+        .context("Failed to connect to the Kubernetes API")?;
+    let api = |kind: &str| {
+        let resource = api_resource(kind);
+        let api = match &settings.namespace {
+            Some(namespace) => Api::namespaced_with(client.clone(), namespace, &resource),
+            None => Api::all_with(client.clone(), &resource),
+        };
+        (api, resource)
+    };
+
+    let mut target_sync = TargetSync::new(targets, aliases);
+    let (target_api, target_resource) = api(TARGET_KIND);
+    tokio::spawn(watch(target_api, target_resource, move |objects| {
+        target_sync.apply(desired_targets(objects));
+    }));
+
+    let (route_api, route_resource) = api(ROUTE_KIND);
+    tokio::spawn(watch(route_api, route_resource, move |objects| {
+        let desired = desired_routes(objects);
+        info!("☸️  {} route(s) applied from the cluster", desired.len());
+        routes.replace(desired);
+    }));
+    Ok(())
+}
+
+fn api_resource(kind: &str) -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(GROUP, VERSION, kind))
+}
+
+/// Mirror the resources of one kind into a store and pass all of them to
+/// `apply` whenever they change. Errors are retried with a backoff.
+async fn watch(
+    api: Api<DynamicObject>,
+    resource: ApiResource,
+    mut apply: impl FnMut(&[Arc<DynamicObject>]) + Send + 'static,
+) {
+    let kind = resource.kind.clone();
+    let writer = reflector::store::Writer::new(resource);
+    let store = writer.as_reader();
+    let mut events = reflector(writer, watcher(api, watcher::Config::default()))
+        .default_backoff()
+        .boxed();
+    while let Some(event) = events.next().await {
+        match event {
+            // The store only holds a complete list once the initial listing is done
+            Ok(watcher::Event::Apply(_) | watcher::Event::Delete(_) | watcher::Event::InitDone) => {
+                apply(&store.state());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("⚠️  Watching {} resources failed: {}", kind, e),
+        }
+    }
+}
+
+/// Targets declared by `TelegramNotificationTarget` resources, by name. If
+/// several resources declare the same name, the first by namespace and
+/// resource name wins.
+fn desired_targets(objects: &[Arc<DynamicObject>]) -> BTreeMap<String, String> {
+    let mut desired = BTreeMap::new();
+    for object in sorted(objects) {
+        let Some(spec) = spec::<TargetSpec>(object) else {
+            continue;
+        };
+        let name = spec.name.unwrap_or_else(|| object.name_any());
+        if provision::validate_name(&name).is_err() {
+            warn!(
+                "⚠️  Ignoring {} {}: invalid target name {:?}",
+                TARGET_KIND,
+                key(object),
+                name
+            );
+            continue;
+        }
+        match desired.entry(name) {
+            Entry::Vacant(entry) => {
+                entry.insert(spec.chat_id);
+            }
+            Entry::Occupied(entry) => warn!(
+                "⚠️  Ignoring {} {}: target {} is already declared",
+                TARGET_KIND,
+                key(object),
+                entry.key()
+            ),
+        }
+    }
+    desired
+}
+
+/// Routes declared by `TelegramRoute` resources, named `<namespace>/<name>`
+fn desired_routes(objects: &[Arc<DynamicObject>]) -> Vec<Route> {
+    sorted(objects)
+        .into_iter()
+        .filter_map(|object| {
+            let spec = spec::<RouteSpec>(object)?;
+            Some(Route {
+                name: key(object),
+                chat: spec.chat,
+                message_contains: spec.message_contains,
+                target: spec.target,
+                priority: spec.priority.unwrap_or(DEFAULT_ROUTE_PRIORITY),
+            })
+        })
+        .collect()
+}
+
+fn sorted(objects: &[Arc<DynamicObject>]) -> Vec<&DynamicObject> {
+    let mut sorted: Vec<&DynamicObject> = objects.iter().map(Arc::as_ref).collect();
+    sorted.sort_by_key(|object| key(object));
+    sorted
+}
+
+/// `<namespace>/<name>` of a resource, for logs
+fn key(object: &DynamicObject) -> String {
+    format!(
+        "{}/{}",
+        object.namespace().unwrap_or_default(),
+        object.name_any()
+    )
+}
+
+/// The resource's spec, or None (with a warning) if it is invalid
+fn spec<T: for<'de> Deserialize<'de>>(object: &DynamicObject) -> Option<T> {
+    let spec = object.data.get("spec").cloned().unwrap_or_default();
+    match serde_json::from_value(spec) {
+        Ok(spec) => Some(spec),
+        Err(e) => {
+            warn!("⚠️  Ignoring {}: invalid spec: {}", key(object), e);
+            None
+        }
+    }
+}
+
+/// Applies declared targets on top of the config file's aliases, so
+/// deleting a resource brings back the alias it may have replaced
+struct TargetSync {
+    targets: Targets,
+    aliases: HashMap<String, String>,
+    applied: BTreeSet<String>,
+}
+
+impl TargetSync {
+    fn new(targets: Targets, aliases: HashMap<String, String>) -> Self {
+        Self {
+            targets,
+            aliases,
+            applied: BTreeSet::new(),
+        }
+    }
+
+    fn apply(&mut self, desired: BTreeMap<String, String>) {
+        for name in &self.applied {
+            if desired.contains_key(name) {
+                continue;
+            }
+            match self.aliases.get(name) {
+                Some(chat_id) => {
+                    self.targets.put(name, chat_id);
+                }
+                None => {
+                    self.targets.remove(name);
+                }
+            }
+        }
+        for (name, chat_id) in &desired {
+            self.targets.put(name, chat_id);
+        }
+        info!("☸️  {} target(s) applied from the cluster", desired.len());
+        self.applied = desired.into_keys().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn object(kind: &str, namespace: &str, name: &str, spec: Value) -> Arc<DynamicObject> {
+        Arc::new(
+            DynamicObject::new(name, &api_resource(kind))
+                .within(namespace)
+                .data(json!({ "spec": spec })),
+        )
+    }
+
+    #[test]
+    fn test_desired_targets() {
+        let objects = [
+            object(TARGET_KIND, "team-b", "ops", json!({"chatId": "-100456"})),
+            object(TARGET_KIND, "team-a", "ops", json!({"chatId": "-100123"})),
+            object(
+                TARGET_KIND,
+                "team-a",
+                "db",
+                json!({"name": "db-team", "chatId": "-100789"}),
+            ),
+            object(TARGET_KIND, "team-a", "broken", json!({"chat": "-100"})),
+        ];
+
+        assert_eq!(
+            desired_targets(&objects),
+            BTreeMap::from([
+                ("db-team".to_string(), "-100789".to_string()),
+                ("ops".to_string(), "-100123".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_desired_routes() {
+        let objects = [object(
+            ROUTE_KIND,
+            "payments",
+            "db-alerts",
+            json!({"chat": "*", "messageContains": "postgres", "target": "db-team"}),
+        )];
+
+        assert_eq!(
+            desired_routes(&objects),
+            vec![Route {
+                name: "payments/db-alerts".to_string(),
+                chat: "*".to_string(),
+                message_contains: Some("postgres".to_string()),
+                target: "db-team".to_string(),
+                priority: DEFAULT_ROUTE_PRIORITY,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_deleted_targets_restore_aliases() {
+        let aliases = HashMap::from([("ops".to_string(), "-100123".to_string())]);
+        let targets = Targets::new(&aliases);
+        let mut sync = TargetSync::new(targets.clone(), aliases);
+
+        sync.apply(BTreeMap::from([
+            ("ops".to_string(), "-100456".to_string()),
+            ("db".to_string(), "-100789".to_string()),
+        ]));
+        assert_eq!(targets.resolve("ops"), Some("-100456".to_string()));
+
+        sync.apply(BTreeMap::new());
+        assert_eq!(targets.resolve("ops"), Some("-100123".to_string()));
+        assert_eq!(targets.resolve("db"), None);
+    }
+}
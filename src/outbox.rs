@@ -0,0 +1,368 @@
+use crate::dlq::DeadLetter;
+use crate::pipeline::Notification;
+use crate::storage;
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A notification waiting in the outbox
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pending {
+    pub notification: Notification,
+    /// Delivery attempts made so far
+    pub attempts: u32,
+    /// Unix timestamp of the next attempt
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+}
+
+/// SQLite database (`--queue-db`) holding every queued notification until it
+/// has been delivered, so a restart doesn't lose them, and the dead letters
+/// of those that failed for good. Cloning shares the same connection.
+#[derive(Clone)]
+pub struct Outbox {
+    connection: Arc<Mutex<Connection>>,
+    /// None for an in-memory database
+    path: Option<Arc<PathBuf>>,
+}
+
+impl Outbox {
+    /// Open the database at `path`, creating it if needed
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("Failed to open queue database {}", path.display()))?;
+        Self::init(connection, Some(path))
+            .with_context(|| format!("Failed to set up queue database {}", path.display()))
+    }
+
+    /// A database only living as long as this outbox, for tests
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?, None)
+    }
+
+    fn init(connection: Connection, path: Option<&Path>) -> Result<Self> {
+        // WAL keeps writes cheap, and NORMAL sync is still crash safe with it
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.busy_timeout(storage::BUSY_TIMEOUT)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                delivery_id TEXT PRIMARY KEY,
+                notification TEXT NOT NULL,
+                emergency INTEGER NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                last_error TEXT,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS dead_letters (
+                delivery_id TEXT PRIMARY KEY,
+                notification TEXT NOT NULL,
+                attempts INTEGER NOT NULL,
+                error TEXT NOT NULL,
+                failed_at INTEGER NOT NULL
+            )",
+        )?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            path: path.map(|path| Arc::new(path.to_path_buf())),
+        })
+    }
+
+    /// Store a notification that is about to be queued
+    pub fn push(&self, notification: &Notification, now: u64) -> Result<()> {
+        let json = serde_json::to_string(notification)?;
+        self.connection.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO outbox
+                (delivery_id, notification, emergency, next_attempt_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![notification.delivery_id, json, notification.emergency, now],
+        )?;
+        Ok(())
+    }
+
+    /// Record a failed attempt and when to try again
+    pub fn reschedule(
+        &self,
+        delivery_id: &str,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: u64,
+    ) -> Result<()> {
+        self.connection.lock().unwrap().execute(
+            "UPDATE outbox SET attempts = ?2, last_error = ?3, next_attempt_at = ?4
+             WHERE delivery_id = ?1",
+            params![delivery_id, attempts, error, next_attempt_at],
+        )?;
+        Ok(())
+    }
+
+    /// Forget a notification once it has been delivered
+    pub fn remove(&self, delivery_id: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM outbox WHERE delivery_id = ?1", [delivery_id])?;
+        Ok(())
+    }
+
+    /// Move a notification that failed for good to the dead letters
+    pub fn bury(&self, letter: &DeadLetter) -> Result<()> {
+        let json = serde_json::to_string(&letter.notification)?;
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM outbox WHERE delivery_id = ?1",
+            [&letter.notification.delivery_id],
+        )?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO dead_letters
+                (delivery_id, notification, attempts, error, failed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                letter.notification.delivery_id,
+                json,
+                letter.attempts,
+                letter.error,
+                letter.failed_at
+            ],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Every dead letter, oldest first
+    pub fn dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT notification, attempts, error, failed_at FROM dead_letters
+             ORDER BY failed_at, rowid",
+        )?;
+        let rows = statement.query_map([], row_to_dead_letter)?;
+        rows.map(|row| row?).collect()
+    }
+
+    /// Remove a dead letter and return it
+    pub fn take_dead_letter(&self, delivery_id: &str) -> Result<Option<DeadLetter>> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let letter = transaction
+            .query_row(
+                "SELECT notification, attempts, error, failed_at
+                 FROM dead_letters WHERE delivery_id = ?1",
+                [delivery_id],
+                row_to_dead_letter,
+            )
+            .optional()?
+            .transpose()?;
+        transaction.execute(
+            "DELETE FROM dead_letters WHERE delivery_id = ?1",
+            [delivery_id],
+        )?;
+        transaction.commit()?;
+        Ok(letter)
+    }
+
+    pub fn get(&self, delivery_id: &str) -> Result<Option<Pending>> {
+        self.connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT notification, attempts, next_attempt_at, last_error
+                 FROM outbox WHERE delivery_id = ?1",
+                [delivery_id],
+                row_to_pending,
+            )
+            .optional()?
+            .transpose()
+    }
+
+    /// Every stored notification, emergencies first, then oldest first
+    pub fn pending(&self) -> Result<Vec<Pending>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT notification, attempts, next_attempt_at, last_error FROM outbox
+             ORDER BY emergency DESC, created_at, rowid",
+        )?;
+        let rows = statement.query_map([], row_to_pending)?;
+        rows.map(|row| row?).collect()
+    }
+
+    pub fn len(&self) -> Result<usize> {
+        let count: i64 = self.connection.lock().unwrap().query_row(
+            "SELECT COUNT(*) FROM outbox",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Size of the database in bytes
+    pub fn size(&self) -> Result<u64> {
+        storage::size(&self.connection.lock().unwrap())
+    }
+
+    /// Give the space of delivered notifications back to the disk
+    pub fn vacuum(&self) -> Result<()> {
+        match &self.path {
+            Some(path) => storage::vacuum(path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Decoding the stored JSON can fail separately from reading the row
+fn row_to_pending(row: &rusqlite::Row) -> rusqlite::Result<Result<Pending>> {
+    let json: String = row.get(0)?;
+    let attempts = row.get(1)?;
+    let next_attempt_at = row.get(2)?;
+    let last_error = row.get(3)?;
+    Ok(serde_json::from_str(&json)
+        .context("Invalid notification in the queue database")
+        .map(|notification| Pending {
+            notification,
+            attempts,
+            next_attempt_at,
+            last_error,
+        }))
+}
+
+fn row_to_dead_letter(row: &rusqlite::Row) -> rusqlite::Result<Result<DeadLetter>> {
+    let json: String = row.get(0)?;
+    let attempts = row.get(1)?;
+    let error = row.get(2)?;
+    let failed_at = row.get(3)?;
+    Ok(serde_json::from_str(&json)
+        .context("Invalid notification in the queue database")
+        .map(|notification| DeadLetter {
+            notification,
+            attempts,
+            error,
+            failed_at,
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::Attachment;
+
+    fn notification(delivery_id: &str, emergency: bool) -> Notification {
+        Notification {
+            message: format!("Alert {delivery_id}"),
+            chat_id: "-100123".to_string(),
+            delivery_id: delivery_id.to_string(),
+            emergency,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pending_order_and_removal() {
+        let outbox = Outbox::in_memory().unwrap();
+
+        outbox.push(&notification("first", false), 1).unwrap();
+        outbox.push(&notification("second", false), 2).unwrap();
+        outbox.push(&notification("urgent", true), 3).unwrap();
+        outbox.remove("second").unwrap();
+
+        let ids: Vec<String> = outbox
+            .pending()
+            .unwrap()
+            .into_iter()
+            .map(|pending| pending.notification.delivery_id)
+            .collect();
+        assert_eq!(ids, ["urgent", "first"]);
+        assert_eq!(outbox.len().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_reschedule() {
+        let outbox = Outbox::in_memory().unwrap();
+        outbox.push(&notification("abc", false), 10).unwrap();
+
+        outbox.reschedule("abc", 2, "Bad Gateway", 40).unwrap();
+
+        let pending = outbox.get("abc").unwrap().unwrap();
+        assert_eq!(pending.attempts, 2);
+        assert_eq!(pending.next_attempt_at, 40);
+        assert_eq!(pending.last_error.as_deref(), Some("Bad Gateway"));
+        assert_eq!(outbox.get("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_bury_moves_to_dead_letters() {
+        let outbox = Outbox::in_memory().unwrap();
+        let failed = notification("abc", false);
+        outbox.push(&failed, 10).unwrap();
+
+        outbox
+            .bury(&DeadLetter {
+                notification: failed.clone(),
+                attempts: 5,
+                error: "Forbidden: bot was blocked by the user".to_string(),
+                failed_at: 99,
+            })
+            .unwrap();
+
+        assert_eq!(outbox.len().unwrap(), 0);
+        let letters = outbox.dead_letters().unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].notification, failed);
+        assert_eq!(letters[0].attempts, 5);
+        assert!(outbox.take_dead_letter("abc").unwrap().is_some());
+        assert!(outbox.dead_letters().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_notifications_survive_reopening() {
+        let dir = std::env::temp_dir().join(format!("tn-outbox-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("queue.db");
+        let mut queued = notification("abc", false);
+        queued.attachments.push(Attachment {
+            file_name: "build.log".to_string(),
+            content_type: Some("text/plain".to_string()),
+            data: vec![0, 159, 146, 150],
+        });
+
+        Outbox::open(&path).unwrap().push(&queued, 1).unwrap();
+        let pending = Outbox::open(&path).unwrap().pending().unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].notification, queued);
+        assert_eq!(pending[0].attempts, 0);
+    }
+
+    #[test]
+    fn test_vacuum_shrinks_database() {
+        let dir = std::env::temp_dir().join(format!("tn-outbox-vacuum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let outbox = Outbox::open(&dir.join("queue.db")).unwrap();
+        for i in 0..500 {
+            let mut queued = notification(&i.to_string(), false);
+            queued.message = "x".repeat(1000);
+            outbox.push(&queued, 1).unwrap();
+        }
+        let full = outbox.size().unwrap();
+        for i in 0..500 {
+            outbox.remove(&i.to_string()).unwrap();
+        }
+        // Delivered notifications leave free pages behind until a vacuum
+        assert_eq!(outbox.size().unwrap(), full);
+
+        // The shared connection isn't needed, so it stays free meanwhile
+        let shared = outbox.connection.lock().unwrap();
+        outbox.vacuum().unwrap();
+        drop(shared);
+        let vacuumed = outbox.size().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(vacuumed < full / 10, "{vacuumed} of {full} bytes left");
+    }
+}
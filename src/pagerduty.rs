@@ -0,0 +1,365 @@
+use crate::api::SendNotificationRequest;
+use crate::bots::Bots;
+use crate::history::{DeliveryStatus, History};
+use crate::pages::escape;
+use crate::telegram::{Button, MAX_BUTTONS, MAX_MESSAGE_LENGTH};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Source of notifications from `POST /integrations/pagerduty`
+pub const SOURCE: &str = "pagerduty";
+
+/// An event in PagerDuty's Events API v2 format
+#[derive(Debug, Clone, Deserialize)]
+pub struct Event {
+    /// PagerDuty's integration key; accepted but not used
+    #[serde(default)]
+    pub routing_key: String,
+    pub event_action: Action,
+    /// Ties acknowledge and resolve events to the trigger
+    #[serde(default)]
+    pub dedup_key: Option<String>,
+    /// Required to trigger
+    #[serde(default)]
+    pub payload: Option<Payload>,
+    /// Name of the monitoring tool, shown on the `client_url` button
+    #[serde(default)]
+    pub client: Option<String>,
+    #[serde(default)]
+    pub client_url: Option<String>,
+    #[serde(default)]
+    pub links: Vec<Link>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Trigger,
+    Acknowledge,
+    Resolve,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Payload {
+    pub summary: String,
+    pub source: String,
+    /// `critical`, `error`, `warning` or `info`
+    pub severity: String,
+    #[serde(default)]
+    pub component: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Link {
+    pub href: String,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+/// PagerDuty's answer to an event, which its clients look for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventResponse {
+    pub status: String,
+    pub message: String,
+    pub dedup_key: String,
+    /// The notification the event sent, if it sent one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery_id: Option<String>,
+}
+
+impl EventResponse {
+    pub fn processed(dedup_key: String, delivery_id: Option<String>) -> Self {
+        Self {
+            status: "success".to_string(),
+            message: "Event processed".to_string(),
+            dedup_key,
+            delivery_id,
+        }
+    }
+}
+
+impl Event {
+    /// PagerDuty's rules: triggers need a payload, the other actions the
+    /// key of the incident they refer to
+    pub fn validate(&self) -> Result<()> {
+        match (&self.event_action, &self.payload) {
+            (Action::Trigger, None) => bail!("Trigger events need a payload"),
+            (Action::Trigger, Some(payload)) => {
+                if payload.summary.trim().is_empty() || payload.source.trim().is_empty() {
+                    bail!("payload.summary and payload.source must not be empty");
+                }
+                if severity_icon(&payload.severity).is_none() {
+                    bail!(
+                        "payload.severity must be critical, error, warning or info, not {:?}",
+                        payload.severity
+                    );
+                }
+            }
+            (Action::Acknowledge | Action::Resolve, _) => {
+                if self.dedup_key.as_deref().is_none_or(|key| key.is_empty()) {
+                    bail!("Acknowledge and resolve events need a dedup_key");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Incidents triggered and not resolved yet, by dedup key, with the
+/// notification each triggered. Kept in memory; cloning shares it.
+#[derive(Clone, Default)]
+pub struct Incidents {
+    open: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Incidents {
+    pub fn open(&self, dedup_key: &str, delivery_id: &str) {
+        self.open
+            .lock()
+            .unwrap()
+            .insert(dedup_key.to_string(), delivery_id.to_string());
+    }
+
+    /// The notification of an open incident
+    pub fn get(&self, dedup_key: &str) -> Option<String> {
+        self.open.lock().unwrap().get(dedup_key).cloned()
+    }
+
+    /// Forget a resolved incident, returning its notification
+    pub fn close(&self, dedup_key: &str) -> Option<String> {
+        self.open.lock().unwrap().remove(dedup_key)
+    }
+}
+
+/// The message of a trigger event, with its severity's colour, its source,
+/// component, group and class below, and buttons for its links
+pub fn render(event: &Event) -> SendNotificationRequest {
+    let Some(payload) = &event.payload else {
+        return SendNotificationRequest::default();
+    };
+    let severity = payload.severity.to_lowercase();
+    let icon = severity_icon(&severity).unwrap_or("🔴");
+    let mut lines = vec![format!(
+        "{icon} <b>[{}]</b> {}",
+        severity.to_uppercase(),
+        escape(&payload.summary)
+    )];
+    let details: Vec<String> = [
+        Some(&payload.source),
+        payload.component.as_ref(),
+        payload.group.as_ref(),
+        payload.class.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|detail| !detail.is_empty())
+    .map(|detail| escape(detail))
+    .collect();
+    lines.push(format!("<i>{}</i>", details.join(" · ")));
+
+    let client_link = event.client_url.as_ref().map(|href| Link {
+        href: href.clone(),
+        text: Some(event.client.clone().unwrap_or_else(|| "Open".to_string())),
+    });
+    let buttons: Vec<Button> = client_link
+        .iter()
+        .chain(&event.links)
+        .filter(|link| link.href.starts_with("http://") || link.href.starts_with("https://"))
+        .take(MAX_BUTTONS)
+        .map(|link| Button {
+            text: format!(
+                "🔗 {}",
+                link.text
+                    .as_deref()
+                    .filter(|text| !text.is_empty())
+                    .unwrap_or("Link")
+            ),
+            url: link.href.clone(),
+        })
+        .collect();
+
+    SendNotificationRequest {
+        message: lines.join("\n"),
+        parse_mode: Some("HTML".to_string()),
+        disable_notification: Some(severity == "info"),
+        severity: Some(severity),
+        buttons: (!buttons.is_empty()).then_some(buttons),
+        ..Default::default()
+    }
+}
+
+/// Message for resolving an incident whose trigger can't be edited
+pub fn render_resolved(dedup_key: &str) -> SendNotificationRequest {
+    SendNotificationRequest {
+        message: format!("✅ <b>Resolved</b> {}", escape(dedup_key)),
+        parse_mode: Some("HTML".to_string()),
+        disable_notification: Some(true),
+        ..Default::default()
+    }
+}
+
+/// The trigger's message marked as acknowledged or resolved
+fn marked(message: &str, action: Action) -> Option<String> {
+    let mark = match action {
+        Action::Trigger => return None,
+        Action::Acknowledge => "👀 <b>Acknowledged</b>",
+        Action::Resolve => "✅ <b>Resolved</b>",
+    };
+    let text = format!("{mark}\n{message}");
+    (text.chars().count() <= MAX_MESSAGE_LENGTH).then_some(text)
+}
+
+/// Edit the message a trigger sent, with the bot that sent it, to show it
+/// was acknowledged or resolved. Fails if the message wasn't sent (yet) or
+/// can't take the mark.
+pub async fn mark(bots: &Bots, history: &History, delivery_id: &str, action: Action) -> Result<()> {
+    let Some(record) = history.get(delivery_id) else {
+        bail!("Notification {delivery_id} is no longer in the history");
+    };
+    let DeliveryStatus::Sent {
+        telegram_message_id: Some(message_id),
+    } = record.status
+    else {
+        bail!("Notification {delivery_id} hasn't been sent");
+    };
+    let sent = &record.notification;
+    let Some(text) = marked(&sent.message, action) else {
+        bail!("Notification {delivery_id} is too long to be marked");
+    };
+    bots.for_notification(sent)
+        .edit_message_text(&sent.chat_id, message_id, &text, sent.parse_mode.as_deref())
+        .await?;
+    Ok(())
+}
+
+fn severity_icon(severity: &str) -> Option<&'static str> {
+    match severity.to_lowercase().as_str() {
+        "critical" => Some("🔴"),
+        "error" => Some("🟠"),
+        "warning" => Some("🟡"),
+        "info" => Some("🔵"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Notification;
+    use crate::telegram::TelegramBot;
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    fn trigger() -> Event {
+        serde_json::from_value(json!({
+            "routing_key": "R0123456789ABCDEF0123456789ABCDE",
+            "event_action": "trigger",
+            "dedup_key": "disk-web-1",
+            "payload": {
+                "summary": "Disk <var> 95% full on web-1",
+                "source": "web-1",
+                "severity": "critical",
+                "component": "postgres",
+                "group": "prod-db",
+                "custom_details": {"free": "2 GB"}
+            },
+            "client": "Nagios",
+            "client_url": "https://nagios.example.com/host/web-1",
+            "links": [{"href": "https://runbooks.example.com/disk", "text": "Runbook"}],
+            "images": [{"src": "https://example.com/graph.png"}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_trigger() {
+        let request = render(&trigger());
+        assert_eq!(
+            request.message,
+            "🔴 <b>[CRITICAL]</b> Disk &lt;var&gt; 95% full on web-1\n<i>web-1 · postgres · prod-db</i>"
+        );
+        assert_eq!(request.disable_notification, Some(false));
+        let buttons = request.buttons.unwrap();
+        assert_eq!(buttons[0].text, "🔗 Nagios");
+        assert_eq!(buttons[1].text, "🔗 Runbook");
+        assert_eq!(buttons[1].url, "https://runbooks.example.com/disk");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(trigger().validate().is_ok());
+        let event = |value| serde_json::from_value::<Event>(value).unwrap();
+        assert!(
+            event(json!({"event_action": "trigger"}))
+                .validate()
+                .is_err()
+        );
+        assert!(
+            event(json!({"event_action": "trigger", "payload": {"summary": "x", "source": "y", "severity": "fatal"}}))
+                .validate()
+                .is_err()
+        );
+        assert!(
+            event(json!({"event_action": "resolve"}))
+                .validate()
+                .is_err()
+        );
+        assert!(
+            event(json!({"event_action": "acknowledge", "dedup_key": "disk-web-1"}))
+                .validate()
+                .is_ok()
+        );
+        assert!(serde_json::from_value::<Event>(json!({"event_action": "escalate"})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mark_edits_trigger_message() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/bottoken/editMessageText")
+            .match_body(Matcher::Json(json!({
+                "chat_id": "-100123",
+                "message_id": 7,
+                "text": "✅ <b>Resolved</b>\n🔴 <b>[CRITICAL]</b> Disk full",
+                "parse_mode": "HTML",
+            })))
+            .with_body(r#"{"ok": true, "result": {"message_id": 7}}"#)
+            .create_async()
+            .await;
+        let bots = Bots::new(TelegramBot::with_base_url(
+            "token".to_string(),
+            &server.url(),
+        ));
+        let history = History::new(10);
+        let notification = Notification {
+            message: "🔴 <b>[CRITICAL]</b> Disk full".to_string(),
+            chat_id: "-100123".to_string(),
+            parse_mode: Some("HTML".to_string()),
+            delivery_id: "first".to_string(),
+            ..Default::default()
+        };
+
+        history.record(&notification, DeliveryStatus::Queued, None);
+        assert!(
+            mark(&bots, &history, "first", Action::Resolve)
+                .await
+                .is_err()
+        );
+        history.set_status(
+            "first",
+            DeliveryStatus::Sent {
+                telegram_message_id: Some(7),
+            },
+        );
+        mark(&bots, &history, "first", Action::Resolve)
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,251 @@
+use crate::history::{DeliveryStatus, Record};
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use crate::scheduler::format_time;
+use crate::telegram::{MAX_MESSAGE_LENGTH, truncate_message};
+use anyhow::Result;
+
+/// Shortens messages too long for a single Telegram message, linking to the
+/// page with their full text instead of splitting them into several messages
+pub struct LinkFullMessage {
+    /// Public URL and base path the `/n/{id}` pages are served under
+    url_prefix: String,
+}
+
+impl LinkFullMessage {
+    pub fn new(public_url: &str, base_path: &str) -> Self {
+        Self {
+            url_prefix: format!("{public_url}{base_path}"),
+        }
+    }
+
+    pub fn page_url(&self, delivery_id: &str) -> String {
+        format!("{}/n/{delivery_id}", self.url_prefix)
+    }
+}
+
+impl Stage for LinkFullMessage {
+    fn name(&self) -> &str {
+        "link-full-message"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Render
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        if notification.message.chars().count() <= MAX_MESSAGE_LENGTH {
+            return Ok(Outcome::Continue);
+        }
+
+        let url = self.page_url(&notification.delivery_id);
+        let mode = notification
+            .parse_mode
+            .as_deref()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let footer = match mode.as_str() {
+            "markdown" | "markdownv2" => format!("[Full message]({url})"),
+            "html" => format!("<a href=\"{url}\">Full message</a>"),
+            _ => format!("Full message: {url}"),
+        };
+        let footer = format!("…\n\n📄 {footer}");
+        let budget = MAX_MESSAGE_LENGTH - footer.chars().count();
+
+        let full = std::mem::take(&mut notification.message);
+        notification.message = truncate_message(&full, budget);
+        notification.message.push_str(&footer);
+        notification.full_message = Some(full);
+        Ok(Outcome::Continue)
+    }
+}
+
+/// HTML page showing a notification's full text and delivery details, as
+/// served at `GET /n/{id}`
+pub fn render(record: &Record) -> String {
+    let notification = &record.notification;
+    let message = notification
+        .full_message
+        .as_deref()
+        .unwrap_or(&notification.message);
+    let status = match &record.status {
+        DeliveryStatus::Scheduled => match notification.send_at {
+            Some(send_at) => format!("scheduled for {}", format_time(send_at)),
+            None => "scheduled".to_string(),
+        },
+        DeliveryStatus::Queued => "queued".to_string(),
+        DeliveryStatus::Sending => "sending".to_string(),
+        DeliveryStatus::Sent {
+            telegram_message_id: Some(id),
+        } => format!("sent (Telegram message {id})"),
+        DeliveryStatus::Sent { .. } => "sent".to_string(),
+        DeliveryStatus::Failed { error } => format!("failed: {error}"),
+        DeliveryStatus::Digested { digest_id } => format!("sent in digest {digest_id}"),
+        DeliveryStatus::Cancelled => "cancelled".to_string(),
+    };
+
+    let mut details = vec![
+        ("Delivery ID", notification.delivery_id.clone()),
+        ("Chat", notification.chat_id.clone()),
+        ("Status", status),
+        ("Attempts", record.attempts.to_string()),
+        ("Created", format_utc(record.created_at)),
+        ("Updated", format_utc(record.updated_at)),
+    ];
+    if let Some(parse_mode) = &notification.parse_mode {
+        details.push(("Parse mode", parse_mode.clone()));
+    }
+    if !notification.attachments.is_empty() {
+        let names: Vec<&str> = notification
+            .attachments
+            .iter()
+            .map(|attachment| attachment.file_name.as_str())
+            .collect();
+        details.push(("Attachments", names.join(", ")));
+    }
+    if let Some(by) = &record.acknowledged_by {
+        details.push(("Acknowledged by", by.clone()));
+    }
+    if let Some(original) = &record.resent_from {
+        details.push(("Re-send of", original.clone()));
+    }
+    let rows: String = details
+        .iter()
+        .map(|(label, value)| format!("<tr><th>{label}</th><td>{}</td></tr>", escape(value)))
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>Notification {id}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; color: #222; }}
+table {{ border-collapse: collapse; margin-bottom: 1.5rem; }}
+th, td {{ text-align: left; padding: 0.25rem 1rem 0.25rem 0; vertical-align: top; }}
+th {{ color: #666; font-weight: normal; }}
+pre {{ white-space: pre-wrap; word-break: break-word; background: #f5f5f5; padding: 1rem; border-radius: 4px; }}
+</style>
+</head>
+<body>
+<h1>Notification {id}</h1>
+<table>{rows}</table>
+<pre>{message}</pre>
+</body>
+</html>
+"#,
+        id = escape(&notification.delivery_id),
+        message = escape(message),
+    )
+}
+
+/// Escape text for HTML, including Telegram's HTML parse mode
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `2025-10-16 08:00:00 UTC` for a Unix timestamp
+fn format_utc(timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86_400, timestamp % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::History;
+
+    fn stage() -> LinkFullMessage {
+        LinkFullMessage::new("https://alerts.example.com", "/telegram")
+    }
+
+    fn notification(message: &str, parse_mode: Option<&str>) -> Notification {
+        Notification {
+            message: message.to_string(),
+            chat_id: "-100".to_string(),
+            parse_mode: parse_mode.map(str::to_string),
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_short_message_untouched() {
+        let mut notification = notification("Disk full", None);
+
+        stage().process(&mut notification).unwrap();
+
+        assert_eq!(notification.message, "Disk full");
+        assert_eq!(notification.full_message, None);
+    }
+
+    #[test]
+    fn test_long_message_truncated_with_link() {
+        let text = "log line\n".repeat(1_000);
+        let mut notification = notification(&text, None);
+
+        stage().process(&mut notification).unwrap();
+
+        assert!(notification.message.chars().count() <= MAX_MESSAGE_LENGTH);
+        assert!(notification.message.starts_with("log line\nlog line\n"));
+        assert!(
+            notification.message.ends_with(
+                "…\n\n📄 Full message: https://alerts.example.com/telegram/n/abc123def456"
+            )
+        );
+        assert_eq!(notification.full_message, Some(text));
+    }
+
+    #[test]
+    fn test_link_matches_parse_mode() {
+        let mut notification = notification(&"x".repeat(5_000), Some("HTML"));
+
+        stage().process(&mut notification).unwrap();
+
+        assert!(notification.message.ends_with(
+            "<a href=\"https://alerts.example.com/telegram/n/abc123def456\">Full message</a>"
+        ));
+    }
+
+    #[test]
+    fn test_render_escapes_full_message() {
+        let history = History::new(10);
+        let mut notification = notification("<b>short</b>", None);
+        notification.full_message = Some("<script>alert(1)</script> & more".to_string());
+        history.record(&notification, DeliveryStatus::Queued, None);
+
+        let page = render(&history.get("abc123def456").unwrap());
+
+        assert!(page.contains("&lt;script&gt;alert(1)&lt;/script&gt; &amp; more"));
+        assert!(!page.contains("<script>"));
+        assert!(page.contains("<tr><th>Status</th><td>queued</td></tr>"));
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(1_760_601_600), "2025-10-16 08:00:00 UTC");
+        assert_eq!(format_utc(951_825_600), "2000-02-29 12:00:00 UTC");
+    }
+}
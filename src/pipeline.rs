@@ -0,0 +1,385 @@
+use crate::telegram::{Attachment, Button};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tracing::warn;
+
+/// Processing phases, in the order a notification passes through them.
+///
+/// Stages registered for an earlier phase always run before stages of a later
+/// phase; stages sharing a phase run in registration order. Delivery happens
+/// once every stage has let the notification through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Auth,
+    Parse,
+    Transform,
+    Dedupe,
+    Route,
+    Render,
+}
+
+/// A notification flowing through the pipeline
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Notification {
+    pub message: String,
+    pub chat_id: String,
+    pub parse_mode: Option<String>,
+    pub disable_notification: bool,
+    /// Send without previews of the links in the message
+    pub disable_link_preview: bool,
+    /// Keep the messages from being forwarded and saved
+    pub protect_content: bool,
+    /// Forum topic the messages are posted in
+    pub thread_id: Option<i64>,
+    /// Named bot (`[bots.<name>]`) sending the notification; the chat's bot
+    /// or the main one if None
+    pub bot: Option<String>,
+    /// Pin the first message once it has been sent
+    pub pin: bool,
+    /// Short ID tracing this notification through logs and responses
+    pub delivery_id: String,
+    /// Alternative message texts keyed by locale
+    pub translations: HashMap<String, String>,
+    /// Files sent after the message text
+    pub attachments: Vec<Attachment>,
+    /// URL buttons below the (last part of the) message
+    pub buttons: Vec<Button>,
+    /// Sent with `X-Priority: emergency`; never dropped, queued ahead of others
+    pub emergency: bool,
+    /// Sent with `X-Priority: low`; may be held back for a digest
+    pub low_priority: bool,
+    /// Unix time the notification is held back until (`send_at`)
+    pub send_at: Option<u64>,
+    /// Complete text of a message shortened to fit into one Telegram message
+    pub full_message: Option<String>,
+    /// Where the notification came from: `api`, `schedule`, `digest`, ...
+    pub source: String,
+    /// How serious the notification is, e.g. `critical`, as the sender or
+    /// the alert says
+    pub severity: Option<String>,
+    /// Labels of the sender or alert, matched by routing rules
+    pub labels: BTreeMap<String, String>,
+    /// Named API key (`[auth.keys.<id>]`) the notification was sent with
+    pub key: Option<String>,
+    /// URL told how the delivery ended (`callback_url`); `--callback-url`
+    /// is used if None
+    pub callback_url: Option<String>,
+}
+
+impl Notification {
+    /// Whether the notification has the severity `critical`
+    pub fn is_critical(&self) -> bool {
+        self.severity
+            .as_deref()
+            .is_some_and(|severity| severity.eq_ignore_ascii_case("critical"))
+    }
+}
+
+/// What a stage decided about the notification it processed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Hand the notification to the next stage
+    Continue,
+    /// Stop processing and silently drop the notification (ignored for
+    /// emergency notifications, which are passed on to the next stage)
+    Drop { reason: String },
+    /// Stop processing and report the notification as invalid
+    Reject { code: String, error: String },
+}
+
+/// Result of running the whole pipeline
+#[derive(Debug, Clone, PartialEq)]
+pub enum Processed {
+    /// Every stage passed; the notification is ready for delivery
+    Ready(Box<Notification>),
+    Dropped {
+        stage: String,
+        reason: String,
+    },
+    Rejected {
+        stage: String,
+        code: String,
+        error: String,
+    },
+}
+
+/// A single pipeline step, e.g. validation, redaction, deduplication or routing
+pub trait Stage: Send + Sync {
+    fn name(&self) -> &str;
+    fn phase(&self) -> Phase;
+    fn process(&self, notification: &mut Notification) -> Result<Outcome>;
+
+    /// Decide as [`Stage::process`] would, without remembering the
+    /// notification; stages keeping state across notifications override it
+    fn preview(&self, notification: &mut Notification) -> Result<Outcome> {
+        self.process(notification)
+    }
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pipeline with the built-in stages every deployment needs
+    pub fn with_defaults() -> Self {
+        let mut pipeline = Self::new();
+        pipeline.register(ValidateMessage);
+        pipeline
+    }
+
+    /// Add a stage, keeping stages ordered by phase and then by registration
+    pub fn register(&mut self, stage: impl Stage + 'static) -> &mut Self {
+        let position = self
+            .stages
+            .iter()
+            .position(|existing| existing.phase() > stage.phase())
+            .unwrap_or(self.stages.len());
+        self.stages.insert(position, Box::new(stage));
+        self
+    }
+
+    /// Registered stages as `(name, phase)` in execution order
+    pub fn stages(&self) -> Vec<(&str, Phase)> {
+        self.stages
+            .iter()
+            .map(|stage| (stage.name(), stage.phase()))
+            .collect()
+    }
+
+    pub fn run(&self, notification: Notification) -> Result<Processed> {
+        self.pass(notification, false)
+    }
+
+    /// Run the notification through the stages as a dry run, leaving state
+    /// such as the deduplicator's and throttles' untouched
+    pub fn preview(&self, notification: Notification) -> Result<Processed> {
+        self.pass(notification, true)
+    }
+
+    fn pass(&self, mut notification: Notification, preview: bool) -> Result<Processed> {
+        for stage in &self.stages {
+            let outcome = if preview {
+                stage.preview(&mut notification)?
+            } else {
+                stage.process(&mut notification)?
+            };
+            match outcome {
+                Outcome::Continue => {}
+                Outcome::Drop { reason } if notification.emergency => {
+                    warn!(
+                        "🚨 Emergency notification {} not dropped by {}: {}",
+                        notification.delivery_id,
+                        stage.name(),
+                        reason
+                    );
+                }
+                Outcome::Drop { reason } => {
+                    return Ok(Processed::Dropped {
+                        stage: stage.name().to_string(),
+                        reason,
+                    });
+                }
+                Outcome::Reject { code, error } => {
+                    return Ok(Processed::Rejected {
+                        stage: stage.name().to_string(),
+                        code,
+                        error,
+                    });
+                }
+            }
+        }
+        Ok(Processed::Ready(Box::new(notification)))
+    }
+}
+
+/// Rejects notifications without any text
+pub struct ValidateMessage;
+
+impl Stage for ValidateMessage {
+    fn name(&self) -> &str {
+        "validate-message"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Parse
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        if notification.message.is_empty() {
+            return Ok(Outcome::Reject {
+                code: "EMPTY_MESSAGE".to_string(),
+                error: "Message cannot be empty".to_string(),
+            });
+        }
+        Ok(Outcome::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(message: &str) -> Notification {
+        Notification {
+            message: message.to_string(),
+            chat_id: "123456789".to_string(),
+            parse_mode: None,
+            disable_notification: false,
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        }
+    }
+
+    struct Append(&'static str, Phase);
+
+    impl Stage for Append {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn phase(&self) -> Phase {
+            self.1
+        }
+
+        fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+            notification.message.push_str(self.0);
+            Ok(Outcome::Continue)
+        }
+    }
+
+    struct DropAll;
+
+    impl Stage for DropAll {
+        fn name(&self) -> &str {
+            "drop-all"
+        }
+
+        fn phase(&self) -> Phase {
+            Phase::Dedupe
+        }
+
+        fn process(&self, _notification: &mut Notification) -> Result<Outcome> {
+            Ok(Outcome::Drop {
+                reason: "duplicate".to_string(),
+            })
+        }
+    }
+
+    struct Failing;
+
+    impl Stage for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn phase(&self) -> Phase {
+            Phase::Transform
+        }
+
+        fn process(&self, _notification: &mut Notification) -> Result<Outcome> {
+            Err(anyhow::anyhow!("boom"))
+        }
+    }
+
+    #[test]
+    fn test_stages_run_in_phase_order() {
+        let mut pipeline = Pipeline::new();
+        pipeline
+            .register(Append("-render", Phase::Render))
+            .register(Append("-route", Phase::Route))
+            .register(Append("-transform1", Phase::Transform))
+            .register(Append("-transform2", Phase::Transform));
+
+        let result = pipeline.run(notification("msg")).unwrap();
+
+        match result {
+            Processed::Ready(n) => {
+                assert_eq!(n.message, "msg-transform1-transform2-route-render")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_stages_listing() {
+        let mut pipeline = Pipeline::with_defaults();
+        pipeline.register(Append("auth", Phase::Auth));
+
+        assert_eq!(
+            pipeline.stages(),
+            vec![("auth", Phase::Auth), ("validate-message", Phase::Parse)]
+        );
+    }
+
+    #[test]
+    fn test_drop_stops_processing() {
+        let mut pipeline = Pipeline::new();
+        pipeline
+            .register(DropAll)
+            .register(Append("-route", Phase::Route));
+
+        let result = pipeline.run(notification("msg")).unwrap();
+
+        assert_eq!(
+            result,
+            Processed::Dropped {
+                stage: "drop-all".to_string(),
+                reason: "duplicate".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_emergency_is_not_dropped() {
+        let mut pipeline = Pipeline::new();
+        pipeline
+            .register(DropAll)
+            .register(Append("-route", Phase::Route));
+        let mut emergency = notification("msg");
+        emergency.emergency = true;
+
+        match pipeline.run(emergency).unwrap() {
+            Processed::Ready(n) => assert_eq!(n.message, "msg-route"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_default_pipeline_rejects_empty_message() {
+        let pipeline = Pipeline::with_defaults();
+        let result = pipeline.run(notification("")).unwrap();
+
+        assert_eq!(
+            result,
+            Processed::Rejected {
+                stage: "validate-message".to_string(),
+                code: "EMPTY_MESSAGE".to_string(),
+                error: "Message cannot be empty".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_default_pipeline_passes_valid_message() {
+        let pipeline = Pipeline::with_defaults();
+        let result = pipeline.run(notification("hello")).unwrap();
+
+        assert_eq!(result, Processed::Ready(Box::new(notification("hello"))));
+    }
+
+    #[test]
+    fn test_stage_error_propagates() {
+        let mut pipeline = Pipeline::new();
+        pipeline.register(Failing);
+
+        assert!(pipeline.run(notification("msg")).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::api::SendNotificationRequest;
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use wasmtime::{
+    Config, Engine, ExternType, Instance, Module, Store, StoreLimits, StoreLimitsBuilder, Trap,
+};
+
+/// Source of notifications from `POST /plugin/{name}`
+pub const SOURCE: &str = "plugin";
+
+/// Instructions (roughly) a plugin may run per webhook
+pub const FUEL: u64 = 100_000_000;
+
+/// Memory a plugin may grow to
+pub const MAX_MEMORY_BYTES: usize = 32 * 1024 * 1024;
+
+/// A receiver for a webhook format of its own, compiled to WebAssembly
+/// (`[plugins.<name>]` in the config file)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginSettings {
+    /// The `.wasm` module
+    pub path: PathBuf,
+}
+
+/// A compiled plugin module. It exports its `memory`, `alloc(len) -> ptr`
+/// for the host to copy the request body into, and
+/// `parse(ptr, len) -> (ptr << 32) | len` pointing at the notification as a
+/// `/notify` request body in JSON, or at `null` (or nothing) to send none.
+/// Plugins can't import anything, so they see nothing but the body. Cloning
+/// shares the compiled module.
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    module: Module,
+}
+
+impl Plugin {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read plugin {}", path.display()))?;
+        Self::from_bytes(&bytes)
+            .with_context(|| format!("Failed to compile plugin {}", path.display()))
+    }
+
+    /// A module in the binary or text format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, bytes)?;
+        if let Some(import) = module.imports().next() {
+            bail!(
+                "Plugins can't import anything, but this one imports {}.{}",
+                import.module(),
+                import.name()
+            );
+        }
+        for (name, wanted) in [
+            ("memory", "a memory"),
+            ("alloc", "a function"),
+            ("parse", "a function"),
+        ] {
+            let found = match module.get_export(name) {
+                Some(ExternType::Memory(_)) => "a memory",
+                Some(ExternType::Func(_)) => "a function",
+                _ => "",
+            };
+            if found != wanted {
+                bail!("Plugins must export {wanted} named {name}");
+            }
+        }
+        Ok(Self { module })
+    }
+
+    /// The notification for a request body, if the plugin makes one of it.
+    /// Every call runs in a fresh instance, limited in fuel and memory.
+    pub fn parse(&self, body: &[u8]) -> Result<Option<SendNotificationRequest>> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .build();
+        let mut store: Store<StoreLimits> = Store::new(self.module.engine(), limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL)?;
+
+        let output = run(&mut store, &self.module, body).map_err(|e| {
+            if e.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) {
+                anyhow!("Plugin ran out of fuel")
+            } else {
+                e
+            }
+        })?;
+        if output.is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_slice(&output).context("Plugin returned an invalid notification")
+    }
+}
+
+/// Copy `body` into a new instance and return what its `parse` points at
+fn run(store: &mut Store<StoreLimits>, module: &Module, body: &[u8]) -> Result<Vec<u8>> {
+    let instance = Instance::new(&mut *store, module, &[])?;
+    let memory = instance
+        .get_memory(&mut *store, "memory")
+        .context("Plugin has no memory")?;
+    let alloc = instance.get_typed_func::<u32, u32>(&mut *store, "alloc")?;
+    let parse = instance.get_typed_func::<(u32, u32), u64>(&mut *store, "parse")?;
+
+    let len = u32::try_from(body.len()).context("Request body too large for a plugin")?;
+    let ptr = alloc.call(&mut *store, len)?;
+    memory
+        .write(&mut *store, ptr as usize, body)
+        .context("Plugin allocated memory out of bounds")?;
+    let result = parse.call(&mut *store, (ptr, len))?;
+
+    let (ptr, len) = ((result >> 32) as usize, (result & 0xffff_ffff) as usize);
+    let mut output = vec![0; len];
+    memory
+        .read(&*store, ptr, &mut output)
+        .context("Plugin returned memory out of bounds")?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plugin answering with `output` whatever the body
+    fn constant(output: &str) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{}")
+                (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "parse") (param i32 i32) (result i64) i64.const {}))"#,
+            output.replace('"', "\\\""),
+            output.len()
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let plugin = Plugin::from_bytes(
+            constant(r#"{"message":"Build failed","chat":"ops-team","severity":"critical"}"#)
+                .as_bytes(),
+        )
+        .unwrap();
+        let request = plugin.parse(b"<build status='failed'/>").unwrap().unwrap();
+        assert_eq!(request.message, "Build failed");
+        assert_eq!(request.chat.as_deref(), Some("ops-team"));
+        assert_eq!(request.severity.as_deref(), Some("critical"));
+
+        for output in ["null", ""] {
+            let plugin = Plugin::from_bytes(constant(output).as_bytes()).unwrap();
+            assert!(plugin.parse(b"{}").unwrap().is_none(), "{output:?}");
+        }
+        let plugin = Plugin::from_bytes(constant("{").as_bytes()).unwrap();
+        assert!(plugin.parse(b"{}").is_err());
+    }
+
+    #[test]
+    fn test_echoes_body() {
+        // `parse` points back at the body it was given
+        let plugin = Plugin::from_bytes(
+            br#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 64)
+                (func (export "parse") (param i32 i32) (result i64)
+                    (i64.or
+                        (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32))
+                        (i64.extend_i32_u (local.get 1)))))"#,
+        )
+        .unwrap();
+        let request = plugin.parse(br#"{"message":"echo"}"#).unwrap().unwrap();
+        assert_eq!(request.message, "echo");
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let endless = Plugin::from_bytes(
+            br#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 0)
+                (func (export "parse") (param i32 i32) (result i64) (loop br 0) i64.const 0))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            endless.parse(b"{}").unwrap_err().to_string(),
+            "Plugin ran out of fuel"
+        );
+
+        let importing = Plugin::from_bytes(
+            br#"(module
+                (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1))"#,
+        );
+        assert!(importing.is_err());
+        let no_parse = Plugin::from_bytes(br#"(module (memory (export "memory") 1))"#);
+        assert!(no_parse.is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::auth::api_key;
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+/// Header asking for a notification's priority: `emergency` or `low`
+pub const PRIORITY_HEADER: &str = "x-priority";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// May be held back for a digest (`X-Priority: low`)
+    Low,
+    #[default]
+    Normal,
+    /// Never dropped, queued ahead of others (`X-Priority: emergency`)
+    Emergency,
+}
+
+/// Keys allowed to send emergency notifications (`[priority]` in the config
+/// file, plus TELEGRAM_NOTIFICATIONS_EMERGENCY_KEYS)
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrioritySettings {
+    pub emergency_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriorityError {
+    /// `X-Priority: emergency` without an allowed key
+    NotAllowed,
+}
+
+impl PriorityError {
+    pub fn code(self) -> &'static str {
+        match self {
+            PriorityError::NotAllowed => "EMERGENCY_NOT_ALLOWED",
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            PriorityError::NotAllowed => {
+                "X-Priority: emergency requires an API key allowed to send emergencies"
+            }
+        }
+    }
+}
+
+impl PrioritySettings {
+    /// Priority the request asks for, failing for emergencies it may not send
+    pub fn priority(&self, headers: &HeaderMap) -> Result<Priority, PriorityError> {
+        if self.emergency(headers)? {
+            Ok(Priority::Emergency)
+        } else if requested(headers, "low") {
+            Ok(Priority::Low)
+        } else {
+            Ok(Priority::Normal)
+        }
+    }
+
+    /// Whether the request asks for, and may send, an emergency notification
+    pub fn emergency(&self, headers: &HeaderMap) -> Result<bool, PriorityError> {
+        if !requested(headers, "emergency") {
+            return Ok(false);
+        }
+
+        match api_key(headers) {
+            Some(key) if self.emergency_keys.iter().any(|allowed| allowed == key) => Ok(true),
+            _ => Err(PriorityError::NotAllowed),
+        }
+    }
+}
+
+fn requested(headers: &HeaderMap, priority: &str) -> bool {
+    headers
+        .get(PRIORITY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case(priority))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::API_KEY_HEADER;
+
+    fn settings() -> PrioritySettings {
+        PrioritySettings {
+            emergency_keys: vec!["pager-key".to_string()],
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_normal_priority_without_header() {
+        assert_eq!(settings().emergency(&HeaderMap::new()), Ok(false));
+        assert_eq!(
+            settings().emergency(&headers(&[(PRIORITY_HEADER, "high")])),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_priority() {
+        let low = headers(&[(PRIORITY_HEADER, "Low")]);
+        let emergency = headers(&[
+            (PRIORITY_HEADER, "emergency"),
+            (API_KEY_HEADER, "pager-key"),
+        ]);
+
+        assert_eq!(settings().priority(&low), Ok(Priority::Low));
+        assert_eq!(settings().priority(&HeaderMap::new()), Ok(Priority::Normal));
+        assert_eq!(settings().priority(&emergency), Ok(Priority::Emergency));
+        assert_eq!(
+            PrioritySettings::default().priority(&emergency),
+            Err(PriorityError::NotAllowed)
+        );
+    }
+
+    #[test]
+    fn test_emergency_with_allowed_key() {
+        let api_key = headers(&[
+            (PRIORITY_HEADER, "Emergency"),
+            (API_KEY_HEADER, "pager-key"),
+        ]);
+        let bearer = headers(&[
+            (PRIORITY_HEADER, "emergency"),
+            ("authorization", "Bearer pager-key"),
+        ]);
+
+        assert_eq!(settings().emergency(&api_key), Ok(true));
+        assert_eq!(settings().emergency(&bearer), Ok(true));
+    }
+
+    #[test]
+    fn test_emergency_rejected_without_allowed_key() {
+        let missing = headers(&[(PRIORITY_HEADER, "emergency")]);
+        let wrong = headers(&[(PRIORITY_HEADER, "emergency"), (API_KEY_HEADER, "other")]);
+
+        assert_eq!(
+            settings().emergency(&missing),
+            Err(PriorityError::NotAllowed)
+        );
+        assert_eq!(settings().emergency(&wrong), Err(PriorityError::NotAllowed));
+        assert_eq!(
+            PrioritySettings::default().emergency(&headers(&[
+                (PRIORITY_HEADER, "emergency"),
+                (API_KEY_HEADER, "")
+            ])),
+            Err(PriorityError::NotAllowed)
+        );
+    }
+}
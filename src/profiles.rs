@@ -0,0 +1,107 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Personal settings for the CLI, kept out of the shell environment
+/// (`~/.config/telegram-notifications/config.toml`)
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    /// Profile used without --profile
+    #[serde(default)]
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A bot and chat to send with (`[profiles.<name>]`); flags and environment
+/// variables still take precedence
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub bot_token: Option<String>,
+    /// Default chat; several separated by commas
+    #[serde(default)]
+    pub chat_id: Option<String>,
+    /// Parse mode of the messages sent, e.g. `HTML`
+    #[serde(default)]
+    pub parse_mode: Option<String>,
+}
+
+/// `$XDG_CONFIG_HOME/telegram-notifications/config.toml`, falling back to
+/// `~/.config`
+pub fn user_config_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("telegram-notifications").join("config.toml"))
+}
+
+/// The profile named `name`, or else the file's default profile, if any.
+/// Fails if a profile is asked for but doesn't exist.
+pub fn load(path: Option<&Path>, name: Option<&str>) -> Result<Option<Profile>> {
+    let user_config = match path.filter(|path| path.exists()) {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            toml::from_str::<UserConfig>(&contents)
+                .with_context(|| format!("Invalid user config {}", path.display()))?
+        }
+        None => UserConfig::default(),
+    };
+    let Some(name) = name.or(user_config.default_profile.as_deref()) else {
+        return Ok(None);
+    };
+    match user_config.profiles.get(name) {
+        Some(profile) => Ok(Some(profile.clone())),
+        None => bail!(
+            "No profile {name:?} in {}",
+            path.map_or("the user config".to_string(), |path| path
+                .display()
+                .to_string())
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_profiles() {
+        let path = env::temp_dir().join(format!("profiles-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+default_profile = "homelab"
+
+[profiles.homelab]
+bot_token = "123:home"
+chat_id = "42"
+
+[profiles.work]
+chat_id = "-100123,-100456"
+parse_mode = "HTML"
+"#,
+        )
+        .unwrap();
+
+        let homelab = load(Some(&path), None).unwrap().unwrap();
+        assert_eq!(homelab.bot_token.as_deref(), Some("123:home"));
+        let work = load(Some(&path), Some("work")).unwrap().unwrap();
+        assert_eq!(work.parse_mode.as_deref(), Some("HTML"));
+        assert_eq!(work.bot_token, None);
+        assert!(load(Some(&path), Some("missing")).is_err());
+
+        std::fs::write(&path, "[profiles.typo]\nbot_tokn = \"x\"\n").unwrap();
+        assert!(load(Some(&path), None).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(load(Some(&path), None).unwrap(), None);
+        assert!(load(Some(&path), Some("homelab")).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::api::ErrorResponse;
+use axum::{
+    http::{
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+        header::{ETAG, IF_MATCH, IF_NONE_MATCH},
+    },
+    response::Json,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+type Rejection = (StatusCode, Json<ErrorResponse>);
+
+/// Longest name accepted for a declared resource
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Strong `ETag` of a resource: a hash of its JSON representation, so the
+/// same state always has the same tag
+pub fn etag(resource: &impl Serialize) -> String {
+    let json = serde_json::to_vec(resource).expect("resources serialize");
+    format!("\"{}\"", &hex::encode(Sha256::digest(json))[..16])
+}
+
+/// Headers carrying a resource's `ETag`
+pub fn etag_header(resource: &impl Serialize) -> [(HeaderName, HeaderValue); 1] {
+    let value = HeaderValue::from_str(&etag(resource)).expect("ETags are ASCII");
+    [(ETAG, value)]
+}
+
+/// Check `If-Match` and `If-None-Match` against the current `ETag` of a
+/// resource (None if it doesn't exist), so declarative clients don't
+/// overwrite changes they haven't seen
+pub fn check_preconditions(headers: &HeaderMap, current: Option<&str>) -> Result<(), Rejection> {
+    let matches = |header| {
+        let value = headers.get(header)?.to_str().ok()?;
+        // `*` matches any existing resource
+        Some(value.split(',').map(str::trim).any(|tag| {
+            current.is_some_and(|current| tag == "*" || current == tag.trim_start_matches("W/"))
+        }))
+    };
+
+    let failed = match (matches(IF_MATCH), matches(IF_NONE_MATCH)) {
+        (Some(false), _) => Some("If-Match does not match the current ETag"),
+        (_, Some(true)) => Some("If-None-Match matches the current resource"),
+        _ => None,
+    };
+    match failed {
+        Some(message) => Err((
+            StatusCode::PRECONDITION_FAILED,
+            Json(ErrorResponse::with_code(
+                message.to_string(),
+                "PRECONDITION_FAILED".to_string(),
+            )),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Names of declared resources end up in URLs and logs, so keep them simple
+pub fn validate_name(name: &str) -> Result<(), Rejection> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        return Ok(());
+    }
+    Err((
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::with_code(
+            format!(
+                "Invalid name {name:?}; use up to {MAX_NAME_LENGTH} letters, digits, '-', '_' or '.'"
+            ),
+            "INVALID_NAME".to_string(),
+        )),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_etag_follows_content() {
+        let tag = etag(&json!({"chat_id": "-100123"}));
+
+        assert!(tag.starts_with('"') && tag.ends_with('"'));
+        assert_eq!(tag, etag(&json!({"chat_id": "-100123"})));
+        assert_ne!(tag, etag(&json!({"chat_id": "-100456"})));
+    }
+
+    #[test]
+    fn test_preconditions() {
+        let current = Some("\"abc\"");
+
+        assert!(check_preconditions(&HeaderMap::new(), current).is_ok());
+        assert!(check_preconditions(&headers(&[("if-match", "\"abc\"")]), current).is_ok());
+        assert!(check_preconditions(&headers(&[("if-match", "W/\"abc\"")]), current).is_ok());
+        assert_eq!(
+            check_preconditions(&headers(&[("if-match", "\"old\"")]), current)
+                .unwrap_err()
+                .0,
+            StatusCode::PRECONDITION_FAILED
+        );
+        // If-Match needs the resource to exist, If-None-Match: * needs it not to
+        assert!(check_preconditions(&headers(&[("if-match", "*")]), None).is_err());
+        assert!(check_preconditions(&headers(&[("if-none-match", "*")]), current).is_err());
+        assert!(check_preconditions(&headers(&[("if-none-match", "*")]), None).is_ok());
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("ops-team").is_ok());
+        assert!(validate_name("release.v2_eu").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("ops team").is_err());
+        assert!(validate_name(&"x".repeat(65)).is_err());
+    }
+}
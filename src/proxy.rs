@@ -0,0 +1,211 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// Address of the client that sent a request, as seen through any trusted
+/// proxies. Added to the request extensions by [`resolve_client_ip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Reverse proxies whose `Forwarded` / `X-Forwarded-For` headers are honored
+/// (`--trusted-proxies`). Headers from anyone else are ignored, since clients
+/// can set them to anything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    /// Parse CIDRs such as `10.0.0.0/8`; bare addresses trust a single host
+    pub fn parse(cidrs: &[String]) -> Result<Self> {
+        let networks = cidrs
+            .iter()
+            .map(|cidr| {
+                let cidr = cidr.trim();
+                cidr.parse::<IpNet>()
+                    .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+                    .with_context(|| format!("Invalid trusted proxy {cidr:?}; expected a CIDR"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { networks })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    pub fn networks(&self) -> &[IpNet] {
+        &self.networks
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|network| network.contains(&ip))
+    }
+
+    /// Client address of a request that reached us from `peer`: the
+    /// rightmost forwarded address not belonging to a trusted proxy
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        let mut client = peer;
+        for hop in forwarded_for(headers).iter().rev() {
+            // Obfuscated or garbled hops end the chain at the last known proxy
+            let Some(ip) = hop else { break };
+            client = *ip;
+            if !self.is_trusted(client) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Forwarded client addresses, oldest first, from the standard `Forwarded`
+/// header or else `X-Forwarded-For`
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| -> Vec<String> {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|hop| hop.trim().to_string())
+            .collect()
+    };
+
+    let forwarded = values("forwarded");
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(key, _)| key.eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect();
+    }
+    values("x-forwarded-for")
+        .iter()
+        .map(|hop| parse_node(hop))
+        .collect()
+}
+
+/// Parse `192.0.2.1`, `192.0.2.1:4711`, `2001:db8::1` or `"[2001:db8::1]:4711"`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    node.parse()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Middleware recording the [`ClientIp`] of each request
+pub async fn resolve_client_ip(
+    State(proxies): State<Arc<TrustedProxies>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if let Some(peer) = peer {
+        let client = proxies.client_ip(peer, request.headers());
+        request.extensions_mut().insert(ClientIp(client));
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies() -> TrustedProxies {
+        TrustedProxies::parse(&["10.0.0.0/8".to_string(), "192.168.1.1".to_string()]).unwrap()
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(proxies().is_trusted(ip("10.1.2.3")));
+        assert!(proxies().is_trusted(ip("192.168.1.1")));
+        assert!(!proxies().is_trusted(ip("192.168.1.2")));
+        assert!(TrustedProxies::parse(&["not-a-cidr".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_untrusted_peer_headers_ignored() {
+        let headers = headers(&[("x-forwarded-for", "1.2.3.4")]);
+
+        assert_eq!(
+            proxies().client_ip(ip("203.0.113.9"), &headers),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn test_x_forwarded_for_through_proxies() {
+        // A client-supplied hop on the left can't override what our proxies saw
+        let headers = headers(&[("x-forwarded-for", "6.6.6.6, 198.51.100.7, 10.0.0.5")]);
+
+        assert_eq!(
+            proxies().client_ip(ip("10.0.0.1"), &headers),
+            ip("198.51.100.7")
+        );
+    }
+
+    #[test]
+    fn test_forwarded_header_preferred() {
+        let headers = headers(&[
+            ("forwarded", r#"for="[2001:db8:cafe::17]:4711";proto=https"#),
+            ("forwarded", "for=10.0.0.5"),
+            ("x-forwarded-for", "198.51.100.7"),
+        ]);
+
+        assert_eq!(
+            proxies().client_ip(ip("10.0.0.1"), &headers),
+            ip("2001:db8:cafe::17")
+        );
+    }
+
+    #[test]
+    fn test_obfuscated_hop_stops_chain() {
+        let headers = headers(&[("forwarded", "for=198.51.100.7, for=_hidden, for=10.0.0.5")]);
+
+        assert_eq!(
+            proxies().client_ip(ip("10.0.0.1"), &headers),
+            ip("10.0.0.5")
+        );
+    }
+
+    #[test]
+    fn test_parse_node() {
+        assert_eq!(parse_node("192.0.2.1:4711"), Some(ip("192.0.2.1")));
+        assert_eq!(parse_node("2001:db8::1"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_node("unknown"), None);
+    }
+}
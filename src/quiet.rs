@@ -0,0 +1,236 @@
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Days, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+/// Hours a chat isn't disturbed in (`[quiet_hours."<chat ID or alias>"]`
+/// in the config file), in the server's local time
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHours {
+    /// When they start, e.g. `22:00`
+    pub start: String,
+    /// When they end, e.g. `07:00`; before `start` for hours spanning
+    /// midnight
+    pub end: String,
+    #[serde(default)]
+    pub action: QuietAction,
+}
+
+/// What happens to notifications sent during quiet hours
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuietAction {
+    /// Send them silently
+    #[default]
+    Silent,
+    /// Hold them until the quiet hours end
+    Hold,
+}
+
+impl QuietHours {
+    /// Start and end, failing if either isn't a time of day
+    pub fn window(&self) -> Result<(NaiveTime, NaiveTime)> {
+        let start = parse_time(&self.start)?;
+        let end = parse_time(&self.end)?;
+        if start == end {
+            bail!("Quiet hours can't start and end at {}", self.start);
+        }
+        Ok((start, end))
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .with_context(|| format!("{time:?} is not a time like 22:00"))
+}
+
+/// Quiet hours of one chat, parsed
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+    action: QuietAction,
+}
+
+impl Window {
+    /// When the quiet hours `at` falls into end, if it does
+    fn end_after<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let time = at.time();
+        let quiet = if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        };
+        if !quiet {
+            return None;
+        }
+        let mut day = at.date_naive();
+        if time >= self.end {
+            day = day.checked_add_days(Days::new(1))?;
+        }
+        // The earlier of two local times repeated when the clocks go back
+        at.timezone()
+            .from_local_datetime(&day.and_time(self.end))
+            .earliest()
+    }
+}
+
+/// Sends notifications to chats in their quiet hours silently, or holds them
+/// until the hours end. Critical and emergency notifications break through.
+pub struct ApplyQuietHours {
+    chats: HashMap<String, Window>,
+}
+
+impl ApplyQuietHours {
+    /// Quiet hours keyed by chat ID
+    pub fn new(quiet_hours: &HashMap<String, QuietHours>) -> Result<Self> {
+        let mut chats = HashMap::new();
+        for (chat_id, hours) in quiet_hours {
+            let (start, end) = hours
+                .window()
+                .with_context(|| format!("Invalid quiet hours for {chat_id}"))?;
+            chats.insert(
+                chat_id.clone(),
+                Window {
+                    start,
+                    end,
+                    action: hours.action,
+                },
+            );
+        }
+        Ok(Self { chats })
+    }
+
+    fn apply<Tz: TimeZone>(&self, notification: &mut Notification, now: &DateTime<Tz>) {
+        if notification.emergency || notification.is_critical() {
+            return;
+        }
+        let Some(window) = self.chats.get(&notification.chat_id) else {
+            return;
+        };
+        // Scheduled notifications are judged by when they go out
+        let at = notification
+            .send_at
+            .and_then(|send_at| now.timezone().timestamp_opt(send_at as i64, 0).single())
+            .unwrap_or_else(|| now.clone());
+        let Some(end) = window.end_after(&at) else {
+            return;
+        };
+        match window.action {
+            QuietAction::Silent => notification.disable_notification = true,
+            QuietAction::Hold => {
+                info!(
+                    "🌙 Notification {} held until the quiet hours of chat {} end",
+                    notification.delivery_id, notification.chat_id
+                );
+                notification.send_at = Some(end.timestamp().max(0) as u64);
+            }
+        }
+    }
+}
+
+impl Stage for ApplyQuietHours {
+    fn name(&self) -> &str {
+        "quiet-hours"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Render
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        self.apply(notification, &Local::now());
+        Ok(Outcome::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn stage(action: QuietAction) -> ApplyQuietHours {
+        ApplyQuietHours::new(&HashMap::from([(
+            "-100123".to_string(),
+            QuietHours {
+                start: "22:00".to_string(),
+                end: "07:00".to_string(),
+                action,
+            },
+        )]))
+        .unwrap()
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    fn notification(chat_id: &str) -> Notification {
+        Notification {
+            chat_id: chat_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_silent_overnight() {
+        let stage = stage(QuietAction::Silent);
+        for (time, silent) in [
+            ("2026-10-16T21:59:00Z", false),
+            ("2026-10-16T22:00:00Z", true),
+            ("2026-10-17T03:00:00Z", true),
+            ("2026-10-17T07:00:00Z", false),
+        ] {
+            let mut quiet = notification("-100123");
+            stage.apply(&mut quiet, &at(time));
+            assert_eq!(quiet.disable_notification, silent, "{time}");
+        }
+
+        let mut other_chat = notification("42");
+        stage.apply(&mut other_chat, &at("2026-10-16T23:00:00Z"));
+        assert!(!other_chat.disable_notification);
+
+        let mut critical = notification("-100123");
+        critical.severity = Some("CRITICAL".to_string());
+        stage.apply(&mut critical, &at("2026-10-16T23:00:00Z"));
+        assert!(!critical.disable_notification);
+    }
+
+    #[test]
+    fn test_hold_until_morning() {
+        let stage = stage(QuietAction::Hold);
+        let morning = at("2026-10-17T07:00:00Z").timestamp() as u64;
+
+        let mut evening = notification("-100123");
+        stage.apply(&mut evening, &at("2026-10-16T23:30:00Z"));
+        assert_eq!(evening.send_at, Some(morning));
+        let mut night = notification("-100123");
+        stage.apply(&mut night, &at("2026-10-17T01:00:00Z"));
+        assert_eq!(night.send_at, Some(morning));
+        assert!(!night.disable_notification);
+
+        // Scheduled for the afternoon, so not held
+        let mut scheduled = notification("-100123");
+        scheduled.send_at = Some(at("2026-10-17T15:00:00Z").timestamp() as u64);
+        stage.apply(&mut scheduled, &at("2026-10-16T23:30:00Z"));
+        assert_eq!(
+            scheduled.send_at,
+            Some(at("2026-10-17T15:00:00Z").timestamp() as u64)
+        );
+    }
+
+    #[test]
+    fn test_invalid_hours() {
+        for (start, end) in [("22:00", "7am"), ("25:00", "07:00"), ("08:00", "08:00")] {
+            let hours = QuietHours {
+                start: start.to_string(),
+                end: end.to_string(),
+                action: QuietAction::Silent,
+            };
+            assert!(hours.window().is_err(), "{start}-{end}");
+        }
+    }
+}
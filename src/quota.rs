@@ -0,0 +1,292 @@
+use crate::keys::KeyStore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+const MINUTE: u64 = 60;
+const DAY: u64 = 24 * 60 * 60;
+
+/// A named API key with optional request quotas (`[auth.keys.<id>]` in the
+/// config file). Requests with named keys are counted per key.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeySettings {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Counted per UTC day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_day: Option<u32>,
+}
+
+/// Requests made in the current minute or day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WindowUsage {
+    pub used: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    pub resets_in_secs: u64,
+}
+
+/// Usage of one key, as returned by `GET /keys/{id}/usage`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyUsage {
+    pub key_id: String,
+    /// Accepted requests since the server started
+    pub requests_total: u64,
+    /// Requests refused with 429 since the server started
+    pub rejected_total: u64,
+    pub minute: WindowUsage,
+    pub day: WindowUsage,
+}
+
+/// Counter for a fixed window starting at `start` (Unix seconds)
+#[derive(Debug, Clone, Copy, Default)]
+struct Window {
+    start: u64,
+    used: u32,
+}
+
+impl Window {
+    /// The count for the window of `length` seconds containing `now`
+    fn current(&mut self, length: u64, now: u64) -> &mut u32 {
+        let start = now - now % length;
+        if self.start != start {
+            *self = Window { start, used: 0 };
+        }
+        &mut self.used
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Usage {
+    total: u64,
+    rejected: u64,
+    minute: Window,
+    day: Window,
+}
+
+/// A named key went over its quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    /// "minute" or "day"
+    pub window: &'static str,
+    pub limit: u32,
+    pub retry_after_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    per_minute: Option<u32>,
+    per_day: Option<u32>,
+}
+
+/// Per-key request counts and quotas. Cloning shares the same counters.
+#[derive(Clone, Default)]
+pub struct KeyQuotas {
+    limits: Arc<HashMap<String, Limits>>,
+    /// Keys created through the admin API, counted under their IDs
+    managed: KeyStore,
+    usage: Arc<Mutex<HashMap<String, Usage>>>,
+}
+
+impl KeyQuotas {
+    pub fn new(keys: &BTreeMap<String, KeySettings>) -> Self {
+        let limits = keys
+            .iter()
+            .map(|(id, key)| {
+                let limits = Limits {
+                    per_minute: key.requests_per_minute,
+                    per_day: key.requests_per_day,
+                };
+                (id.clone(), limits)
+            })
+            .collect();
+        Self {
+            limits: Arc::new(limits),
+            managed: KeyStore::default(),
+            usage: Arc::default(),
+        }
+    }
+
+    /// Also count the keys of `store`, with the quotas they were given
+    pub fn with_managed_keys(mut self, store: KeyStore) -> Self {
+        self.managed = store;
+        self
+    }
+
+    /// Whether `id` names a key of `[auth.keys]`
+    pub fn is_configured(&self, id: &str) -> bool {
+        self.limits.contains_key(id)
+    }
+
+    fn limits(&self, id: &str) -> Option<Limits> {
+        self.limits.get(id).copied().or_else(|| {
+            let quota = self.managed.get(id)?.quota;
+            Some(Limits {
+                per_minute: quota.requests_per_minute,
+                per_day: quota.requests_per_day,
+            })
+        })
+    }
+
+    /// Count a request by key `id` at `now` (Unix seconds), refusing it if
+    /// the key has used up its quota for the minute or day
+    pub fn record(&self, id: &str, now: u64) -> Result<(), QuotaExceeded> {
+        let Some(Limits {
+            per_minute,
+            per_day,
+        }) = self.limits(id)
+        else {
+            return Ok(());
+        };
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage.entry(id.to_string()).or_default();
+
+        let minute = *usage.minute.current(MINUTE, now);
+        let day = *usage.day.current(DAY, now);
+        let exceeded = [
+            ("minute", per_minute, minute, MINUTE),
+            ("day", per_day, day, DAY),
+        ]
+        .into_iter()
+        .find_map(|(window, limit, used, length)| {
+            let limit = limit?;
+            (used >= limit).then(|| QuotaExceeded {
+                window,
+                limit,
+                retry_after_secs: length - now % length,
+            })
+        });
+        if let Some(exceeded) = exceeded {
+            usage.rejected += 1;
+            return Err(exceeded);
+        }
+
+        usage.total += 1;
+        *usage.minute.current(MINUTE, now) += 1;
+        *usage.day.current(DAY, now) += 1;
+        Ok(())
+    }
+
+    /// Usage of key `id`, or None if there is no key with that name or ID
+    pub fn usage(&self, id: &str, now: u64) -> Option<KeyUsage> {
+        let Limits {
+            per_minute,
+            per_day,
+        } = self.limits(id)?;
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage.entry(id.to_string()).or_default();
+        Some(KeyUsage {
+            key_id: id.to_string(),
+            requests_total: usage.total,
+            rejected_total: usage.rejected,
+            minute: WindowUsage {
+                used: *usage.minute.current(MINUTE, now),
+                limit: per_minute,
+                resets_in_secs: MINUTE - now % MINUTE,
+            },
+            day: WindowUsage {
+                used: *usage.day.current(DAY, now),
+                limit: per_day,
+                resets_in_secs: DAY - now % DAY,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{Quota, Scope};
+
+    /// Start of a UTC day, 10 seconds into its first minute
+    const NOW: u64 = 1_760_572_810;
+
+    fn quotas(per_minute: Option<u32>, per_day: Option<u32>) -> KeyQuotas {
+        KeyQuotas::new(&BTreeMap::from([(
+            "staging".to_string(),
+            KeySettings {
+                key: "staging-key".to_string(),
+                requests_per_minute: per_minute,
+                requests_per_day: per_day,
+            },
+        )]))
+    }
+
+    #[test]
+    fn test_minute_quota() {
+        let quotas = quotas(Some(2), None);
+
+        assert!(quotas.record("staging", NOW).is_ok());
+        assert!(quotas.record("staging", NOW + 1).is_ok());
+        assert_eq!(
+            quotas.record("staging", NOW + 2),
+            Err(QuotaExceeded {
+                window: "minute",
+                limit: 2,
+                retry_after_secs: 48,
+            })
+        );
+        // The next minute starts a new count
+        assert!(quotas.record("staging", NOW + 50).is_ok());
+    }
+
+    #[test]
+    fn test_day_quota() {
+        let quotas = quotas(None, Some(1));
+
+        assert!(quotas.record("staging", NOW).is_ok());
+        let exceeded = quotas.record("staging", NOW + 3_600).unwrap_err();
+        assert_eq!(exceeded.window, "day");
+        assert_eq!(exceeded.retry_after_secs, DAY - 3_610);
+        assert!(quotas.record("staging", NOW + DAY).is_ok());
+    }
+
+    #[test]
+    fn test_managed_key_quota() {
+        let store = KeyStore::default();
+        let quota = Quota {
+            requests_per_minute: Some(1),
+            requests_per_day: None,
+        };
+        let key = store.create(None, Scope::Send, quota, 0).unwrap();
+        let quotas = quotas(None, None).with_managed_keys(store.clone());
+
+        assert!(quotas.record(&key.id, NOW).is_ok());
+        assert_eq!(quotas.record(&key.id, NOW).unwrap_err().window, "minute");
+        assert_eq!(quotas.usage(&key.id, NOW).unwrap().rejected_total, 1);
+        assert!(!quotas.is_configured(&key.id));
+
+        // Revoked keys are no longer counted
+        store.revoke(&key.id).unwrap();
+        assert!(quotas.record(&key.id, NOW).is_ok());
+        assert!(quotas.usage(&key.id, NOW).is_none());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_not_limited() {
+        let quotas = quotas(Some(0), Some(0));
+
+        assert!(quotas.record("production", NOW).is_ok());
+        assert!(quotas.usage("production", NOW).is_none());
+    }
+
+    #[test]
+    fn test_usage() {
+        let quotas = quotas(Some(1), Some(100));
+        quotas.record("staging", NOW).unwrap();
+        quotas.record("staging", NOW).unwrap_err();
+
+        let usage = quotas.usage("staging", NOW).unwrap();
+
+        assert_eq!(usage.requests_total, 1);
+        assert_eq!(usage.rejected_total, 1);
+        assert_eq!(usage.minute.used, 1);
+        assert_eq!(usage.minute.limit, Some(1));
+        assert_eq!(usage.minute.resets_in_secs, 50);
+        assert_eq!(usage.day.used, 1);
+        // Counts reset once the window has passed
+        assert_eq!(quotas.usage("staging", NOW + 60).unwrap().minute.used, 0);
+    }
+}
@@ -0,0 +1,309 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Telegram's limit for messages to the same group, per minute
+pub const DEFAULT_CHAT_RATE_PER_MINUTE: u32 = 20;
+
+/// Telegram's overall limit for messages sent by one bot, per second
+pub const DEFAULT_GLOBAL_RATE_PER_SECOND: u32 = 30;
+
+/// Messages a chat may receive back to back before throttling kicks in
+const CHAT_BURST: f64 = 3.0;
+
+/// Number of recent 429 responses kept for introspection
+const RECENT_THROTTLES: usize = 50;
+
+/// Token bucket; tokens go negative while sends are waiting for their turn
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: f64, per_second: f64, now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            capacity,
+            per_second,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.updated = self.updated.max(now);
+    }
+
+    /// Take a token, returning how long the caller has to wait for it
+    fn reserve(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        self.wait()
+    }
+
+    fn wait(&self) -> Duration {
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+
+    fn state(&self) -> BucketState {
+        BucketState {
+            tokens_remaining: self.tokens.max(0.0).floor() as u64,
+            capacity: self.capacity as u64,
+            refill_per_second: self.per_second,
+            waiting: (-self.tokens).max(0.0).ceil() as u64,
+            wait_secs: self.wait().as_secs_f64(),
+        }
+    }
+}
+
+/// A 429 "Too Many Requests" answer from Telegram
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Throttle {
+    pub chat_id: String,
+    pub retry_after_secs: u64,
+    /// Unix timestamp of the response
+    pub at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BucketState {
+    pub tokens_remaining: u64,
+    pub capacity: u64,
+    pub refill_per_second: f64,
+    /// Sends currently waiting for a token
+    pub waiting: u64,
+    /// Estimated wait for the next send
+    pub wait_secs: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatBucketState {
+    pub chat_id: String,
+    #[serde(flatten)]
+    pub bucket: BucketState,
+}
+
+/// Current limiter state, as returned by `GET /admin/ratelimits`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RateLimitSnapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub global: Option<BucketState>,
+    /// Chats that sent recently; idle chats with a full bucket are omitted
+    pub chats: Vec<ChatBucketState>,
+    /// Most recent first
+    pub recent_429s: Vec<Throttle>,
+}
+
+struct Limits {
+    global: Option<Bucket>,
+    chat_per_second: f64,
+    chats: HashMap<String, Bucket>,
+    throttles: VecDeque<Throttle>,
+}
+
+/// Keeps sends within Telegram's flood limits, per chat and for the whole
+/// bot. Cloning shares the same buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: Arc<Mutex<Limits>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHAT_RATE_PER_MINUTE, DEFAULT_GLOBAL_RATE_PER_SECOND)
+    }
+}
+
+impl RateLimiter {
+    /// Limiter allowing `chat_per_minute` messages per chat and
+    /// `global_per_second` overall; 0 turns a limit off
+    pub fn new(chat_per_minute: u32, global_per_second: u32) -> Self {
+        let global = (global_per_second > 0).then(|| {
+            let rate = f64::from(global_per_second);
+            Bucket::new(rate, rate, Instant::now())
+        });
+        Self {
+            limits: Arc::new(Mutex::new(Limits {
+                global,
+                chat_per_second: f64::from(chat_per_minute) / 60.0,
+                chats: HashMap::new(),
+                throttles: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Reserve a send to `chat_id`, returning how long to wait before sending
+    pub fn reserve(&self, chat_id: &str, now: Instant) -> Duration {
+        let mut limits = self.limits.lock().unwrap();
+        let global_wait = limits
+            .global
+            .as_mut()
+            .map_or(Duration::ZERO, |bucket| bucket.reserve(now));
+        if limits.chat_per_second <= 0.0 {
+            return global_wait;
+        }
+
+        // Forget chats whose bucket has refilled
+        limits.chats.retain(|_, bucket| {
+            bucket.refill(now);
+            bucket.tokens < bucket.capacity
+        });
+        let per_second = limits.chat_per_second;
+        let chat_wait = limits
+            .chats
+            .entry(chat_id.to_string())
+            .or_insert_with(|| Bucket::new(CHAT_BURST, per_second, now))
+            .reserve(now);
+        global_wait.max(chat_wait)
+    }
+
+    /// Record a 429 for `chat_id`, holding back its sends for `retry_after`
+    pub fn throttled(&self, chat_id: &str, retry_after: Duration, now: Instant) {
+        let mut limits = self.limits.lock().unwrap();
+        let per_second = limits.chat_per_second;
+        if per_second > 0.0 {
+            let bucket = limits
+                .chats
+                .entry(chat_id.to_string())
+                .or_insert_with(|| Bucket::new(CHAT_BURST, per_second, now));
+            bucket.refill(now);
+            bucket.tokens = bucket.tokens.min(-retry_after.as_secs_f64() * per_second);
+        }
+
+        if limits.throttles.len() == RECENT_THROTTLES {
+            limits.throttles.pop_back();
+        }
+        limits.throttles.push_front(Throttle {
+            chat_id: chat_id.to_string(),
+            retry_after_secs: retry_after.as_secs(),
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        });
+    }
+
+    pub fn snapshot(&self, now: Instant) -> RateLimitSnapshot {
+        let mut limits = self.limits.lock().unwrap();
+        let global = limits.global.as_mut().map(|bucket| {
+            bucket.refill(now);
+            bucket.state()
+        });
+        let mut chats: Vec<ChatBucketState> = limits
+            .chats
+            .iter_mut()
+            .filter_map(|(chat_id, bucket)| {
+                bucket.refill(now);
+                (bucket.tokens < bucket.capacity).then(|| ChatBucketState {
+                    chat_id: chat_id.clone(),
+                    bucket: bucket.state(),
+                })
+            })
+            .collect();
+        chats.sort_by(|a, b| a.chat_id.cmp(&b.chat_id));
+
+        RateLimitSnapshot {
+            global,
+            chats,
+            recent_429s: limits.throttles.iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_chat_burst_then_wait() {
+        let limiter = RateLimiter::new(60, 0);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve("-100", now), Duration::ZERO);
+        }
+        // One message per second once the burst is used up
+        assert_eq!(limiter.reserve("-100", now), SECOND);
+        assert_eq!(limiter.reserve("-100", now), 2 * SECOND);
+        // Other chats are unaffected
+        assert_eq!(limiter.reserve("-200", now), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_tokens_refill() {
+        let limiter = RateLimiter::new(60, 0);
+        let now = Instant::now();
+        for _ in 0..4 {
+            limiter.reserve("-100", now);
+        }
+
+        assert_eq!(limiter.reserve("-100", now + 2 * SECOND), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_global_limit() {
+        let limiter = RateLimiter::new(0, 2);
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve("-100", now), Duration::ZERO);
+        assert_eq!(limiter.reserve("-200", now), Duration::ZERO);
+        assert_eq!(limiter.reserve("-300", now), SECOND / 2);
+    }
+
+    #[test]
+    fn test_throttled_holds_back_chat() {
+        let limiter = RateLimiter::new(60, 0);
+        let now = Instant::now();
+
+        limiter.throttled("-100", 5 * SECOND, now);
+
+        assert_eq!(limiter.reserve("-100", now), 6 * SECOND);
+        let snapshot = limiter.snapshot(now);
+        assert_eq!(snapshot.recent_429s.len(), 1);
+        assert_eq!(snapshot.recent_429s[0].chat_id, "-100");
+        assert_eq!(snapshot.recent_429s[0].retry_after_secs, 5);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let limiter = RateLimiter::new(60, 30);
+        let now = Instant::now();
+        for _ in 0..5 {
+            limiter.reserve("-100", now);
+        }
+
+        let snapshot = limiter.snapshot(now);
+
+        let global = snapshot.global.unwrap();
+        assert_eq!(global.tokens_remaining, 25);
+        assert_eq!(global.capacity, 30);
+        assert_eq!(snapshot.chats.len(), 1);
+        let chat = &snapshot.chats[0];
+        assert_eq!(chat.chat_id, "-100");
+        assert_eq!(chat.bucket.tokens_remaining, 0);
+        assert_eq!(chat.bucket.waiting, 2);
+        assert_eq!(chat.bucket.wait_secs, 2.0);
+    }
+
+    #[test]
+    fn test_idle_chats_are_forgotten() {
+        let limiter = RateLimiter::new(60, 0);
+        let now = Instant::now();
+        limiter.reserve("-100", now);
+
+        assert_eq!(limiter.snapshot(now).chats.len(), 1);
+        assert!(limiter.snapshot(now + 10 * SECOND).chats.is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::notifier::Notifier;
+use anyhow::{Context, Result, bail};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Default for how long a bot verification answers readiness probes
+pub const DEFAULT_BOT_CHECK_CACHE_SECS: u64 = 60;
+
+/// Default for how long `healthcheck` waits for the server to answer
+pub const DEFAULT_PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// Outcome of the last `getMe` call, reused for `--bot-check-cache-secs` so
+/// readiness probes every few seconds don't each ask Telegram. Cloning shares
+/// the cached outcome.
+#[derive(Clone)]
+pub struct BotCheck {
+    ttl: Duration,
+    last: Arc<Mutex<Option<Checked>>>,
+}
+
+struct Checked {
+    at: Instant,
+    outcome: Result<(), String>,
+}
+
+impl BotCheck {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            last: Arc::default(),
+        }
+    }
+
+    /// Cache starting out with the verification done at startup
+    pub fn verified(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            last: Arc::new(Mutex::new(Some(Checked {
+                at: Instant::now(),
+                outcome: Ok(()),
+            }))),
+        }
+    }
+
+    /// Whether the bot token works, asking Telegram only once the cached
+    /// outcome is older than the TTL. Probes arriving meanwhile wait for the
+    /// same call.
+    pub async fn check(&self, notifier: &dyn Notifier) -> Result<(), String> {
+        let mut last = self.last.lock().await;
+        if let Some(checked) = last.as_ref()
+            && checked.at.elapsed() < self.ttl
+        {
+            return checked.outcome.clone();
+        }
+        let outcome = notifier.health_check().await.map_err(|e| e.to_string());
+        *last = Some(Checked {
+            at: Instant::now(),
+            outcome: outcome.clone(),
+        });
+        outcome
+    }
+}
+
+/// Ask the server at `server_url` whether it's ready (`healthcheck`), so
+/// container images need no curl for their health checks. `insecure`
+/// accepts any certificate of an HTTPS server.
+pub async fn probe(server_url: &str, timeout: Duration, insecure: bool) -> Result<()> {
+    let url = format!("{}/readyz", server_url.trim_end_matches('/'));
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(insecure)
+        .build()?;
+    let response = client
+        .get(&url)
+        .timeout(timeout)
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {url}"))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        bail!("Not ready ({status}): {body}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifier::mock::MockNotifier;
+    use crate::telegram::TelegramBot;
+    use mockito::Server;
+
+    #[tokio::test]
+    async fn test_outcome_is_cached() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/bottoken/getMe")
+            .with_status(200)
+            .with_body(r#"{"ok": true, "result": {"username": "alerts_bot"}}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let bot = TelegramBot::with_base_url("token".to_string(), &server.url());
+
+        let cached = BotCheck::new(Duration::from_secs(60));
+        assert_eq!(cached.check(&bot).await, Ok(()));
+        assert_eq!(cached.check(&bot).await, Ok(()));
+        // Verified at startup, so no call at all
+        let verified = BotCheck::verified(Duration::from_secs(60));
+        assert_eq!(verified.check(&bot).await, Ok(()));
+        let uncached = BotCheck::new(Duration::ZERO);
+        assert_eq!(uncached.check(&bot).await, Ok(()));
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_failure_is_cached() {
+        let notifier = MockNotifier::default();
+        *notifier.failing.lock().unwrap() = Some("Unauthorized".to_string());
+        let check = BotCheck::new(Duration::from_secs(60));
+        assert_eq!(
+            check.check(&notifier).await,
+            Err("Unauthorized".to_string())
+        );
+
+        *notifier.failing.lock().unwrap() = None;
+        assert_eq!(
+            check.check(&notifier).await,
+            Err("Unauthorized".to_string())
+        );
+        assert_eq!(BotCheck::new(Duration::ZERO).check(&notifier).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_probe() {
+        let mut server = Server::new_async().await;
+        let ready = server
+            .mock("GET", "/telegram/readyz")
+            .with_status(200)
+            .with_body(r#"{"status": "ready"}"#)
+            .create_async()
+            .await;
+        let url = format!("{}/telegram/", server.url());
+        assert!(probe(&url, Duration::from_secs(5), false).await.is_ok());
+        ready.remove_async().await;
+
+        server
+            .mock("GET", "/telegram/readyz")
+            .with_status(503)
+            .with_body(r#"{"status": "not_ready"}"#)
+            .create_async()
+            .await;
+        let error = probe(&url, Duration::from_secs(5), false)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("503"), "{error}");
+    }
+}
@@ -0,0 +1,144 @@
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use crate::targets::Targets;
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+
+/// Default priority of a route; lower priorities are checked first
+pub const DEFAULT_ROUTE_PRIORITY: i32 = 100;
+
+/// Sends notifications addressed to `chat` to `target` instead
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Route {
+    pub name: String,
+    /// Chat ID or target name the notification is addressed to, `"*"` for any
+    pub chat: String,
+    /// Only match messages containing this text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_contains: Option<String>,
+    /// Target name or chat ID to deliver to
+    pub target: String,
+    pub priority: i32,
+}
+
+impl Route {
+    fn matches(&self, notification: &Notification, targets: &Targets) -> bool {
+        let chat_matches = self.chat == "*"
+            || notification.chat_id
+                == targets
+                    .resolve(&self.chat)
+                    .unwrap_or_else(|| self.chat.clone());
+        chat_matches
+            && self
+                .message_contains
+                .as_deref()
+                .is_none_or(|text| notification.message.contains(text))
+    }
+}
+
+/// Routes applied to every notification, the first matching one winning.
+/// Target names are resolved when a notification is routed, so re-pointing a
+/// target applies to the routes using it. Cloning shares the same routes.
+#[derive(Clone, Default)]
+pub struct RouteTable {
+    routes: Arc<RwLock<Vec<Route>>>,
+    targets: Targets,
+}
+
+impl RouteTable {
+    pub fn new(targets: Targets) -> Self {
+        Self {
+            routes: Arc::default(),
+            targets,
+        }
+    }
+
+    /// Replace every route, ordering them by priority and then name
+    pub fn replace(&self, mut routes: Vec<Route>) {
+        routes.sort_by(|a, b| a.priority.cmp(&b.priority).then(a.name.cmp(&b.name)));
+        *self.routes.write().unwrap() = routes;
+    }
+
+    pub fn list(&self) -> Vec<Route> {
+        self.routes.read().unwrap().clone()
+    }
+
+    /// Chat ID the first matching route sends a notification to
+    pub fn route(&self, notification: &Notification) -> Option<String> {
+        let routes = self.routes.read().unwrap();
+        let route = routes
+            .iter()
+            .find(|route| route.matches(notification, &self.targets))?;
+        Some(
+            self.targets
+                .resolve(&route.target)
+                .unwrap_or_else(|| route.target.clone()),
+        )
+    }
+}
+
+impl Stage for RouteTable {
+    fn name(&self) -> &str {
+        "route-table"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Route
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        if let Some(chat_id) = self.route(notification) {
+            notification.chat_id = chat_id;
+        }
+        Ok(Outcome::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn route(name: &str, chat: &str, contains: Option<&str>, target: &str, priority: i32) -> Route {
+        Route {
+            name: name.to_string(),
+            chat: chat.to_string(),
+            message_contains: contains.map(str::to_string),
+            target: target.to_string(),
+            priority,
+        }
+    }
+
+    fn routed(table: &RouteTable, chat_id: &str, message: &str) -> String {
+        let mut notification = Notification {
+            chat_id: chat_id.to_string(),
+            message: message.to_string(),
+            ..Default::default()
+        };
+        table.process(&mut notification).unwrap();
+        notification.chat_id
+    }
+
+    #[test]
+    fn test_first_matching_route_wins() {
+        let targets = Targets::new(&HashMap::from([
+            ("ops-team".to_string(), "-100123".to_string()),
+            ("db-team".to_string(), "-100456".to_string()),
+        ]));
+        let table = RouteTable::new(targets.clone());
+        table.replace(vec![
+            route("catch-all", "*", None, "-100999", DEFAULT_ROUTE_PRIORITY),
+            route("database", "ops-team", Some("postgres"), "db-team", 10),
+        ]);
+
+        assert_eq!(routed(&table, "-100123", "postgres is down"), "-100456");
+        assert_eq!(routed(&table, "-100123", "Disk full"), "-100999");
+
+        // Targets are looked up for every notification
+        targets.put("db-team", "-100789");
+        assert_eq!(routed(&table, "-100123", "postgres is down"), "-100789");
+
+        table.replace(Vec::new());
+        assert_eq!(routed(&table, "-100123", "Disk full"), "-100123");
+    }
+}
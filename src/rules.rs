@@ -0,0 +1,303 @@
+use crate::pages::escape;
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use crate::scripting::{Hook, ScriptLimits};
+use crate::webhooks::Template;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::{debug, error};
+
+/// A routing rule (`[[rules]]` in the config file): notifications matching
+/// every condition set are sent the way the rule says. A rule without
+/// conditions matches every notification.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoutingRule {
+    /// Shown in the logs instead of the rule's number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Severities matched, ignoring case, e.g. `["critical", "error"]`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub severity: Vec<String>,
+    /// Sources matched, e.g. `["alertmanager", "grafana"]`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source: Vec<String>,
+    /// Labels the notification must have, with these values
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Chat ID or alias to send to instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat: Option<String>,
+    /// Send silently, or with sound even if the sender asked for silence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub silent: Option<bool>,
+    /// Message with `{{ <JSONPath> }}` placeholders filled in from the
+    /// notification's `message`, `severity`, `source`, `chat_id` and
+    /// `labels`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Rhai script run last, which may rewrite or drop the notification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hook: Option<PathBuf>,
+}
+
+/// A rule with its template parsed and hook compiled
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub rule: RoutingRule,
+    template: Option<Template>,
+    hook: Option<Hook>,
+}
+
+impl Rule {
+    pub fn new(rule: RoutingRule, limits: ScriptLimits) -> Result<Self> {
+        if rule.chat.is_none()
+            && rule.silent.is_none()
+            && rule.template.is_none()
+            && rule.hook.is_none()
+        {
+            bail!("A rule needs a chat, silent, template or hook to apply");
+        }
+        let template = rule.template.as_deref().map(Template::parse).transpose()?;
+        let hook = rule
+            .hook
+            .as_deref()
+            .map(|path| Hook::load(path, limits))
+            .transpose()?;
+        Ok(Self {
+            rule,
+            template,
+            hook,
+        })
+    }
+
+    fn matches(&self, notification: &Notification) -> bool {
+        let rule = &self.rule;
+        let severity_matches = rule.severity.is_empty()
+            || notification.severity.as_deref().is_some_and(|severity| {
+                rule.severity
+                    .iter()
+                    .any(|wanted| wanted.eq_ignore_ascii_case(severity))
+            });
+        let source_matches = rule.source.is_empty() || rule.source.contains(&notification.source);
+        severity_matches
+            && source_matches
+            && rule
+                .labels
+                .iter()
+                .all(|(name, value)| notification.labels.get(name) == Some(value))
+    }
+
+    fn apply(&self, notification: &mut Notification) -> Outcome {
+        if let Some(template) = &self.template {
+            // The message is already in the notification's parse mode; the
+            // other values are escaped for HTML
+            let html = notification
+                .parse_mode
+                .as_deref()
+                .is_some_and(|mode| mode.eq_ignore_ascii_case("html"));
+            let text = |value: &str| {
+                if html {
+                    escape(value)
+                } else {
+                    value.to_string()
+                }
+            };
+            let values = json!({
+                "message": notification.message,
+                "severity": notification.severity.as_deref().map(text),
+                "source": text(&notification.source),
+                "chat_id": notification.chat_id,
+                "labels": notification
+                    .labels
+                    .iter()
+                    .map(|(name, value)| (name.clone(), text(value)))
+                    .collect::<BTreeMap<_, _>>(),
+            });
+            notification.message = template.render(&values, false);
+        }
+        if let Some(chat_id) = &self.rule.chat {
+            notification.chat_id = chat_id.clone();
+        }
+        if let Some(silent) = self.rule.silent {
+            notification.disable_notification = silent;
+        }
+        let Some(hook) = &self.hook else {
+            return Outcome::Continue;
+        };
+        hook.apply(notification).unwrap_or_else(|e| {
+            // Like a failing routing script, leave the notification as it is
+            error!(
+                "❌ {}; sending notification {} unchanged",
+                e, notification.delivery_id
+            );
+            Outcome::Continue
+        })
+    }
+}
+
+/// Applies the first rule a notification matches
+pub struct RoutingRules {
+    rules: Vec<Rule>,
+}
+
+impl RoutingRules {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+}
+
+impl Stage for RoutingRules {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Route
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        let Some((index, rule)) = self
+            .rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(notification))
+        else {
+            return Ok(Outcome::Continue);
+        };
+        debug!(
+            "🧭 Notification {} matched rule {}",
+            notification.delivery_id,
+            rule.rule
+                .name
+                .clone()
+                .unwrap_or_else(|| (index + 1).to_string())
+        );
+        Ok(rule.apply(notification))
+    }
+}
+
+/// Parse every rule, numbering the invalid one in the error
+pub fn compile(rules: Vec<RoutingRule>, limits: ScriptLimits) -> Result<Vec<Rule>> {
+    rules
+        .into_iter()
+        .enumerate()
+        .map(|(index, rule)| {
+            let name = rule.name.clone().unwrap_or_else(|| (index + 1).to_string());
+            Rule::new(rule, limits).with_context(|| format!("Invalid rule {name}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> RoutingRules {
+        #[derive(Deserialize)]
+        struct File {
+            rules: Vec<RoutingRule>,
+        }
+        let file: File = toml::from_str(toml).unwrap();
+        RoutingRules::new(compile(file.rules, ScriptLimits::default()).unwrap())
+    }
+
+    fn alert(severity: &str, team: &str) -> Notification {
+        Notification {
+            message: "<b>Disk full</b>".to_string(),
+            chat_id: "42".to_string(),
+            parse_mode: Some("HTML".to_string()),
+            source: "alertmanager".to_string(),
+            severity: Some(severity.to_string()),
+            labels: BTreeMap::from([("team".to_string(), team.to_string())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_applies() {
+        let stage = rules(
+            r#"
+[[rules]]
+name = "db-pages"
+severity = ["critical"]
+labels = { team = "db" }
+chat = "-100456"
+template = "🚨 {{ $.message }} ({{ $.labels.team }})"
+
+[[rules]]
+source = ["alertmanager"]
+severity = ["warning", "info"]
+silent = true
+"#,
+        );
+
+        let mut critical = alert("CRITICAL", "db");
+        stage.process(&mut critical).unwrap();
+        assert_eq!(critical.chat_id, "-100456");
+        assert_eq!(critical.message, "🚨 <b>Disk full</b> (db)");
+        assert!(!critical.disable_notification);
+
+        let mut warning = alert("warning", "db");
+        stage.process(&mut warning).unwrap();
+        assert_eq!(warning.chat_id, "42");
+        assert_eq!(warning.message, "<b>Disk full</b>");
+        assert!(warning.disable_notification);
+
+        let mut unmatched = alert("critical", "web");
+        unmatched.source = "api".to_string();
+        let expected = unmatched.clone();
+        stage.process(&mut unmatched).unwrap();
+        assert_eq!(unmatched, expected);
+    }
+
+    #[test]
+    fn test_rule_hook() {
+        let path = std::env::temp_dir().join(format!("tn-hook-{}.rhai", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"if labels.team == "web" { suppress = true; } else { message += " #" + labels.team; }"#,
+        )
+        .unwrap();
+        let rule = RoutingRule {
+            severity: vec!["critical".to_string()],
+            hook: Some(path.clone()),
+            ..Default::default()
+        };
+        let stage = RoutingRules::new(compile(vec![rule], ScriptLimits::default()).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let mut db = alert("critical", "db");
+        assert_eq!(stage.process(&mut db).unwrap(), Outcome::Continue);
+        assert_eq!(db.message, "<b>Disk full</b> #db");
+        assert!(matches!(
+            stage.process(&mut alert("critical", "web")).unwrap(),
+            Outcome::Drop { .. }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let error = compile(
+            vec![RoutingRule {
+                severity: vec!["critical".to_string()],
+                ..Default::default()
+            }],
+            ScriptLimits::default(),
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid rule 1");
+        assert!(
+            compile(
+                vec![RoutingRule {
+                    template: Some("{{ $.message".to_string()),
+                    ..Default::default()
+                }],
+                ScriptLimits::default()
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use crate::bots::Bots;
+use crate::pages::escape;
+use crate::telegram::MAX_MESSAGE_LENGTH;
+use crate::uptime::format_duration;
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tracing::{error, info};
+
+/// Lines of output included in the notification by default
+pub const DEFAULT_TAIL_LINES: usize = 20;
+
+/// Exit code when the command can't be started, as shells use for a
+/// command that isn't found
+const NOT_STARTED: i32 = 127;
+
+/// How a wrapped command ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    /// Exit code, None if the command was killed by a signal
+    pub code: Option<i32>,
+    pub duration: Duration,
+    /// Last lines of stdout and stderr, interleaved as they were written
+    pub tail: Vec<String>,
+}
+
+impl Outcome {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// Run `command`, passing its output through while keeping the last
+/// `tail_lines` lines. Fails if it can't be started.
+pub async fn execute(command: &[String], tail_lines: usize) -> Result<Outcome> {
+    let Some((program, args)) = command.split_first() else {
+        anyhow::bail!("No command given");
+    };
+    let started = Instant::now();
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let tail = Arc::new(Mutex::new(VecDeque::with_capacity(tail_lines)));
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| tokio::spawn(forward(out, tokio::io::stdout(), tail.clone(), tail_lines)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| tokio::spawn(forward(err, tokio::io::stderr(), tail.clone(), tail_lines)));
+    let status = child.wait().await?;
+    for task in [stdout, stderr].into_iter().flatten() {
+        let _ = task.await;
+    }
+
+    let tail = tail.lock().unwrap().drain(..).collect();
+    Ok(Outcome {
+        code: status.code(),
+        duration: started.elapsed(),
+        tail,
+    })
+}
+
+/// Copy a stream of the command to ours, keeping its last lines
+async fn forward(
+    from: impl AsyncRead + Unpin,
+    mut to: impl AsyncWrite + Unpin,
+    tail: Arc<Mutex<VecDeque<String>>>,
+    tail_lines: usize,
+) {
+    let mut reader = BufReader::new(from);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).await.unwrap_or(0) > 0 {
+        let _ = to.write_all(&line).await;
+        if tail_lines > 0 {
+            let mut tail = tail.lock().unwrap();
+            if tail.len() == tail_lines {
+                tail.pop_front();
+            }
+            tail.push_back(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+        line.clear();
+    }
+    let _ = to.flush().await;
+}
+
+/// The notification for a finished command: its result and duration, and
+/// the tail of its output, cut from the top to fit one message
+pub fn render(name: &str, outcome: &Outcome) -> String {
+    let took = format_duration(outcome.duration.as_secs());
+    let headline = match outcome.code {
+        Some(0) => format!("✅ <b>{}</b> succeeded in {took}", escape(name)),
+        Some(code) => format!(
+            "❌ <b>{}</b> failed with exit code {code} after {took}",
+            escape(name)
+        ),
+        None => format!("❌ <b>{}</b> was killed after {took}", escape(name)),
+    };
+    let mut lines: Vec<&str> = outcome.tail.iter().map(String::as_str).collect();
+    while !lines.is_empty() {
+        let message = format!("{headline}\n<pre>{}</pre>", escape(&lines.join("\n")));
+        if message.chars().count() <= MAX_MESSAGE_LENGTH {
+            return message;
+        }
+        lines.remove(0);
+    }
+    headline
+}
+
+/// Run the command and notify `chat_ids` of how it went, each with its
+/// bot; successes silently, and only if `failures_only` isn't set. Returns
+/// the exit code to exit with: the command's, or 1 if it succeeded but a
+/// notification couldn't be sent.
+pub async fn run(
+    bots: &Bots,
+    chat_ids: &[String],
+    name: &str,
+    command: &[String],
+    tail_lines: usize,
+    failures_only: bool,
+) -> Result<i32> {
+    let (message, code, success) = match execute(command, tail_lines).await {
+        Ok(outcome) => {
+            let code = outcome.code.unwrap_or(1);
+            (render(name, &outcome), code, outcome.success())
+        }
+        Err(e) => (
+            format!(
+                "❌ <b>{}</b> could not be started: {}",
+                escape(name),
+                escape(&e.to_string())
+            ),
+            NOT_STARTED,
+            false,
+        ),
+    };
+    if success && failures_only {
+        return Ok(code);
+    }
+    let mut failed = false;
+    for chat_id in chat_ids {
+        match bots
+            .for_chat(chat_id)
+            .send_message_advanced(chat_id, &message, Some("HTML"), success)
+            .await
+        {
+            Ok(_) => info!("📤 Sent the result of {} to chat ID: {}", name, chat_id),
+            Err(e) => {
+                error!(
+                    "❌ Failed to send the result of {} to {}: {}",
+                    name, chat_id, e
+                );
+                failed = true;
+            }
+        }
+    }
+    Ok(if failed && success { 1 } else { code })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telegram::TelegramBot;
+
+    #[test]
+    fn test_render() {
+        let outcome = Outcome {
+            code: Some(2),
+            duration: Duration::from_secs(75),
+            tail: vec!["copying <db>".to_string(), "disk full".to_string()],
+        };
+        assert_eq!(
+            render("backup.sh", &outcome),
+            "❌ <b>backup.sh</b> failed with exit code 2 after 1m 15s\n<pre>copying &lt;db&gt;\ndisk full</pre>"
+        );
+
+        let outcome = Outcome {
+            code: Some(0),
+            duration: Duration::from_millis(300),
+            tail: vec!["x".repeat(3000), "y".repeat(3000)],
+        };
+        let message = render("backup.sh", &outcome);
+        assert!(message.starts_with("✅ <b>backup.sh</b> succeeded in 0s\n<pre>yyy"));
+        assert!(!message.contains('x'));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_notifies_every_chat() {
+        let mut server = mockito::Server::new_async().await;
+        let mut sent = |token: &str, chat_id: &str| {
+            server
+                .mock("POST", format!("/bot{token}/sendMessage").as_str())
+                .match_body(mockito::Matcher::PartialJson(
+                    serde_json::json!({"chat_id": chat_id}),
+                ))
+                .with_body(r#"{"ok":true,"result":{"message_id":1}}"#)
+                .create()
+        };
+        let main = sent("1:main", "42");
+        let billing = sent("2:billing", "-100200");
+        let bots = Bots::new(TelegramBot::with_base_url(
+            "1:main".to_string(),
+            &server.url(),
+        ))
+        .with_bot(
+            "billing",
+            TelegramBot::with_base_url("2:billing".to_string(), &server.url()),
+            vec!["-100200".to_string()],
+            Vec::new(),
+        );
+        let chat_ids = ["42".to_string(), "-100200".to_string()];
+        let command = ["sh", "-c", "exit 3"].map(str::to_string);
+
+        let code = run(&bots, &chat_ids, "backup", &command, 5, false)
+            .await
+            .unwrap();
+        assert_eq!(code, 3);
+        main.assert_async().await;
+        billing.assert_async().await;
+
+        // A success that couldn't be reported everywhere exits with 1
+        let command = ["true".to_string()];
+        let chat_ids = ["42".to_string(), "-100999".to_string()];
+        let code = run(&bots, &chat_ids, "backup", &command, 5, false)
+            .await
+            .unwrap();
+        assert_eq!(code, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_keeps_tail() {
+        let command =
+            ["sh", "-c", "echo one; echo two >&2; echo three; exit 3"].map(str::to_string);
+        let outcome = execute(&command, 2).await.unwrap();
+        assert_eq!(outcome.code, Some(3));
+        assert_eq!(outcome.tail.len(), 2);
+        assert!(outcome.tail.contains(&"three".to_string()));
+
+        assert!(
+            execute(&["no-such-command-here".to_string()], 2)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+/// Request formats the server accepts, for which sample payloads can be generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Adapter {
+    /// The native `/notify` request body
+    Notify,
+    /// A Prometheus Alertmanager webhook
+    Alertmanager,
+    /// A Grafana alerting webhook
+    Grafana,
+    /// An Uptime Kuma monitor webhook
+    Uptime,
+    /// A PagerDuty Events API v2 event
+    Pagerduty,
+}
+
+impl Adapter {
+    /// Endpoint the payload is posted to
+    pub fn endpoint(self) -> &'static str {
+        match self {
+            Adapter::Notify => "/notify",
+            Adapter::Alertmanager => "/integrations/alertmanager",
+            Adapter::Grafana => "/integrations/grafana",
+            Adapter::Uptime => "/integrations/uptime",
+            Adapter::Pagerduty => "/integrations/pagerduty",
+        }
+    }
+}
+
+/// Alert lifecycle state to simulate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// A realistic example payload for `adapter` in `state`
+pub fn sample_payload(adapter: Adapter, state: AlertState) -> Value {
+    match adapter {
+        Adapter::Notify => {
+            let message = match state {
+                AlertState::Firing => {
+                    "🔥 *[FIRING]* HighCpuUsage on `web-1`\nCPU usage has been above 90% for 5 minutes."
+                }
+                AlertState::Resolved => {
+                    "✅ *[RESOLVED]* HighCpuUsage on `web-1`\nCPU usage is back below 90%."
+                }
+            };
+            json!({
+                "message": message,
+                "parse_mode": "Markdown",
+                "disable_notification": state == AlertState::Resolved,
+            })
+        }
+        Adapter::Alertmanager => {
+            let (status, ends_at) = match state {
+                AlertState::Firing => ("firing", "0001-01-01T00:00:00Z"),
+                AlertState::Resolved => ("resolved", "2026-01-15T10:20:00Z"),
+            };
+            let labels = json!({
+                "alertname": "HighCpuUsage",
+                "instance": "web-1",
+                "severity": "critical",
+            });
+            json!({
+                "version": "4",
+                "groupKey": "{}:{alertname=\"HighCpuUsage\"}",
+                "truncatedAlerts": 0,
+                "status": status,
+                "receiver": "telegram",
+                "groupLabels": {"alertname": "HighCpuUsage"},
+                "commonLabels": labels,
+                "commonAnnotations": {"summary": "CPU usage above 90% for 5 minutes"},
+                "externalURL": "http://localhost:9093",
+                "alerts": [{
+                    "status": status,
+                    "labels": labels,
+                    "annotations": {"summary": "CPU usage above 90% for 5 minutes"},
+                    "startsAt": "2026-01-15T10:00:00Z",
+                    "endsAt": ends_at,
+                    "generatorURL": "http://localhost:9090/graph",
+                    "fingerprint": "5d1b9e2c7a3f4e60",
+                }],
+            })
+        }
+        Adapter::Grafana => {
+            let (status, ends_at) = match state {
+                AlertState::Firing => ("firing", "0001-01-01T00:00:00Z"),
+                AlertState::Resolved => ("resolved", "2026-01-15T10:20:00Z"),
+            };
+            let labels = json!({
+                "alertname": "High memory usage",
+                "grafana_folder": "Infrastructure",
+                "instance": "web-1",
+            });
+            json!({
+                "version": "1",
+                "orgId": 1,
+                "status": status,
+                "state": if state == AlertState::Firing { "alerting" } else { "ok" },
+                "receiver": "telegram",
+                "groupLabels": {"alertname": "High memory usage"},
+                "commonLabels": labels,
+                "commonAnnotations": {"summary": "Memory usage above 90% for 10 minutes"},
+                "externalURL": "http://localhost:3001/",
+                "truncatedAlerts": 0,
+                "alerts": [{
+                    "status": status,
+                    "labels": labels,
+                    "annotations": {"summary": "Memory usage above 90% for 10 minutes"},
+                    "startsAt": "2026-01-15T10:00:00Z",
+                    "endsAt": ends_at,
+                    "dashboardURL": "http://localhost:3001/d/node?orgId=1",
+                    "panelURL": "http://localhost:3001/d/node?orgId=1&viewPanel=4",
+                    "fingerprint": "c6eadffa33fcdf37",
+                }],
+            })
+        }
+        Adapter::Uptime => {
+            let (status, time, msg) = match state {
+                AlertState::Firing => (0, "2026-01-15 10:00:00.000", "connect ECONNREFUSED"),
+                AlertState::Resolved => (1, "2026-01-15 10:12:05.000", "200 - OK"),
+            };
+            let label = if status == 0 { "🔴 Down" } else { "✅ Up" };
+            json!({
+                "heartbeat": {
+                    "monitorID": 4,
+                    "status": status,
+                    "time": time,
+                    "msg": msg,
+                    "important": true,
+                    "duration": 60,
+                },
+                "monitor": {
+                    "id": 4,
+                    "name": "Checkout API",
+                    "type": "http",
+                    "url": "https://shop.example.com/health",
+                },
+                "msg": format!("[Checkout API] [{label}] {msg}"),
+            })
+        }
+        Adapter::Pagerduty => match state {
+            AlertState::Firing => json!({
+                "routing_key": "R0123456789ABCDEF0123456789ABCDE",
+                "event_action": "trigger",
+                "dedup_key": "high-cpu-web-1",
+                "payload": {
+                    "summary": "CPU usage above 90% on web-1",
+                    "source": "web-1",
+                    "severity": "critical",
+                    "component": "nginx",
+                    "group": "web",
+                    "custom_details": {"cpu": "93%"},
+                },
+                "client": "Prometheus",
+                "client_url": "http://localhost:9090/graph",
+            }),
+            AlertState::Resolved => json!({
+                "routing_key": "R0123456789ABCDEF0123456789ABCDE",
+                "event_action": "resolve",
+                "dedup_key": "high-cpu-web-1",
+            }),
+        },
+    }
+}
+
+/// A `curl` command posting the payload to a local server
+pub fn curl_command(adapter: Adapter, payload: &Value, base_url: &str) -> String {
+    let body = payload.to_string().replace('\'', r"'\''");
+    format!(
+        "curl -X POST {}{} \\\n  -H \"Content-Type: application/json\" \\\n  -d '{}'",
+        base_url.trim_end_matches('/'),
+        adapter.endpoint(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alertmanager::{self, Webhook};
+    use crate::api::SendNotificationRequest;
+    use crate::grafana;
+    use crate::pagerduty;
+    use crate::uptime;
+
+    #[test]
+    fn test_notify_samples_are_valid_requests() {
+        for state in [AlertState::Firing, AlertState::Resolved] {
+            let payload = sample_payload(Adapter::Notify, state);
+            let request: SendNotificationRequest = serde_json::from_value(payload).unwrap();
+            assert!(!request.message.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_alertmanager_samples_render() {
+        for state in [AlertState::Firing, AlertState::Resolved] {
+            let payload = sample_payload(Adapter::Alertmanager, state);
+            let webhook: Webhook = serde_json::from_value(payload).unwrap();
+            let request = alertmanager::render(&webhook);
+            assert!(request.message.contains("HighCpuUsage"));
+            assert_eq!(
+                request.disable_notification,
+                Some(state == AlertState::Resolved)
+            );
+        }
+    }
+
+    #[test]
+    fn test_grafana_samples_render() {
+        for state in [AlertState::Firing, AlertState::Resolved] {
+            let payload = sample_payload(Adapter::Grafana, state);
+            let webhook: grafana::Webhook = serde_json::from_value(payload).unwrap();
+            let request = grafana::render(&webhook);
+            assert!(request.message.contains("High memory usage"));
+            assert_eq!(request.buttons.unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_uptime_samples_render() {
+        for (state, status) in [
+            (AlertState::Firing, uptime::Status::Down),
+            (AlertState::Resolved, uptime::Status::Up),
+        ] {
+            let payload = sample_payload(Adapter::Uptime, state);
+            let webhook: uptime::Webhook = serde_json::from_value(payload).unwrap();
+            assert_eq!(webhook.status(), Some(status));
+            assert!(
+                uptime::render(&webhook, None)
+                    .message
+                    .contains("Checkout API")
+            );
+        }
+    }
+
+    #[test]
+    fn test_pagerduty_samples_are_valid_events() {
+        for state in [AlertState::Firing, AlertState::Resolved] {
+            let payload = sample_payload(Adapter::Pagerduty, state);
+            let event: pagerduty::Event = serde_json::from_value(payload).unwrap();
+            event.validate().unwrap();
+            assert_eq!(event.dedup_key.as_deref(), Some("high-cpu-web-1"));
+        }
+    }
+
+    #[test]
+    fn test_resolved_sample_is_silent() {
+        let payload = sample_payload(Adapter::Notify, AlertState::Resolved);
+        assert_eq!(payload["disable_notification"], true);
+        assert!(payload["message"].as_str().unwrap().contains("RESOLVED"));
+    }
+
+    #[test]
+    fn test_curl_command() {
+        let payload = json!({"message": "it's down"});
+        let command = curl_command(Adapter::Notify, &payload, "http://localhost:3000/");
+
+        assert!(command.starts_with("curl -X POST http://localhost:3000/notify"));
+        assert!(command.contains(r#"-d '{"message":"it'\''s down"}'"#));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::delivery::DeliveryQueue;
+use crate::history::{DeliveryStatus, History};
+use crate::pipeline::Notification;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, SecondsFormat};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// How often scheduled notifications are checked for being due
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Notifications held until their `send_at` time, then queued for delivery.
+/// Cloning shares the scheduled notifications.
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    pending: Arc<Mutex<HashMap<String, Notification>>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold a notification until its `send_at` time
+    pub fn schedule(&self, notification: Notification) {
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(notification.delivery_id.clone(), notification);
+    }
+
+    /// Scheduled notifications, the next one due first
+    pub fn list(&self) -> Vec<Notification> {
+        let mut pending: Vec<_> = self.pending.lock().unwrap().values().cloned().collect();
+        pending.sort_by_key(|notification| notification.send_at);
+        pending
+    }
+
+    /// Remove a notification that hasn't been sent yet
+    pub fn cancel(&self, delivery_id: &str) -> Option<Notification> {
+        self.pending.lock().unwrap().remove(delivery_id)
+    }
+
+    /// Take the notifications due at `now` (Unix time), the earliest first
+    pub fn due(&self, now: u64) -> Vec<Notification> {
+        let mut pending = self.pending.lock().unwrap();
+        let due: Vec<String> = pending
+            .values()
+            .filter(|notification| notification.send_at.is_none_or(|at| at <= now))
+            .map(|notification| notification.delivery_id.clone())
+            .collect();
+        let mut due: Vec<_> = due.iter().filter_map(|id| pending.remove(id)).collect();
+        due.sort_by_key(|notification| notification.send_at);
+        due
+    }
+}
+
+/// Unix time of an RFC 3339 timestamp such as `2026-10-16T09:00:00+02:00`
+pub fn parse_time(value: &str) -> Result<u64> {
+    let time = DateTime::parse_from_rfc3339(value.trim())
+        .with_context(|| format!("{value:?} is not an RFC 3339 timestamp"))?;
+    match u64::try_from(time.timestamp()) {
+        Ok(seconds) => Ok(seconds),
+        Err(_) => bail!("{value:?} is before 1970"),
+    }
+}
+
+/// RFC 3339 timestamp in UTC of a Unix time
+pub fn format_time(seconds: u64) -> String {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+/// Background task queueing scheduled notifications once they are due
+pub async fn deliver_due(scheduler: Scheduler, queue: DeliveryQueue, history: History) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        for notification in scheduler.due(unix_now()) {
+            let delivery_id = notification.delivery_id.clone();
+            history.set_status(&delivery_id, DeliveryStatus::Queued);
+            match queue.enqueue(notification) {
+                Ok(()) => info!("⏰ Scheduled notification {} queued", delivery_id),
+                Err(_) => {
+                    warn!(
+                        "⚠️ Delivery queue full, dropping scheduled notification {}",
+                        delivery_id
+                    );
+                    history.set_status(
+                        &delivery_id,
+                        DeliveryStatus::Failed {
+                            error: "Delivery queue full".to_string(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(delivery_id: &str, send_at: u64) -> Notification {
+        Notification {
+            message: "Standup in 10 minutes".to_string(),
+            chat_id: "-100123".to_string(),
+            delivery_id: delivery_id.to_string(),
+            send_at: Some(send_at),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_and_format_time() {
+        assert_eq!(parse_time("2026-10-16T09:00:00Z").unwrap(), 1_792_141_200);
+        assert_eq!(
+            parse_time("2026-10-16T11:00:00+02:00").unwrap(),
+            1_792_141_200
+        );
+        assert_eq!(format_time(1_792_141_200), "2026-10-16T09:00:00Z");
+        assert!(parse_time("2026-10-16 09:00").is_err());
+        assert!(parse_time("1969-12-31T23:59:59Z").is_err());
+    }
+
+    #[test]
+    fn test_due_and_cancel() {
+        let scheduler = Scheduler::new();
+        scheduler.schedule(notification("later", 200));
+        scheduler.schedule(notification("sooner", 100));
+        scheduler.schedule(notification("cancelled", 100));
+
+        let ids = |notifications: Vec<Notification>| -> Vec<String> {
+            notifications
+                .into_iter()
+                .map(|notification| notification.delivery_id)
+                .collect()
+        };
+        assert_eq!(ids(scheduler.list())[2], "later");
+        assert!(scheduler.cancel("cancelled").is_some());
+        assert!(scheduler.cancel("cancelled").is_none());
+
+        assert!(scheduler.due(99).is_empty());
+        assert_eq!(ids(scheduler.due(150)), vec!["sooner"]);
+        assert_eq!(ids(scheduler.list()), vec!["later"]);
+        assert_eq!(ids(scheduler.due(200)), vec!["later"]);
+        assert!(scheduler.list().is_empty());
+    }
+}
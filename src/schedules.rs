@@ -0,0 +1,300 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, TimeZone, Timelike};
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+/// A recurring notification (`[schedules.<id>]` in the config file or
+/// `POST /schedules`)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schedule {
+    /// Cron expression in the server's local time, e.g. `50 9 * * 1-5`; a
+    /// sixth leading field gives the seconds
+    pub cron: String,
+    pub message: String,
+    /// Chat to send to; the default chat if neither this nor `chat` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<String>,
+    /// Chat alias, instead of `chat_id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(default)]
+    pub disable_notification: bool,
+}
+
+impl Schedule {
+    /// First run strictly after `after`, ignoring its fractions of a second
+    pub fn next_run<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Result<DateTime<Tz>> {
+        let after = after.with_nanosecond(0).unwrap_or_else(|| after.clone());
+        parse_cron(&self.cron)?
+            .find_next_occurrence(&after, false)
+            .map_err(|e| anyhow!("{e:?}"))
+            .with_context(|| format!("No next run for {:?}", self.cron))
+    }
+}
+
+/// Check a cron expression, returning why it is invalid
+pub fn parse_cron(cron: &str) -> Result<Cron> {
+    Cron::new(cron.trim())
+        .with_seconds_optional()
+        .parse()
+        .map_err(|e| anyhow!("{cron:?} is not a valid cron expression: {e:?}"))
+}
+
+/// Where a schedule was defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The config file; can't be changed at runtime
+    Config,
+    /// `POST /schedules`
+    Api,
+}
+
+/// Recurring notifications from the config file plus those created through
+/// the API, which are saved to `--schedules-file` when set so they survive
+/// restarts. Cloning shares the same schedules.
+#[derive(Clone, Default)]
+pub struct ScheduleStore {
+    path: Option<Arc<PathBuf>>,
+    configured: Arc<BTreeMap<String, Schedule>>,
+    created: Arc<RwLock<BTreeMap<String, Schedule>>>,
+}
+
+impl ScheduleStore {
+    /// Store with the schedules from the config file and those saved at
+    /// `path`; without a path created schedules only live until the server
+    /// stops
+    pub fn open(configured: BTreeMap<String, Schedule>, path: Option<&Path>) -> Result<Self> {
+        for (id, schedule) in &configured {
+            parse_cron(&schedule.cron).with_context(|| format!("Invalid schedule {id}"))?;
+        }
+        let created = match path {
+            Some(path) if path.exists() => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read schedules file {}", path.display()))?;
+                serde_json::from_str(&contents)
+                    .with_context(|| format!("Invalid schedules file {}", path.display()))?
+            }
+            _ => BTreeMap::new(),
+        };
+        Ok(Self {
+            path: path.map(|path| Arc::new(path.to_path_buf())),
+            configured: Arc::new(configured),
+            created: Arc::new(RwLock::new(created)),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.list().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every schedule by ID; the config file wins over a created one with
+    /// the same ID
+    pub fn list(&self) -> Vec<(String, Schedule, Source)> {
+        let created = self.created.read().unwrap();
+        let mut schedules: BTreeMap<_, _> = created
+            .iter()
+            .map(|(id, schedule)| (id.clone(), (schedule.clone(), Source::Api)))
+            .collect();
+        for (id, schedule) in self.configured.iter() {
+            schedules.insert(id.clone(), (schedule.clone(), Source::Config));
+        }
+        schedules
+            .into_iter()
+            .map(|(id, (schedule, source))| (id, schedule, source))
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<(Schedule, Source)> {
+        if let Some(schedule) = self.configured.get(id) {
+            return Some((schedule.clone(), Source::Config));
+        }
+        let created = self.created.read().unwrap();
+        created
+            .get(id)
+            .map(|schedule| (schedule.clone(), Source::Api))
+    }
+
+    /// Save a new schedule under `id`, or a generated ID if None. Returns the
+    /// ID, or None if a schedule with that ID exists.
+    pub fn create(&self, id: Option<String>, schedule: Schedule) -> Result<Option<String>> {
+        parse_cron(&schedule.cron)?;
+        let id = id.unwrap_or_else(|| Uuid::new_v4().simple().to_string()[..12].to_string());
+        let mut created = self.created.write().unwrap();
+        if self.configured.contains_key(&id) || created.contains_key(&id) {
+            return Ok(None);
+        }
+        let mut updated = created.clone();
+        updated.insert(id.clone(), schedule);
+        self.save(&updated)?;
+        *created = updated;
+        Ok(Some(id))
+    }
+
+    /// Remove a created schedule, returning it if there was one
+    pub fn delete(&self, id: &str) -> Result<Option<Schedule>> {
+        let mut created = self.created.write().unwrap();
+        if !created.contains_key(id) {
+            return Ok(None);
+        }
+        let mut updated = created.clone();
+        let deleted = updated.remove(id);
+        self.save(&updated)?;
+        *created = updated;
+        Ok(deleted)
+    }
+
+    /// Schedules with a run after `after` and no later than `until`. A run
+    /// missed while the server was down isn't made up for.
+    pub fn due<Tz: TimeZone>(
+        &self,
+        after: &DateTime<Tz>,
+        until: &DateTime<Tz>,
+    ) -> Vec<(String, Schedule)> {
+        self.list()
+            .into_iter()
+            .filter(|(_, schedule, _)| {
+                schedule
+                    .next_run(after)
+                    .is_ok_and(|next_run| next_run <= *until)
+            })
+            .map(|(id, schedule, _)| (id, schedule))
+            .collect()
+    }
+
+    /// Write the schedules to a temporary file next to the schedules file and
+    /// move it into place, so a crash can't leave a half-written file
+    fn save(&self, schedules: &BTreeMap<String, Schedule>) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(schedules)?;
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, json)
+            .with_context(|| format!("Failed to write schedules file {}", temp.display()))?;
+        std::fs::rename(&temp, path.as_path())
+            .with_context(|| format!("Failed to replace schedules file {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn schedule(cron: &str) -> Schedule {
+        Schedule {
+            cron: cron.to_string(),
+            message: "Standup in 10 minutes".to_string(),
+            chat_id: None,
+            chat: Some("ops-team".to_string()),
+            parse_mode: None,
+            disable_notification: false,
+        }
+    }
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_next_run() {
+        // Weekdays at 09:50; 2026-10-16 is a Friday
+        let standup = schedule("50 9 * * 1-5");
+        assert_eq!(
+            standup.next_run(&time("2026-10-16T09:00:00Z")).unwrap(),
+            time("2026-10-16T09:50:00Z")
+        );
+        assert_eq!(
+            standup.next_run(&time("2026-10-16T09:50:00Z")).unwrap(),
+            time("2026-10-19T09:50:00Z")
+        );
+        assert_eq!(
+            schedule("30 0 8 * * *")
+                .next_run(&time("2026-10-16T09:00:00Z"))
+                .unwrap(),
+            time("2026-10-17T08:00:30Z")
+        );
+        assert!(parse_cron("50 9 * *").is_err());
+        assert!(parse_cron("every monday").is_err());
+    }
+
+    #[test]
+    fn test_due() {
+        let store = ScheduleStore::open(
+            BTreeMap::from([("standup".to_string(), schedule("50 9 * * 1-5"))]),
+            None,
+        )
+        .unwrap();
+        store
+            .create(Some("weekly".to_string()), schedule("0 10 * * 1"))
+            .unwrap();
+
+        let ids = |due: Vec<(String, Schedule)>| -> Vec<String> {
+            due.into_iter().map(|(id, _)| id).collect()
+        };
+        assert_eq!(
+            ids(store.due(&time("2026-10-16T09:49:59Z"), &time("2026-10-16T09:50:00Z"))),
+            vec!["standup"]
+        );
+        // Each run fires once, however the checks fall within the second
+        assert_eq!(
+            ids(store.due(
+                &time("2026-10-16T09:49:59.7Z"),
+                &time("2026-10-16T09:50:00.6Z")
+            )),
+            vec!["standup"]
+        );
+        assert!(
+            store
+                .due(
+                    &time("2026-10-16T09:50:00.6Z"),
+                    &time("2026-10-16T09:50:01.5Z")
+                )
+                .is_empty()
+        );
+        assert_eq!(
+            ids(store.due(&time("2026-10-19T09:59:59Z"), &time("2026-10-19T10:00:00Z"))),
+            vec!["weekly"]
+        );
+    }
+
+    #[test]
+    fn test_create_and_delete() {
+        let dir = std::env::temp_dir().join(format!("tn-schedules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("schedules.json");
+        let configured = BTreeMap::from([("standup".to_string(), schedule("50 9 * * 1-5"))]);
+        let store = ScheduleStore::open(configured.clone(), Some(&path)).unwrap();
+
+        let id = store.create(None, schedule("0 10 * * 1")).unwrap().unwrap();
+        assert_eq!(id.len(), 12);
+        assert_eq!(
+            store
+                .create(Some("standup".to_string()), schedule("0 10 * * 1"))
+                .unwrap(),
+            None
+        );
+        assert!(store.create(None, schedule("whenever")).is_err());
+
+        // Created schedules survive a restart, config ones can't be deleted
+        let reopened = ScheduleStore::open(configured, Some(&path)).unwrap();
+        assert_eq!(reopened.len(), 2);
+        assert_eq!(reopened.get(&id).unwrap().1, Source::Api);
+        assert_eq!(reopened.delete("standup").unwrap(), None);
+        assert!(reopened.delete(&id).unwrap().is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(reopened.len(), 1);
+    }
+}
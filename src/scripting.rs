@@ -0,0 +1,558 @@
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use anyhow::{Context, Result};
+use rhai::{AST, Dynamic, Engine, EvalAltResult, Map, Scope};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::error;
+
+/// Default instruction budget for a single script evaluation
+pub const DEFAULT_MAX_OPERATIONS: u64 = 100_000;
+
+/// Default wall-clock budget for a single script evaluation
+pub const DEFAULT_TIMEOUT_MS: u64 = 50;
+
+/// Sandbox limits applied to every script evaluation
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptLimits {
+    pub max_operations: u64,
+    pub timeout: Duration,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: DEFAULT_MAX_OPERATIONS,
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+        }
+    }
+}
+
+/// Input made available to a routing script as scope variables
+#[derive(Debug, Clone)]
+pub struct RoutingContext {
+    pub message: String,
+    pub chat_id: String,
+    pub parse_mode: Option<String>,
+    pub disable_notification: bool,
+}
+
+/// Outcome of evaluating a routing script
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoutingDecision {
+    /// Deliver the message to `chat_id`
+    Send {
+        chat_id: String,
+        disable_notification: bool,
+    },
+    /// Drop the message without sending it
+    Suppress { reason: String },
+}
+
+/// A compiled Rhai script deciding where (and whether) a notification is delivered.
+///
+/// The script sees `message`, `chat_id`, `parse_mode` and `silent` as variables.
+/// It may reassign `chat_id` and `silent`, or set `suppress = true` (optionally
+/// with a `reason` string) to drop the notification.
+pub struct RoutingScript {
+    ast: AST,
+    limits: ScriptLimits,
+}
+
+impl RoutingScript {
+    pub fn load(path: &Path, limits: ScriptLimits) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read routing script {}", path.display()))?;
+        Self::from_source(&source, limits)
+            .with_context(|| format!("Failed to compile routing script {}", path.display()))
+    }
+
+    pub fn from_source(source: &str, limits: ScriptLimits) -> Result<Self> {
+        let ast = sandboxed_engine(limits)
+            .compile(source)
+            .map_err(|e| anyhow::anyhow!("Script syntax error: {e}"))?;
+        Ok(Self { ast, limits })
+    }
+
+    pub fn evaluate(&self, context: &RoutingContext) -> Result<RoutingDecision> {
+        let engine = sandboxed_engine(self.limits);
+
+        let mut scope = Scope::new();
+        scope.push("message", context.message.clone());
+        scope.push("chat_id", context.chat_id.clone());
+        scope.push("parse_mode", context.parse_mode.clone().unwrap_or_default());
+        scope.push("silent", context.disable_notification);
+        scope.push("suppress", false);
+        scope.push("reason", String::new());
+
+        engine
+            .run_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| eval_error(*e, "Routing script", self.limits))?;
+
+        if scope.get_value::<bool>("suppress").unwrap_or(false) {
+            let reason = scope
+                .get_value::<String>("reason")
+                .filter(|r| !r.is_empty())
+                .unwrap_or_else(|| "suppressed by routing script".to_string());
+            return Ok(RoutingDecision::Suppress { reason });
+        }
+
+        let chat_id = scope
+            .get_value::<String>("chat_id")
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| {
+                anyhow::anyhow!("Routing script must leave `chat_id` as a non-empty string")
+            })?;
+        let disable_notification = scope
+            .get_value::<bool>("silent")
+            .unwrap_or(context.disable_notification);
+
+        Ok(RoutingDecision::Send {
+            chat_id,
+            disable_notification,
+        })
+    }
+}
+
+/// Runs in the route phase. A failing script leaves the requested route in
+/// place so alerts are never lost.
+impl Stage for RoutingScript {
+    fn name(&self) -> &str {
+        "routing-script"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Route
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        let context = RoutingContext {
+            message: notification.message.clone(),
+            chat_id: notification.chat_id.clone(),
+            parse_mode: notification.parse_mode.clone(),
+            disable_notification: notification.disable_notification,
+        };
+
+        match self.evaluate(&context) {
+            Ok(RoutingDecision::Send {
+                chat_id,
+                disable_notification,
+            }) => {
+                notification.chat_id = chat_id;
+                notification.disable_notification = disable_notification;
+                Ok(Outcome::Continue)
+            }
+            Ok(RoutingDecision::Suppress { reason }) => Ok(Outcome::Drop { reason }),
+            Err(e) => {
+                error!("❌ {}; using requested route", e);
+                Ok(Outcome::Continue)
+            }
+        }
+    }
+}
+
+/// A routing script that can be replaced while the server runs, e.g. when a
+/// new version is pulled from the config repository. Until one is loaded
+/// notifications keep their requested route.
+#[derive(Clone, Default)]
+pub struct SharedRoutingScript {
+    script: Arc<RwLock<Option<Arc<RoutingScript>>>>,
+}
+
+impl SharedRoutingScript {
+    pub fn replace(&self, script: RoutingScript) {
+        *self.script.write().unwrap() = Some(Arc::new(script));
+    }
+}
+
+impl Stage for SharedRoutingScript {
+    fn name(&self) -> &str {
+        "routing-script"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Route
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        // Clone out of the lock so a reload doesn't wait for the script to run
+        let script = self.script.read().unwrap().clone();
+        match script {
+            Some(script) => script.process(notification),
+            None => Ok(Outcome::Continue),
+        }
+    }
+}
+
+/// A Rhai hook run by a routing rule before the notification is sent
+/// (`hook` of a `[[rules]]` entry), for rewrites and enrichment too complex
+/// for a template.
+///
+/// The script sees `message`, `severity`, `source`, `chat_id`, `parse_mode`,
+/// `silent` and `labels` (a map) as variables. It may reassign any of them
+/// but `source` and `parse_mode`, or set `suppress = true` (optionally with a
+/// `reason` string) to drop the notification.
+#[derive(Debug, Clone)]
+pub struct Hook {
+    ast: AST,
+    limits: ScriptLimits,
+}
+
+impl Hook {
+    pub fn load(path: &Path, limits: ScriptLimits) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read hook {}", path.display()))?;
+        Self::from_source(&source, limits)
+            .with_context(|| format!("Failed to compile hook {}", path.display()))
+    }
+
+    pub fn from_source(source: &str, limits: ScriptLimits) -> Result<Self> {
+        let ast = sandboxed_engine(limits)
+            .compile(source)
+            .map_err(|e| anyhow::anyhow!("Script syntax error: {e}"))?;
+        Ok(Self { ast, limits })
+    }
+
+    /// Run the hook on `notification`, which is only changed if it succeeds
+    pub fn apply(&self, notification: &mut Notification) -> Result<Outcome> {
+        let engine = sandboxed_engine(self.limits);
+
+        let labels: Map = notification
+            .labels
+            .iter()
+            .map(|(name, value)| (name.into(), value.clone().into()))
+            .collect();
+        let mut scope = Scope::new();
+        scope.push("message", notification.message.clone());
+        scope.push(
+            "severity",
+            notification.severity.clone().unwrap_or_default(),
+        );
+        scope.push("source", notification.source.clone());
+        scope.push("chat_id", notification.chat_id.clone());
+        scope.push(
+            "parse_mode",
+            notification.parse_mode.clone().unwrap_or_default(),
+        );
+        scope.push("silent", notification.disable_notification);
+        scope.push("labels", labels);
+        scope.push("suppress", false);
+        scope.push("reason", String::new());
+
+        engine
+            .run_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| eval_error(*e, "Hook", self.limits))?;
+
+        if scope.get_value::<bool>("suppress").unwrap_or(false) {
+            let reason = scope
+                .get_value::<String>("reason")
+                .filter(|r| !r.is_empty())
+                .unwrap_or_else(|| "suppressed by hook".to_string());
+            return Ok(Outcome::Drop { reason });
+        }
+
+        let text = |name: &str| {
+            scope
+                .get_value::<String>(name)
+                .ok_or_else(|| anyhow::anyhow!("Hook must leave `{name}` as a string"))
+        };
+        let message = text("message")?;
+        let severity = text("severity")?;
+        let chat_id = text("chat_id")?;
+        if chat_id.is_empty() {
+            anyhow::bail!("Hook must leave `chat_id` as a non-empty string");
+        }
+        let labels = scope
+            .get_value::<Map>("labels")
+            .ok_or_else(|| anyhow::anyhow!("Hook must leave `labels` as a map"))?;
+
+        notification.message = message;
+        notification.severity = (!severity.is_empty()).then_some(severity);
+        notification.chat_id = chat_id;
+        if let Some(silent) = scope.get_value::<bool>("silent") {
+            notification.disable_notification = silent;
+        }
+        notification.labels = labels
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Ok(Outcome::Continue)
+    }
+}
+
+fn eval_error(error: EvalAltResult, what: &str, limits: ScriptLimits) -> anyhow::Error {
+    match error {
+        EvalAltResult::ErrorTerminated(..) => {
+            anyhow::anyhow!("{what} timed out after {}ms", limits.timeout.as_millis())
+        }
+        EvalAltResult::ErrorTooManyOperations(..) => {
+            anyhow::anyhow!("{what} exceeded {} operations", limits.max_operations)
+        }
+        other => anyhow::anyhow!("{what} failed: {other}"),
+    }
+}
+
+fn sandboxed_engine(limits: ScriptLimits) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(limits.max_operations);
+    engine.set_max_call_levels(16);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(64 * 1024);
+    engine.set_max_array_size(1024);
+    engine.set_max_map_size(1024);
+
+    let started = Instant::now();
+    let timeout = limits.timeout;
+    engine.on_progress(move |_| {
+        if started.elapsed() > timeout {
+            Some(Dynamic::from("script timed out"))
+        } else {
+            None
+        }
+    });
+
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(message: &str) -> RoutingContext {
+        RoutingContext {
+            message: message.to_string(),
+            chat_id: "123456789".to_string(),
+            parse_mode: Some("Markdown".to_string()),
+            disable_notification: false,
+        }
+    }
+
+    #[test]
+    fn test_empty_script_keeps_default_route() {
+        let script = RoutingScript::from_source("", ScriptLimits::default()).unwrap();
+        let decision = script.evaluate(&context("hello")).unwrap();
+
+        assert_eq!(
+            decision,
+            RoutingDecision::Send {
+                chat_id: "123456789".to_string(),
+                disable_notification: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_script_reroutes_and_silences() {
+        let source = r#"
+            if message.contains("[billing]") {
+                chat_id = "-1001";
+                silent = true;
+            }
+        "#;
+        let script = RoutingScript::from_source(source, ScriptLimits::default()).unwrap();
+        let decision = script.evaluate(&context("[billing] invoice paid")).unwrap();
+
+        assert_eq!(
+            decision,
+            RoutingDecision::Send {
+                chat_id: "-1001".to_string(),
+                disable_notification: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_script_suppresses_with_reason() {
+        let source = r#"
+            if message.starts_with("DEBUG") {
+                suppress = true;
+                reason = "debug noise";
+            }
+        "#;
+        let script = RoutingScript::from_source(source, ScriptLimits::default()).unwrap();
+        let decision = script.evaluate(&context("DEBUG cache warmed")).unwrap();
+
+        assert_eq!(
+            decision,
+            RoutingDecision::Suppress {
+                reason: "debug noise".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_script_suppress_default_reason() {
+        let script =
+            RoutingScript::from_source("suppress = true;", ScriptLimits::default()).unwrap();
+        let decision = script.evaluate(&context("anything")).unwrap();
+
+        assert_eq!(
+            decision,
+            RoutingDecision::Suppress {
+                reason: "suppressed by routing script".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_script_syntax_error() {
+        let result = RoutingScript::from_source("if {", ScriptLimits::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_script_operation_limit() {
+        let limits = ScriptLimits {
+            max_operations: 1_000,
+            timeout: Duration::from_secs(5),
+        };
+        let script = RoutingScript::from_source("loop { }", limits).unwrap();
+        let result = script.evaluate(&context("hello"));
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("operations"));
+    }
+
+    #[test]
+    fn test_script_timeout() {
+        let limits = ScriptLimits {
+            max_operations: 0, // unlimited, only the timeout applies
+            timeout: Duration::from_millis(20),
+        };
+        let script = RoutingScript::from_source("loop { }", limits).unwrap();
+        let result = script.evaluate(&context("hello"));
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("timed out"));
+    }
+
+    #[test]
+    fn test_script_empty_chat_id_is_error() {
+        let script =
+            RoutingScript::from_source(r#"chat_id = "";"#, ScriptLimits::default()).unwrap();
+        assert!(script.evaluate(&context("hello")).is_err());
+    }
+
+    #[test]
+    fn test_stage_applies_decision() {
+        let script =
+            RoutingScript::from_source(r#"chat_id = "-42";"#, ScriptLimits::default()).unwrap();
+        let mut notification = Notification {
+            message: "hello".to_string(),
+            chat_id: "123456789".to_string(),
+            parse_mode: None,
+            disable_notification: false,
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        };
+
+        let outcome = script.process(&mut notification).unwrap();
+
+        assert_eq!(outcome, Outcome::Continue);
+        assert_eq!(notification.chat_id, "-42");
+    }
+
+    #[test]
+    fn test_stage_falls_back_on_error() {
+        let script =
+            RoutingScript::from_source(r#"throw "oops";"#, ScriptLimits::default()).unwrap();
+        let mut notification = Notification {
+            message: "hello".to_string(),
+            chat_id: "123456789".to_string(),
+            parse_mode: None,
+            disable_notification: false,
+            delivery_id: "abc123def456".to_string(),
+            ..Default::default()
+        };
+
+        let outcome = script.process(&mut notification).unwrap();
+
+        assert_eq!(outcome, Outcome::Continue);
+        assert_eq!(notification.chat_id, "123456789");
+    }
+
+    #[test]
+    fn test_shared_script_replaced() {
+        let shared = SharedRoutingScript::default();
+        let mut notification = Notification {
+            chat_id: "123456789".to_string(),
+            ..Default::default()
+        };
+
+        shared.process(&mut notification).unwrap();
+        assert_eq!(notification.chat_id, "123456789");
+
+        shared.replace(
+            RoutingScript::from_source(r#"chat_id = "-42";"#, ScriptLimits::default()).unwrap(),
+        );
+        shared.process(&mut notification).unwrap();
+        assert_eq!(notification.chat_id, "-42");
+    }
+
+    #[test]
+    fn test_hook_rewrites_and_enriches() {
+        let source = r#"
+            if labels.env == "prod" {
+                message = "[" + labels.env.to_upper() + "] " + message;
+                severity = "critical";
+                labels.runbook = "https://runbooks.example.com/" + source;
+                labels.attempts = 3;
+            }
+        "#;
+        let hook = Hook::from_source(source, ScriptLimits::default()).unwrap();
+        let mut notification = Notification {
+            message: "Disk full".to_string(),
+            chat_id: "42".to_string(),
+            source: "alertmanager".to_string(),
+            labels: [("env".to_string(), "prod".to_string())].into(),
+            ..Default::default()
+        };
+
+        assert_eq!(hook.apply(&mut notification).unwrap(), Outcome::Continue);
+        assert_eq!(notification.message, "[PROD] Disk full");
+        assert!(notification.is_critical());
+        assert_eq!(
+            notification.labels["runbook"],
+            "https://runbooks.example.com/alertmanager"
+        );
+        assert_eq!(notification.labels["attempts"], "3");
+        assert_eq!(notification.chat_id, "42");
+    }
+
+    #[test]
+    fn test_hook_suppresses_or_fails_unchanged() {
+        let hook = Hook::from_source(
+            r#"if severity == "" { suppress = true; }"#,
+            ScriptLimits::default(),
+        )
+        .unwrap();
+        let mut notification = Notification {
+            message: "hello".to_string(),
+            chat_id: "42".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            hook.apply(&mut notification).unwrap(),
+            Outcome::Drop {
+                reason: "suppressed by hook".to_string()
+            }
+        );
+
+        let hook = Hook::from_source(
+            r#"message = "changed"; labels = 1;"#,
+            ScriptLimits::default(),
+        )
+        .unwrap();
+        assert!(hook.apply(&mut notification).is_err());
+        assert_eq!(notification.message, "hello");
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let result = RoutingScript::load(
+            Path::new("/nonexistent/routing.rhai"),
+            ScriptLimits::default(),
+        );
+        assert!(result.is_err());
+    }
+}
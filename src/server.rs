@@ -0,0 +1,841 @@
+use crate::actions::{self, ActionLinks, SilenceFilter, Silences};
+use crate::auth::{self, ApiKeys, Authenticator, ReplayGuard, SignatureVerifier};
+use crate::backends::Backends;
+use crate::bots::Bots;
+use crate::breaker::CircuitBreaker;
+use crate::callbacks::Callbacks;
+use crate::checkins::CheckIns;
+use crate::config::ConfigResolved;
+use crate::dedupe::Deduplicator;
+use crate::defaults::ApplyDefaults;
+use crate::delivery::{self, DeliveryQueue, EmbedDeliveryId, Outcomes};
+use crate::digest::{self, Digests};
+use crate::effective;
+use crate::fallback::Fallback;
+use crate::filters::Filters;
+use crate::gitsync::{self, ConfigSync};
+use crate::handlers::{self, AppState};
+use crate::history::{self, History};
+use crate::i18n::{self, Localize};
+use crate::idempotency::IdempotencyStore;
+#[cfg(any(feature = "nats", feature = "amqp", feature = "redis"))]
+use crate::inputs;
+use crate::jwt::JwtValidator;
+use crate::keys::KeyStore;
+use crate::limits::{self, BodyLimits};
+use crate::listen;
+use crate::logging;
+use crate::notifier::Notifier;
+use crate::outbox::Outbox;
+use crate::pagerduty::Incidents;
+use crate::pages::LinkFullMessage;
+use crate::pipeline::Pipeline;
+#[cfg(feature = "plugins")]
+use crate::plugins::Plugin;
+use crate::proxy;
+use crate::quiet::ApplyQuietHours;
+use crate::quota::KeyQuotas;
+use crate::ratelimit::RateLimiter;
+use crate::readiness::BotCheck;
+use crate::rules::RoutingRules;
+use crate::scheduler::{self, Scheduler};
+use crate::schedules::ScheduleStore;
+use crate::scripting::{RoutingScript, SharedRoutingScript};
+use crate::stats::Stats;
+use crate::storage::Stores;
+use crate::targets::Targets;
+use crate::telegram::TelegramBot;
+use crate::throttle::Throttles;
+use crate::tls;
+use crate::uptime::Outages;
+#[cfg(feature = "operator")]
+use crate::{operator, routes::RouteTable};
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    extract::{DefaultBodyLimit, Request},
+    middleware,
+    routing::{delete, get, post},
+};
+use axum_server::Handle;
+use axum_server::tls_rustls::RustlsConfig;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceBuilder;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+use tracing::{info, warn};
+
+/// The bot of `--bot-token`, with the configured rate limits and circuit
+/// breaker
+pub fn main_bot(config: &ConfigResolved) -> TelegramBot {
+    TelegramBot::new(config.bot_token.clone())
+        .with_rate_limiter(RateLimiter::new(
+            config.chat_rate_limit,
+            config.global_rate_limit,
+        ))
+        .with_circuit_breaker(config.circuit_breaker.clone())
+}
+
+/// `bot` plus those of `[bots]`, each with its own rate limits and circuit
+/// breaker
+pub fn bots(config: &ConfigResolved, bot: TelegramBot) -> Bots {
+    let mut bots = Bots::new(bot);
+    for (name, settings) in &config.bots {
+        let named = TelegramBot::new(settings.token.clone())
+            .with_rate_limiter(RateLimiter::new(
+                config.chat_rate_limit,
+                config.global_rate_limit,
+            ))
+            .with_circuit_breaker(CircuitBreaker::new(
+                config.circuit_breaker.threshold(),
+                config.circuit_breaker.cooldown(),
+            ));
+        bots = bots.with_bot(name, named, settings.chats.clone(), settings.keys.clone());
+    }
+    bots
+}
+
+/// [`bots`], the tokens of the named ones checked unless in test mode
+async fn named_bots(config: &ConfigResolved, bot: TelegramBot) -> Result<Bots> {
+    if !delivery::test_mode() {
+        for (name, settings) in &config.bots {
+            TelegramBot::new(settings.token.clone())
+                .get_me()
+                .await
+                .with_context(|| format!("The token of bot {name} was not accepted"))?;
+        }
+    }
+    let bots = bots(config, bot);
+    if !config.bots.is_empty() {
+        info!(
+            "🤖 Named bots: {}",
+            bots.names().collect::<Vec<_>>().join(", ")
+        );
+    }
+    Ok(bots)
+}
+
+/// Start the server's background tasks and build its routes, e.g. to serve
+/// them next to an application's own. `bot_username` enables action links.
+pub async fn app(
+    config: &ConfigResolved,
+    bot: TelegramBot,
+    bot_username: Option<String>,
+) -> Result<Router> {
+    let bots = named_bots(config, bot.clone()).await?;
+    let stats = Stats::new();
+    let mut pipeline = Pipeline::with_defaults();
+    if !config.filters.is_empty() {
+        pipeline.register(Filters::new(config.filters.clone(), stats.clone()));
+        info!("🔉 {} suppression filter(s) loaded", config.filters.len());
+    }
+    let targets = Targets::new(&config.chat_aliases).with_destinations(&config.destinations);
+    #[cfg(feature = "operator")]
+    if let Some(settings) = &config.operator {
+        // Before routing scripts, so they still see and may change the result
+        let routes = RouteTable::new(targets.clone());
+        pipeline.register(routes.clone());
+        operator::start(
+            settings,
+            targets.clone(),
+            routes,
+            config.chat_aliases.clone(),
+        )
+        .await?;
+        info!(
+            "☸️  Operator mode: applying {} and {} resources from {}",
+            operator::TARGET_KIND,
+            operator::ROUTE_KIND,
+            settings
+                .namespace
+                .as_deref()
+                .map_or("all namespaces".to_string(), |namespace| format!(
+                    "namespace {namespace}"
+                ))
+        );
+    }
+    if !config.rules.is_empty() {
+        pipeline.register(RoutingRules::new(config.rules.clone()));
+        info!("🧭 {} routing rule(s) loaded", config.rules.len());
+    }
+    if let Some(path) = &config.routing_script {
+        pipeline.register(RoutingScript::load(path, config.script_limits)?);
+        info!("📜 Routing script loaded from {}", path.display());
+    }
+    let config_sync = match &config.config_repo {
+        Some(settings) => {
+            let script = SharedRoutingScript::default();
+            pipeline.register(script.clone());
+            let sync = Arc::new(ConfigSync::new(
+                settings.clone(),
+                config.script_limits,
+                script,
+            ));
+            // Alerts keep flowing on their requested route if the first pull fails
+            match sync.sync().await {
+                Ok(result) => info!(
+                    "📜 Routing script loaded from {} at commit {}",
+                    gitsync::redact(&settings.url),
+                    gitsync::short(&result.commit)
+                ),
+                Err(e) => warn!("⚠️  Config repository sync failed: {:#}", e),
+            }
+            tokio::spawn(gitsync::keep_in_sync(sync.clone()));
+            info!(
+                "🔄 Pulling the config repository every {}s",
+                settings.interval.as_secs()
+            );
+            Some(sync)
+        }
+        None => None,
+    };
+    let history = match &config.history_db {
+        Some(path) => {
+            let history = History::open(config.history_size, path)?;
+            info!(
+                "💾 The history is stored in {} ({} notification(s))",
+                path.display(),
+                history.len()
+            );
+            history
+        }
+        None => History::new(config.history_size),
+    };
+    if let Some(window) = config.dedupe_window {
+        let mut dedupe = Deduplicator::new(window, history.clone());
+        if config.dedupe_counter {
+            dedupe = dedupe.with_counter(bots.clone());
+        }
+        pipeline.register(dedupe);
+        info!(
+            "🔁 Dropping repeated messages within {}s{}",
+            window.as_secs(),
+            if config.dedupe_counter {
+                ", counting them on the first"
+            } else {
+                ""
+            }
+        );
+    }
+    if !config.throttles.is_empty() {
+        // After deduplication, so repeats don't use up a throttle
+        pipeline.register(Throttles::new(&config.throttles));
+        info!(
+            "🚥 Throttles: {}",
+            config
+                .throttles
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    // Chats are silenced through action links or /admin/silences
+    let silences = Silences::new();
+    pipeline.register(SilenceFilter::new(silences.clone()));
+    let mut action_links = false;
+    if !config.action_links.chats.is_empty() {
+        match &bot_username {
+            Some(_) => action_links = true,
+            None => warn!("⚠️  Action links need the bot username; skipped (test mode)"),
+        }
+    }
+    pipeline.register(Localize::new(config.locales.clone()));
+    if !config.defaults.is_empty() {
+        // Once routing has settled the chat, whose overrides apply
+        pipeline.register(ApplyDefaults::new(config.defaults.clone()));
+    }
+    if !config.quiet_hours.is_empty() {
+        pipeline.register(ApplyQuietHours::new(&config.quiet_hours)?);
+        info!(
+            "🌙 Quiet hours set for {} chat(s)",
+            config.quiet_hours.len()
+        );
+    }
+    if let (true, Some(username)) = (action_links, &bot_username) {
+        // After localization, which replaces the message text
+        pipeline.register(ActionLinks::new(
+            username.clone(),
+            config.action_links.clone(),
+        ));
+    }
+    if config.embed_delivery_id {
+        pipeline.register(EmbedDeliveryId);
+    }
+    if let Some(public_url) = &config.public_url {
+        // Last, so the length check sees the final text
+        pipeline.register(LinkFullMessage::new(public_url, &config.base_path));
+        info!(
+            "📃 Long messages link to their full text under {}",
+            public_url
+        );
+    }
+    let stage_names: Vec<&str> = pipeline.stages().iter().map(|(name, _)| *name).collect();
+    info!("🧩 Pipeline stages: {}", stage_names.join(" → "));
+
+    if !config.priority.emergency_keys.is_empty() {
+        info!(
+            "🚨 {} key(s) may send X-Priority: emergency notifications",
+            config.priority.emergency_keys.len()
+        );
+    }
+
+    let key_store = KeyStore::open(config.auth.keys_file.as_deref())?;
+    let quotas = KeyQuotas::new(&config.auth.keys).with_managed_keys(key_store.clone());
+    if let Some(path) = &config.auth.keys_file {
+        info!(
+            "🔑 {} API key(s) loaded from {}",
+            key_store.len(),
+            path.display()
+        );
+    }
+    if !config.auth.keys.is_empty() {
+        info!(
+            "🚦 Counting requests for {} named key(s)",
+            config.auth.keys.len()
+        );
+    }
+    if let Some(retention) = config.history_retention {
+        tokio::spawn(history::enforce_retention(history.clone(), retention));
+        info!(
+            "🧹 Notifications are removed from the history after {}s",
+            retention.as_secs()
+        );
+    }
+    let outbox = match &config.queue_db {
+        Some(path) => {
+            let outbox = Outbox::open(path)?;
+            info!(
+                "💾 Queued notifications are stored in {} ({} waiting)",
+                path.display(),
+                outbox.len()?
+            );
+            Some(outbox)
+        }
+        None => None,
+    };
+    let fallback = Fallback::new(config.fallback.clone());
+    if let Some(url) = &config.fallback.webhook {
+        // Only the host, webhook URLs often hold a token
+        info!(
+            "🛟 Undeliverable notifications fall back to the webhook at {}",
+            url.host_str().unwrap_or_default()
+        );
+    }
+    if let Some(email) = &config.fallback.email {
+        info!(
+            "🛟 Undeliverable notifications fall back to mail to {} via {}",
+            email.to.join(", "),
+            email.server
+        );
+    }
+    let callbacks = Callbacks::new(config.callbacks.clone());
+    if let Some(url) = &config.callbacks.default_url {
+        info!(
+            "📣 Delivery outcomes are reported to {}{}",
+            url.host_str().unwrap_or_default(),
+            if config.callbacks.secret.is_some() {
+                ", signed"
+            } else {
+                ""
+            }
+        );
+    }
+    let mut backends = Backends::new(bots.clone());
+    for (name, settings) in &config.backends {
+        backends = backends.with_backend(settings.notifier()?, settings.chats());
+        info!(
+            "📮 Backend {name} sends to chats {}",
+            settings.chats().join(", ")
+        );
+    }
+    let notifier: Arc<dyn Notifier> = Arc::new(backends);
+    let queue = DeliveryQueue::start_with(
+        notifier.clone(),
+        config.queue_capacity,
+        delivery::test_mode(),
+        history.clone(),
+        config.queue_retry,
+        outbox.clone(),
+        Outcomes {
+            fallback: fallback.clone(),
+            callbacks: callbacks.clone(),
+            stats,
+        },
+    )?;
+    let scheduler = Scheduler::new();
+    tokio::spawn(scheduler::deliver_due(
+        scheduler.clone(),
+        queue.clone(),
+        history.clone(),
+    ));
+    let digests = Digests::new(config.digests.clone());
+    if digests.is_enabled() {
+        tokio::spawn(digest::deliver_due(
+            digests.clone(),
+            queue.clone(),
+            history.clone(),
+        ));
+        info!(
+            "🗞 Low-priority notifications to {} chat(s) are sent as digests",
+            config.digests.len()
+        );
+    }
+    let schedules =
+        ScheduleStore::open(config.schedules.clone(), config.schedules_file.as_deref())?;
+    if !schedules.is_empty() {
+        info!("🔁 {} recurring notification(s) scheduled", schedules.len());
+    }
+    let checkins = CheckIns::start(config.checkins.clone());
+    if !checkins.is_empty() {
+        info!("⏱️ {} job(s) expected to check in", checkins.len());
+    }
+    if !config.webhooks.is_empty() {
+        info!(
+            "🪝 Mapped webhooks: {}",
+            config
+                .webhooks
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    #[cfg(feature = "plugins")]
+    let plugins = {
+        let mut plugins = std::collections::BTreeMap::new();
+        for (name, settings) in &config.plugins {
+            let plugin =
+                Plugin::load(&settings.path).with_context(|| format!("Invalid plugin {name}"))?;
+            info!("🧩 Plugin {} loaded from {}", name, settings.path.display());
+            plugins.insert(name.clone(), plugin);
+        }
+        plugins
+    };
+    if action_links {
+        tokio::spawn(actions::handle_updates(
+            bot.clone(),
+            history.clone(),
+            silences.clone(),
+        ));
+        info!("🔗 Action links enabled; handling /start commands sent to the bot");
+    }
+
+    // Keys allowed to raise emergencies or bound to a bot may also call the API
+    let api_keys = ApiKeys::new(
+        config
+            .auth
+            .api_keys
+            .iter()
+            .chain(&config.priority.emergency_keys)
+            .chain(config.bots.values().flat_map(|bot| &bot.keys))
+            .cloned(),
+    )
+    .with_named(
+        config
+            .auth
+            .keys
+            .iter()
+            .map(|(name, key)| (name.clone(), key.key.clone())),
+    );
+    let state = Arc::new(AppState {
+        bots,
+        notifier,
+        bot_username,
+        default_chat_id: config.chat_id.clone(),
+        base_path: config.base_path.clone(),
+        pipeline,
+        queue,
+        fallback,
+        callbacks,
+        digests,
+        scheduler,
+        schedules,
+        idempotency: IdempotencyStore::new(config.idempotency_window),
+        history,
+        targets,
+        silences,
+        priority: config.priority.clone(),
+        quotas: quotas.clone(),
+        api_keys: api_keys.clone(),
+        keys: key_store.clone(),
+        config_sync,
+        bot_check: BotCheck::verified(config.bot_check_cache),
+        outages: Outages::default(),
+        webhooks: config.webhooks.clone(),
+        #[cfg(feature = "plugins")]
+        plugins,
+        incidents: Incidents::default(),
+        checkins,
+        effective_config: effective::effective_config(config),
+    });
+    tokio::spawn(handlers::run_schedules(state.clone()));
+    tokio::spawn(handlers::run_checkins(state.clone()));
+    let stores = Stores {
+        history: state.history.clone(),
+        outbox,
+    };
+    if !stores.sizes().is_empty() {
+        if let Some(interval) = config.db_vacuum_interval {
+            info!(
+                "🧹 The databases are vacuumed every {}s",
+                interval.as_secs()
+            );
+        }
+        if let Some(limit) = config.db_size_alert_bytes {
+            info!(
+                "🚨 Alerting when a database grows past {} MB",
+                limit / 1024 / 1024
+            );
+        }
+        tokio::spawn(handlers::run_storage_maintenance(
+            state.clone(),
+            stores,
+            config.db_vacuum_interval,
+            config.db_size_alert_bytes,
+        ));
+    }
+    #[cfg(feature = "nats")]
+    if let Some(settings) = &config.nats {
+        inputs::nats::start(settings, state.clone()).await?;
+        info!(
+            "📨 Taking notifications from NATS subject {}{}",
+            settings.subject,
+            settings
+                .stream
+                .as_deref()
+                .map_or(String::new(), |stream| format!(
+                    " (JetStream stream {stream})"
+                ))
+        );
+    }
+    #[cfg(feature = "amqp")]
+    if let Some(settings) = &config.amqp {
+        inputs::amqp::start(settings, state.clone()).await?;
+        info!("📨 Taking notifications from AMQP queue {}", settings.queue);
+    }
+    #[cfg(feature = "redis")]
+    if let Some(settings) = &config.redis {
+        inputs::redis::start(settings, state.clone()).await?;
+        match &settings.channel {
+            Some(channel) => info!("📨 Taking notifications from Redis channel {}", channel),
+            None => info!("📨 Taking notifications from Redis list {}", settings.list),
+        }
+    }
+
+    // Key management and operator endpoints, open to admin keys only
+    let admin_routes = Router::new()
+        .route(
+            "/admin/keys",
+            get(handlers::list_keys).post(handlers::create_key),
+        )
+        .route(
+            "/admin/keys/{id}",
+            get(handlers::get_key)
+                .put(handlers::put_key)
+                .delete(handlers::revoke_key),
+        )
+        .route("/admin/targets", get(handlers::list_targets))
+        .route(
+            "/admin/targets/{name}",
+            get(handlers::get_target)
+                .put(handlers::put_target)
+                .delete(handlers::delete_target),
+        )
+        .route("/admin/silences", get(handlers::list_silences))
+        .route(
+            "/admin/silences/{chat}",
+            get(handlers::get_silence)
+                .put(handlers::put_silence)
+                .delete(handlers::delete_silence),
+        )
+        .route("/admin/config", get(handlers::effective_config))
+        .route("/admin/dlq", get(handlers::list_dead_letters))
+        .route("/admin/dlq/{id}/retry", post(handlers::retry_dead_letter))
+        .route("/admin/ratelimits", get(handlers::rate_limits))
+        .route("/admin/config/sync", post(handlers::sync_config))
+        .route_layer(middleware::from_fn(auth::require_admin));
+
+    // Routes accepting notifications or exposing their history and delivery
+    // state, guarded by the optional auth layers
+    let mut notify_routes = Router::new()
+        .route("/notify", post(handlers::notify))
+        .route("/notify/preview", post(handlers::preview))
+        .route("/send", post(handlers::send))
+        .route("/integrations/alertmanager", post(handlers::alertmanager))
+        .route("/integrations/grafana", post(handlers::grafana))
+        .route("/integrations/uptime", post(handlers::uptime))
+        .route("/integrations/pagerduty", post(handlers::pagerduty))
+        .route("/webhook/{name}", post(handlers::mapped_webhook))
+        .route("/checkin/{slug}", post(handlers::checkin))
+        .route("/history", get(handlers::history_list))
+        .route("/history/export", get(handlers::history_export))
+        .route(
+            "/history/{id}",
+            get(handlers::history_get).delete(handlers::history_delete),
+        )
+        .route("/history/{id}/resend", post(handlers::history_resend))
+        .route("/messages/{id}", get(handlers::message_status))
+        .route("/scheduled", get(handlers::list_scheduled))
+        .route("/scheduled/{id}", delete(handlers::cancel_scheduled))
+        .route(
+            "/schedules",
+            get(handlers::list_schedules).post(handlers::create_schedule),
+        )
+        .route(
+            "/schedules/{id}",
+            get(handlers::get_schedule).delete(handlers::delete_schedule),
+        )
+        .route("/n/{id}", get(handlers::notification_page))
+        .route("/keys/{id}/usage", get(handlers::key_usage))
+        .route("/stats", get(handlers::stats))
+        .merge(admin_routes)
+        .layer(DefaultBodyLimit::max(config.max_body_bytes));
+    #[cfg(feature = "plugins")]
+    {
+        notify_routes = notify_routes.route(
+            "/plugin/{name}",
+            post(handlers::plugin_webhook).layer(DefaultBodyLimit::max(config.max_body_bytes)),
+        );
+    }
+    if let Some(window) = config.replay_window {
+        info!(
+            "🛡️ Replay protection enabled ({}s window)",
+            window.as_secs()
+        );
+        notify_routes = notify_routes.route_layer(middleware::from_fn_with_state(
+            Arc::new(ReplayGuard::new(window)),
+            auth::replay_protection,
+        ));
+    }
+    let jwt = match &config.auth.jwt {
+        Some(settings) => {
+            let validator = JwtValidator::new(settings).context("Invalid [auth.jwt] settings")?;
+            info!("🔑 JWT authentication enabled ({})", settings.algorithm);
+            Some(validator)
+        }
+        None => None,
+    };
+    let mut authenticator = Authenticator::new(api_keys, jwt)
+        .with_admin_keys(ApiKeys::new(config.auth.admin_keys.iter().cloned()))
+        .with_managed_keys(key_store)
+        .with_quotas(quotas.clone());
+    if let Some(secret) = &config.auth.hmac_secret {
+        info!("🔏 Signed requests (X-Signature) accepted");
+        authenticator = authenticator.with_signatures(SignatureVerifier::new(
+            secret.clone(),
+            config.max_body_bytes,
+        ));
+    }
+    if let Some(credentials) = &config.auth.basic {
+        info!(
+            "🔑 HTTP Basic auth accepted for user {}",
+            credentials.username
+        );
+        authenticator = authenticator.with_basic(credentials.clone());
+    }
+    if authenticator.is_enabled() {
+        info!("🔑 Authentication required for the notification endpoints");
+        // Runs before replay protection, which trusts verified signatures only
+        notify_routes = notify_routes.route_layer(middleware::from_fn_with_state(
+            Arc::new(authenticator),
+            auth::require_auth,
+        ));
+    } else {
+        warn!("⚠️  No API keys configured; the notification endpoints are open to anyone");
+    }
+    // Outermost, so oversized bodies are refused before auth reads them
+    notify_routes = notify_routes.route_layer(middleware::from_fn_with_state(
+        BodyLimits {
+            buffered: config.max_body_bytes,
+            streamed: config.max_webhook_body_bytes,
+        },
+        limits::reject_oversized,
+    ));
+
+    // Browsers only let other origins call the API as far as [cors] allows
+    let cors = config.cors.layer()?;
+    if cors.is_none() {
+        info!("🌍 CORS disabled; browsers only allow same-origin calls");
+    } else if config
+        .cors
+        .allowed_origins
+        .iter()
+        .all(|origin| origin != "*")
+    {
+        info!(
+            "🌍 CORS allowed for {}",
+            config.cors.allowed_origins.join(", ")
+        );
+    }
+
+    let routes = Router::new()
+        .route("/", get(handlers::root))
+        .route("/health", get(handlers::health))
+        .route("/livez", get(handlers::livez))
+        .route("/readyz", get(handlers::readyz))
+        .merge(notify_routes);
+    // Mounted below a shared ingress path, every route moves under the prefix
+    let app = if config.base_path.is_empty() {
+        routes
+    } else {
+        Router::new().nest(&config.base_path, routes)
+    };
+    let app = app
+        .layer(
+            ServiceBuilder::new()
+                // Keep the client's X-Request-Id, or make one up, and answer with it
+                .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+                .layer(PropagateRequestIdLayer::x_request_id())
+                .layer(middleware::from_fn_with_state(
+                    Arc::new(config.trusted_proxies.clone()),
+                    proxy::resolve_client_ip,
+                ))
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(|request: &Request| logging::request_span(request))
+                        .on_response(logging::log_response),
+                )
+                .layer(middleware::from_fn(i18n::localize_errors))
+                .option_layer(cors),
+        )
+        .with_state(state);
+    Ok(app)
+}
+
+/// Serve the API on the configured address until Ctrl+C or SIGTERM
+pub async fn run(
+    config: ConfigResolved,
+    bot: TelegramBot,
+    bot_username: Option<String>,
+) -> Result<()> {
+    let app = app(&config, bot, bot_username).await?;
+    let addr = format!("{}:{}", config.host, config.port);
+    let listener = listen::listener(&addr, config.reuse_port).await?;
+    let addr = listener.local_addr()?;
+
+    info!("🚀 Telegram Notifications API server starting...");
+    let scheme = if config.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    info!("🌐 Listening on {scheme}://{}{}", addr, config.base_path);
+    if let Some(tls) = &config.tls {
+        info!(
+            "🔐 Serving certificate {}{}",
+            tls.cert.display(),
+            match tls.reload_interval {
+                Some(interval) => format!(", reloaded when renewed (checked every {interval:?})"),
+                None => String::new(),
+            }
+        );
+    }
+    info!("📝 Default chat ID: {}", config.chat_id);
+    if !config.trusted_proxies.is_empty() {
+        info!("🔀 Honoring forwarding headers from trusted proxies");
+    }
+    info!("📄 Available endpoints:");
+    info!("    GET  /       - API information");
+    info!("    GET  /health - Health check and bot status");
+    info!("    GET  /livez - Liveness probe");
+    info!("    GET  /readyz - Readiness probe");
+    info!("    POST /notify - Send notification");
+    info!("    POST /send   - Send notification (alias)");
+    info!("    POST /notify/preview - Preview notification without sending");
+    info!("    POST /integrations/alertmanager - Receive Prometheus Alertmanager webhooks");
+    info!("    POST /integrations/grafana - Receive Grafana alerting webhooks");
+    info!("    POST /integrations/uptime - Receive Uptime Kuma monitor webhooks");
+    info!("    POST /integrations/pagerduty - Receive PagerDuty Events API v2 events");
+    info!("    POST /webhook/{{name}} - Receive a webhook mapped in the config file");
+    #[cfg(feature = "plugins")]
+    info!("    POST /plugin/{{name}} - Receive a webhook parsed by a WebAssembly plugin");
+    info!("    POST /checkin/{{slug}} - Check in a job watched for missed runs");
+    info!("    GET  /history - Search past notifications");
+    info!("    GET  /history/{{id}} - Look up a past notification");
+    info!("    GET  /history/export - Export the history as JSONL or CSV");
+    info!("    DELETE /history/{{id}} - Remove a notification from the history");
+    info!("    POST /history/{{id}}/resend - Re-deliver a past notification");
+    info!("    GET  /messages/{{id}} - Delivery state of a notification");
+    info!("    GET  /n/{{id}} - Full text of a notification");
+    info!("    GET  /scheduled - Notifications waiting for their send_at time");
+    info!("    DELETE /scheduled/{{id}} - Cancel a scheduled notification");
+    info!("    GET  /schedules - Recurring notifications and their next runs");
+    info!("    POST /schedules - Create a recurring notification");
+    info!("    GET  /schedules/{{id}} - Look up a recurring notification");
+    info!("    DELETE /schedules/{{id}} - Delete a recurring notification");
+    info!("    GET  /keys/{{id}}/usage - Request counts and quota of a named key");
+    info!("    GET  /stats - Delivery counts, queue depth and last error");
+    info!("    GET  /admin/ratelimits - Rate limiter state (admin key)");
+    info!("    POST /admin/config/sync - Pull the config repository now (admin key)");
+    info!("    GET  /admin/keys - List API keys (admin key)");
+    info!("    POST /admin/keys - Create an API key (admin key)");
+    info!("    GET  /admin/keys/{{id}} - Look up an API key (admin key)");
+    info!("    PUT  /admin/keys/{{id}} - Create or update an API key (admin key)");
+    info!("    DELETE /admin/keys/{{id}} - Revoke an API key (admin key)");
+    info!("    GET  /admin/targets - List named chats (admin key)");
+    info!("    GET  /admin/targets/{{name}} - Look up a named chat (admin key)");
+    info!("    PUT  /admin/targets/{{name}} - Create or update a named chat (admin key)");
+    info!("    DELETE /admin/targets/{{name}} - Remove a named chat (admin key)");
+    info!("    GET  /admin/silences - List silenced chats (admin key)");
+    info!("    GET  /admin/silences/{{chat}} - Look up a chat's silence (admin key)");
+    info!("    PUT  /admin/silences/{{chat}} - Silence a chat (admin key)");
+    info!("    DELETE /admin/silences/{{chat}} - Lift a chat's silence (admin key)");
+    info!("    GET  /admin/config - Show the settings in effect (admin key)");
+    info!("    GET  /admin/dlq - List notifications that failed for good (admin key)");
+    info!("    POST /admin/dlq/{{id}}/retry - Queue a failed notification again (admin key)");
+
+    // Peer addresses feed the client IP resolution
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match &config.tls {
+        Some(tls) => {
+            let rustls = RustlsConfig::from_config(tls.server_config()?);
+            tokio::spawn(tls::watch(tls.clone(), rustls.clone()));
+            let handle = Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                async move {
+                    shutdown_signal().await;
+                    handle.graceful_shutdown(None);
+                }
+            });
+            axum_server::from_tcp_rustls(listener.into_std()?, rustls)?
+                .handle(handle)
+                .serve(service)
+                .await?;
+        }
+        None => {
+            axum::serve(listener, service)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?
+        }
+    }
+    info!("👋 Server stopped");
+    Ok(())
+}
+
+/// Resolves on Ctrl+C or SIGTERM, after which the server stops accepting
+/// connections and finishes the requests it is handling
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for Ctrl+C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("🛑 Shutting down, finishing open requests");
+}
@@ -0,0 +1,193 @@
+use crate::pipeline::Notification;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Delivery outcomes since startup, by chat and by source, for `GET /stats`.
+/// Cloning shares the same counts.
+#[derive(Clone)]
+pub struct Stats {
+    started: Instant,
+    counts: Arc<Mutex<Counts>>,
+}
+
+#[derive(Default)]
+struct Counts {
+    by_chat: HashMap<String, Tally>,
+    by_source: HashMap<String, Tally>,
+    last_error: Option<LastError>,
+    /// Unix timestamp of the last notification Telegram accepted
+    last_sent_at: Option<u64>,
+    suppressed: HashMap<String, u64>,
+}
+
+/// Notifications sent and failed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Tally {
+    pub sent: u64,
+    pub failed: u64,
+}
+
+/// Most recent failed delivery
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LastError {
+    pub delivery_id: String,
+    pub chat_id: String,
+    pub error: String,
+    /// Unix timestamp
+    pub at: u64,
+}
+
+/// Counts as returned by `GET /stats`
+#[derive(Debug, Serialize)]
+pub struct StatsSnapshot {
+    pub uptime_secs: u64,
+    pub sent: u64,
+    pub failed: u64,
+    /// Notifications waiting in the delivery queue
+    pub queue_depth: usize,
+    pub by_chat: BTreeMap<String, Tally>,
+    /// `api`, `schedule`, `digest`, ...
+    pub by_source: BTreeMap<String, Tally>,
+    pub last_error: Option<LastError>,
+    /// Notifications dropped or downgraded, by filter
+    pub suppressed: BTreeMap<String, u64>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            counts: Arc::default(),
+        }
+    }
+
+    /// Count a notification Telegram accepted
+    pub fn sent(&self, notification: &Notification) {
+        self.count(notification, |tally| tally.sent += 1);
+        self.counts.lock().unwrap().last_sent_at = Some(unix_now());
+    }
+
+    /// Count a notification that couldn't be delivered
+    pub fn failed(&self, notification: &Notification, error: &str) {
+        self.count(notification, |tally| tally.failed += 1);
+        self.counts.lock().unwrap().last_error = Some(LastError {
+            delivery_id: notification.delivery_id.clone(),
+            chat_id: notification.chat_id.clone(),
+            error: error.to_string(),
+            at: unix_now(),
+        });
+    }
+
+    /// Count a notification a filter dropped or downgraded
+    pub fn suppressed(&self, filter: &str) {
+        *self
+            .counts
+            .lock()
+            .unwrap()
+            .suppressed
+            .entry(filter.to_string())
+            .or_default() += 1;
+    }
+
+    fn count(&self, notification: &Notification, add: impl Fn(&mut Tally)) {
+        let mut counts = self.counts.lock().unwrap();
+        add(counts
+            .by_chat
+            .entry(notification.chat_id.clone())
+            .or_default());
+        add(counts
+            .by_source
+            .entry(notification.source.clone())
+            .or_default());
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Unix timestamp of the last notification Telegram accepted
+    pub fn last_sent_at(&self) -> Option<u64> {
+        self.counts.lock().unwrap().last_sent_at
+    }
+
+    pub fn snapshot(&self, queue_depth: usize) -> StatsSnapshot {
+        let counts = self.counts.lock().unwrap();
+        let by_chat: BTreeMap<_, _> = counts
+            .by_chat
+            .iter()
+            .map(|(chat, tally)| (chat.clone(), *tally))
+            .collect();
+        StatsSnapshot {
+            uptime_secs: self.uptime().as_secs(),
+            sent: by_chat.values().map(|tally| tally.sent).sum(),
+            failed: by_chat.values().map(|tally| tally.failed).sum(),
+            queue_depth,
+            by_chat,
+            by_source: counts
+                .by_source
+                .iter()
+                .map(|(source, tally)| (source.clone(), *tally))
+                .collect(),
+            last_error: counts.last_error.clone(),
+            suppressed: counts
+                .suppressed
+                .iter()
+                .map(|(filter, count)| (filter.clone(), *count))
+                .collect(),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(chat_id: &str, source: &str) -> Notification {
+        Notification {
+            message: "Backup finished".to_string(),
+            chat_id: chat_id.to_string(),
+            delivery_id: "abc123".to_string(),
+            source: source.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let stats = Stats::new();
+        assert_eq!(stats.last_sent_at(), None);
+        stats.sent(&notification("-100123", "api"));
+        stats.sent(&notification("-100123", "schedule"));
+        stats.failed(
+            &notification("-100999", "api"),
+            "Bad Request: chat not found",
+        );
+
+        let snapshot = stats.snapshot(3);
+        assert!(stats.last_sent_at().is_some());
+
+        assert_eq!((snapshot.sent, snapshot.failed), (2, 1));
+        assert_eq!(snapshot.queue_depth, 3);
+        assert_eq!(snapshot.by_chat["-100123"], Tally { sent: 2, failed: 0 });
+        assert_eq!(snapshot.by_source["api"], Tally { sent: 1, failed: 1 });
+        assert_eq!(snapshot.by_source["schedule"].sent, 1);
+        let last_error = snapshot.last_error.unwrap();
+        assert_eq!(last_error.chat_id, "-100999");
+        assert_eq!(last_error.error, "Bad Request: chat not found");
+    }
+}
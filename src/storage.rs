@@ -0,0 +1,198 @@
+use crate::api::SendNotificationRequest;
+use crate::history::History;
+use crate::outbox::Outbox;
+use anyhow::Result;
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Source of alerts about databases growing too large
+pub const SOURCE: &str = "storage";
+
+/// Default for `--db-vacuum-interval-secs`: once a day
+pub const DEFAULT_VACUUM_INTERVAL_SECS: u64 = 86_400;
+
+/// How often the database sizes are checked
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Size of a SQLite database in bytes, pending WAL writes included
+pub(crate) fn size(connection: &Connection) -> Result<u64> {
+    let pages: u64 = connection.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let page_size: u64 = connection.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    Ok(pages * page_size)
+}
+
+/// How long a write waits for another connection's, such as a vacuum,
+/// before failing
+pub(crate) const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Rebuild the SQLite database at `path` without its free pages, then
+/// truncate the WAL so the file on disk shrinks too. This uses a connection
+/// of its own, so the shared one stays free for reads meanwhile.
+pub(crate) fn vacuum(path: &Path) -> Result<()> {
+    let connection = Connection::open(path)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.execute_batch("VACUUM")?;
+    connection.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok(())
+}
+
+/// The SQLite databases in use: `--history-db` and `--queue-db`. Cloning
+/// shares them.
+#[derive(Clone)]
+pub struct Stores {
+    pub history: History,
+    pub outbox: Option<Outbox>,
+}
+
+impl Stores {
+    /// Size in bytes of each database, by name. One that can't be read is
+    /// left out; the readiness check reports it.
+    pub fn sizes(&self) -> Vec<(&'static str, u64)> {
+        let mut sizes = Vec::new();
+        match self.history.storage_size() {
+            Ok(Some(size)) => sizes.push(("history", size)),
+            Ok(None) => {}
+            Err(e) => warn!("⚠️ Failed to size the history database: {:#}", e),
+        }
+        if let Some(outbox) = &self.outbox {
+            match outbox.size() {
+                Ok(size) => sizes.push(("queue", size)),
+                Err(e) => warn!("⚠️ Failed to size the queue database: {:#}", e),
+            }
+        }
+        sizes
+    }
+
+    /// Vacuum every database, reporting the space given back. Blocks while
+    /// the databases are rebuilt.
+    pub fn vacuum(&self) {
+        let before = self.sizes();
+        if let Err(e) = self.history.vacuum() {
+            warn!("⚠️ Failed to vacuum the history database: {:#}", e);
+        }
+        if let Some(outbox) = &self.outbox
+            && let Err(e) = outbox.vacuum()
+        {
+            warn!("⚠️ Failed to vacuum the queue database: {:#}", e);
+        }
+        for (name, after) in self.sizes() {
+            let Some((_, before)) = before.iter().find(|(other, _)| *other == name) else {
+                continue;
+            };
+            if after < *before {
+                info!(
+                    "🧹 Vacuumed the {} database from {} to {}",
+                    name,
+                    format_size(*before),
+                    format_size(after)
+                );
+            }
+        }
+    }
+}
+
+/// Alerts for databases over `--db-size-alert-mb`, each once until it is
+/// back under the limit
+pub struct SizeWatch {
+    limit: u64,
+    over: HashSet<&'static str>,
+}
+
+impl SizeWatch {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            over: HashSet::new(),
+        }
+    }
+
+    /// The alerts for the databases newly over the limit
+    pub fn check(&mut self, sizes: &[(&'static str, u64)]) -> Vec<SendNotificationRequest> {
+        let mut alerts = Vec::new();
+        for &(name, size) in sizes {
+            if size <= self.limit {
+                if self.over.remove(name) {
+                    info!(
+                        "💾 The {} database is back under {} ({})",
+                        name,
+                        format_size(self.limit),
+                        format_size(size)
+                    );
+                }
+                continue;
+            }
+            if !self.over.insert(name) {
+                continue;
+            }
+            warn!(
+                "🚨 The {} database takes {}, over the {} limit",
+                name,
+                format_size(size),
+                format_size(self.limit)
+            );
+            alerts.push(SendNotificationRequest {
+                message: format!(
+                    "🚨 <b>The {name} database takes {}</b>\nThe alert threshold is {}; \
+                     shorten the retention or free up disk space",
+                    format_size(size),
+                    format_size(self.limit)
+                ),
+                parse_mode: Some("HTML".to_string()),
+                ..Default::default()
+            });
+        }
+        alerts
+    }
+}
+
+/// Bytes as megabytes, e.g. "12.5 MB"
+fn format_size(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_size_watch_alerts_once_until_back_under() {
+        let mut watch = SizeWatch::new(100 * MB);
+        assert!(
+            watch
+                .check(&[("history", 50 * MB), ("queue", MB)])
+                .is_empty()
+        );
+
+        let alerts = watch.check(&[("history", 150 * MB), ("queue", MB)]);
+        assert_eq!(alerts.len(), 1);
+        assert!(
+            alerts[0]
+                .message
+                .contains("history database takes 150.0 MB")
+        );
+        assert!(alerts[0].message.contains("threshold is 100.0 MB"));
+        assert!(watch.check(&[("history", 160 * MB)]).is_empty());
+
+        // Alerted again after a vacuum brought it under for a while
+        assert!(watch.check(&[("history", 90 * MB)]).is_empty());
+        assert_eq!(watch.check(&[("history", 120 * MB)]).len(), 1);
+    }
+
+    #[test]
+    fn test_stores_sizes() {
+        let stores = Stores {
+            history: History::new(10),
+            outbox: Some(Outbox::in_memory().unwrap()),
+        };
+        let sizes = stores.sizes();
+        assert_eq!(sizes.len(), 1);
+        assert_eq!(sizes[0].0, "queue");
+        assert!(sizes[0].1 > 0);
+        stores.vacuum();
+    }
+}
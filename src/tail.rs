@@ -0,0 +1,266 @@
+use crate::bots::Bots;
+use crate::pages::escape;
+use crate::telegram::MAX_MESSAGE_LENGTH;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Seconds matching lines are gathered for before they are sent together
+pub const DEFAULT_BATCH_SECS: u64 = 10;
+
+/// How often the file is checked for new lines
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reads the lines appended to a file, like `tail -F`: when the file is
+/// rotated (replaced by a new file) or truncated, it continues at the start
+/// of the new contents
+pub struct Follower {
+    path: PathBuf,
+    reader: BufReader<File>,
+    /// Identity of the open file, to notice it was replaced
+    id: Option<u64>,
+    offset: u64,
+    /// Start of a line not finished yet
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    /// Open the file, at its end unless `from_start`
+    pub fn open(path: &Path, from_start: bool) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let id = file_id(&file);
+        let mut reader = BufReader::new(file);
+        let offset = if from_start {
+            0
+        } else {
+            reader.seek(SeekFrom::End(0))?
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader,
+            id,
+            offset,
+            partial: Vec::new(),
+        })
+    }
+
+    /// The complete lines written since the last call
+    pub fn read_lines(&mut self) -> Result<Vec<String>> {
+        let mut lines = self.drain()?;
+        // Switch over only once the old file was read to its end
+        let Ok(current) = std::fs::metadata(&self.path) else {
+            // Between moving the old file away and creating the new one
+            return Ok(lines);
+        };
+        let truncated = current.len() < self.offset;
+        if truncated || (self.id.is_some() && metadata_id(&current) != self.id) {
+            let file = File::open(&self.path)
+                .with_context(|| format!("Failed to reopen {}", self.path.display()))?;
+            self.id = file_id(&file);
+            self.reader = BufReader::new(file);
+            self.offset = 0;
+            self.partial.clear();
+            lines.extend(self.drain()?);
+        }
+        Ok(lines)
+    }
+
+    fn drain(&mut self) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let read = self.reader.read_until(b'\n', &mut self.partial)?;
+            if read == 0 {
+                return Ok(lines);
+            }
+            self.offset += read as u64;
+            if self.partial.ends_with(b"\n") {
+                lines.push(
+                    String::from_utf8_lossy(&self.partial)
+                        .trim_end()
+                        .to_string(),
+                );
+                self.partial.clear();
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn metadata_id(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+/// Without inodes, rotation is only noticed as the file getting shorter
+#[cfg(not(unix))]
+fn metadata_id(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+fn file_id(file: &File) -> Option<u64> {
+    file.metadata().ok().as_ref().and_then(metadata_id)
+}
+
+/// One message for a batch of matching lines; the oldest ones are left out
+/// if they don't fit
+pub fn render(file_name: &str, lines: &[String]) -> String {
+    let count = lines.len();
+    let headline = match count {
+        1 => format!("📄 <b>{}</b>: 1 matching line", escape(file_name)),
+        _ => format!("📄 <b>{}</b>: {count} matching lines", escape(file_name)),
+    };
+    // Room for the lines, leaving some for the tags and the omitted count
+    let budget = MAX_MESSAGE_LENGTH - headline.chars().count() - 40;
+    let mut kept = Vec::new();
+    let mut used = 0;
+    for line in lines.iter().rev() {
+        let escaped = escape(line);
+        used += escaped.chars().count() + 1;
+        if used > budget {
+            break;
+        }
+        kept.push(escaped);
+    }
+    if kept.is_empty() {
+        // A single line too long for a message; escaping grows it at most
+        // sixfold
+        let start: String = lines[count - 1].chars().take(budget / 6).collect();
+        kept.push(format!("{}…", escape(&start)));
+    }
+    kept.reverse();
+    let omitted = match count - kept.len() {
+        0 => String::new(),
+        skipped => format!("… {skipped} earlier\n"),
+    };
+    format!("{headline}\n<pre>{omitted}{}</pre>", kept.join("\n"))
+}
+
+/// Follow `path` until interrupted, sending the lines matching `pattern`
+/// (all lines without one) to `chat_ids`, each with its bot, at most one
+/// message per `batch`
+pub async fn run(
+    bots: &Bots,
+    chat_ids: &[String],
+    path: &Path,
+    pattern: Option<&str>,
+    batch: Duration,
+    from_start: bool,
+) -> Result<()> {
+    let pattern = pattern
+        .map(Regex::new)
+        .transpose()
+        .context("Invalid pattern")?;
+    let mut follower = Follower::open(path, from_start)?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+    info!(
+        "👀 Following {} for chat ID: {}",
+        path.display(),
+        chat_ids.join(", ")
+    );
+
+    let mut pending = Vec::new();
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    let mut flush = tokio::time::interval(batch.max(POLL_INTERVAL));
+    loop {
+        tokio::select! {
+            _ = poll.tick() => match follower.read_lines() {
+                Ok(lines) => pending.extend(
+                    lines
+                        .into_iter()
+                        .filter(|line| pattern.as_ref().is_none_or(|pattern| pattern.is_match(line))),
+                ),
+                Err(e) => warn!("⚠️ Failed to read {}: {:#}", path.display(), e),
+            },
+            _ = flush.tick() => {
+                if pending.is_empty() {
+                    continue;
+                }
+                let message = render(&file_name, &pending);
+                for chat_id in chat_ids {
+                    let bot = bots.for_chat(chat_id);
+                    match bot.send_message_advanced(chat_id, &message, Some("HTML"), false).await {
+                        Ok(_) => info!("📤 Sent {} lines of {} to {}", pending.len(), file_name, chat_id),
+                        Err(e) => warn!("⚠️ Dropped {} lines of {} for {}: {}", pending.len(), file_name, chat_id, e),
+                    }
+                }
+                pending.clear();
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_follow_across_rotation() {
+        let dir = std::env::temp_dir().join(format!("tail-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        append(&path, "old line\n");
+
+        let mut follower = Follower::open(&path, false).unwrap();
+        assert!(follower.read_lines().unwrap().is_empty());
+
+        append(&path, "ERROR one\nhalf");
+        assert_eq!(follower.read_lines().unwrap(), vec!["ERROR one"]);
+        append(&path, " done\n");
+        assert_eq!(follower.read_lines().unwrap(), vec!["half done"]);
+
+        // Rotated: the rest of the old file, then the new one from its start
+        append(&path, "last of old\n");
+        std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+        append(&path, "first of new\n");
+        assert_eq!(
+            follower.read_lines().unwrap(),
+            vec!["last of old", "first of new"]
+        );
+
+        // Truncated in place
+        std::fs::write(&path, "cut\n").unwrap();
+        assert_eq!(follower.read_lines().unwrap(), vec!["cut"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_render() {
+        let lines = vec!["ERROR <db> down".to_string()];
+        assert_eq!(
+            render("app.log", &lines),
+            "📄 <b>app.log</b>: 1 matching line\n<pre>ERROR &lt;db&gt; down</pre>"
+        );
+
+        let lines: Vec<String> = (0..100)
+            .map(|i| format!("{i:03} {}", "x".repeat(60)))
+            .collect();
+        let message = render("app.log", &lines);
+        assert!(message.starts_with("📄 <b>app.log</b>: 100 matching lines\n<pre>… "));
+        assert!(message.contains("099 "));
+        assert!(message.chars().count() <= MAX_MESSAGE_LENGTH);
+
+        let message = render("app.log", &["<".repeat(5000)]);
+        assert!(message.ends_with("&lt;…</pre>"));
+        assert!(message.chars().count() <= MAX_MESSAGE_LENGTH);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::defaults::ChatDefaults;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+/// A `[chats]` entry: just the chat ID, or a named channel with settings of
+/// its own
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ChatEntry {
+    ChatId(String),
+    Channel(Channel),
+}
+
+/// A named channel (`ops = { chat_id = "-100123", thread_id = 7 }`)
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Channel {
+    pub chat_id: String,
+    /// Forum topic messages are posted in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    /// Send without a notification sound
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub silent: Option<bool>,
+}
+
+impl ChatEntry {
+    pub fn chat_id(&self) -> &str {
+        match self {
+            ChatEntry::ChatId(chat_id) => chat_id,
+            ChatEntry::Channel(channel) => &channel.chat_id,
+        }
+    }
+
+    /// How messages to the channel's chat are sent, if it says
+    pub fn defaults(&self) -> Option<ChatDefaults> {
+        let ChatEntry::Channel(channel) = self else {
+            return None;
+        };
+        let defaults = ChatDefaults {
+            parse_mode: channel.parse_mode.clone(),
+            disable_notification: channel.silent,
+            thread_id: channel.thread_id,
+            ..Default::default()
+        };
+        (defaults != ChatDefaults::default()).then_some(defaults)
+    }
+}
+
+/// Named chats notifications can be sent to: the `[chats]` aliases from the
+/// config file plus any declared through `PUT /admin/targets/{name}`.
+/// Cloning shares the same targets.
+#[derive(Clone, Default)]
+pub struct Targets {
+    chats: Arc<RwLock<BTreeMap<String, String>>>,
+    /// Destination URL of an alias → its chat ID; resolved but not listed,
+    /// as the URLs carry credentials
+    destinations: Arc<HashMap<String, String>>,
+}
+
+impl Targets {
+    pub fn new(aliases: &HashMap<String, String>) -> Self {
+        let chats = aliases
+            .iter()
+            .map(|(name, chat_id)| (name.clone(), chat_id.clone()))
+            .collect();
+        Self {
+            chats: Arc::new(RwLock::new(chats)),
+            destinations: Arc::default(),
+        }
+    }
+
+    /// Also resolve the destination URLs of aliases to their chats
+    pub fn with_destinations(mut self, destinations: &HashMap<String, String>) -> Self {
+        self.destinations = Arc::new(destinations.clone());
+        self
+    }
+
+    /// Chat ID a target name or destination URL points to
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        match self.chats.read().unwrap().get(name) {
+            Some(chat_id) => Some(chat_id.clone()),
+            None => self.destinations.get(name.trim()).cloned(),
+        }
+    }
+
+    /// All targets as `(name, chat ID)`, sorted by name
+    pub fn list(&self) -> Vec<(String, String)> {
+        self.chats
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, chat_id)| (name.clone(), chat_id.clone()))
+            .collect()
+    }
+
+    /// Create or update a target, returning the chat it pointed to before
+    pub fn put(&self, name: &str, chat_id: &str) -> Option<String> {
+        self.chats
+            .write()
+            .unwrap()
+            .insert(name.to_string(), chat_id.to_string())
+    }
+
+    /// Remove a target, returning the chat it pointed to
+    pub fn remove(&self, name: &str) -> Option<String> {
+        self.chats.write().unwrap().remove(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_put_and_remove() {
+        let targets = Targets::new(&HashMap::from([(
+            "ops-team".to_string(),
+            "-100123".to_string(),
+        )]));
+
+        assert_eq!(targets.put("releases", "-100456"), None);
+        assert_eq!(
+            targets.put("ops-team", "-100789"),
+            Some("-100123".to_string())
+        );
+        assert_eq!(targets.resolve("ops-team"), Some("-100789".to_string()));
+        assert_eq!(
+            targets.list()[1],
+            ("releases".to_string(), "-100456".to_string())
+        );
+        assert_eq!(targets.remove("releases"), Some("-100456".to_string()));
+        assert_eq!(targets.resolve("releases"), None);
+    }
+
+    #[test]
+    fn test_destinations() {
+        let targets = Targets::new(&HashMap::from([("phone".to_string(), "phone".to_string())]))
+            .with_destinations(&HashMap::from([(
+                "ntfy://acme-alerts".to_string(),
+                "phone".to_string(),
+            )]));
+
+        assert_eq!(
+            targets.resolve("ntfy://acme-alerts"),
+            Some("phone".to_string())
+        );
+        assert_eq!(targets.resolve("ntfy://other"), None);
+        assert_eq!(targets.list().len(), 1);
+    }
+}
@@ -1,9 +1,21 @@
+use crate::breaker::CircuitBreaker;
+use crate::ratelimit::RateLimiter;
 use anyhow::{Context, Result};
-use reqwest::Client;
+use reqwest::{Client, Response, multipart};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Value, json};
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
 
-const TELEGRAM_API_BASE: &str = "https://api.telegram.org/bot";
+pub(crate) const TELEGRAM_API_BASE: &str = "https://api.telegram.org/bot";
+
+/// Maximum length of a single Telegram text message, in characters
+pub const MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Maximum length of the caption of a file or photo, in characters
+pub const MAX_CAPTION_LENGTH: usize = 1024;
 
 #[derive(Debug, Serialize)]
 pub struct SendMessageRequest {
@@ -13,6 +25,83 @@ pub struct SendMessageRequest {
     pub parse_mode: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub disable_notification: Option<bool>,
+    /// Forum topic the message is posted in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_thread_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_preview_options: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protect_content: Option<bool>,
+    /// Inline keyboard of URL buttons
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<Value>,
+}
+
+/// How a message is delivered, besides its text and parse mode
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MessageOptions {
+    pub disable_notification: bool,
+    pub disable_link_preview: bool,
+    /// Keep the message from being forwarded and saved
+    pub protect_content: bool,
+    /// Forum topic to post in
+    pub thread_id: Option<i64>,
+}
+
+impl MessageOptions {
+    pub fn silent(disable_notification: bool) -> Self {
+        Self {
+            disable_notification,
+            ..Default::default()
+        }
+    }
+}
+
+impl SendMessageRequest {
+    pub fn new(
+        chat_id: &str,
+        message: &str,
+        parse_mode: Option<&str>,
+        disable_notification: bool,
+        buttons: &[Button],
+    ) -> Self {
+        Self::with_options(
+            chat_id,
+            message,
+            parse_mode,
+            MessageOptions::silent(disable_notification),
+            buttons,
+        )
+    }
+
+    pub fn with_options(
+        chat_id: &str,
+        message: &str,
+        parse_mode: Option<&str>,
+        options: MessageOptions,
+        buttons: &[Button],
+    ) -> Self {
+        Self {
+            chat_id: chat_id.to_string(),
+            text: message.to_string(),
+            parse_mode: parse_mode.map(|s| s.to_string()),
+            disable_notification: options.disable_notification.then_some(true),
+            message_thread_id: options.thread_id,
+            link_preview_options: options
+                .disable_link_preview
+                .then(|| json!({ "is_disabled": true })),
+            protect_content: options.protect_content.then_some(true),
+            reply_markup: inline_keyboard(buttons),
+        }
+    }
+}
+
+/// Fail for captions longer than Telegram takes
+pub fn check_caption(caption: &str) -> Result<()> {
+    if caption.chars().count() > MAX_CAPTION_LENGTH {
+        anyhow::bail!("Captions are limited to {MAX_CAPTION_LENGTH} characters");
+    }
+    Ok(())
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,11 +113,156 @@ pub struct TelegramResponse {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<ResponseParameters>,
+}
+
+/// Extra details of a failed request, e.g. how long to back off after a 429
+#[derive(Debug, Deserialize)]
+pub struct ResponseParameters {
+    pub retry_after: Option<u64>,
+}
+
+/// Error reported by the Bot API (`ok: false`)
+#[derive(Debug)]
+pub struct TelegramError {
+    pub description: String,
+    pub error_code: Option<i32>,
+    /// Seconds to wait before retrying, sent with 429 Too Many Requests
+    pub retry_after: Option<u64>,
+}
+
+impl fmt::Display for TelegramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Telegram API error: {} (code: {:?})",
+            self.description, self.error_code
+        )
+    }
+}
+
+impl std::error::Error for TelegramError {}
+
+/// Most URL buttons a message can carry
+pub const MAX_BUTTONS: usize = 12;
+
+/// Buttons per row of the inline keyboard
+const BUTTONS_PER_ROW: usize = 3;
+
+/// An inline keyboard button opening a URL
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Button {
+    pub text: String,
+    pub url: String,
+}
+
+/// Check buttons before Telegram refuses the whole message over them
+pub fn check_buttons(buttons: &[Button]) -> Result<()> {
+    if buttons.len() > MAX_BUTTONS {
+        anyhow::bail!("At most {MAX_BUTTONS} buttons are allowed");
+    }
+    for button in buttons {
+        if button.text.trim().is_empty() {
+            anyhow::bail!("Button for {:?} has no text", button.url);
+        }
+        if !["http://", "https://", "tg://"]
+            .iter()
+            .any(|scheme| button.url.starts_with(scheme))
+        {
+            anyhow::bail!(
+                "Button URL {:?} must start with http://, https:// or tg://",
+                button.url
+            );
+        }
+    }
+    Ok(())
 }
 
+/// `reply_markup` showing the buttons in rows
+fn inline_keyboard(buttons: &[Button]) -> Option<Value> {
+    if buttons.is_empty() {
+        return None;
+    }
+    let rows: Vec<&[Button]> = buttons.chunks(BUTTONS_PER_ROW).collect();
+    Some(json!({ "inline_keyboard": rows }))
+}
+
+/// A file sent along with a notification
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Attachment {
+    pub file_name: String,
+    pub content_type: Option<String>,
+    #[serde(with = "base64_data")]
+    pub data: Vec<u8>,
+}
+
+/// Attachment contents as base64 strings rather than arrays of numbers
+mod base64_data {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64.decode(encoded).map_err(D::Error::custom)
+    }
+}
+
+impl Attachment {
+    /// Read a file to send, its content type guessed from the extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .with_context(|| format!("{} is not a file", path.display()))?;
+        let content_type = path
+            .extension()
+            .and_then(|ext| content_type(&ext.to_string_lossy().to_lowercase()))
+            .map(str::to_string);
+        Ok(Self {
+            file_name,
+            content_type,
+            data,
+        })
+    }
+
+    /// Images Telegram can show inline are sent as photos, everything else as documents
+    pub fn is_photo(&self) -> bool {
+        matches!(
+            self.content_type.as_deref(),
+            Some("image/jpeg" | "image/png" | "image/webp")
+        )
+    }
+}
+
+fn content_type(extension: &str) -> Option<&'static str> {
+    match extension {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "webp" => Some("image/webp"),
+        "gif" => Some("image/gif"),
+        "pdf" => Some("application/pdf"),
+        "txt" | "log" => Some("text/plain"),
+        "csv" => Some("text/csv"),
+        "json" => Some("application/json"),
+        "zip" => Some("application/zip"),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
 pub struct TelegramBot {
     client: Client,
     api_url: String,
+    limiter: RateLimiter,
+    breaker: CircuitBreaker,
 }
 
 impl TelegramBot {
@@ -37,9 +271,42 @@ impl TelegramBot {
         Self {
             client: Client::new(),
             api_url,
+            limiter: RateLimiter::default(),
+            breaker: CircuitBreaker::default(),
+        }
+    }
+
+    /// Bot talking to a different Bot API server, e.g. a self-hosted one
+    pub fn with_base_url(bot_token: String, base_url: &str) -> Self {
+        let api_url = format!("{}/bot{bot_token}", base_url.trim_end_matches('/'));
+        Self {
+            client: Client::new(),
+            api_url,
+            limiter: RateLimiter::default(),
+            breaker: CircuitBreaker::default(),
         }
     }
 
+    /// Use `limiter` instead of Telegram's default flood limits
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// Use `breaker` instead of the default circuit breaker settings
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
     pub async fn send_message(&self, chat_id: &str, message: &str) -> Result<TelegramResponse> {
         self.send_message_advanced(chat_id, message, Some("Markdown"), false)
             .await
@@ -52,72 +319,391 @@ impl TelegramBot {
         parse_mode: Option<&str>,
         disable_notification: bool,
     ) -> Result<TelegramResponse> {
-        let request = SendMessageRequest {
-            chat_id: chat_id.to_string(),
-            text: message.to_string(),
-            parse_mode: parse_mode.map(|s| s.to_string()),
-            disable_notification: if disable_notification {
-                Some(true)
-            } else {
-                None
-            },
-        };
+        self.send_message_with_buttons(chat_id, message, parse_mode, disable_notification, &[])
+            .await
+    }
+
+    /// Send a message with URL buttons below it
+    pub async fn send_message_with_buttons(
+        &self,
+        chat_id: &str,
+        message: &str,
+        parse_mode: Option<&str>,
+        disable_notification: bool,
+        buttons: &[Button],
+    ) -> Result<TelegramResponse> {
+        self.send_message_with_options(
+            chat_id,
+            message,
+            parse_mode,
+            MessageOptions::silent(disable_notification),
+            buttons,
+        )
+        .await
+    }
+
+    /// Send a message with URL buttons below it and the given options
+    pub async fn send_message_with_options(
+        &self,
+        chat_id: &str,
+        message: &str,
+        parse_mode: Option<&str>,
+        options: MessageOptions,
+        buttons: &[Button],
+    ) -> Result<TelegramResponse> {
+        let request =
+            SendMessageRequest::with_options(chat_id, message, parse_mode, options, buttons);
 
         let url = format!("{}/sendMessage", self.api_url);
 
-        let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send request to Telegram API")?;
+        let result = self
+            .guarded(async {
+                self.throttle(chat_id).await;
+                let response = self
+                    .client
+                    .post(&url)
+                    .json(&request)
+                    .send()
+                    .await
+                    .context("Failed to send request to Telegram API")?;
+                parse_response(response).await
+            })
+            .await;
+        self.note_flood_control(chat_id, &result);
+        result
+    }
 
-        let telegram_response: TelegramResponse = response
-            .json()
-            .await
-            .context("Failed to parse Telegram API response")?;
-
-        if !telegram_response.ok {
-            return Err(anyhow::anyhow!(
-                "Telegram API error: {} (code: {:?})",
-                telegram_response
-                    .description
-                    .unwrap_or_else(|| "Unknown error".to_string()),
-                telegram_response.error_code
-            ));
+    /// Send a file as a photo or document, depending on its type
+    pub async fn send_attachment(
+        &self,
+        chat_id: &str,
+        attachment: &Attachment,
+        disable_notification: bool,
+    ) -> Result<TelegramResponse> {
+        self.send_file(
+            chat_id,
+            attachment,
+            attachment.is_photo(),
+            None,
+            disable_notification,
+        )
+        .await
+    }
+
+    /// Send a file as a photo (`sendPhoto`) or document (`sendDocument`),
+    /// with an optional plain-text caption
+    pub async fn send_file(
+        &self,
+        chat_id: &str,
+        attachment: &Attachment,
+        as_photo: bool,
+        caption: Option<&str>,
+        disable_notification: bool,
+    ) -> Result<TelegramResponse> {
+        self.send_file_with_options(
+            chat_id,
+            attachment,
+            as_photo,
+            caption,
+            MessageOptions::silent(disable_notification),
+        )
+        .await
+    }
+
+    /// Send a file as by [`send_file`](Self::send_file), with the given
+    /// options (link previews don't apply to files)
+    pub async fn send_file_with_options(
+        &self,
+        chat_id: &str,
+        attachment: &Attachment,
+        as_photo: bool,
+        caption: Option<&str>,
+        options: MessageOptions,
+    ) -> Result<TelegramResponse> {
+        if let Some(caption) = caption {
+            check_caption(caption)?;
+        }
+        let (method, field) = if as_photo {
+            ("sendPhoto", "photo")
+        } else {
+            ("sendDocument", "document")
+        };
+
+        let mut part =
+            multipart::Part::bytes(attachment.data.clone()).file_name(attachment.file_name.clone());
+        if let Some(content_type) = &attachment.content_type {
+            part = part
+                .mime_str(content_type)
+                .context("Invalid attachment content type")?;
+        }
+        let mut form = multipart::Form::new()
+            .text("chat_id", chat_id.to_string())
+            .part(field, part);
+        if let Some(caption) = caption {
+            form = form.text("caption", caption.to_string());
+        }
+        if options.disable_notification {
+            form = form.text("disable_notification", "true");
+        }
+        if options.protect_content {
+            form = form.text("protect_content", "true");
+        }
+        if let Some(thread_id) = options.thread_id {
+            form = form.text("message_thread_id", thread_id.to_string());
+        }
+
+        let url = format!("{}/{method}", self.api_url);
+
+        let result = self
+            .guarded(async {
+                self.throttle(chat_id).await;
+                let response = self
+                    .client
+                    .post(&url)
+                    .multipart(form)
+                    .send()
+                    .await
+                    .context("Failed to send file to Telegram API")?;
+                parse_response(response).await
+            })
+            .await;
+        self.note_flood_control(chat_id, &result);
+        result
+    }
+
+    /// Send a request unless the circuit breaker is open, telling it whether
+    /// Telegram could be reached
+    async fn guarded(
+        &self,
+        request: impl Future<Output = Result<TelegramResponse>>,
+    ) -> Result<TelegramResponse> {
+        self.breaker.allow(Instant::now())?;
+        let result = request.await;
+        self.breaker.record(is_outage(&result), Instant::now());
+        result
+    }
+
+    /// Wait until the rate limiter lets a message to `chat_id` through
+    async fn throttle(&self, chat_id: &str) {
+        let wait = self.limiter.reserve(chat_id, Instant::now());
+        if !wait.is_zero() {
+            debug!(
+                "⏳ Rate limited, waiting {:?} before sending to {}",
+                wait, chat_id
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Hold back further sends to a chat after a 429 from Telegram
+    fn note_flood_control(&self, chat_id: &str, result: &Result<TelegramResponse>) {
+        let retry_after = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<TelegramError>())
+            .and_then(|e| e.retry_after);
+        if let Some(secs) = retry_after {
+            warn!(
+                "⚠️ Telegram flood control for chat {}, retry after {}s",
+                chat_id, secs
+            );
+            self.limiter
+                .throttled(chat_id, Duration::from_secs(secs), Instant::now());
+        }
+    }
+
+    /// Pin a message; in channels this needs the "Edit messages" admin right,
+    /// elsewhere "Pin messages"
+    pub async fn pin_chat_message(
+        &self,
+        chat_id: &str,
+        message_id: i64,
+        disable_notification: bool,
+    ) -> Result<TelegramResponse> {
+        self.call(
+            "pinChatMessage",
+            &json!({
+                "chat_id": chat_id,
+                "message_id": message_id,
+                "disable_notification": disable_notification,
+            }),
+        )
+        .await
+    }
+
+    pub async fn unpin_chat_message(
+        &self,
+        chat_id: &str,
+        message_id: i64,
+    ) -> Result<TelegramResponse> {
+        self.call(
+            "unpinChatMessage",
+            &json!({ "chat_id": chat_id, "message_id": message_id }),
+        )
+        .await
+    }
+
+    /// Replace the text of a message the bot sent earlier
+    pub async fn edit_message_text(
+        &self,
+        chat_id: &str,
+        message_id: i64,
+        text: &str,
+        parse_mode: Option<&str>,
+    ) -> Result<TelegramResponse> {
+        let mut body = json!({ "chat_id": chat_id, "message_id": message_id, "text": text });
+        if let Some(parse_mode) = parse_mode {
+            body["parse_mode"] = json!(parse_mode);
         }
+        self.call("editMessageText", &body).await
+    }
+
+    /// Delete a message; bots can only delete messages up to 48 hours old
+    pub async fn delete_message(&self, chat_id: &str, message_id: i64) -> Result<TelegramResponse> {
+        self.call(
+            "deleteMessage",
+            &json!({ "chat_id": chat_id, "message_id": message_id }),
+        )
+        .await
+    }
+
+    pub async fn get_chat(&self, chat_id: &str) -> Result<TelegramResponse> {
+        self.call("getChat", &json!({ "chat_id": chat_id })).await
+    }
+
+    pub async fn get_chat_member(&self, chat_id: &str, user_id: i64) -> Result<TelegramResponse> {
+        self.call(
+            "getChatMember",
+            &json!({ "chat_id": chat_id, "user_id": user_id }),
+        )
+        .await
+    }
+
+    /// Long-poll for new messages sent to the bot, starting at update `offset`
+    pub async fn get_updates(&self, offset: i64, timeout_secs: u64) -> Result<TelegramResponse> {
+        self.call(
+            "getUpdates",
+            &json!({
+                "offset": offset,
+                "timeout": timeout_secs,
+                "allowed_updates": ["message"],
+            }),
+        )
+        .await
+    }
 
-        Ok(telegram_response)
+    /// Call a Bot API method taking a JSON body
+    async fn call(&self, method: &str, body: &Value) -> Result<TelegramResponse> {
+        let url = format!("{}/{method}", self.api_url);
+
+        self.guarded(async {
+            let response = self
+                .client
+                .post(&url)
+                .json(body)
+                .send()
+                .await
+                .with_context(|| format!("Failed to send {method} request to Telegram API"))?;
+            parse_response(response).await
+        })
+        .await
     }
 
     pub async fn get_me(&self) -> Result<TelegramResponse> {
         let url = format!("{}/getMe", self.api_url);
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send getMe request to Telegram API")?;
+        self.guarded(async {
+            let response = self
+                .client
+                .get(&url)
+                .send()
+                .await
+                .context("Failed to send getMe request to Telegram API")?;
+            parse_response(response).await
+        })
+        .await
+    }
+}
 
-        let telegram_response: TelegramResponse = response
-            .json()
-            .await
-            .context("Failed to parse Telegram API response")?;
-
-        if !telegram_response.ok {
-            return Err(anyhow::anyhow!(
-                "Telegram API error: {} (code: {:?})",
-                telegram_response
-                    .description
-                    .unwrap_or_else(|| "Unknown error".to_string()),
-                telegram_response.error_code
-            ));
+/// Whether a failed request means Telegram is unreachable or broken, rather
+/// than that it refused this particular request
+fn is_outage(result: &Result<TelegramResponse>) -> bool {
+    match result {
+        Ok(_) => false,
+        Err(e) => match e.downcast_ref::<TelegramError>() {
+            Some(error) => error.error_code.is_some_and(|code| code >= 500),
+            None => true,
+        },
+    }
+}
+
+/// Parse a Bot API response, turning `ok: false` into an error
+async fn parse_response(response: Response) -> Result<TelegramResponse> {
+    let telegram_response: TelegramResponse = response
+        .json()
+        .await
+        .context("Failed to parse Telegram API response")?;
+
+    if !telegram_response.ok {
+        return Err(TelegramError {
+            description: telegram_response
+                .description
+                .unwrap_or_else(|| "Unknown error".to_string()),
+            error_code: telegram_response.error_code,
+            retry_after: telegram_response
+                .parameters
+                .and_then(|parameters| parameters.retry_after),
         }
+        .into());
+    }
+
+    Ok(telegram_response)
+}
 
-        Ok(telegram_response)
+/// Split text into parts Telegram accepts, preferring to break at line boundaries
+pub fn split_message(text: &str) -> Vec<String> {
+    split_message_at(text, MAX_MESSAGE_LENGTH)
+}
+
+/// The first `max_len` characters of text, cut at a line boundary if possible
+pub fn truncate_message(text: &str, max_len: usize) -> String {
+    split_message_at(text, max_len).swap_remove(0)
+}
+
+fn split_message_at(text: &str, max_len: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_len = line.chars().count();
+
+        if current_len + line_len > max_len && !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+
+        if line_len > max_len {
+            // A single line longer than the limit has to be cut mid-line
+            let chars: Vec<char> = line.chars().collect();
+            for chunk in chars.chunks(max_len) {
+                if chunk.len() == max_len {
+                    parts.push(chunk.iter().collect());
+                } else {
+                    current = chunk.iter().collect();
+                    current_len = chunk.len();
+                }
+            }
+        } else {
+            current.push_str(line);
+            current_len += line_len;
+        }
+    }
+
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(current);
     }
+    parts
 }
 
 #[cfg(test)]
@@ -129,10 +715,7 @@ mod tests {
     // Helper function to create a test bot with mock server URL
     async fn create_test_bot(server: &Server) -> TelegramBot {
         let bot_token = "test_token_123:ABCdefGHIjklMNOpqrSTUvwxyz";
-        let mut bot = TelegramBot::new(bot_token.to_string());
-        // Override the API URL to use our mock server
-        bot.api_url = format!("{}/bot{}", server.url(), bot_token);
-        bot
+        TelegramBot::with_base_url(bot_token.to_string(), &server.url())
     }
 
     #[tokio::test]
@@ -185,6 +768,149 @@ mod tests {
         mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn test_circuit_breaker_fails_fast() {
+        let mut server = Server::new_async().await;
+
+        let mock = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/sendMessage",
+            )
+            .with_status(502)
+            .with_body("<html>Bad Gateway</html>")
+            .expect(2)
+            .create_async()
+            .await;
+
+        let bot = create_test_bot(&server)
+            .await
+            .with_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60)));
+        for _ in 0..2 {
+            assert!(bot.send_message("987654321", "Disk full").await.is_err());
+        }
+        let error = bot
+            .send_message("987654321", "Disk full")
+            .await
+            .unwrap_err();
+
+        // The third request never reached Telegram
+        mock.assert_async().await;
+        assert!(error.is::<crate::breaker::CircuitOpen>(), "{error}");
+        assert_eq!(bot.circuit_breaker().state(Instant::now()).state, "open");
+    }
+
+    #[tokio::test]
+    async fn test_flood_control_is_recorded() {
+        let mut server = Server::new_async().await;
+
+        let _mock = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/sendMessage",
+            )
+            .with_status(429)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "ok": false,
+                    "error_code": 429,
+                    "description": "Too Many Requests: retry after 7",
+                    "parameters": {"retry_after": 7}
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let bot = create_test_bot(&server).await;
+        let error = bot
+            .send_message("-1001234567890", "Test message")
+            .await
+            .unwrap_err();
+
+        let telegram_error = error.downcast_ref::<TelegramError>().unwrap();
+        assert_eq!(telegram_error.error_code, Some(429));
+        assert_eq!(telegram_error.retry_after, Some(7));
+
+        let snapshot = bot.rate_limiter().snapshot(Instant::now());
+        assert_eq!(snapshot.recent_429s[0].chat_id, "-1001234567890");
+        assert_eq!(snapshot.recent_429s[0].retry_after_secs, 7);
+        assert!(snapshot.chats[0].bucket.wait_secs > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_pin_chat_message() {
+        let mut server = Server::new_async().await;
+
+        let mock = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/pinChatMessage",
+            )
+            .match_body(Matcher::Json(json!({
+                "chat_id": "-1001234567890",
+                "message_id": 42,
+                "disable_notification": true,
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"ok": true, "result": true}).to_string())
+            .create_async()
+            .await;
+
+        let bot = create_test_bot(&server).await;
+        let result = bot.pin_chat_message("-1001234567890", 42, true).await;
+
+        assert!(result.is_ok());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_edit_and_delete_message() {
+        let mut server = Server::new_async().await;
+
+        let edit = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/editMessageText",
+            )
+            .match_body(Matcher::Json(json!({
+                "chat_id": "-1001234567890",
+                "message_id": 42,
+                "text": "✅ *Resolved*",
+                "parse_mode": "Markdown",
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"ok": true, "result": {"message_id": 42}}).to_string())
+            .create_async()
+            .await;
+        let delete = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/deleteMessage",
+            )
+            .match_body(Matcher::Json(json!({
+                "chat_id": "-1001234567890",
+                "message_id": 42,
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"ok": true, "result": true}).to_string())
+            .create_async()
+            .await;
+
+        let bot = create_test_bot(&server).await;
+        bot.edit_message_text("-1001234567890", 42, "✅ *Resolved*", Some("Markdown"))
+            .await
+            .unwrap();
+        bot.delete_message("-1001234567890", 42).await.unwrap();
+
+        edit.assert_async().await;
+        delete.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_send_message_advanced_success() {
         let mut server = Server::new_async().await;
@@ -235,6 +961,58 @@ mod tests {
         mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn test_send_message_with_buttons() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/sendMessage",
+            )
+            .match_body(Matcher::PartialJson(json!({
+                "reply_markup": {"inline_keyboard": [[
+                    {"text": "Panel", "url": "https://grafana.example.com/d/abc?viewPanel=2"},
+                    {"text": "Dashboard", "url": "https://grafana.example.com/d/abc"},
+                ]]}
+            })))
+            .with_status(200)
+            .with_body(json!({"ok": true, "result": {"message_id": 44}}).to_string())
+            .create_async()
+            .await;
+
+        let bot = create_test_bot(&server).await;
+        let buttons = [
+            Button {
+                text: "Panel".to_string(),
+                url: "https://grafana.example.com/d/abc?viewPanel=2".to_string(),
+            },
+            Button {
+                text: "Dashboard".to_string(),
+                url: "https://grafana.example.com/d/abc".to_string(),
+            },
+        ];
+        assert!(check_buttons(&buttons).is_ok());
+        bot.send_message_with_buttons("987654321", "CPU high", None, false, &buttons)
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_check_buttons() {
+        let button = |text: &str, url: &str| Button {
+            text: text.to_string(),
+            url: url.to_string(),
+        };
+        assert!(check_buttons(&[button("Chat", "tg://resolve?domain=ops")]).is_ok());
+        assert!(check_buttons(&[button(" ", "https://example.com")]).is_err());
+        assert!(check_buttons(&[button("Run", "javascript:alert(1)")]).is_err());
+        assert!(
+            check_buttons(&vec![button("Go", "https://example.com"); MAX_BUTTONS + 1]).is_err()
+        );
+    }
+
     #[tokio::test]
     async fn test_send_message_advanced_no_parse_mode() {
         let mut server = Server::new_async().await;
@@ -418,6 +1196,10 @@ mod tests {
             text: "Hello World".to_string(),
             parse_mode: Some("Markdown".to_string()),
             disable_notification: Some(true),
+            message_thread_id: None,
+            link_preview_options: None,
+            protect_content: None,
+            reply_markup: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -429,6 +1211,33 @@ mod tests {
         assert_eq!(parsed["disable_notification"], true);
     }
 
+    #[test]
+    fn test_send_message_request_with_options() {
+        let options = MessageOptions {
+            disable_notification: false,
+            disable_link_preview: true,
+            protect_content: true,
+            thread_id: Some(7),
+        };
+        let request = SendMessageRequest::with_options(
+            "123456789",
+            "See https://example.com",
+            None,
+            options,
+            &[],
+        );
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "chat_id": "123456789",
+                "text": "See https://example.com",
+                "message_thread_id": 7,
+                "link_preview_options": {"is_disabled": true},
+                "protect_content": true
+            })
+        );
+    }
+
     #[tokio::test]
     async fn test_send_message_request_serialization_minimal() {
         let request = SendMessageRequest {
@@ -436,6 +1245,10 @@ mod tests {
             text: "Hello World".to_string(),
             parse_mode: None,
             disable_notification: None,
+            message_thread_id: None,
+            link_preview_options: None,
+            protect_content: None,
+            reply_markup: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -534,6 +1347,168 @@ mod tests {
         mock.assert_async().await;
     }
 
+    #[test]
+    fn test_split_message_short() {
+        assert_eq!(split_message("Hello"), vec!["Hello".to_string()]);
+    }
+
+    #[test]
+    fn test_split_message_at_line_boundaries() {
+        let parts = split_message_at("aaaa\nbbbb\ncccc", 10);
+        assert_eq!(parts, vec!["aaaa\nbbbb\n".to_string(), "cccc".to_string()]);
+    }
+
+    #[test]
+    fn test_split_message_long_line() {
+        let parts = split_message_at("abcdefghij\nxy", 4);
+        assert_eq!(parts, vec!["abcd", "efgh", "ij\n", "xy"]);
+    }
+
+    #[test]
+    fn test_split_message_counts_characters() {
+        let text = "é".repeat(MAX_MESSAGE_LENGTH);
+        assert_eq!(split_message(&text).len(), 1);
+
+        let text = "é".repeat(MAX_MESSAGE_LENGTH + 1);
+        let parts = split_message(&text);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1], "é");
+    }
+
+    #[test]
+    fn test_telegram_bot_with_base_url() {
+        let bot = TelegramBot::with_base_url("123:ABC".to_string(), "http://localhost:8081/");
+        assert_eq!(bot.api_url, "http://localhost:8081/bot123:ABC");
+    }
+
+    #[tokio::test]
+    async fn test_send_attachment_as_document() {
+        let mut server = Server::new_async().await;
+
+        let mock = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/sendDocument",
+            )
+            .match_header(
+                "content-type",
+                Matcher::Regex("multipart/form-data".to_string()),
+            )
+            .match_body(Matcher::AllOf(vec![
+                Matcher::Regex(r#"name="document"; filename="build.log""#.to_string()),
+                Matcher::Regex("error: linking failed".to_string()),
+                Matcher::Regex(r#"name="disable_notification""#.to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"ok": true, "result": {"message_id": 45}}).to_string())
+            .create_async()
+            .await;
+
+        let bot = create_test_bot(&server).await;
+        let attachment = Attachment {
+            file_name: "build.log".to_string(),
+            content_type: Some("text/plain".to_string()),
+            data: b"error: linking failed".to_vec(),
+        };
+        let result = bot.send_attachment("987654321", &attachment, true).await;
+
+        assert!(result.is_ok());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_attachment_as_photo() {
+        let mut server = Server::new_async().await;
+
+        let mock = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/sendPhoto",
+            )
+            .match_body(Matcher::Regex(
+                r#"name="photo"; filename="graph.png""#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"ok": true, "result": {"message_id": 46}}).to_string())
+            .create_async()
+            .await;
+
+        let bot = create_test_bot(&server).await;
+        let attachment = Attachment {
+            file_name: "graph.png".to_string(),
+            content_type: Some("image/png".to_string()),
+            data: vec![0x89, b'P', b'N', b'G'],
+        };
+        let result = bot.send_attachment("987654321", &attachment, false).await;
+
+        assert!(result.is_ok());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_file_with_caption() {
+        let mut server = Server::new_async().await;
+
+        let mock = server
+            .mock(
+                "POST",
+                "/bottest_token_123:ABCdefGHIjklMNOpqrSTUvwxyz/sendPhoto",
+            )
+            .match_body(Matcher::AllOf(vec![
+                Matcher::Regex(r#"name="photo"; filename="report-\d+\.pdf""#.to_string()),
+                Matcher::Regex(r#"name="caption"\r\n\r\nNightly report"#.to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({"ok": true, "result": {"message_id": 47}}).to_string())
+            .create_async()
+            .await;
+
+        let bot = create_test_bot(&server).await;
+        let path = std::env::temp_dir().join(format!("report-{}.pdf", std::process::id()));
+        std::fs::write(&path, b"%PDF-1.7").unwrap();
+        let attachment = Attachment::from_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(attachment.content_type.as_deref(), Some("application/pdf"));
+
+        let result = bot
+            .send_file(
+                "987654321",
+                &attachment,
+                true,
+                Some("Nightly report"),
+                false,
+            )
+            .await;
+        assert!(result.is_ok());
+        mock.assert_async().await;
+
+        let long = "x".repeat(MAX_CAPTION_LENGTH + 1);
+        assert!(
+            bot.send_file("987654321", &attachment, false, Some(&long), false)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_attachment_is_photo() {
+        let mut attachment = Attachment {
+            file_name: "a".to_string(),
+            content_type: Some("image/jpeg".to_string()),
+            data: Vec::new(),
+        };
+        assert!(attachment.is_photo());
+
+        attachment.content_type = Some("image/gif".to_string());
+        assert!(!attachment.is_photo());
+
+        attachment.content_type = None;
+        assert!(!attachment.is_photo());
+    }
+
     #[test]
     fn test_telegram_api_base_constant() {
         assert_eq!(TELEGRAM_API_BASE, "https://api.telegram.org/bot");
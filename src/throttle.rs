@@ -0,0 +1,275 @@
+use crate::pipeline::{Notification, Outcome, Phase, Stage};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// A limit on the notifications from one key or source
+/// (`[throttles.<name>]` in the config file), apart from Telegram's own
+/// rate limits
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThrottleSettings {
+    /// Named API key (`[auth.keys.<id>]`) whose notifications are limited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Source whose notifications are limited, e.g. `alertmanager`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Notifications let through per window
+    pub max: u32,
+    /// Length of the window
+    pub per_minutes: u64,
+    #[serde(default)]
+    pub overflow: Overflow,
+}
+
+/// What happens to notifications over the limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    /// Don't send them
+    #[default]
+    Drop,
+    /// Send them as low priority, i.e. in the chat's digest if it has one
+    Digest,
+    /// Hold them until a later window has room
+    Defer,
+}
+
+impl ThrottleSettings {
+    pub fn check(&self) -> Result<()> {
+        if self.max == 0 || self.per_minutes == 0 {
+            bail!("A throttle needs a max and per_minutes above 0");
+        }
+        Ok(())
+    }
+
+    fn matches(&self, notification: &Notification) -> bool {
+        self.key
+            .as_ref()
+            .is_none_or(|key| notification.key.as_ref() == Some(key))
+            && self
+                .source
+                .as_ref()
+                .is_none_or(|source| &notification.source == source)
+    }
+}
+
+struct Throttle {
+    name: String,
+    settings: ThrottleSettings,
+    /// Window start (Unix seconds) → notifications sent or deferred in it
+    windows: Mutex<BTreeMap<u64, u32>>,
+}
+
+impl Throttle {
+    /// None if the notification fits into the current window, else the
+    /// start of the first later window with room, counting it there unless
+    /// it's only a preview
+    fn take(&self, now: u64, defer: bool, preview: bool) -> Option<u64> {
+        let length = self.settings.per_minutes * 60;
+        let current = now - now % length;
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|start, _| *start >= current);
+        let mut start = current;
+        loop {
+            let used = windows.get(&start).copied().unwrap_or_default();
+            if used < self.settings.max {
+                if !preview {
+                    windows.insert(start, used + 1);
+                }
+                return (start != current).then_some(start);
+            }
+            if !defer {
+                return Some(start + length);
+            }
+            start += length;
+        }
+    }
+}
+
+/// Holds notifications over their throttle back, dropping, digesting or
+/// deferring them. Every matching throttle counts a notification; emergency
+/// notifications are never throttled.
+pub struct Throttles {
+    throttles: Vec<Throttle>,
+}
+
+impl Throttles {
+    pub fn new(throttles: &BTreeMap<String, ThrottleSettings>) -> Self {
+        Self {
+            throttles: throttles
+                .iter()
+                .map(|(name, settings)| Throttle {
+                    name: name.clone(),
+                    settings: settings.clone(),
+                    windows: Mutex::default(),
+                })
+                .collect(),
+        }
+    }
+
+    fn apply(&self, notification: &mut Notification, now: u64, preview: bool) -> Outcome {
+        if notification.emergency {
+            return Outcome::Continue;
+        }
+        for throttle in &self.throttles {
+            if !throttle.settings.matches(notification) {
+                continue;
+            }
+            let overflow = throttle.settings.overflow;
+            let Some(next) = throttle.take(now, overflow == Overflow::Defer, preview) else {
+                continue;
+            };
+            match overflow {
+                Overflow::Drop => {
+                    return Outcome::Drop {
+                        reason: format!(
+                            "Over throttle {} ({} per {} min)",
+                            throttle.name, throttle.settings.max, throttle.settings.per_minutes
+                        ),
+                    };
+                }
+                Overflow::Digest => notification.low_priority = true,
+                Overflow::Defer => {
+                    info!(
+                        "🚥 Notification {} deferred by throttle {}",
+                        notification.delivery_id, throttle.name
+                    );
+                    notification.send_at = notification.send_at.max(Some(next));
+                }
+            }
+        }
+        Outcome::Continue
+    }
+}
+
+impl Stage for Throttles {
+    fn name(&self) -> &str {
+        "throttles"
+    }
+
+    fn phase(&self) -> Phase {
+        Phase::Dedupe
+    }
+
+    fn process(&self, notification: &mut Notification) -> Result<Outcome> {
+        Ok(self.apply(notification, unix_now(), false))
+    }
+
+    fn preview(&self, notification: &mut Notification) -> Result<Outcome> {
+        Ok(self.apply(notification, unix_now(), true))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttles(overflow: Overflow) -> Throttles {
+        Throttles::new(&BTreeMap::from([(
+            "staging".to_string(),
+            ThrottleSettings {
+                key: Some("staging-ci".to_string()),
+                source: None,
+                max: 2,
+                per_minutes: 10,
+                overflow,
+            },
+        )]))
+    }
+
+    fn from_key(key: Option<&str>) -> Notification {
+        Notification {
+            key: key.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_drop_and_digest() {
+        let now = 6000;
+        let stage = throttles(Overflow::Drop);
+        assert_eq!(
+            stage.apply(&mut from_key(Some("staging-ci")), now, false),
+            Outcome::Continue
+        );
+        assert_eq!(
+            stage.apply(&mut from_key(Some("staging-ci")), now, false),
+            Outcome::Continue
+        );
+        assert!(matches!(
+            stage.apply(&mut from_key(Some("staging-ci")), now + 10, false),
+            Outcome::Drop { .. }
+        ));
+        // Other keys aren't limited, and the next window starts over
+        assert_eq!(
+            stage.apply(&mut from_key(None), now, false),
+            Outcome::Continue
+        );
+        assert_eq!(
+            stage.apply(&mut from_key(Some("staging-ci")), now + 600, false),
+            Outcome::Continue
+        );
+
+        let stage = throttles(Overflow::Digest);
+        let mut notifications: Vec<_> = (0..3).map(|_| from_key(Some("staging-ci"))).collect();
+        for notification in &mut notifications {
+            assert_eq!(stage.apply(notification, now, false), Outcome::Continue);
+        }
+        let low_priority: Vec<_> = notifications.iter().map(|n| n.low_priority).collect();
+        assert_eq!(low_priority, vec![false, false, true]);
+
+        let mut emergency = from_key(Some("staging-ci"));
+        emergency.emergency = true;
+        assert_eq!(stage.apply(&mut emergency, now, false), Outcome::Continue);
+        assert!(!emergency.low_priority);
+    }
+
+    #[test]
+    fn test_preview_takes_no_room() {
+        let now = 6000;
+        let stage = throttles(Overflow::Drop);
+        for _ in 0..3 {
+            assert_eq!(
+                stage.apply(&mut from_key(Some("staging-ci")), now, true),
+                Outcome::Continue
+            );
+        }
+        for _ in 0..2 {
+            stage.apply(&mut from_key(Some("staging-ci")), now, false);
+        }
+        // Full, which a preview shows without taking anything itself
+        assert!(matches!(
+            stage.apply(&mut from_key(Some("staging-ci")), now, true),
+            Outcome::Drop { .. }
+        ));
+    }
+
+    #[test]
+    fn test_defer_fills_later_windows() {
+        let now = 6000;
+        let stage = throttles(Overflow::Defer);
+        let send_at: Vec<_> = (0..5)
+            .map(|_| {
+                let mut notification = from_key(Some("staging-ci"));
+                stage.apply(&mut notification, now, false);
+                notification.send_at
+            })
+            .collect();
+        assert_eq!(
+            send_at,
+            vec![None, None, Some(6600), Some(6600), Some(7200)]
+        );
+    }
+}
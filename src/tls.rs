@@ -0,0 +1,152 @@
+use anyhow::{Context, Result, bail};
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tracing::{info, warn};
+
+/// Certificate and key the server serves HTTPS with
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsSettings {
+    /// PEM certificate chain, the server's own certificate first
+    pub cert: PathBuf,
+    /// PEM private key
+    pub key: PathBuf,
+    /// How often the files are checked for a renewed certificate; none to
+    /// load them once
+    pub reload_interval: Option<Duration>,
+}
+
+impl TlsSettings {
+    /// The rustls configuration, failing if the files can't be read or the
+    /// key isn't the certificate's
+    pub fn server_config(&self) -> Result<Arc<ServerConfig>> {
+        let certs = CertificateDer::pem_file_iter(&self.cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .with_context(|| format!("Failed to read TLS certificate {}", self.cert.display()))?;
+        if certs.is_empty() {
+            bail!("No certificate in {}", self.cert.display());
+        }
+        let key = PrivateKeyDer::from_pem_file(&self.key)
+            .with_context(|| format!("Failed to read TLS key {}", self.key.display()))?;
+        let provider = tokio_rustls::rustls::crypto::ring::default_provider();
+        let mut config = ServerConfig::builder_with_provider(Arc::new(provider))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .with_context(|| {
+                format!(
+                    "TLS key {} doesn't fit certificate {}",
+                    self.key.display(),
+                    self.cert.display()
+                )
+            })?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+
+    /// When the files last changed, to notice a renewal
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Some((modified(&self.cert)?, modified(&self.key)?))
+    }
+}
+
+/// Serve a renewed certificate once its files change, every
+/// `reload_interval`. Connections already open keep the old one; a
+/// certificate that fails to load is reported and the current one kept.
+pub async fn watch(settings: TlsSettings, config: RustlsConfig) {
+    let Some(interval) = settings.reload_interval else {
+        return;
+    };
+    let mut last = settings.modified();
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let modified = settings.modified();
+        if modified == last {
+            continue;
+        }
+        last = modified;
+        match settings.server_config() {
+            Ok(server_config) => {
+                config.reload_from_config(server_config);
+                info!("🔐 Reloaded TLS certificate {}", settings.cert.display());
+            }
+            Err(e) => warn!("⚠️  Keeping the current TLS certificate: {e:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed certificate and key for `name`, written to the temporary
+    /// directory
+    fn write_cert(name: &str) -> TlsSettings {
+        let cert = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        let dir = std::env::temp_dir();
+        let settings = TlsSettings {
+            cert: dir.join(format!("tn-tls-{name}-{}.crt", std::process::id())),
+            key: dir.join(format!("tn-tls-{name}-{}.key", std::process::id())),
+            reload_interval: Some(Duration::from_millis(20)),
+        };
+        std::fs::write(&settings.cert, cert.cert.pem()).unwrap();
+        std::fs::write(&settings.key, cert.signing_key.serialize_pem()).unwrap();
+        settings
+    }
+
+    #[test]
+    fn test_server_config() {
+        let settings = write_cert("localhost");
+        let config = settings.server_config().unwrap();
+        assert_eq!(config.alpn_protocols[1], b"http/1.1");
+
+        let other = write_cert("other.example.com");
+        let mismatched = TlsSettings {
+            key: other.key.clone(),
+            ..settings.clone()
+        };
+        let error = format!("{:#}", mismatched.server_config().unwrap_err());
+        assert!(error.contains("doesn't fit"), "{error}");
+        let missing = TlsSettings {
+            cert: PathBuf::from("/nonexistent/tls.crt"),
+            ..settings.clone()
+        };
+        assert!(missing.server_config().is_err());
+
+        for path in [&settings.cert, &settings.key, &other.cert, &other.key] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_reloads_renewed_certificate() {
+        let settings = write_cert("renewed.example.com");
+        let config = RustlsConfig::from_config(settings.server_config().unwrap());
+        let before = config.get_inner();
+        tokio::spawn(watch(settings.clone(), config.clone()));
+
+        // A renewal rewrites both files with a new certificate
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let renewed = write_cert("renewed.example.com");
+        assert_eq!(renewed, settings);
+        let mut reloaded = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            if !Arc::ptr_eq(&config.get_inner(), &before) {
+                reloaded = true;
+                break;
+            }
+        }
+        assert!(reloaded);
+
+        std::fs::remove_file(&settings.cert).unwrap();
+        std::fs::remove_file(&settings.key).unwrap();
+    }
+}
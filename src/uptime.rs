@@ -0,0 +1,275 @@
+use crate::api::SendNotificationRequest;
+use crate::pages::escape;
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of notifications from `POST /integrations/uptime`
+pub const SOURCE: &str = "uptime";
+
+/// Body of an Uptime Kuma webhook (`application/json` body), or of any
+/// monitor reporting `{"monitor": "...", "status": "down", "msg": "..."}`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Webhook {
+    /// Uptime Kuma's latest check of the monitor
+    pub heartbeat: Option<Heartbeat>,
+    pub monitor: Option<Monitor>,
+    /// Status of generic monitors, which send no heartbeat
+    pub status: Option<StatusValue>,
+    pub msg: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Heartbeat {
+    pub status: Option<StatusValue>,
+    pub msg: String,
+    /// UTC time of the check, e.g. `2026-10-16 08:00:00.123`
+    pub time: Option<String>,
+}
+
+/// The monitor, as Uptime Kuma's object or just its name
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Monitor {
+    Name(String),
+    Details {
+        #[serde(default)]
+        id: Option<i64>,
+        name: String,
+        #[serde(default)]
+        url: Option<String>,
+    },
+}
+
+/// Uptime Kuma's numeric status (0 down, 1 up, 2 pending, 3 maintenance) or
+/// its name
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum StatusValue {
+    Code(u8),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Down,
+    Up,
+    Pending,
+    Maintenance,
+}
+
+impl StatusValue {
+    fn status(&self) -> Option<Status> {
+        match self {
+            StatusValue::Code(0) => Some(Status::Down),
+            StatusValue::Code(1) => Some(Status::Up),
+            StatusValue::Code(2) => Some(Status::Pending),
+            StatusValue::Code(3) => Some(Status::Maintenance),
+            StatusValue::Code(_) => None,
+            StatusValue::Name(name) => match name.to_lowercase().as_str() {
+                "down" => Some(Status::Down),
+                "up" => Some(Status::Up),
+                "pending" => Some(Status::Pending),
+                "maintenance" => Some(Status::Maintenance),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl Webhook {
+    pub fn status(&self) -> Option<Status> {
+        self.heartbeat
+            .as_ref()
+            .and_then(|heartbeat| heartbeat.status.as_ref())
+            .or(self.status.as_ref())
+            .and_then(StatusValue::status)
+    }
+
+    /// Key the monitor's outages are tracked under
+    pub fn monitor_key(&self) -> Option<String> {
+        match self.monitor.as_ref()? {
+            Monitor::Name(name) => Some(name.clone()),
+            Monitor::Details { id: Some(id), .. } => Some(format!("#{id}")),
+            Monitor::Details { name, .. } => Some(name.clone()),
+        }
+    }
+
+    /// Unix time of the check, the time it arrived if the webhook doesn't say
+    pub fn checked_at(&self) -> u64 {
+        self.heartbeat
+            .as_ref()
+            .and_then(|heartbeat| heartbeat.time.as_deref())
+            .and_then(|time| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f").ok())
+            .and_then(|time| u64::try_from(time.and_utc().timestamp()).ok())
+            .unwrap_or_else(unix_now)
+    }
+}
+
+/// When each monitor that is down went down, so the recovery message can
+/// say for how long. Kept in memory; cloning shares it.
+#[derive(Clone, Default)]
+pub struct Outages {
+    down_since: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl Outages {
+    /// Note a check of the monitor; returns how long it was down once it is
+    /// up again. Repeated down checks keep the time of the first.
+    pub fn record(&self, monitor: &str, status: Status, at: u64) -> Option<u64> {
+        let mut down_since = self.down_since.lock().unwrap();
+        match status {
+            Status::Down => {
+                down_since.entry(monitor.to_string()).or_insert(at);
+                None
+            }
+            Status::Up => down_since
+                .remove(monitor)
+                .map(|since| at.saturating_sub(since)),
+            Status::Pending | Status::Maintenance => None,
+        }
+    }
+}
+
+/// Red message for a monitor going down, green one (sent silently) with the
+/// downtime for it coming back up. Webhooks without a monitor, such as Uptime
+/// Kuma's test notification, are sent as they are.
+pub fn render(webhook: &Webhook, downtime: Option<u64>) -> SendNotificationRequest {
+    let (Some(monitor), Some(status)) = (&webhook.monitor, webhook.status()) else {
+        return SendNotificationRequest {
+            message: webhook.msg.clone(),
+            ..Default::default()
+        };
+    };
+    let (name, url) = match monitor {
+        Monitor::Name(name) => (name.as_str(), None),
+        Monitor::Details { name, url, .. } => (name.as_str(), url.as_deref()),
+    };
+    let name = escape(name);
+    let mut lines = vec![match status {
+        Status::Down => format!("🔴 <b>{name} is down</b>"),
+        Status::Up => format!("🟢 <b>{name} is up</b>"),
+        Status::Pending => format!("🟡 <b>{name} is pending</b>"),
+        Status::Maintenance => format!("🔧 <b>{name} is under maintenance</b>"),
+    }];
+    if let Some(secs) = downtime {
+        lines.push(format!("Down for {}", format_duration(secs)));
+    }
+    let detail = match &webhook.heartbeat {
+        Some(heartbeat) if !heartbeat.msg.is_empty() => &heartbeat.msg,
+        _ => &webhook.msg,
+    };
+    if !detail.is_empty() {
+        lines.push(escape(detail));
+    }
+    if let Some(url) = url.filter(|url| url.starts_with("http://") || url.starts_with("https://")) {
+        lines.push(escape(url));
+    }
+
+    SendNotificationRequest {
+        message: lines.join("\n"),
+        parse_mode: Some("HTML".to_string()),
+        disable_notification: Some(status == Status::Up),
+        ..Default::default()
+    }
+}
+
+/// `1h 2m 5s`, leaving out leading zero units
+pub(crate) fn format_duration(secs: u64) -> String {
+    let (days, hours, minutes, seconds) =
+        (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    let parts = [(days, "d"), (hours, "h"), (minutes, "m"), (seconds, "s")];
+    let first = parts.iter().position(|(value, _)| *value > 0).unwrap_or(3);
+    parts[first..]
+        .iter()
+        .map(|(value, unit)| format!("{value}{unit}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn kuma(status: u8, time: &str, msg: &str) -> Webhook {
+        serde_json::from_value(json!({
+            "heartbeat": {"monitorID": 4, "status": status, "time": time, "msg": msg, "important": true},
+            "monitor": {"id": 4, "name": "Checkout API", "url": "https://shop.example.com/health", "type": "http"},
+            "msg": format!("[Checkout API] {msg}"),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_down_then_up_with_downtime() {
+        let outages = Outages::default();
+        let down = kuma(0, "2026-10-16 08:00:00.000", "connect ECONNREFUSED");
+        let key = down.monitor_key().unwrap();
+        assert_eq!(outages.record(&key, Status::Down, down.checked_at()), None);
+        // Still down a minute later; the outage started at the first check
+        assert_eq!(
+            outages.record(&key, Status::Down, down.checked_at() + 60),
+            None
+        );
+        assert_eq!(
+            render(&down, None).message,
+            "🔴 <b>Checkout API is down</b>\nconnect ECONNREFUSED\nhttps://shop.example.com/health"
+        );
+
+        let up = kuma(1, "2026-10-16 08:12:05.500", "200 - OK");
+        let downtime = outages.record(&key, Status::Up, up.checked_at());
+        assert_eq!(downtime, Some(725));
+        let request = render(&up, downtime);
+        assert_eq!(
+            request.message,
+            "🟢 <b>Checkout API is up</b>\nDown for 12m 5s\n200 - OK\nhttps://shop.example.com/health"
+        );
+        assert_eq!(request.disable_notification, Some(true));
+        assert_eq!(outages.record(&key, Status::Up, up.checked_at()), None);
+    }
+
+    #[test]
+    fn test_generic_and_test_payloads() {
+        let generic: Webhook = serde_json::from_value(json!({
+            "monitor": "Nightly <backup>",
+            "status": "DOWN",
+            "msg": "No heartbeat for 2h",
+        }))
+        .unwrap();
+        assert_eq!(generic.status(), Some(Status::Down));
+        assert_eq!(
+            render(&generic, None).message,
+            "🔴 <b>Nightly &lt;backup&gt; is down</b>\nNo heartbeat for 2h"
+        );
+
+        let test: Webhook = serde_json::from_value(json!({
+            "heartbeat": null,
+            "monitor": null,
+            "msg": "Uptime Kuma Testing Notification",
+        }))
+        .unwrap();
+        let request = render(&test, None);
+        assert_eq!(request.message, "Uptime Kuma Testing Notification");
+        assert_eq!(request.parse_mode, None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(59), "59s");
+        assert_eq!(format_duration(3600), "1h 0m 0s");
+        assert_eq!(format_duration(90_061), "1d 1h 1m 1s");
+    }
+}
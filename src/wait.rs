@@ -0,0 +1,152 @@
+use anyhow::{Context, Result, bail};
+use reqwest::{Client, Url};
+use std::fmt;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+use tracing::info;
+
+/// Default time `--wait-for` waits for all dependencies together
+pub const DEFAULT_WAIT_TIMEOUT_SECS: u64 = 60;
+
+/// Pause between two checks of a dependency that isn't up yet
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest a single check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Something the server needs before it starts (`--wait-for`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dependency {
+    /// A `host:port` accepting TCP connections
+    Tcp(String),
+    /// An HTTP(S) URL answering with a success status
+    Http(Url),
+}
+
+impl Dependency {
+    /// Parse `host:port`, `tcp://host:port` or an `http(s)://` URL
+    pub fn parse(target: &str) -> Result<Self> {
+        let target = target.trim();
+        if target.starts_with("http://") || target.starts_with("https://") {
+            let url = Url::parse(target).with_context(|| format!("Invalid URL {target:?}"))?;
+            return Ok(Self::Http(url));
+        }
+        let address = target.strip_prefix("tcp://").unwrap_or(target);
+        match address.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(Self::Tcp(address.to_string()))
+            }
+            _ => bail!("Invalid dependency {target:?}; expected host:port or an http(s):// URL"),
+        }
+    }
+
+    async fn check(&self, client: &Client) -> Result<()> {
+        match self {
+            Self::Tcp(address) => {
+                TcpStream::connect(address.as_str()).await?;
+            }
+            Self::Http(url) => {
+                client.get(url.clone()).send().await?.error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(address) => write!(f, "tcp://{address}"),
+            Self::Http(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// Check every dependency until it is available, failing once `timeout` has
+/// passed, so the server doesn't start before what it relies on is up
+pub async fn wait_for(dependencies: &[Dependency], timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    let client = Client::builder().timeout(CHECK_TIMEOUT).build()?;
+    for dependency in dependencies {
+        info!("⏳ Waiting for {}", dependency);
+        loop {
+            let error = match tokio::time::timeout(CHECK_TIMEOUT, dependency.check(&client)).await {
+                Ok(Ok(())) => break,
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => "no answer".to_string(),
+            };
+            if Instant::now() + RETRY_INTERVAL > deadline {
+                bail!(
+                    "Gave up waiting for {} after {}s: {}",
+                    dependency,
+                    timeout.as_secs(),
+                    error
+                );
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+        info!("✅ {} is available", dependency);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Dependency::parse("postgres:5432").unwrap(),
+            Dependency::Tcp("postgres:5432".to_string())
+        );
+        assert_eq!(
+            Dependency::parse("tcp://[::1]:6379").unwrap(),
+            Dependency::Tcp("[::1]:6379".to_string())
+        );
+        assert_eq!(
+            Dependency::parse("http://vault:8200/v1/sys/health")
+                .unwrap()
+                .to_string(),
+            "http://vault:8200/v1/sys/health"
+        );
+        assert!(Dependency::parse("postgres").is_err());
+        assert!(Dependency::parse("postgres:http").is_err());
+        assert!(Dependency::parse("http://").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_tcp() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let up = Dependency::Tcp(address.clone());
+        assert!(wait_for(&[up], Duration::ZERO).await.is_ok());
+
+        drop(listener);
+        let down = Dependency::Tcp(address);
+        let error = wait_for(&[down], Duration::ZERO).await.unwrap_err();
+        assert!(error.to_string().contains("Gave up waiting"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_http() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/ready")
+            .with_status(200)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/starting")
+            .with_status(503)
+            .create_async()
+            .await;
+
+        let url = |path: &str| Dependency::parse(&format!("{}{path}", server.url())).unwrap();
+        assert!(wait_for(&[url("/ready")], Duration::ZERO).await.is_ok());
+        assert!(wait_for(&[url("/starting")], Duration::ZERO).await.is_err());
+    }
+}
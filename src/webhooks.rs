@@ -0,0 +1,186 @@
+use crate::api::SendNotificationRequest;
+use crate::pages::escape;
+use anyhow::{Context, Result, bail};
+use jsonpath_rust::JsonPath;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Source of notifications from `POST /webhook/{name}`
+pub const SOURCE: &str = "webhook";
+
+/// A webhook mapped to a notification (`[webhooks.<name>]` in the config
+/// file), for services without a receiver of their own
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookMapping {
+    /// Message with `{{ <JSONPath> }}` placeholders filled in from the
+    /// request body, e.g. `{{ $.repository.name }}`
+    pub message: String,
+    /// Chat to send to; the default chat if neither this nor `chat` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<String>,
+    /// Chat alias, instead of `chat_id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat: Option<String>,
+    /// With `HTML`, the values filled in are escaped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    #[serde(default)]
+    pub disable_notification: bool,
+}
+
+/// A mapping with its message template parsed
+#[derive(Debug, Clone)]
+pub struct MappedWebhook {
+    pub mapping: WebhookMapping,
+    template: Template,
+}
+
+impl MappedWebhook {
+    pub fn new(mapping: WebhookMapping) -> Result<Self> {
+        let template = Template::parse(&mapping.message)?;
+        Ok(Self { mapping, template })
+    }
+
+    /// The notification for a request body
+    pub fn render(&self, body: &Value) -> SendNotificationRequest {
+        let html = self
+            .mapping
+            .parse_mode
+            .as_deref()
+            .is_some_and(|mode| mode.eq_ignore_ascii_case("html"));
+        SendNotificationRequest {
+            message: self.template.render(body, html),
+            chat_id: self.mapping.chat_id.clone(),
+            chat: self.mapping.chat.clone(),
+            parse_mode: self.mapping.parse_mode.clone(),
+            disable_notification: Some(self.mapping.disable_notification),
+            ..Default::default()
+        }
+    }
+}
+
+/// Text with `{{ <JSONPath> }}` placeholders, also used for the templates
+/// of `send --template`
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Path(JsonPath),
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let Some(end) = rest[start..].find("}}") else {
+                bail!("Unclosed {{{{ in template {template:?}");
+            };
+            let path = rest[start + 2..start + end].trim();
+            parts.push(Part::Path(JsonPath::from_str(path).with_context(|| {
+                format!("Invalid JSONPath {path:?} in template")
+            })?));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The text with every placeholder replaced by what its path finds:
+    /// strings as they are, several matches separated by commas, nothing if
+    /// there is no match
+    pub fn render(&self, body: &Value, html: bool) -> String {
+        let mut text = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(literal) => text.push_str(literal),
+                Part::Path(path) => {
+                    let value = path
+                        .find_slice(body)
+                        .into_iter()
+                        .filter_map(|found| match found.to_data() {
+                            Value::Null => None,
+                            Value::String(s) => Some(s),
+                            other => Some(other.to_string()),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    if html {
+                        text.push_str(&escape(&value));
+                    } else {
+                        text.push_str(&value);
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mapped(message: &str, parse_mode: Option<&str>) -> MappedWebhook {
+        MappedWebhook::new(WebhookMapping {
+            message: message.to_string(),
+            chat_id: None,
+            chat: Some("ops-team".to_string()),
+            parse_mode: parse_mode.map(str::to_string),
+            disable_notification: false,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_template() {
+        let body = json!({
+            "action": "published",
+            "release": {"tag_name": "v1.4.0", "prerelease": false, "assets": [{"name": "a.tar.gz"}, {"name": "b.zip"}]},
+            "repository": {"full_name": "acme/<shop>"},
+        });
+
+        let request = mapped(
+            "📦 {{ $.repository.full_name }} {{$.release.tag_name}} {{ $.action }} \
+             (prerelease: {{ $.release.prerelease }}, assets: {{ $.release.assets[*].name }}){{ $.missing }}",
+            None,
+        )
+        .render(&body);
+        assert_eq!(
+            request.message,
+            "📦 acme/<shop> v1.4.0 published (prerelease: false, assets: a.tar.gz, b.zip)"
+        );
+        assert_eq!(request.chat.as_deref(), Some("ops-team"));
+
+        let request = mapped("<b>{{ $.repository.full_name }}</b>", Some("HTML")).render(&body);
+        assert_eq!(request.message, "<b>acme/&lt;shop&gt;</b>");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let parse = |message: &str| {
+            MappedWebhook::new(WebhookMapping {
+                message: message.to_string(),
+                chat_id: None,
+                chat: None,
+                parse_mode: None,
+                disable_notification: false,
+            })
+        };
+        assert!(parse("Deploy {{ $.app").is_err());
+        assert!(parse("Deploy {{ $.app[ }}").is_err());
+        assert!(parse("No placeholders").is_ok());
+    }
+}
@@ -0,0 +1,288 @@
+//! Contract tests for the webhook adapters. Every captured request in
+//! tests/fixtures/<adapter>/<case>.http runs through its adapter, and what
+//! the adapter makes of it has to match <case>.golden.json next to it, so a
+//! change in how a payload is read can't go unnoticed. After an intended
+//! change, run with UPDATE_GOLDEN=1 to rewrite the golden files and review
+//! their diff.
+
+use axum::Json;
+use axum::body::{Body, to_bytes};
+use axum::extract::{FromRequest, Query, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use serde_json::{Map, Value, json};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use telegram_notifications::alertmanager::{self, Webhook};
+use telegram_notifications::api::{IntegrationQuery, SendNotificationRequest};
+use telegram_notifications::extract::{NotifyBody, StreamedJson};
+use telegram_notifications::{grafana, pagerduty, uptime};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// What the adapter the fixture directory is named after makes of a request
+async fn render(adapter: &str, request: Request) -> Value {
+    match adapter {
+        "notify" => notify(request).await,
+        "alertmanager" => alertmanager(request).await,
+        "grafana" => grafana(request).await,
+        "uptime" => uptime(request).await,
+        "pagerduty" => pagerduty(request).await,
+        _ => panic!("No adapter named {adapter:?}; name fixture directories after their adapter"),
+    }
+}
+
+/// The native request formats of `/notify` and `/send`
+async fn notify(request: Request) -> Value {
+    let body = match NotifyBody::from_request(request, &()).await {
+        Ok(body) => body,
+        Err(rejection) => return rejected(rejection).await,
+    };
+
+    let mut rendered = rendered_request(body.request);
+    if !body.attachments.is_empty() {
+        let attachments = body
+            .attachments
+            .iter()
+            .map(|attachment| {
+                json!({
+                    "file_name": attachment.file_name,
+                    "content_type": attachment.content_type,
+                    "size": attachment.data.len(),
+                })
+            })
+            .collect();
+        rendered.insert("attachments".to_string(), Value::Array(attachments));
+    }
+    Value::Object(rendered)
+}
+
+/// Prometheus Alertmanager webhooks to `/integrations/alertmanager`
+async fn alertmanager(request: Request) -> Value {
+    let Query(query) = Query::<IntegrationQuery>::try_from_uri(request.uri()).unwrap();
+    let webhook = match StreamedJson::<Webhook>::from_request(request, &()).await {
+        Ok(StreamedJson(webhook)) => webhook,
+        Err(rejection) => return rejected(rejection).await,
+    };
+
+    let mut request = alertmanager::render(&webhook);
+    request.chat_id = query.chat_id;
+    request.chat = query.chat;
+    Value::Object(rendered_request(request))
+}
+
+/// Grafana alerting webhooks to `/integrations/grafana`, with the
+/// screenshots that would be downloaded
+async fn grafana(request: Request) -> Value {
+    let Query(query) = Query::<IntegrationQuery>::try_from_uri(request.uri()).unwrap();
+    let webhook = match StreamedJson::<grafana::Webhook>::from_request(request, &()).await {
+        Ok(StreamedJson(webhook)) => webhook,
+        Err(rejection) => return rejected(rejection).await,
+    };
+
+    let mut request = grafana::render(&webhook);
+    request.chat_id = query.chat_id;
+    request.chat = query.chat;
+    let mut rendered = rendered_request(request);
+    let screenshots = grafana::screenshots(&webhook);
+    if !screenshots.is_empty() {
+        rendered.insert("screenshots".to_string(), json!(screenshots));
+    }
+    Value::Object(rendered)
+}
+
+/// Uptime Kuma and generic monitor webhooks to `/integrations/uptime`, as
+/// the first report of the monitor's status (so without a downtime)
+async fn uptime(request: Request) -> Value {
+    let Query(query) = Query::<IntegrationQuery>::try_from_uri(request.uri()).unwrap();
+    let webhook = match Json::<uptime::Webhook>::from_request(request, &()).await {
+        Ok(Json(webhook)) => webhook,
+        Err(rejection) => return rejected(rejection.into_response()).await,
+    };
+
+    let mut request = uptime::render(&webhook, None);
+    request.chat_id = query.chat_id;
+    request.chat = query.chat;
+    Value::Object(rendered_request(request))
+}
+
+/// PagerDuty Events API v2 events to `/integrations/pagerduty`: the message
+/// of a trigger, the incident other events mark
+async fn pagerduty(request: Request) -> Value {
+    let Query(query) = Query::<IntegrationQuery>::try_from_uri(request.uri()).unwrap();
+    let event = match Json::<pagerduty::Event>::from_request(request, &()).await {
+        Ok(Json(event)) => event,
+        Err(rejection) => return rejected(rejection.into_response()).await,
+    };
+    if let Err(e) = event.validate() {
+        return json!({ "rejected": { "status": 400, "body": e.to_string() } });
+    }
+    if event.event_action != pagerduty::Action::Trigger {
+        return json!({
+            "event_action": format!("{:?}", event.event_action).to_lowercase(),
+            "dedup_key": event.dedup_key,
+        });
+    }
+
+    let mut request = pagerduty::render(&event);
+    request.chat_id = query.chat_id;
+    request.chat = query.chat;
+    Value::Object(rendered_request(request))
+}
+
+/// The fields of a notification request that are set
+fn rendered_request(request: SendNotificationRequest) -> Map<String, Value> {
+    let mut rendered = Map::new();
+    rendered.insert("message".to_string(), json!(request.message));
+    let optional = [
+        ("chat_id", json!(request.chat_id)),
+        ("chat", json!(request.chat)),
+        ("chat_ids", json!(request.chat_ids)),
+        ("chats", json!(request.chats)),
+        ("parse_mode", json!(request.parse_mode)),
+        ("disable_notification", json!(request.disable_notification)),
+        ("pin", json!(request.pin)),
+        ("translations", json!(request.translations)),
+        ("async", json!(request.async_delivery)),
+        ("dedup_key", json!(request.dedup_key)),
+        ("deadline_ms", json!(request.deadline_ms)),
+        ("send_at", json!(request.send_at)),
+        ("callback_url", json!(request.callback_url)),
+        ("buttons", json!(request.buttons)),
+    ];
+    for (name, value) in optional {
+        if !value.is_null() {
+            rendered.insert(name.to_string(), value);
+        }
+    }
+    rendered
+}
+
+/// Status and body of a refused request
+async fn rejected(response: Response) -> Value {
+    let status = response.status().as_u16();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = serde_json::from_slice(&bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+    json!({ "rejected": { "status": status, "body": body } })
+}
+
+/// Read a captured request: the request line, headers, an empty line, then
+/// the body. The file's final newline isn't part of the body, and multipart
+/// bodies get the CRLF line endings they are sent with.
+fn parse_fixture(path: &Path) -> Request {
+    let text = fs::read_to_string(path).unwrap();
+    let (head, body) = text
+        .split_once("\n\n")
+        .unwrap_or_else(|| panic!("{}: no empty line after the headers", path.display()));
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or_default();
+    let (method, uri) = request_line
+        .split_once(' ')
+        .unwrap_or_else(|| panic!("{}: invalid request line", path.display()));
+
+    let mut builder = Request::builder().method(method).uri(uri);
+    let mut multipart = false;
+    for line in lines {
+        let (name, value) = line
+            .split_once(':')
+            .unwrap_or_else(|| panic!("{}: invalid header {line:?}", path.display()));
+        let value = value.trim();
+        if name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()) {
+            multipart = value.starts_with("multipart/");
+        }
+        builder = builder.header(name, value);
+    }
+
+    let mut body = body.strip_suffix('\n').unwrap_or(body).to_string();
+    if multipart {
+        body = body.replace('\n', "\r\n");
+    }
+    builder.body(Body::from(body)).unwrap()
+}
+
+/// Every `.http` fixture, grouped by adapter
+fn fixtures() -> Vec<(String, PathBuf)> {
+    let mut fixtures = Vec::new();
+    for adapter in fs::read_dir(FIXTURES).unwrap() {
+        let adapter = adapter.unwrap().path();
+        if !adapter.is_dir() {
+            continue;
+        }
+        let name = adapter.file_name().unwrap().to_string_lossy().into_owned();
+        for fixture in fs::read_dir(&adapter).unwrap() {
+            let fixture = fixture.unwrap().path();
+            if fixture
+                .extension()
+                .is_some_and(|extension| extension == "http")
+            {
+                fixtures.push((name.clone(), fixture));
+            }
+        }
+    }
+    fixtures.sort();
+    fixtures
+}
+
+fn golden_path(fixture: &Path) -> PathBuf {
+    fixture.with_extension("golden.json")
+}
+
+#[tokio::test]
+async fn test_adapter_fixtures_match_golden_files() {
+    let update = env::var("UPDATE_GOLDEN").is_ok_and(|value| value == "1");
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "No fixtures in {FIXTURES}");
+
+    let mut mismatches = Vec::new();
+    for (adapter, fixture) in &fixtures {
+        let rendered = render(adapter, parse_fixture(fixture)).await;
+        let rendered = serde_json::to_string_pretty(&rendered).unwrap() + "\n";
+        let golden = golden_path(fixture);
+        if update {
+            fs::write(&golden, &rendered).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&golden) {
+            Ok(expected) if expected == rendered => {}
+            Ok(expected) => mismatches.push(format!(
+                "{}:\n--- expected\n{expected}+++ rendered\n{rendered}",
+                fixture.display()
+            )),
+            Err(_) => mismatches.push(format!(
+                "{}: no golden file; run with UPDATE_GOLDEN=1 to create it",
+                fixture.display()
+            )),
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "Adapter output changed:\n\n{}",
+        mismatches.join("\n")
+    );
+}
+
+#[test]
+fn test_every_golden_file_has_a_fixture() {
+    let fixtures: Vec<PathBuf> = fixtures()
+        .iter()
+        .map(|(_, fixture)| golden_path(fixture))
+        .collect();
+    for adapter in fs::read_dir(FIXTURES).unwrap() {
+        let adapter = adapter.unwrap().path();
+        if !adapter.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(&adapter).unwrap() {
+            let file = file.unwrap().path();
+            if file.to_string_lossy().ends_with(".golden.json") {
+                assert!(
+                    fixtures.contains(&file),
+                    "{} has no fixture left",
+                    file.display()
+                );
+            }
+        }
+    }
+}
@@ -5,6 +5,11 @@ use std::time::Duration;
 
 /// Test helper to start the server process with a specific port
 fn start_test_server(port: u16) -> std::process::Child {
+    start_test_server_with_env(port, &[])
+}
+
+/// Start a test server with extra environment variables
+fn start_test_server_with_env(port: u16, env: &[(&str, &str)]) -> std::process::Child {
     Command::new("cargo")
         .args([
             "run",
@@ -22,6 +27,7 @@ fn start_test_server(port: u16) -> std::process::Child {
         .env("TELEGRAM_CHAT_ID", "123456789")
         .env("TELEGRAM_NOTIFICATIONS_SKIP_VALIDATION", "true") // Skip bot validation in tests
         .env("RUST_LOG", "warn") // Minimize logging during tests but show warnings
+        .envs(env.iter().copied())
         .spawn()
         .expect("Failed to start test server")
 }
@@ -72,7 +78,7 @@ async fn test_e2e_server_startup_and_info_endpoint() {
 
     let body: Value = response.json().await.unwrap();
     assert_eq!(body["name"], "Telegram Notifications API");
-    assert_eq!(body["endpoints"].as_array().unwrap().len(), 4);
+    assert_eq!(body["endpoints"].as_array().unwrap().len(), 46);
 
     // Cleanup
     let _ = server_process.kill();
@@ -110,6 +116,9 @@ async fn test_e2e_health_endpoint() {
     assert!(body.get("version").is_some());
     assert!(body.get("status").is_some());
     assert_eq!(body["bot_verified"], false); // Should be false due to skipped validation
+    assert!(body["uptime_secs"].is_u64());
+    assert_eq!(body["queued_messages"], 0);
+    assert!(body.get("last_successful_send").is_none());
 
     // Cleanup
     let _ = server_process.kill();
@@ -169,6 +178,7 @@ async fn test_e2e_notify_endpoint_validation() {
     let body: Value = response.json().await.unwrap();
     assert_eq!(body["success"], true);
     assert!(body["message"].as_str().unwrap().contains("test mode"));
+    assert_eq!(body["delivery_id"].as_str().unwrap().len(), 12);
 
     // Cleanup
     let _ = server_process.kill();
@@ -275,6 +285,3180 @@ async fn test_e2e_method_not_allowed() {
     let _ = server_process.wait();
 }
 
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_preview_endpoint() {
+    let port = 3007;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    // Preview uses the default chat and never sends
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .json(&json!({"message": "Preview me", "parse_mode": "HTML"}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["would_send"], true);
+    assert_eq!(body["chat_id"], "123456789");
+    assert_eq!(body["parse_mode"], "HTML");
+    assert_eq!(body["parts"], json!(["Preview me"]));
+
+    // Long messages are split into Telegram-sized parts
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .json(&json!({"message": "x".repeat(5000), "chat_id": "-100"}))
+        .send()
+        .await
+        .unwrap();
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["chat_id"], "-100");
+    assert_eq!(body["parts"].as_array().unwrap().len(), 2);
+
+    // Validation errors are reported the same way as /notify
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .json(&json!({"message": ""}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 400);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_async_delivery() {
+    let port = 3008;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    // Body flag
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "Queued via body", "async": true}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["success"], true);
+    assert_eq!(body["message"], "Notification queued for delivery");
+    assert!(body["delivery_id"].is_string());
+    assert!(body.get("telegram_message_id").is_none());
+
+    // Query parameter
+    let response = client
+        .post(format!("{server_url}/send?mode=async"))
+        .json(&json!({"message": "Queued via query"}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 202);
+
+    // Validation still happens before queueing
+    let response = client
+        .post(format!("{server_url}/notify?mode=async"))
+        .json(&json!({"message": ""}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 400);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_oversized_body_rejected() {
+    let port = 3009;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    // 2 MiB payload exceeds the default 1 MiB limit
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "x".repeat(2 * 1024 * 1024)}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 413);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_idempotency_key() {
+    let port = 3010;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let send = |key: &'static str| {
+        client
+            .post(format!("{server_url}/notify"))
+            .header("Idempotency-Key", key)
+            .json(&json!({"message": "Deploy finished"}))
+            .send()
+    };
+
+    // A retry with the same key returns the original result
+    let first: Value = send("deploy-42").await.unwrap().json().await.unwrap();
+    let retry: Value = send("deploy-42").await.unwrap().json().await.unwrap();
+    assert_eq!(first["success"], true);
+    assert_eq!(first["delivery_id"], retry["delivery_id"]);
+
+    // A different key is a new notification
+    let other: Value = send("deploy-43").await.unwrap().json().await.unwrap();
+    assert_ne!(first["delivery_id"], other["delivery_id"]);
+
+    // The body field works when no header is sent
+    let body = json!({"message": "Deploy finished", "dedup_key": "deploy-44"});
+    let first: Value = client
+        .post(format!("{server_url}/send"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let retry: Value = client
+        .post(format!("{server_url}/send"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(first["delivery_id"], retry["delivery_id"]);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_multipart_attachments() {
+    let port = 3011;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let form = || {
+        reqwest::multipart::Form::new()
+            .text("message", "Build #42 failed")
+            .text("chat_id", "-100")
+            .part(
+                "file",
+                reqwest::multipart::Part::bytes(b"error: linking failed".to_vec())
+                    .file_name("build.log")
+                    .mime_str("text/plain")
+                    .unwrap(),
+            )
+    };
+
+    // Preview lists the attachments
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .multipart(form())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["chat_id"], "-100");
+    assert_eq!(body["parts"], json!(["Build #42 failed"]));
+    assert_eq!(body["attachments"], json!(["build.log"]));
+
+    // Sending works the same way
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .multipart(form())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+
+    // The message field is required
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .multipart(reqwest::multipart::Form::new().text("chat_id", "-100"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "MISSING_MESSAGE");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_plain_text_body() {
+    let port = 3012;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .header("Content-Type", "text/plain")
+        .body("disk full on web-1\n")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["chat_id"], "123456789");
+    assert_eq!(body["parts"], json!(["disk full on web-1"]));
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("Content-Type", "text/plain")
+        .body("disk full on web-1")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+
+    // Empty bodies are rejected like an empty JSON message
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("Content-Type", "text/plain")
+        .body("")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 400);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_history_resend_and_delete() {
+    let port = 3013;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "Deploy finished"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let delivery_id = body["delivery_id"].as_str().unwrap().to_string();
+
+    let response = client
+        .get(format!("{server_url}/history/{delivery_id}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let record: Value = response.json().await.unwrap();
+    assert_eq!(record["message"], "Deploy finished");
+    assert_eq!(record["chat_id"], "123456789");
+    assert_eq!(record["status"], "sent");
+
+    // Resend to another chat
+    let response = client
+        .post(format!("{server_url}/history/{delivery_id}/resend"))
+        .json(&json!({"chat_id": "-100987654321"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let resend_id = body["delivery_id"].as_str().unwrap().to_string();
+    assert_ne!(resend_id, delivery_id);
+
+    let resend: Value = client
+        .get(format!("{server_url}/history/{resend_id}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(resend["chat_id"], "-100987654321");
+    assert_eq!(resend["resent_from"], delivery_id.as_str());
+
+    let original: Value = client
+        .get(format!("{server_url}/history/{delivery_id}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(original["resends"], json!([resend_id]));
+
+    // Deleted records can't be looked up or resent
+    let response = client
+        .delete(format!("{server_url}/history/{delivery_id}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+
+    let response = client
+        .get(format!("{server_url}/history/{delivery_id}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .post(format!("{server_url}/history/{delivery_id}/resend"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_chat_alias_errors() {
+    let port = 3014;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "hello", "chat": "ops-team"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "UNKNOWN_CHAT");
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "hello", "chat": "ops-team", "chat_id": "-100"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "CONFLICTING_CHAT");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_fan_out() {
+    let port = 3015;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({
+            "message": "Deploy finished",
+            "chat_ids": ["-100111", "-100222", "-100111"]
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["success"], true);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2, "duplicate chats are sent once");
+    assert_eq!(results[0]["chat_id"], "-100111");
+    assert_eq!(results[1]["chat_id"], "-100222");
+    assert!(results.iter().all(|r| r["success"] == true));
+    assert_ne!(results[0]["delivery_id"], results[1]["delivery_id"]);
+
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .json(&json!({"message": "Deploy finished", "chat_ids": ["-100111", "-100222"]}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["targets"].as_array().unwrap().len(), 2);
+    assert_eq!(body["targets"][1]["chat_id"], "-100222");
+
+    // A single target and a list can't be combined
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "hi", "chat_id": "-100", "chat_ids": ["-200"]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // Limiter state is for admin keys only
+    let response = client
+        .get(format!("{server_url}/admin/ratelimits"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_message_status() {
+    let port = 3016;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{server_url}/notify?mode=async"))
+        .json(&json!({"message": "Backup finished"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    let delivery_id = body["delivery_id"].as_str().unwrap().to_string();
+
+    // The background worker picks the notification up shortly
+    let mut status = Value::Null;
+    for _ in 0..20 {
+        status = client
+            .get(format!("{server_url}/messages/{delivery_id}"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if status["state"] == "sent" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(status["state"], "sent");
+    assert_eq!(status["attempts"], 1);
+    assert_eq!(status["telegram_message_id"], 42);
+    assert_eq!(status["chat_id"], "123456789");
+
+    let response = client
+        .get(format!("{server_url}/messages/unknown"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_emergency_requires_key() {
+    let port = 3017;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("X-Priority", "emergency")
+        .header("X-Api-Key", "not-allowed")
+        .json(&json!({"message": "Primary database is down"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "EMERGENCY_NOT_ALLOWED");
+
+    // Other priorities are sent as usual
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("X-Priority", "high")
+        .json(&json!({"message": "Disk at 80%"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_api_key_auth() {
+    let port = 3018;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process =
+        start_test_server_with_env(port, &[("TELEGRAM_NOTIFICATIONS_API_KEYS", "ci-key")]);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    // Health checks stay open
+    let response = client
+        .get(format!("{server_url}/health"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "hello"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "MISSING_API_KEY");
+
+    let response = client
+        .post(format!("{server_url}/send"))
+        .header("X-Api-Key", "wrong")
+        .json(&json!({"message": "hello"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVALID_API_KEY");
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .bearer_auth("ci-key")
+        .json(&json!({"message": "hello"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_delivery_deadline() {
+    let port = 3019;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    // Sent well within the deadline
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "Deploy finished", "deadline_ms": 5000}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["telegram_message_id"], 42);
+    let delivery_id = body["delivery_id"].as_str().unwrap();
+
+    let status: Value = client
+        .get(format!("{server_url}/messages/{delivery_id}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["state"], "sent");
+
+    let response = client
+        .post(format!("{server_url}/notify?deadline_ms=soon"))
+        .json(&json!({"message": "Deploy finished"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_base_path() {
+    let port = 3020;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process =
+        start_test_server_with_env(port, &[("TELEGRAM_NOTIFICATIONS_BASE_PATH", "/telegram/")]);
+
+    let client = Client::new();
+
+    // Wait for server to start; `/` itself is no longer served
+    let mut ready = false;
+    for _ in 0..20 {
+        if let Ok(response) = client.get(format!("{server_url}/telegram")).send().await
+            && response.status().is_success()
+        {
+            ready = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    assert!(ready, "Server failed to start");
+
+    let info: Value = client
+        .get(format!("{server_url}/telegram"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(info["endpoints"][0]["path"], "/telegram");
+    assert_eq!(info["endpoints"][2]["path"], "/telegram/notify");
+
+    let response = client
+        .post(format!("{server_url}/telegram/notify"))
+        .json(&json!({"message": "hello"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "hello"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_jwt_auth() {
+    use jsonwebtoken::{EncodingKey, Header, encode, get_current_timestamp};
+
+    let port = 3021;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process =
+        start_test_server_with_env(port, &[("TELEGRAM_NOTIFICATIONS_JWT_SECRET", "e2e-secret")]);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let token = encode(
+        &Header::default(),
+        &json!({"sub": "e2e", "exp": get_current_timestamp() + 60}),
+        &EncodingKey::from_secret(b"e2e-secret"),
+    )
+    .unwrap();
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .bearer_auth(&token)
+        .json(&json!({"message": "hello"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .bearer_auth("not-a-jwt")
+        .json(&json!({"message": "hello"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVALID_TOKEN");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_hmac_signing() {
+    use telegram_notifications::auth::sign;
+
+    let port = 3022;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[("TELEGRAM_NOTIFICATIONS_HMAC_SECRET", "webhook-secret")],
+    );
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let body = r#"{"message": "Deploy finished"}"#;
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("Content-Type", "application/json")
+        .header("X-Timestamp", "1700000000")
+        .header(
+            "X-Signature",
+            sign("webhook-secret", Some("1700000000"), body.as_bytes()),
+        )
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // A tampered body no longer matches
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("Content-Type", "application/json")
+        .header("X-Signature", sign("webhook-secret", None, body.as_bytes()))
+        .body(r#"{"message": "Deploy failed"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "INVALID_SIGNATURE");
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_localized_errors() {
+    let port = 3023;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("Accept-Language", "de-DE,de;q=0.9,en;q=0.8")
+        .json(&json!({"message": "hello", "chat": "ops-team"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(response.headers()["content-language"], "de");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "UNKNOWN_CHAT");
+    assert_eq!(body["error"], "Unbekannter Chat-Alias");
+    assert_eq!(body["detail"], "Unknown chat alias: ops-team");
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("Accept-Language", "es-MX")
+        .json(&json!({"message": ""}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "El mensaje no puede estar vacío");
+
+    // English, or no preference, keeps the original message
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("Accept-Language", "fr, en;q=0.5")
+        .json(&json!({"message": "hello", "chat": "ops-team"}))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "Unknown chat alias: ops-team");
+    assert!(body.get("detail").is_none());
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_basic_auth() {
+    let port = 3024;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[
+            ("TELEGRAM_NOTIFICATIONS_BASIC_AUTH_USERNAME", "legacy"),
+            ("TELEGRAM_NOTIFICATIONS_BASIC_AUTH_PASSWORD", "change-me"),
+        ],
+    );
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .json(&json!({"message": "Disk full"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let challenges: Vec<_> = response
+        .headers()
+        .get_all("www-authenticate")
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .collect();
+    assert!(challenges.iter().any(|value| value.starts_with("Basic ")));
+
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .basic_auth("legacy", Some("wrong"))
+        .json(&json!({"message": "Disk full"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "INVALID_CREDENTIALS");
+
+    let response = client
+        .post(format!("{server_url}/notify/preview"))
+        .basic_auth("legacy", Some("change-me"))
+        .json(&json!({"message": "Disk full"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_full_message_page() {
+    let port = 3025;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TELEGRAM_NOTIFICATIONS_PUBLIC_URL",
+            "https://alerts.example.com/",
+        )],
+    );
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let message = format!("{}<end of log>", "stack frame\n".repeat(500));
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": message}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let delivery_id = body["delivery_id"].as_str().unwrap().to_string();
+
+    let response = client
+        .get(format!("{server_url}/history/{delivery_id}"))
+        .send()
+        .await
+        .unwrap();
+    let record: Value = response.json().await.unwrap();
+    let sent = record["message"].as_str().unwrap();
+    assert!(sent.chars().count() <= 4096);
+    assert!(sent.ends_with(&format!(
+        "Full message: https://alerts.example.com/n/{delivery_id}"
+    )));
+    assert_eq!(record["full_message"], message);
+
+    let response = client
+        .get(format!("{server_url}/n/{delivery_id}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(
+        response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html")
+    );
+    let page = response.text().await.unwrap();
+    assert!(page.contains("&lt;end of log&gt;"));
+
+    let response = client
+        .get(format!("{server_url}/n/unknown"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_history_export() {
+    let port = 3026;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    // Wait for server to start
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    for message in ["Deploy started", "Deploy finished, 3 hosts"] {
+        let response = client
+            .post(format!("{server_url}/notify"))
+            .json(&json!({"message": message}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    let response = client
+        .get(format!(
+            "{server_url}/history/export?format=jsonl&from=2020-01-01"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = response.text().await.unwrap();
+    let records: Vec<Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["message"], "Deploy started");
+
+    let response = client
+        .get(format!("{server_url}/history/export?format=csv"))
+        .send()
+        .await
+        .unwrap();
+    let body = response.text().await.unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert!(lines[0].starts_with("delivery_id,chat_id,status"));
+    assert!(lines[2].ends_with(",\"Deploy finished, 3 hosts\""));
+
+    // Nothing was sent before 2020
+    let response = client
+        .get(format!("{server_url}/history/export?to=2020-01-01"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "");
+
+    let response = client
+        .get(format!("{server_url}/history/export?from=last-week"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // The CLI downloads the same export
+    let output = Command::new("cargo")
+        .args(["run", "--", "history", "export", "--url", &server_url])
+        .output()
+        .expect("Failed to run history export");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 2);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_key_quotas() {
+    let port = 3027;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let config_path = std::env::temp_dir().join(format!("key-quotas-{port}.toml"));
+    std::fs::write(
+        &config_path,
+        r#"
+[auth]
+api_keys = ["admin-key"]
+
+[auth.keys.staging]
+key = "staging-key"
+requests_per_day = 2
+"#,
+    )
+    .unwrap();
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TELEGRAM_NOTIFICATIONS_CONFIG",
+            config_path.to_str().unwrap(),
+        )],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let usage = |key: &str| {
+        client
+            .get(format!("{server_url}/keys/staging/usage"))
+            .bearer_auth(key)
+            .send()
+    };
+
+    for _ in 0..2 {
+        assert_eq!(usage("staging-key").await.unwrap().status(), 200);
+    }
+    let response = usage("staging-key").await.unwrap();
+    assert_eq!(response.status(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "QUOTA_EXCEEDED");
+
+    // Unnamed keys aren't limited and can look at the usage
+    let body: Value = usage("admin-key").await.unwrap().json().await.unwrap();
+    assert_eq!(body["key_id"], "staging");
+    assert_eq!(body["requests_total"], 2);
+    assert_eq!(body["rejected_total"], 1);
+    assert_eq!(body["day"]["limit"], 2);
+
+    let response = client
+        .get(format!("{server_url}/keys/production/usage"))
+        .bearer_auth("admin-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    let _ = std::fs::remove_file(config_path);
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_admin_keys() {
+    let port = 3028;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let dir = std::env::temp_dir().join(format!("admin-keys-{port}"));
+    std::fs::create_dir_all(&dir).unwrap();
+    let keys_file = dir.join("keys.json");
+    let _ = std::fs::remove_file(&keys_file);
+    let config_path = dir.join("config.toml");
+    std::fs::write(
+        &config_path,
+        format!("[auth]\nkeys_file = {:?}\n", keys_file.to_str().unwrap()),
+    )
+    .unwrap();
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[
+            (
+                "TELEGRAM_NOTIFICATIONS_CONFIG",
+                config_path.to_str().unwrap(),
+            ),
+            ("TELEGRAM_NOTIFICATIONS_ADMIN_KEYS", "root-key"),
+        ],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .post(format!("{server_url}/admin/keys"))
+        .bearer_auth("root-key")
+        .json(&json!({"label": "grafana"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["scope"], "send");
+    let key = created["key"].as_str().unwrap().to_string();
+    let id = created["id"].as_str().unwrap().to_string();
+    assert!(std::fs::read_to_string(&keys_file).unwrap().contains(&key));
+
+    // The new key can send but not manage keys
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .bearer_auth(&key)
+        .json(&json!({"message": "From a runtime key"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .get(format!("{server_url}/admin/keys"))
+        .bearer_auth(&key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "ADMIN_KEY_REQUIRED");
+
+    // Listing never shows the key itself
+    let body: Value = client
+        .get(format!("{server_url}/admin/keys"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["keys"][0]["label"], "grafana");
+    assert!(body["keys"][0].get("key").is_none());
+
+    let response = client
+        .delete(format!("{server_url}/admin/keys/{id}"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .bearer_auth(&key)
+        .json(&json!({"message": "Revoked"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client
+        .delete(format!("{server_url}/admin/keys/{id}"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_config_repo_sync() {
+    let port = 3029;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let root = std::env::temp_dir().join(format!("config-repo-{port}"));
+    let _ = std::fs::remove_dir_all(&root);
+    let origin = root.join("origin");
+    std::fs::create_dir_all(&origin).unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&origin)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .status()
+            .expect("Failed to run git");
+        assert!(status.success());
+    };
+    let commit_script = |script: &str| {
+        std::fs::write(origin.join("routing.rhai"), script).unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-qm", "Update routing"]);
+    };
+    git(&["init", "-q"]);
+    commit_script(r#"chat_id = "-1001";"#);
+
+    // Default checkout of the server on this port, left over from earlier runs
+    let checkout = std::env::temp_dir().join(format!("telegram-notifications-config-{port}"));
+    let _ = std::fs::remove_dir_all(&checkout);
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[
+            (
+                "TELEGRAM_NOTIFICATIONS_CONFIG_REPO",
+                origin.to_str().unwrap(),
+            ),
+            ("TELEGRAM_NOTIFICATIONS_ADMIN_KEYS", "root-key"),
+        ],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let routed_chat = || async {
+        let body: Value = client
+            .post(format!("{server_url}/notify/preview"))
+            .bearer_auth("root-key")
+            .json(&json!({"message": "Disk full"}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        body["chat_id"].as_str().unwrap().to_string()
+    };
+    assert_eq!(routed_chat().await, "-1001");
+
+    // A push webhook applies the new commit without waiting for the interval
+    commit_script(r#"chat_id = "-1002";"#);
+    let response = client
+        .post(format!("{server_url}/admin/config/sync"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["reloaded"], true);
+    assert_eq!(routed_chat().await, "-1002");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    let _ = std::fs::remove_dir_all(root);
+    let _ = std::fs::remove_dir_all(checkout);
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_declarative_provisioning() {
+    let port = 3030;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process =
+        start_test_server_with_env(port, &[("TELEGRAM_NOTIFICATIONS_ADMIN_KEYS", "root-key")]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let put_target = |chat_id: &'static str, if_match: Option<String>| {
+        let mut request = client
+            .put(format!("{server_url}/admin/targets/ops-team"))
+            .bearer_auth("root-key")
+            .json(&json!({"chat_id": chat_id}));
+        if let Some(tag) = if_match {
+            request = request.header("If-Match", tag);
+        }
+        request.send()
+    };
+
+    // Applying the same state twice creates once and then changes nothing
+    let response = put_target("-1001", None).await.unwrap();
+    assert_eq!(response.status(), 201);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let response = put_target("-1001", None).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"].to_str().unwrap(), etag);
+
+    // A stale ETag means someone else changed the target
+    let response = put_target("-1002", Some(etag.clone())).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = put_target("-1003", Some(etag)).await.unwrap();
+    assert_eq!(response.status(), 412);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "PRECONDITION_FAILED");
+
+    let preview = || async {
+        client
+            .post(format!("{server_url}/notify/preview"))
+            .bearer_auth("root-key")
+            .json(&json!({"message": "Disk full", "chat": "ops-team"}))
+            .send()
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap()
+    };
+    let body = preview().await;
+    assert_eq!(body["chat_id"], "-1002");
+    assert_eq!(body["would_send"], true);
+
+    // Test mode never calls Telegram, so the limiter is untouched
+    let response = client
+        .get(format!("{server_url}/admin/ratelimits"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["global"]["capacity"], 30);
+    assert_eq!(body["chats"], json!([]));
+    assert_eq!(body["recent_429s"], json!([]));
+
+    // Silencing the target drops its notifications until lifted
+    let until = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        + 3_600;
+    let response = client
+        .put(format!("{server_url}/admin/silences/ops-team"))
+        .bearer_auth("root-key")
+        .json(&json!({"until": until}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["chat_id"], "-1002");
+    assert_eq!(preview().await["would_send"], false);
+    let response = client
+        .delete(format!("{server_url}/admin/silences/-1002"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    assert_eq!(preview().await["would_send"], true);
+
+    // A declared key is only returned when it is created
+    let put_key = || {
+        client
+            .put(format!("{server_url}/admin/keys/grafana"))
+            .bearer_auth("root-key")
+            .json(&json!({"label": "Dashboards"}))
+            .send()
+    };
+    let response = put_key().await.unwrap();
+    assert_eq!(response.status(), 201);
+    let created: Value = response.json().await.unwrap();
+    assert!(created["key"].is_string());
+    let response = put_key().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let updated: Value = response.json().await.unwrap();
+    assert!(updated.get("key").is_none());
+    assert_eq!(updated["created_at"], created["created_at"]);
+
+    // Provisioning needs an admin key
+    let response = client
+        .get(format!("{server_url}/admin/targets"))
+        .bearer_auth(created["key"].as_str().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_cors_policy() {
+    let port = 3031;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let config_path = std::env::temp_dir().join(format!("cors-{port}.toml"));
+    std::fs::write(
+        &config_path,
+        "[cors]\nallowed_origins = [\"https://dash.example.com\"]\nallowed_methods = [\"POST\"]\n",
+    )
+    .unwrap();
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TELEGRAM_NOTIFICATIONS_CONFIG",
+            config_path.to_str().unwrap(),
+        )],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let preflight = |origin: &'static str| {
+        client
+            .request(reqwest::Method::OPTIONS, format!("{server_url}/notify"))
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "POST")
+            .send()
+    };
+
+    let response = preflight("https://dash.example.com").await.unwrap();
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://dash.example.com"
+    );
+    assert_eq!(headers["access-control-allow-methods"], "POST");
+
+    // Other origins get no CORS headers, so browsers block the call
+    let response = preflight("https://evil.example.com").await.unwrap();
+    assert!(
+        response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none()
+    );
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    let _ = std::fs::remove_file(config_path);
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_namespaced_env_vars() {
+    let port = 3032;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[
+            ("TN__AUTH__API_KEYS", "env-key,second-key"),
+            ("TN__TARGETS", r#"{"ops-team": {"chat_id": "-100123"}}"#),
+            ("TN__TARGETS__RELEASES__CHAT_ID", "@releases"),
+        ],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let preview = |chat: &'static str, key: Option<&'static str>| {
+        let mut request = client
+            .post(format!("{server_url}/notify/preview"))
+            .json(&json!({"message": "Configured from the environment", "chat": chat}));
+        if let Some(key) = key {
+            request = request.bearer_auth(key);
+        }
+        request.send()
+    };
+
+    let response = preview("ops-team", None).await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    let body: Value = preview("ops-team", Some("env-key"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["chat_id"], "-100123");
+    let body: Value = preview("releases", Some("second-key"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["chat_id"], "@releases");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_persistent_queue() {
+    let port = 3033;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let dir = std::env::temp_dir().join(format!("tn-e2e-queue-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = dir.join("queue.db");
+    let mut server_process =
+        start_test_server_with_env(port, &[("TN__QUEUE_DB", db.to_str().unwrap())]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .post(format!("{server_url}/notify?mode=async"))
+        .json(&json!({"message": "Survives restarts"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    let delivery_id = body["delivery_id"].as_str().unwrap().to_string();
+
+    let mut state = Value::Null;
+    for _ in 0..50 {
+        let record: Value = client
+            .get(format!("{server_url}/messages/{delivery_id}"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        state = record["state"].clone();
+        if state != "queued" && state != "sending" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(state, "sent");
+    assert!(db.exists());
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_effective_config() {
+    let port = 3051;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[
+            ("TELEGRAM_NOTIFICATIONS_API_KEYS", "send-key"),
+            ("TELEGRAM_NOTIFICATIONS_ADMIN_KEYS", "root-key"),
+        ],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .get(format!("{server_url}/admin/config"))
+        .bearer_auth("send-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    let response = client
+        .get(format!("{server_url}/admin/config"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["port"], port);
+    assert_eq!(body["chat_id"], "123456789");
+    assert_eq!(body["auth"]["api_keys"], json!(["***"]));
+    assert_eq!(body["auth"]["admin_keys"], json!(["***"]));
+    assert!(!body.to_string().contains("root-key"));
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_named_bots() {
+    let port = 3052;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[
+            ("TELEGRAM_NOTIFICATIONS_API_KEYS", "send-key"),
+            (
+                "TN__BOTS",
+                r#"{"billing": {"token": "654321:billing", "chats": ["-100200"], "keys": ["billing-key"]}}"#,
+            ),
+        ],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    // The billing key sends with its bot, to the bot's first chat by default
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .bearer_auth("billing-key")
+        .json(&json!({"message": "Invoice run done"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let delivery_id = body["delivery_id"].as_str().unwrap().to_string();
+    let record: Value = client
+        .get(format!("{server_url}/history/{delivery_id}"))
+        .bearer_auth("send-key")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(record["chat_id"], "-100200");
+    assert_eq!(record["bot"], "billing");
+
+    // ... and nowhere else
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .bearer_auth("billing-key")
+        .json(&json!({"message": "Hello", "chat_id": "-100999"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "CHAT_NOT_ALLOWED");
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .bearer_auth("send-key")
+        .json(&json!({"message": "Hello", "bot": "marketing"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "UNKNOWN_BOT");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_routing_rules() {
+    let port = 3053;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TN__RULES",
+            r#"[{"severity": ["critical"], "labels": {"team": "db"}, "chat": "-100456", "template": "🚨 {{ $.message }}"}, {"source": ["alertmanager"], "silent": true}]"#,
+        )],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let history = |delivery_id: String| {
+        let client = client.clone();
+        let server_url = server_url.clone();
+        async move {
+            client
+                .get(format!("{server_url}/history/{delivery_id}"))
+                .send()
+                .await
+                .unwrap()
+                .json::<Value>()
+                .await
+                .unwrap()
+        }
+    };
+
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({
+            "message": "Replica lag 5m",
+            "severity": "critical",
+            "labels": {"team": "db"},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let record = history(body["delivery_id"].as_str().unwrap().to_string()).await;
+    assert_eq!(record["chat_id"], "-100456");
+    assert_eq!(record["message"], "🚨 Replica lag 5m");
+
+    // Alerts carry their labels; the second rule silences what the first misses
+    let webhook = json!({
+        "status": "firing",
+        "receiver": "telegram",
+        "commonLabels": {"alertname": "DiskFull", "severity": "warning", "team": "db"},
+        "alerts": [{"status": "firing", "labels": {}, "annotations": {}}],
+    });
+    let response = client
+        .post(format!("{server_url}/integrations/alertmanager?mode=sync"))
+        .json(&webhook)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let record = history(body["delivery_id"].as_str().unwrap().to_string()).await;
+    assert_ne!(record["chat_id"], "-100456");
+    assert_eq!(record["disable_notification"], true);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_suppression_filters() {
+    let port = 3054;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TN__FILTERS",
+            r#"[{"name": "noisy-disk", "pattern": "(?i)disk usage at 8\\d%"}]"#,
+        )],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    for message in ["Disk usage at 85%", "Disk usage at 95%"] {
+        let response = client
+            .post(format!("{server_url}/notify"))
+            .json(&json!({"message": message}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(
+            body["telegram_message_id"].is_null(),
+            message.contains("85%"),
+            "{body}"
+        );
+    }
+
+    let stats: Value = client
+        .get(format!("{server_url}/stats"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["suppressed"]["noisy-disk"], 1);
+    assert_eq!(stats["sent"], 1);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_throttles() {
+    let port = 3055;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let config_path = std::env::temp_dir().join(format!("throttles-{port}.toml"));
+    std::fs::write(
+        &config_path,
+        r#"
+[auth]
+api_keys = ["prod-key"]
+
+[auth.keys.staging-ci]
+key = "staging-key"
+
+[throttles.staging]
+key = "staging-ci"
+max = 1
+per_minutes = 10
+"#,
+    )
+    .unwrap();
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TELEGRAM_NOTIFICATIONS_CONFIG",
+            config_path.to_str().unwrap(),
+        )],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let notify = |key: &'static str| {
+        client
+            .post(format!("{server_url}/notify"))
+            .bearer_auth(key)
+            .json(&json!({"message": "Build finished"}))
+            .send()
+    };
+
+    let body: Value = notify("staging-key").await.unwrap().json().await.unwrap();
+    assert_eq!(body["telegram_message_id"], 42);
+    let response = notify("staging-key").await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("Over throttle staging"),
+        "{body}"
+    );
+
+    // Other keys aren't throttled
+    let body: Value = notify("prod-key").await.unwrap().json().await.unwrap();
+    assert_eq!(body["telegram_message_id"], 42);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    let _ = std::fs::remove_file(&config_path);
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_dead_letter_queue() {
+    let port = 3034;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[
+            ("TELEGRAM_NOTIFICATIONS_API_KEYS", "send-key"),
+            ("TELEGRAM_NOTIFICATIONS_ADMIN_KEYS", "root-key"),
+        ],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .get(format!("{server_url}/admin/dlq"))
+        .bearer_auth("send-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    let response = client
+        .get(format!("{server_url}/admin/dlq"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["dead_letters"], json!([]));
+
+    let response = client
+        .post(format!("{server_url}/admin/dlq/abc123def456/retry"))
+        .bearer_auth("root-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "NOT_FOUND");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_wait_for_dependencies() {
+    let port = 3035;
+    let server_url = format!("http://127.0.0.1:{port}");
+
+    // Nothing listens on a port that was just released
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed_address = closed.local_addr().unwrap().to_string();
+    drop(closed);
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[
+            ("TN__WAIT_FOR", &closed_address),
+            ("TN__WAIT_FOR_TIMEOUT", "1"),
+        ],
+    );
+    let mut exit_status = None;
+    for _ in 0..600 {
+        exit_status = server_process.try_wait().unwrap();
+        if exit_status.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let _ = server_process.kill();
+    assert!(
+        exit_status.is_some_and(|status| !status.success()),
+        "Server should give up on a dependency that is down"
+    );
+
+    let dependency = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = dependency.local_addr().unwrap().to_string();
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[("TN__WAIT_FOR", &address), ("TN__WAIT_FOR_TIMEOUT", "5")],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_duplicate_suppression() {
+    let port = 3036;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(port, &[("TN__DEDUPE_WINDOW_SECS", "300")]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let preview = || async {
+        client
+            .post(format!("{server_url}/notify/preview"))
+            .json(&json!({"message": "🔴 web-1 down"}))
+            .send()
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap()
+    };
+    // A preview isn't remembered, so the notification after it still goes out
+    assert_eq!(preview().await["would_send"], true);
+
+    let mut results = Vec::new();
+    for message in ["🔴 web-1 down", "🔴 web-1 down", "🟢 web-1 up"] {
+        let response = client
+            .post(format!("{server_url}/notify"))
+            .json(&json!({"message": message}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        results.push(body);
+    }
+
+    let first = results[0]["delivery_id"].as_str().unwrap();
+    assert_eq!(results[0]["telegram_message_id"], 42);
+    assert_eq!(
+        results[1]["message"],
+        format!("Notification suppressed: Duplicate of {first} (×2 within 300s)")
+    );
+    assert_eq!(results[1]["telegram_message_id"], Value::Null);
+    assert_eq!(results[2]["telegram_message_id"], 42);
+    assert_eq!(preview().await["would_send"], false);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_digest_holds_low_priority() {
+    let port = 3037;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[("TN__DIGESTS", r#"{"123456789": {"every_minutes": 30}}"#)],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("X-Priority", "low")
+        .json(&json!({"message": "Backup of db-1 finished"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "Notification held for the next digest, due in 30 min"
+    );
+    let delivery_id = body["delivery_id"].as_str().unwrap();
+    let record: Value = client
+        .get(format!("{server_url}/messages/{delivery_id}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(record["state"], "queued");
+
+    // Normal priority isn't held
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "Deploy finished"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // This test requires actual server startup, run with --ignored
+async fn test_e2e_scheduled_delivery() {
+    let port = 3038;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let in_seconds = |seconds: i64| {
+        (chrono::DateTime::<chrono::Utc>::from(std::time::SystemTime::now())
+            + chrono::Duration::seconds(seconds))
+        .to_rfc3339()
+    };
+    let schedule = |send_at: String| {
+        client
+            .post(format!("{server_url}/notify"))
+            .json(&json!({"message": "Standup in 10 minutes", "send_at": send_at}))
+            .send()
+    };
+
+    let response = schedule(in_seconds(3600)).await.unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    let later = body["delivery_id"].as_str().unwrap().to_string();
+    let response = schedule(in_seconds(1)).await.unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    let soon = body["delivery_id"].as_str().unwrap().to_string();
+
+    let state = |delivery_id: String| {
+        let client = client.clone();
+        let url = format!("{server_url}/messages/{delivery_id}");
+        async move {
+            let record: Value = client.get(url).send().await.unwrap().json().await.unwrap();
+            record["state"].as_str().unwrap().to_string()
+        }
+    };
+    assert_eq!(state(later.clone()).await, "scheduled");
+    let mut soon_state = String::new();
+    for _ in 0..50 {
+        soon_state = state(soon.clone()).await;
+        if soon_state == "sent" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(soon_state, "sent");
+
+    let scheduled: Value = client
+        .get(format!("{server_url}/scheduled"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let scheduled = scheduled["scheduled"].as_array().unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0]["delivery_id"], later.as_str());
+
+    let cancel = || {
+        client
+            .delete(format!("{server_url}/scheduled/{later}"))
+            .send()
+    };
+    assert_eq!(cancel().await.unwrap().status(), 204);
+    assert_eq!(cancel().await.unwrap().status(), 404);
+    assert_eq!(state(later.clone()).await, "cancelled");
+
+    let response = schedule("tomorrow morning".to_string()).await.unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVALID_SEND_AT");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_e2e_recurring_schedules() {
+    let port = 3039;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TN__SCHEDULES",
+            r#"{"heartbeat": {"cron": "* * * * * *", "message": "Still alive"}}"#,
+        )],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    // The config schedule runs every second
+    let mut fired = false;
+    for _ in 0..50 {
+        let history = client
+            .get(format!("{server_url}/history/export?format=jsonl"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        if history.contains("Still alive") {
+            fired = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(fired, "Config schedule never ran");
+
+    let response = client
+        .post(format!("{server_url}/schedules"))
+        .json(&json!({"id": "standup", "cron": "50 9 * * 1-5", "message": "Standup in 10 minutes"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["source"], "api");
+    assert!(body["next_run"].as_str().unwrap().contains("T09:50:00"));
+
+    let response = client
+        .post(format!("{server_url}/schedules"))
+        .json(&json!({"id": "standup", "cron": "0 10 * * 1", "message": "Again"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 409);
+    let response = client
+        .post(format!("{server_url}/schedules"))
+        .json(&json!({"cron": "every monday", "message": "Weekly report"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "INVALID_CRON");
+
+    let list: Value = client
+        .get(format!("{server_url}/schedules"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let ids: Vec<&str> = list["schedules"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|schedule| schedule["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["heartbeat", "standup"]);
+
+    let delete = |id: &str| client.delete(format!("{server_url}/schedules/{id}")).send();
+    assert_eq!(delete("heartbeat").await.unwrap().status(), 409);
+    assert_eq!(delete("standup").await.unwrap().status(), 204);
+    let response = client
+        .get(format!("{server_url}/schedules/standup"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_e2e_json_logs() {
+    let port = 3040;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--server",
+            "--port",
+            &port.to_string(),
+            "--host",
+            "127.0.0.1",
+        ])
+        .env(
+            "TELEGRAM_BOT_TOKEN",
+            "test_token:ABCdefGHIjklMNOpqrSTUvwxyz",
+        )
+        .env("TELEGRAM_CHAT_ID", "123456789")
+        .env("TELEGRAM_NOTIFICATIONS_SKIP_VALIDATION", "true")
+        .env("RUST_LOG", "telegram_notifications=info")
+        .env("TN__LOG_FORMAT", "json")
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to start test server");
+
+    // Killing cargo can leave the server holding stdout, so read it on the side
+    let stdout = server_process.stdout.take().unwrap();
+    let (lines, logged) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::BufRead::lines(std::io::BufReader::new(stdout)) {
+            let Ok(line) = line else { break };
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .header("X-Request-Id", "req-3040")
+        .json(&json!({"message": "Structured logs"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-request-id"], "req-3040");
+
+    let mut sent = None;
+    let mut finished = None;
+    while sent.is_none() || finished.is_none() {
+        let line = logged
+            .recv_timeout(Duration::from_secs(10))
+            .expect("Expected log events are missing");
+        let event: Value = serde_json::from_str(&line)
+            .unwrap_or_else(|e| panic!("Log line isn't JSON ({e}): {line}"));
+        if event["span"]["request_id"] != "req-3040" {
+            continue;
+        }
+        if event["message_id"] == 42 {
+            sent = Some(event);
+        } else if event["latency_ms"].is_u64() {
+            finished = Some(event);
+        }
+    }
+    assert_eq!(sent.unwrap()["chat_id"], "123456789");
+    assert_eq!(finished.unwrap()["status"], 200);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_e2e_stats() {
+    let port = 3041;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server(port);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    for body in [
+        json!({"message": "Deploy finished"}),
+        json!({"message": "Backup finished", "async": true}),
+    ] {
+        let response = client
+            .post(format!("{server_url}/notify"))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+
+    let mut stats = Value::Null;
+    for _ in 0..50 {
+        stats = client
+            .get(format!("{server_url}/stats"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if stats["sent"] == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(stats["sent"], 2);
+    assert_eq!(stats["failed"], 0);
+    assert_eq!(stats["queue_depth"], 0);
+    assert_eq!(stats["by_chat"]["123456789"]["sent"], 2);
+    assert_eq!(stats["by_source"]["api"]["sent"], 2);
+    assert!(stats["uptime_secs"].is_u64());
+    assert!(stats["last_error"].is_null());
+
+    let health: Value = client
+        .get(format!("{server_url}/health"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(health["queued_messages"], 0);
+    assert!(health["last_successful_send"].is_u64());
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_e2e_history_search() {
+    let port = 3042;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let dir = std::env::temp_dir().join(format!("tn-e2e-history-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = dir.join("history.db");
+    let env = [("TN__HISTORY_DB", db.to_str().unwrap())];
+    let mut server_process = start_test_server_with_env(port, &env);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    for (message, chat_id) in [
+        ("Deploy started", "123456789"),
+        ("Deploy finished", "123456789"),
+        ("Backup finished", "-1001234567890"),
+    ] {
+        let response = client
+            .post(format!("{server_url}/notify"))
+            .json(&json!({"message": message, "chat_id": chat_id}))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+
+    let search = |query: &'static str| {
+        let client = client.clone();
+        let server_url = server_url.clone();
+        async move {
+            client
+                .get(format!("{server_url}/history?{query}"))
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+    let page: Value = search("chat=123456789&status=sent&limit=1")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["next_offset"], 1);
+    assert_eq!(page["records"][0]["message"], "Deploy finished");
+    let page: Value = search("chat=123456789&limit=1&offset=1")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(page["records"][0]["message"], "Deploy started");
+    assert!(page.get("next_offset").is_none());
+    let failed: Value = search("status=failed").await.json().await.unwrap();
+    assert_eq!(failed["total"], 0);
+    assert_eq!(search("status=lost").await.status(), 400);
+    assert_eq!(search("since=yesterday").await.status(), 400);
+
+    // The history survives a restart
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    let mut server_process = start_test_server_with_env(port, &env);
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to restart"
+    );
+    let all: Value = search("").await.json().await.unwrap();
+    assert_eq!(all["total"], 3);
+    assert_eq!(all["records"][0]["message"], "Backup finished");
+    assert_eq!(all["records"][0]["status"], "sent");
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_e2e_probes() {
+    let port = 3043;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process =
+        start_test_server_with_env(port, &[("TELEGRAM_NOTIFICATIONS_API_KEYS", "send-key")]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    // Probes don't need the API key
+    let client = Client::new();
+    let response = client
+        .get(format!("{server_url}/livez"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "alive");
+
+    let response = client
+        .get(format!("{server_url}/readyz"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ready");
+    for check in ["bot", "queue", "storage"] {
+        assert_eq!(body[check]["ok"], true, "{check} check failed");
+    }
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_e2e_delivery_callbacks() {
+    let port = 3044;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut receiver = mockito::Server::new_async().await;
+    let callback = receiver
+        .mock("POST", "/deliveries")
+        .match_header("x-signature", mockito::Matcher::Regex("^sha256=".into()))
+        .match_body(mockito::Matcher::PartialJson(json!({
+            "chat_id": "123456789",
+            "status": "sent",
+            "telegram_message_id": 42,
+        })))
+        .with_status(204)
+        .expect(2)
+        .create_async()
+        .await;
+    let mut server_process =
+        start_test_server_with_env(port, &[("TN__CALLBACK_SECRET", "callback-secret")]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let callback_url = format!("{}/deliveries", receiver.url());
+    for delivery in [json!(false), json!(true)] {
+        let response = client
+            .post(format!("{server_url}/notify"))
+            .json(&json!({
+                "message": "Deploy finished",
+                "async": delivery,
+                "callback_url": callback_url,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({"message": "Deploy finished", "callback_url": "file:///etc/passwd"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    for _ in 0..50 {
+        if callback.matched_async().await {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    callback.assert_async().await;
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // Requires server to be running
+async fn test_e2e_alertmanager() {
+    let port = 3045;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(port, &[]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let webhook = json!({
+        "version": "4",
+        "status": "firing",
+        "receiver": "telegram",
+        "groupLabels": {"alertname": "HighCpuUsage"},
+        "commonLabels": {"alertname": "HighCpuUsage", "severity": "critical"},
+        "commonAnnotations": {},
+        "externalURL": "http://alertmanager:9093",
+        "alerts": [{
+            "status": "firing",
+            "labels": {"alertname": "HighCpuUsage", "severity": "critical", "instance": "web-1"},
+            "annotations": {"summary": "CPU usage above 90%"},
+            "startsAt": "2026-10-16T08:00:00Z",
+            "endsAt": "0001-01-01T00:00:00Z",
+        }],
+        "truncatedAlerts": 0,
+    });
+
+    // Queued by default
+    let response = client
+        .post(format!("{server_url}/integrations/alertmanager"))
+        .json(&webhook)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+
+    let response = client
+        .post(format!(
+            "{server_url}/integrations/alertmanager?chat_id=-100555&mode=sync"
+        ))
+        .json(&webhook)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["telegram_message_id"], 42);
+
+    let stats: Value = client
+        .get(format!("{server_url}/stats"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(stats["by_source"]["alertmanager"]["sent"].as_u64().unwrap() >= 1);
+    assert_eq!(stats["by_chat"]["-100555"]["sent"], 1);
+
+    let response = client
+        .post(format!("{server_url}/integrations/alertmanager"))
+        .header("content-type", "application/json")
+        .body("{\"alerts\": [")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // Requires server to be running
+async fn test_e2e_grafana() {
+    let port = 3046;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut grafana = mockito::Server::new_async().await;
+    let screenshot = grafana
+        .mock("GET", "/public/img/attachments/a1.png")
+        .with_header("content-type", "image/png")
+        .with_body([0x89, b'P', b'N', b'G'])
+        .create_async()
+        .await;
+    let mut server_process = start_test_server_with_env(port, &[]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .post(format!("{server_url}/integrations/grafana?mode=sync"))
+        .json(&json!({
+            "version": "1",
+            "status": "firing",
+            "receiver": "telegram",
+            "commonLabels": {"alertname": "High memory usage"},
+            "externalURL": grafana.url(),
+            "alerts": [{
+                "status": "firing",
+                "labels": {"alertname": "High memory usage", "instance": "web-1"},
+                "annotations": {"summary": "Memory usage above 90%"},
+                "dashboardURL": format!("{}/d/node", grafana.url()),
+                "panelURL": format!("{}/d/node?viewPanel=4", grafana.url()),
+                "imageURL": format!("{}/public/img/attachments/a1.png", grafana.url()),
+            }],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    screenshot.assert_async().await;
+
+    let record: Value = client
+        .get(format!(
+            "{server_url}/history/{}",
+            body["delivery_id"].as_str().unwrap()
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(
+        record["message"]
+            .as_str()
+            .unwrap()
+            .contains("High memory usage")
+    );
+
+    // Buttons on the native endpoint are checked before sending
+    let response = client
+        .post(format!("{server_url}/notify"))
+        .json(&json!({
+            "message": "Deploy finished",
+            "buttons": [{"text": "Run", "url": "javascript:alert(1)"}],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // Requires server to be running
+async fn test_e2e_uptime() {
+    let port = 3047;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(port, &[]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let mut messages = Vec::new();
+    for (status, time, msg) in [
+        (0, "2026-10-16 08:00:00.000", "connect ECONNREFUSED"),
+        (0, "2026-10-16 08:01:00.000", "connect ECONNREFUSED"),
+        (1, "2026-10-16 08:05:30.000", "200 - OK"),
+    ] {
+        let response = client
+            .post(format!("{server_url}/integrations/uptime?mode=sync"))
+            .json(&json!({
+                "heartbeat": {"monitorID": 9, "status": status, "time": time, "msg": msg},
+                "monitor": {"id": 9, "name": "Checkout API", "type": "http"},
+                "msg": format!("[Checkout API] {msg}"),
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        let record: Value = client
+            .get(format!(
+                "{server_url}/history/{}",
+                body["delivery_id"].as_str().unwrap()
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        messages.push(record["message"].as_str().unwrap().to_string());
+    }
+    assert!(messages[0].starts_with("🔴 <b>Checkout API is down</b>"));
+    assert!(messages[2].starts_with("🟢 <b>Checkout API is up</b>\nDown for 5m 30s"));
+
+    // Simpler payloads of other monitors
+    let response = client
+        .post(format!("{server_url}/integrations/uptime"))
+        .json(&json!({"monitor": "Nightly backup", "status": "down", "msg": "No check-in"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // Requires server to be running
+async fn test_e2e_mapped_webhook() {
+    let port = 3048;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let dir = std::env::temp_dir().join(format!("mapped-webhook-{port}"));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config_path = dir.join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[webhooks.releases]\n\
+         message = \"📦 <b>{{ $.repository.full_name }}</b> {{ $.release.tag_name }} released\"\n\
+         parse_mode = \"HTML\"\n",
+    )
+    .unwrap();
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TELEGRAM_NOTIFICATIONS_CONFIG",
+            config_path.to_str().unwrap(),
+        )],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .post(format!("{server_url}/webhook/releases?mode=sync"))
+        .json(&json!({
+            "action": "published",
+            "release": {"tag_name": "v2.0.0"},
+            "repository": {"full_name": "acme/<shop>"},
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let record: Value = client
+        .get(format!(
+            "{server_url}/history/{}",
+            body["delivery_id"].as_str().unwrap()
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        record["message"],
+        "📦 <b>acme/&lt;shop&gt;</b> v2.0.0 released"
+    );
+
+    let response = client
+        .post(format!("{server_url}/webhook/unknown"))
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // Requires server to be running
+async fn test_e2e_pagerduty() {
+    let port = 3049;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let mut server_process = start_test_server_with_env(port, &[]);
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let trigger = json!({
+        "routing_key": "R0123456789ABCDEF0123456789ABCDE",
+        "event_action": "trigger",
+        "dedup_key": "disk-web-1",
+        "payload": {"summary": "Disk 95% full", "source": "web-1", "severity": "critical"},
+    });
+    let response = client
+        .post(format!("{server_url}/integrations/pagerduty?mode=sync"))
+        .json(&trigger)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "success");
+    assert_eq!(body["dedup_key"], "disk-web-1");
+    let delivery_id = body["delivery_id"].as_str().unwrap().to_string();
+    let record: Value = client
+        .get(format!("{server_url}/history/{delivery_id}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        record["message"],
+        "🔴 <b>[CRITICAL]</b> Disk 95% full\n<i>web-1</i>"
+    );
+
+    // Triggering the open incident again is a duplicate
+    let body: Value = client
+        .post(format!("{server_url}/integrations/pagerduty"))
+        .json(&trigger)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["delivery_id"], delivery_id.as_str());
+
+    // Resolving marks the trigger's message instead of sending another
+    let response = client
+        .post(format!("{server_url}/integrations/pagerduty"))
+        .json(&json!({"routing_key": "R0123456789ABCDEF0123456789ABCDE", "event_action": "resolve", "dedup_key": "disk-web-1"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["delivery_id"], delivery_id.as_str());
+
+    let response = client
+        .post(format!("{server_url}/integrations/pagerduty"))
+        .json(&json!({"event_action": "resolve"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
+#[tokio::test]
+#[ignore] // Requires server to be running
+async fn test_e2e_checkin() {
+    let port = 3050;
+    let server_url = format!("http://127.0.0.1:{port}");
+    let dir = std::env::temp_dir().join(format!("checkin-{port}"));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config_path = dir.join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[checkins.nightly-backup]\nperiod_minutes = 1440\ngrace_minutes = 60\n",
+    )
+    .unwrap();
+    let mut server_process = start_test_server_with_env(
+        port,
+        &[(
+            "TELEGRAM_NOTIFICATIONS_CONFIG",
+            config_path.to_str().unwrap(),
+        )],
+    );
+
+    assert!(
+        wait_for_server_ready(&server_url, 20).await,
+        "Server failed to start"
+    );
+
+    let client = Client::new();
+    let response = client
+        .post(format!("{server_url}/checkin/nightly-backup"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["slug"], "nightly-backup");
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let next_due = body["next_due"].as_u64().unwrap();
+    assert!(next_due >= now + 1500 * 60 - 5 && next_due <= now + 1500 * 60 + 5);
+
+    let response = client
+        .post(format!("{server_url}/checkin/unknown"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Cleanup
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+}
+
 #[test]
 fn test_cli_help_command() {
     let output = Command::new("cargo")
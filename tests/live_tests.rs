@@ -0,0 +1,197 @@
+//! Tests against the real Bot API, catching behavior changes on Telegram's
+//! side. Only built with `--features live-tests`, and skipped unless
+//! TELEGRAM_LIVE_BOT_TOKEN and TELEGRAM_LIVE_CHAT_ID name a bot and a chat
+//! it may post, pin and delete in.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::Value;
+use std::env;
+use std::time::Instant;
+use telegram_notifications::telegram::{
+    Attachment, MAX_MESSAGE_LENGTH, TelegramBot, TelegramError, TelegramResponse, split_message,
+};
+
+/// 32x32 PNG, big enough for Telegram to accept as a photo
+const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAACAAAAAgCAIAAAD8GO2jAAAAKklEQVR42u3NQQkAAAgEsMtjZ1sawBQ+hMH+S/WcikAgEAgEAoFAIPgSLBmK7FvlF4yxAAAAAElFTkSuQmCC";
+
+/// The bot and chat to test with, or None (with a note) to skip
+fn live_bot() -> Option<(TelegramBot, String)> {
+    let var = |name: &str| env::var(name).ok().filter(|value| !value.trim().is_empty());
+    match (var("TELEGRAM_LIVE_BOT_TOKEN"), var("TELEGRAM_LIVE_CHAT_ID")) {
+        (Some(token), Some(chat_id)) => Some((TelegramBot::new(token), chat_id)),
+        _ => {
+            eprintln!(
+                "Skipping live test: set TELEGRAM_LIVE_BOT_TOKEN and TELEGRAM_LIVE_CHAT_ID to run it"
+            );
+            None
+        }
+    }
+}
+
+fn result(response: &TelegramResponse) -> &Value {
+    response.result.as_ref().expect("response without result")
+}
+
+fn message_id(response: &TelegramResponse) -> i64 {
+    result(response)["message_id"]
+        .as_i64()
+        .expect("result without message_id")
+}
+
+/// Code of the Bot API error `error` holds
+fn error_code(error: &anyhow::Error) -> Option<i32> {
+    error
+        .downcast_ref::<TelegramError>()
+        .expect("not a Bot API error")
+        .error_code
+}
+
+#[tokio::test]
+async fn test_live_get_me() {
+    let Some((bot, _)) = live_bot() else { return };
+
+    let response = bot.get_me().await.unwrap();
+
+    let me = result(&response);
+    assert_eq!(me["is_bot"], true);
+    assert!(me["username"].is_string());
+}
+
+#[tokio::test]
+async fn test_live_send_edit_delete() {
+    let Some((bot, chat_id)) = live_bot() else {
+        return;
+    };
+
+    let sent = bot
+        .send_message_advanced(&chat_id, "🧪 *Live test* `send`", Some("Markdown"), true)
+        .await
+        .unwrap();
+    let message = result(&sent);
+    assert_eq!(message["text"], "🧪 Live test send");
+    assert_eq!(message["entities"][0]["type"], "bold");
+    let id = message_id(&sent);
+
+    let edited = bot
+        .edit_message_text(&chat_id, id, "🧪 *Live test* `edited`", Some("Markdown"))
+        .await
+        .unwrap();
+    assert_eq!(result(&edited)["text"], "🧪 Live test edited");
+
+    let deleted = bot.delete_message(&chat_id, id).await.unwrap();
+    assert_eq!(result(&deleted), &Value::Bool(true));
+
+    // A message that is gone can't be deleted twice
+    let error = bot.delete_message(&chat_id, id).await.unwrap_err();
+    assert_eq!(error_code(&error), Some(400));
+}
+
+#[tokio::test]
+async fn test_live_pin_and_unpin() {
+    let Some((bot, chat_id)) = live_bot() else {
+        return;
+    };
+
+    let sent = bot
+        .send_message_advanced(&chat_id, "🧪 Live test: pin", None, true)
+        .await
+        .unwrap();
+    let id = message_id(&sent);
+
+    let pinned = bot.pin_chat_message(&chat_id, id, true).await;
+    let unpinned = bot.unpin_chat_message(&chat_id, id).await;
+    bot.delete_message(&chat_id, id).await.unwrap();
+
+    assert_eq!(result(&pinned.unwrap()), &Value::Bool(true));
+    assert_eq!(result(&unpinned.unwrap()), &Value::Bool(true));
+}
+
+#[tokio::test]
+async fn test_live_send_photo() {
+    let Some((bot, chat_id)) = live_bot() else {
+        return;
+    };
+
+    let photo = Attachment {
+        file_name: "pixel.png".to_string(),
+        content_type: Some("image/png".to_string()),
+        data: BASE64.decode(PNG).unwrap(),
+    };
+    let sent = bot.send_attachment(&chat_id, &photo, true).await.unwrap();
+    let id = message_id(&sent);
+    bot.delete_message(&chat_id, id).await.unwrap();
+
+    let sizes = result(&sent)["photo"]
+        .as_array()
+        .expect("not sent as a photo");
+    assert!(!sizes.is_empty());
+}
+
+#[tokio::test]
+async fn test_live_send_document() {
+    let Some((bot, chat_id)) = live_bot() else {
+        return;
+    };
+
+    let log = Attachment {
+        file_name: "build.log".to_string(),
+        content_type: Some("text/plain".to_string()),
+        data: b"step 3/7 failed: exit code 1\n".to_vec(),
+    };
+    let sent = bot.send_attachment(&chat_id, &log, true).await.unwrap();
+    let id = message_id(&sent);
+    bot.delete_message(&chat_id, id).await.unwrap();
+
+    assert_eq!(result(&sent)["document"]["file_name"], "build.log");
+}
+
+#[tokio::test]
+async fn test_live_message_length_limit() {
+    let Some((bot, chat_id)) = live_bot() else {
+        return;
+    };
+
+    let text = "Live test: long message\n".repeat(200);
+    let parts = split_message(&text);
+    assert!(parts.len() > 1);
+
+    // Every part fits, while a single character more is refused
+    for part in &parts {
+        let sent = bot
+            .send_message_advanced(&chat_id, part, None, true)
+            .await
+            .unwrap();
+        bot.delete_message(&chat_id, message_id(&sent))
+            .await
+            .unwrap();
+    }
+    let too_long = "x".repeat(MAX_MESSAGE_LENGTH + 1);
+    let error = bot
+        .send_message_advanced(&chat_id, &too_long, None, true)
+        .await
+        .unwrap_err();
+    assert_eq!(error_code(&error), Some(400));
+}
+
+#[tokio::test]
+async fn test_live_refused_requests_keep_breaker_closed() {
+    let Some((bot, _)) = live_bot() else { return };
+
+    // Unknown chats are refused with a client error, not an outage
+    let error = bot
+        .send_message_advanced("0", "🧪 Live test: nowhere", None, true)
+        .await
+        .unwrap_err();
+    assert_eq!(error_code(&error), Some(400));
+    assert_eq!(
+        bot.circuit_breaker()
+            .state(Instant::now())
+            .consecutive_failures,
+        0
+    );
+
+    let unknown = TelegramBot::new("123456:not-a-real-token".to_string());
+    let error = unknown.get_me().await.unwrap_err();
+    assert_eq!(error_code(&error), Some(401));
+}
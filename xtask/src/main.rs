@@ -0,0 +1,304 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Parser, Subcommand};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const BINARY: &str = "telegram-notifications";
+
+/// Targets built when no `--target` is given
+const DEFAULT_TARGETS: &[&str] = &["x86_64-unknown-linux-musl", "aarch64-unknown-linux-musl"];
+
+/// Platforms built into the container image when no `--platform` is given
+const DEFAULT_PLATFORMS: &str = "linux/amd64,linux/arm64";
+
+/// Release tooling for telegram-notifications (run via `cargo xtask`)
+#[derive(Parser, Debug)]
+#[command(name = "xtask")]
+struct Cli {
+    #[command(subcommand)]
+    command: Task,
+}
+
+#[derive(Subcommand, Debug)]
+enum Task {
+    /// Build release binaries and pack them as checksummed (optionally signed) archives
+    Dist(BuildArgs),
+    /// Build .deb and .rpm packages with the systemd unit
+    Package(BuildArgs),
+    /// Build the multi-arch container image
+    Container(ContainerArgs),
+    /// Run dist, package and container in one go
+    Release {
+        #[command(flatten)]
+        build: BuildArgs,
+        #[command(flatten)]
+        container: ContainerArgs,
+    },
+}
+
+#[derive(Args, Debug)]
+struct BuildArgs {
+    /// Rust target triples to build (defaults to x86_64 and aarch64 musl)
+    #[arg(long = "target")]
+    targets: Vec<String>,
+
+    /// Build with `cross` instead of `cargo` (needed for foreign targets)
+    #[arg(long, default_value_t = false)]
+    cross: bool,
+
+    /// GPG key used to sign archives and the checksum file
+    #[arg(long)]
+    sign_key: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ContainerArgs {
+    /// Comma-separated container platforms
+    #[arg(long, default_value = DEFAULT_PLATFORMS)]
+    platform: String,
+
+    /// Image name without tag
+    #[arg(long, default_value = "ghcr.io/grimvoodoo/telegram-notifications")]
+    image: String,
+
+    /// Push the image instead of only building it
+    #[arg(long, default_value_t = false)]
+    push: bool,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let root = project_root();
+    let version = package_version(&root)?;
+
+    match cli.command {
+        Task::Dist(args) => dist(&root, &version, &args),
+        Task::Package(args) => package(&root, &args),
+        Task::Container(args) => container(&root, &version, &args),
+        Task::Release {
+            build,
+            container: c,
+        } => {
+            dist(&root, &version, &build)?;
+            package(&root, &build)?;
+            container(&root, &version, &c)
+        }
+    }
+}
+
+fn dist(root: &Path, version: &str, args: &BuildArgs) -> Result<()> {
+    let dist_dir = dist_dir(root);
+    fs::create_dir_all(&dist_dir)?;
+
+    let mut archives = Vec::new();
+    for target in targets(args) {
+        build(root, target, args.cross)?;
+
+        let name = archive_name(version, target);
+        let staging = dist_dir.join(name.trim_end_matches(".tar.gz"));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        fs::create_dir_all(&staging)?;
+        fs::copy(binary_path(root, target), staging.join(BINARY))?;
+        for file in [
+            "README.md",
+            "config.example.toml",
+            "packaging/telegram-notifications.service",
+        ] {
+            let source = root.join(file);
+            fs::copy(&source, staging.join(source.file_name().unwrap()))?;
+        }
+
+        run(Command::new("tar")
+            .current_dir(&dist_dir)
+            .args(["-czf", &name])
+            .arg(staging.file_name().unwrap()))?;
+        fs::remove_dir_all(&staging)?;
+        println!("📦 {}", dist_dir.join(&name).display());
+        archives.push(name);
+    }
+
+    // One checksum file covering every archive
+    let output = Command::new("sha256sum")
+        .current_dir(&dist_dir)
+        .args(&archives)
+        .output()
+        .context("Failed to run sha256sum")?;
+    if !output.status.success() {
+        bail!("sha256sum failed");
+    }
+    fs::write(dist_dir.join("SHA256SUMS"), output.stdout)?;
+
+    if let Some(key) = &args.sign_key {
+        for file in archives.iter().map(String::as_str).chain(["SHA256SUMS"]) {
+            run(Command::new("gpg")
+                .current_dir(&dist_dir)
+                .args(["--batch", "--yes", "--armor", "--detach-sign"])
+                .args(["--local-user", key])
+                .arg(file))?;
+        }
+        println!("🔏 Signed archives with {key}");
+    }
+
+    Ok(())
+}
+
+fn package(root: &Path, args: &BuildArgs) -> Result<()> {
+    let dist_dir = dist_dir(root);
+    fs::create_dir_all(&dist_dir)?;
+
+    for target in targets(args) {
+        build(root, target, args.cross)?;
+
+        run(Command::new("cargo")
+            .current_dir(root)
+            .args([
+                "deb",
+                "--no-build",
+                "--no-strip",
+                "--target",
+                target,
+                "--output",
+            ])
+            .arg(&dist_dir))
+        .context("cargo-deb is required: cargo install cargo-deb")?;
+
+        run(Command::new("cargo")
+            .current_dir(root)
+            .args(["generate-rpm", "--target", target, "--output"])
+            .arg(&dist_dir))
+        .context("cargo-generate-rpm is required: cargo install cargo-generate-rpm")?;
+    }
+    println!("📦 Packages written to {}", dist_dir.display());
+    Ok(())
+}
+
+fn container(root: &Path, version: &str, args: &ContainerArgs) -> Result<()> {
+    // `--load` only supports a single platform
+    if !args.push && args.platform.contains(',') {
+        bail!("Building several platforms requires --push (or a single --platform)");
+    }
+
+    let mut command = Command::new("docker");
+    command
+        .current_dir(root)
+        .args(["buildx", "build", "--platform", &args.platform])
+        .args(["--tag", &format!("{}:{version}", args.image)])
+        .args(["--tag", &format!("{}:latest", args.image)])
+        .arg(if args.push { "--push" } else { "--load" })
+        .arg(".");
+    run(&mut command)
+}
+
+fn build(root: &Path, target: &str, cross: bool) -> Result<()> {
+    run(Command::new(if cross { "cross" } else { "cargo" })
+        .current_dir(root)
+        .args(["build", "--release", "--bin", BINARY, "--target", target]))
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {:?}", command.get_program()))?;
+    if !status.success() {
+        bail!("{:?} exited with {}", command.get_program(), status);
+    }
+    Ok(())
+}
+
+fn targets(args: &BuildArgs) -> Vec<&str> {
+    if args.targets.is_empty() {
+        DEFAULT_TARGETS.to_vec()
+    } else {
+        args.targets.iter().map(String::as_str).collect()
+    }
+}
+
+fn archive_name(version: &str, target: &str) -> String {
+    format!("{BINARY}-v{version}-{target}.tar.gz")
+}
+
+fn binary_path(root: &Path, target: &str) -> PathBuf {
+    root.join("target")
+        .join(target)
+        .join("release")
+        .join(BINARY)
+}
+
+fn dist_dir(root: &Path) -> PathBuf {
+    root.join("target").join("dist")
+}
+
+fn project_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives in the workspace root")
+        .to_path_buf()
+}
+
+/// Version of the main package, read from its manifest
+fn package_version(root: &Path) -> Result<String> {
+    let manifest = fs::read_to_string(root.join("Cargo.toml"))?;
+    parse_version(&manifest).context("No version in Cargo.toml")
+}
+
+fn parse_version(manifest: &str) -> Option<String> {
+    let manifest: toml::Table = manifest.parse().ok()?;
+    Some(
+        manifest
+            .get("package")?
+            .get("version")?
+            .as_str()?
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        let manifest = r#"
+            [package]
+            name = "telegram-notifications"
+            version = "1.2.3"
+
+            [dependencies]
+            tokio = { version = "1.0" }
+        "#;
+        assert_eq!(parse_version(manifest), Some("1.2.3".to_string()));
+    }
+
+    #[test]
+    fn test_parse_version_ignores_other_sections() {
+        let manifest = r#"
+            [dependencies]
+            version = "9.9.9"
+
+            [package]
+            name = "telegram-notifications"
+        "#;
+        assert_eq!(parse_version(manifest), None);
+    }
+
+    #[test]
+    fn test_archive_name() {
+        assert_eq!(
+            archive_name("0.1.0", "x86_64-unknown-linux-musl"),
+            "telegram-notifications-v0.1.0-x86_64-unknown-linux-musl.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_default_targets() {
+        let args = BuildArgs {
+            targets: Vec::new(),
+            cross: false,
+            sign_key: None,
+        };
+        assert_eq!(targets(&args), DEFAULT_TARGETS);
+    }
+}